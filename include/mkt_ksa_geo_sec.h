@@ -0,0 +1,189 @@
+#ifndef MKT_KSA_GEO_SEC_H
+#define MKT_KSA_GEO_SEC_H
+
+#pragma once
+
+// Auto-generated by cbindgen. Do not edit.
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * رمز الحالة الذي تعيده كل دوال `mkt_*`.
+ * Status code returned by every `mkt_*` function.
+ */
+enum MktStatus {
+  MKT_STATUS_OK = 0,
+  MKT_STATUS_NULL_POINTER = 1,
+  MKT_STATUS_INVALID_UTF8 = 2,
+  MKT_STATUS_INVALID_JSON = 3,
+  MKT_STATUS_INVALID_CONFIG = 4,
+  MKT_STATUS_ENGINE_ERROR = 5,
+  MKT_STATUS_PANIC = 6,
+};
+typedef int32_t MktStatus;
+
+/**
+ * مقبض معتم يملك كل المحركات وبيئة تشغيل tokio.
+ * Opaque handle owning every engine and a tokio runtime.
+ */
+typedef struct MktEngine MktEngine;
+
+/**
+ * توليد بصمة (واجهة C)
+ * Generate fingerprint (C interface)
+ *
+ * # Safety
+ * - يجب أن تكون المؤشرات `os`, `device_info`, `env_data` صالحة وغير فارغة وتشير إلى سلاسل C منتهية بـ NUL.
+ * - يجب أن تبقى الذاكرة المشار إليها صالحة طوال مدة النداء.
+ * - السلسلة المعادة يجب تحريرها عبر `free_fingerprint_string` فقط لتفادي تسرب الذاكرة.
+ * - This function expects valid NUL-terminated C strings and returns an owned C string that
+ *   must be freed with `free_fingerprint_string`.
+ */
+char *generate_adaptive_fingerprint(const char *os,
+                                    const char *device_info,
+                                    const char *env_data);
+
+/**
+ * تحرير الذاكرة
+ * Free memory
+ *
+ * # Safety
+ * - يجب تمرير مؤشر تم استلامه سابقاً من `generate_adaptive_fingerprint` فقط.
+ * - لا تستدعِ هذه الدالة بمؤشر تم تحريره مسبقاً أو مؤشر غير صالح.
+ * - The pointer must originate from `generate_adaptive_fingerprint` and be freed exactly once.
+ */
+void free_fingerprint_string(char *ptr);
+
+/**
+ * ينشئ مقبض محرك من إعدادات JSON (`NULL` للإعدادات الافتراضية).
+ * Creates an engine handle from a JSON configuration (`NULL` for the defaults).
+ *
+ * # Safety
+ * `config_json` فارغ أو سلسلة C صالحة، و `out_engine` مؤشر صالح للكتابة.
+ * المقبض يُحرر مرة واحدة فقط عبر `mkt_engine_destroy`.
+ * `config_json` is null or a valid C string and `out_engine` is valid for writes.
+ * The handle must be freed exactly once with `mkt_engine_destroy`.
+ */
+MktStatus mkt_engine_create(const char *config_json,
+                            struct MktEngine **out_engine);
+
+/**
+ * يعيد تهيئة مقبض قائم بإعدادات جديدة؛ عند الفشل يبقى المقبض بإعداداته السابقة.
+ * يُصفّر تاريخ السلوك، ولا يجوز أن يجري أي استدعاء آخر على المقبض نفسه أثناءه.
+ * Reconfigures a live handle; on failure the handle keeps its previous configuration.
+ * Behavior history is reset, and no other call may run on the same handle meanwhile.
+ *
+ * # Safety
+ * `engine` مقبض حي من `mkt_engine_create` و `config_json` فارغ أو سلسلة C صالحة.
+ * `engine` is a live handle from `mkt_engine_create` and `config_json` is null or a valid C string.
+ */
+MktStatus mkt_engine_configure(struct MktEngine *engine,
+                               const char *config_json);
+
+/**
+ * يحرر مقبض المحرك (تجاهل `NULL`).
+ * Frees an engine handle (`NULL` is ignored).
+ *
+ * # Safety
+ * `engine` فارغ أو مقبض من `mkt_engine_create` لم يُحرر بعد.
+ * `engine` is null or a handle from `mkt_engine_create` that was not freed yet.
+ */
+void mkt_engine_destroy(struct MktEngine *engine);
+
+/**
+ * يولد بصمة الجهاز؛ الطلب بصيغة جسم `POST /api/device/resolve`.
+ * Generates a device fingerprint; the request has the `POST /api/device/resolve` body.
+ *
+ * # Safety
+ * `engine` مقبض حي، `request_json` سلسلة C صالحة، و `out_json` صالح للكتابة؛
+ * النتيجة تُحرر عبر `mkt_string_free`.
+ * `engine` is a live handle, `request_json` a valid C string and `out_json` valid for
+ * writes; the result is freed with `mkt_string_free`.
+ */
+MktStatus mkt_fingerprint_generate(const struct MktEngine *engine,
+                                   const char *request_json,
+                                   char **out_json);
+
+/**
+ * يحل الموقع من `{ip_address, gps_data}`.
+ * Resolves a location from `{ip_address, gps_data}`.
+ *
+ * # Safety
+ * مثل `mkt_fingerprint_generate`.
+ * Same as `mkt_fingerprint_generate`.
+ */
+MktStatus mkt_geo_resolve(const struct MktEngine *engine,
+                          const char *request_json,
+                          char **out_json);
+
+/**
+ * يحلل السلوك؛ الطلب بصيغة جسم `POST /api/behavior/analyze`.
+ * Analyzes behavior; the request has the `POST /api/behavior/analyze` body.
+ *
+ * # Safety
+ * مثل `mkt_fingerprint_generate`.
+ * Same as `mkt_fingerprint_generate`.
+ */
+MktStatus mkt_behavior_analyze(const struct MktEngine *engine,
+                               const char *request_json,
+                               char **out_json);
+
+/**
+ * يحلل الشبكة؛ الطلب بصيغة جسم `POST /api/network/analyze`.
+ * Analyzes the network; the request has the `POST /api/network/analyze` body.
+ *
+ * # Safety
+ * مثل `mkt_fingerprint_generate`.
+ * Same as `mkt_fingerprint_generate`.
+ */
+MktStatus mkt_network_analyze(const struct MktEngine *engine,
+                              const char *request_json,
+                              char **out_json);
+
+/**
+ * يحلل قراءة حساس؛ الطلب بصيغة جسم `POST /api/sensors/analyze`.
+ * Analyzes a sensor reading; the request has the `POST /api/sensors/analyze` body.
+ *
+ * # Safety
+ * مثل `mkt_fingerprint_generate`.
+ * Same as `mkt_fingerprint_generate`.
+ */
+MktStatus mkt_sensors_analyze(const struct MktEngine *engine,
+                              const char *request_json,
+                              char **out_json);
+
+/**
+ * التحقق المتقاطع الكامل؛ الطلب بصيغة جسم `POST /api/geo/resolve`.
+ * Full cross-validation; the request has the `POST /api/geo/resolve` body.
+ *
+ * # Safety
+ * مثل `mkt_fingerprint_generate`.
+ * Same as `mkt_fingerprint_generate`.
+ */
+MktStatus mkt_cross_validate(const struct MktEngine *engine,
+                             const char *request_json,
+                             char **out_json);
+
+/**
+ * رسالة آخر خطأ على هذا الخيط، أو `NULL` إذا نجح آخر استدعاء.
+ * المؤشر يبقى صالحاً حتى الاستدعاء التالي لأي دالة `mkt_*` على الخيط نفسه ولا يُحرر.
+ * Message of the last error on this thread, or `NULL` if the last call succeeded.
+ * The pointer stays valid until the next `mkt_*` call on the same thread and must not be freed.
+ */
+const char *mkt_last_error_message(void);
+
+/**
+ * يحرر سلسلة JSON أعادتها دالة `mkt_*`.
+ * Frees a JSON string returned by a `mkt_*` function.
+ *
+ * # Safety
+ * `ptr` فارغ أو سلسلة من `out_json` لم تُحرر بعد.
+ * `ptr` is null or a string from `out_json` that was not freed yet.
+ */
+void mkt_string_free(char *ptr);
+
+#endif  /* MKT_KSA_GEO_SEC_H */
@@ -28,10 +28,10 @@
 use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 use uuid::Uuid;
 
-use crate::api::api_error;
 use crate::api::authorize_request;
 use crate::api::ok_json_with_trace;
-use crate::db::crud;
+use crate::api::{insufficient_permissions, CallerPolicy};
+use crate::core::behavior_bio::{BehaviorError, UserService};
 use crate::AppState;
 
 /// نقطة نهاية لجلب بيانات مستخدم معين بناءً على معرفه.
@@ -56,23 +56,28 @@ pub async fn get_user(
         Err(resp) => return resp,
     };
 
-    let can_read = claims.sub == target_user_id || claims.roles.iter().any(|r| r == "admin");
-    if !can_read {
-        return api_error(
-            actix_web::http::StatusCode::FORBIDDEN,
-            "INSUFFICIENT_PERMISSIONS",
-            "Insufficient permissions",
-        );
-    }
-
     let Some(pool) = &app_data.db_pool else {
         return HttpResponse::ServiceUnavailable()
             .body("Database backend is disabled. Configure DATABASE_URL=sqlite://...");
     };
 
-    match crud::get_user_by_id(pool, &target_user_id).await {
-        Ok(Some(user)) => ok_json_with_trace(&req, user),
+    // --- الصلاحيات والبيانات عبر UserService (ReadUserProfile) ---
+    // Permissions and data via UserService (ReadUserProfile)
+    let caller = match CallerPolicy::load(&app_data, &claims).await {
+        Ok(caller) => caller,
+        Err(e) => {
+            log::warn!("failed to load caller policy context: {e}");
+            return insufficient_permissions();
+        }
+    };
+    let service = UserService::new(pool.clone());
+    match service
+        .get_user_profile_data(&caller.context(), target_user_id)
+        .await
+    {
+        Ok(Some(profile)) => ok_json_with_trace(&req, profile),
         Ok(None) => HttpResponse::NotFound().body("User not found"),
+        Err(BehaviorError::PolicyError(_)) => insufficient_permissions(),
         Err(_) => HttpResponse::InternalServerError().body("Database error"),
     }
 }
@@ -27,14 +27,21 @@
 ******************************************************************************************/
 use crate::api::api_error;
 use crate::api::authorize_request;
+use crate::api::feedback::{record_analysis, RecordedAnalysis};
 use crate::api::ok_json_with_trace;
 use crate::api::parse_json_payload;
 use crate::api::BearerToken;
+use crate::api::{attach_device_similarity, bind_entity};
+use crate::api::{insufficient_permissions, policy_allows};
+use crate::core::account_correlation::AccessEvent;
 use crate::core::behavior_bio::BehaviorInput;
+use crate::db::crud;
+use crate::security::policy::Action;
 use crate::AppState;
 use actix_web::http::StatusCode;
 use actix_web::{post, web, HttpRequest, Responder};
 use serde::Deserialize;
+use serde_json::json;
 
 /// نموذج الطلب لتحليل السلوك.
 /// Request model for behavior analysis.
@@ -53,20 +60,42 @@ pub async fn analyze_behavior(
     bearer: BearerToken,
     payload_bytes: web::Bytes,
 ) -> impl Responder {
-    if let Err(resp) = authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
-        return resp;
-    }
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(claims) => claims,
+        Err(resp) => return resp,
+    };
 
-    let payload: BehaviorAnalyzeRequest = match parse_json_payload(&payload_bytes) {
+    let mut payload: BehaviorAnalyzeRequest = match parse_json_payload(&payload_bytes) {
         Ok(v) => v,
         Err(resp) => return resp,
     };
+    bind_entity(&app_data, &claims, &mut payload.input).await;
+    attach_device_similarity(&app_data, &claims, &mut payload.input).await;
 
     // --- تمرير الطلب لمحرك core ---
     let engine = &app_data.x_engine.behavior_engine;
     match engine.process(payload.input.clone()).await {
-        Ok(result) => ok_json_with_trace(&req, result), // إعادة نتيجة التحليل بنجاح
-        // Return analysis result on success
+        Ok(result) => {
+            // تخزين القرار ليصنفه المحللون لاحقاً
+            // Store the decision so analysts can label it later
+            let rules = result.anomalies.iter().map(|a| a.code.clone()).collect();
+            let analysis_id = record_analysis(
+                &app_data,
+                &claims,
+                "behavior",
+                &payload.input,
+                result.risk_score,
+                rules,
+            )
+            .await;
+            ok_json_with_trace(
+                &req,
+                RecordedAnalysis {
+                    result,
+                    analysis_id,
+                },
+            )
+        }
         Err(_) => api_error(
             StatusCode::INTERNAL_SERVER_ERROR,
             "BEHAVIOR_ANALYSIS_INTERNAL_ERROR",
@@ -74,3 +103,46 @@ pub async fn analyze_behavior(
         ),
     }
 }
+
+/// نقطة نهاية لتسجيل حدث دخول وربطه عبر الحسابات عبر POST /behavior/access
+/// يعيد تنبيهات مشاركة الحسابات أو حشو بيانات الاعتماد التي أطلقها الحدث ويحفظها.
+/// Endpoint to record an access event and correlate it across accounts via POST /behavior/access
+/// Returns, and persists, the account-sharing or credential-stuffing alerts the event raised.
+#[post("/behavior/access")]
+pub async fn record_access(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    bearer: BearerToken,
+    payload_bytes: web::Bytes,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(claims) => claims,
+        Err(resp) => return resp,
+    };
+    // الأحداث تطلق تنبيهات على حسابات أخرى، فلا يرسلها إلا حساب خدمة (بوابة الدخول) أو مدير
+    // Events raise alerts against other accounts, so only a service account (the login
+    // gateway) or an admin may submit them
+    if !policy_allows(&app_data, &claims, &Action::SubmitOnBehalf).await {
+        return insufficient_permissions();
+    }
+
+    let event: AccessEvent = match parse_json_payload(&payload_bytes) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    let alerts = app_data.access_correlator.observe(event).await;
+    for alert in &alerts {
+        app_data.alert_memory.push(alert.alert_type.clone()).await;
+        if let Some(pool) = &app_data.db_pool {
+            if crud::create_security_alert(pool, alert).await.is_err() {
+                return api_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "ALERT_PERSISTENCE_FAILED",
+                    "Failed to persist alert",
+                );
+            }
+        }
+    }
+    ok_json_with_trace(&req, json!({ "alerts": alerts }))
+}
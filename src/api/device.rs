@@ -30,23 +30,70 @@ use crate::api::authorize_request;
 use crate::api::ok_json_with_trace;
 use crate::api::parse_json_payload;
 use crate::api::BearerToken;
+use crate::api::{database_disabled, insufficient_permissions, policy_allows};
+use crate::core::client_fingerprint::{ClientFingerprintError, TransportFingerprintInput};
+use crate::core::device_attributes::DeviceAttributes;
+use crate::core::device_fp::{AdaptiveFingerprint, FingerprintError};
+use crate::core::device_registry::{DeviceRegistration, DeviceRegistry, DeviceRegistryError};
+use crate::db::models::{Device, DeviceTrustState};
+use crate::security::jwt::Claims;
+use crate::security::policy::Action;
+use crate::utils::ip_prefix::canonical_ip;
 use crate::AppState;
 use actix_web::http::StatusCode;
-use actix_web::{post, web, HttpRequest, Responder};
-use serde::Deserialize;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
 
 /// نموذج الطلب لتحليل بصمة الجهاز.
 /// Request model for device fingerprint analysis.
+/// يمكن إرسال `attributes` المنظمة أو الحقول النصية القديمة؛ وبدونهما تُقرأ
+/// الخصائص من ترويسات `User-Agent` و `Sec-CH-UA-*`.
+/// Either typed `attributes` or the legacy string fields may be sent; without both,
+/// attributes are read from the `User-Agent` and `Sec-CH-UA-*` headers.
 #[derive(Deserialize)]
 pub struct DeviceResolveRequest {
+    #[serde(default)]
     pub os: String, // نظام التشغيل للجهاز
     // Device operating system
+    #[serde(default)]
     pub device_info: String, // معلومات الجهاز (موديل، نوع...)
     // Device information (model, type, ...)
+    #[serde(default)]
     pub environment_data: String, // بيانات البيئة (شبكة، موقع، إلخ)
-                                  // Environment data (network, location, etc.)
+    // Environment data (network, location, etc.)
+    #[serde(default)]
+    pub attributes: Option<DeviceAttributes>, // خصائص الجهاز المنظمة
+                                              // Typed device attributes
 }
 
+/// نتيجة حل البصمة مع الجهاز المسجل عند تفعيل قاعدة البيانات.
+/// Fingerprint resolution result with the registered device when the database is enabled.
+#[derive(Serialize)]
+pub struct DeviceResolveResponse {
+    #[serde(flatten)]
+    pub fingerprint: AdaptiveFingerprint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration: Option<DeviceRegistration>,
+}
+
+/// طلب تغيير حالة الثقة في جهاز.
+/// Request to change a device's trust state.
+#[derive(Deserialize)]
+pub struct DeviceTrustRequest {
+    pub trust_state: DeviceTrustState,
+}
+
+/// طلب إعادة تسمية جهاز.
+/// Request to rename a device.
+#[derive(Deserialize)]
+pub struct DeviceRenameRequest {
+    pub friendly_name: String,
+}
+
+const MAX_FRIENDLY_NAME_LEN: usize = 64;
+
 /// نقطة نهاية لحل بصمة الجهاز عبر POST /device/resolve
 /// Endpoint to resolve device fingerprint via POST /device/resolve
 #[post("/device/resolve")]
@@ -56,27 +103,381 @@ pub async fn resolve_device(
     bearer: BearerToken,
     payload_bytes: web::Bytes,
 ) -> impl Responder {
-    if let Err(resp) = authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
-        return resp;
-    }
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
 
     let payload: DeviceResolveRequest = match parse_json_payload(&payload_bytes) {
         Ok(v) => v,
         Err(resp) => return resp,
     };
+    let Ok(transport) = proxy_transport(&app_data, &req) else {
+        return invalid_transport();
+    };
 
     // --- تمرير الطلب لمحرك core ---
     let engine = &app_data.x_engine.fp_engine;
-    match engine
-        .generate_fingerprint(&payload.os, &payload.device_info, &payload.environment_data)
+    let header_attributes = DeviceAttributes::from_headers(
+        req.headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+    );
+    let result = if let Some(attributes) = &payload.attributes {
+        engine
+            .generate_fingerprint_from_attributes(attributes)
+            .await
+    } else if payload.os.is_empty() && payload.device_info.is_empty() {
+        engine
+            .generate_fingerprint_from_attributes(&header_attributes)
+            .await
+    } else {
+        engine
+            .generate_fingerprint(&payload.os, &payload.device_info, &payload.environment_data)
+            .await
+    };
+    let mut fingerprint = match result {
+        Ok(fingerprint) => fingerprint,
+        Err(FingerprintError::ResourceExceeded(_)) => {
+            return api_error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "DEVICE_FINGERPRINT_BUDGET_EXCEEDED",
+                "Device fingerprint exceeded its processing time budget",
+            )
+        }
+        Err(_) => {
+            return api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "DEVICE_FINGERPRINT_INTERNAL_ERROR",
+                "Internal error while processing device fingerprint",
+            )
+        }
+    };
+
+    // --- مقارنة مكدس TLS بالمتصفح المعلن (في الخصائص أو في User-Agent) ---
+    // --- Compare the TLS stack with the claimed browser (from attributes or User-Agent) ---
+    let claimed = payload
+        .attributes
+        .as_ref()
+        .and_then(|attributes| attributes.browser.as_ref())
+        .or(header_attributes.browser.as_ref())
+        .map(|browser| browser.name.as_str());
+    match &transport {
+        ProxyTransport::Untrusted => {}
+        ProxyTransport::Missing => engine.flag_missing_transport(&mut fingerprint, claimed),
+        ProxyTransport::Present(input) => {
+            if engine
+                .assess_transport(&mut fingerprint, claimed, input)
+                .is_err()
+            {
+                return invalid_transport();
+            }
+        }
+    }
+
+    // --- ربط الجهاز بالمستخدم عند أول ظهور ---
+    // --- Bind the device to the user on first sight ---
+    let registration = match &app_data.db_pool {
+        Some(pool) => match DeviceRegistry::new(pool.clone())
+            .register_or_touch(&claims.sub, &fingerprint)
+            .await
+        {
+            Ok(registration) => Some(registration),
+            Err(_) => {
+                return api_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "DEVICE_REGISTRY_INTERNAL_ERROR",
+                    "Internal error while registering device",
+                )
+            }
+        },
+        None => None,
+    };
+    ok_json_with_trace(
+        &req,
+        DeviceResolveResponse {
+            fingerprint,
+            registration,
+        },
+    )
+}
+
+/// نقطة نهاية لسرد أجهزة المستخدم الحالي عبر GET /devices
+/// Endpoint listing the current user's devices via GET /devices
+#[get("/devices")]
+pub async fn list_own_devices(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    bearer: BearerToken,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &web::Bytes::new()).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    list_devices(&app_data, &req, &claims, claims.sub).await
+}
+
+/// نقطة نهاية لسرد أجهزة مستخدم معين (للمالك أو المشرف) عبر GET /users/{id}/devices
+/// Endpoint listing a given user's devices (owner or moderator) via GET /users/{id}/devices
+#[get("/users/{id}/devices")]
+pub async fn list_user_devices(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    bearer: BearerToken,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &web::Bytes::new()).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    list_devices(&app_data, &req, &claims, path.into_inner()).await
+}
+
+/// نقطة نهاية لإلغاء جهاز (للمالك أو المدير) عبر POST /devices/{id}/revoke
+/// Endpoint revoking a device (owner or admin) via POST /devices/{id}/revoke
+#[post("/devices/{id}/revoke")]
+pub async fn revoke_device(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    bearer: BearerToken,
+    payload_bytes: web::Bytes,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    let registry =
+        match owned_device_registry(&app_data, &claims, &path, DeviceAction::Manage).await {
+            Ok(registry) => registry,
+            Err(resp) => return resp,
+        };
+    device_response(&req, registry.revoke(&path).await)
+}
+
+/// نقطة نهاية إدارية لتغيير حالة الثقة عبر POST /devices/{id}/trust
+/// Admin endpoint changing the trust state via POST /devices/{id}/trust
+#[post("/devices/{id}/trust")]
+pub async fn set_device_trust(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    bearer: BearerToken,
+    payload_bytes: web::Bytes,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    let payload: DeviceTrustRequest = match parse_json_payload(&payload_bytes) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+    let registry =
+        match owned_device_registry(&app_data, &claims, &path, DeviceAction::SetTrust).await {
+            Ok(registry) => registry,
+            Err(resp) => return resp,
+        };
+    device_response(
+        &req,
+        registry.set_trust_state(&path, payload.trust_state).await,
+    )
+}
+
+/// نقطة نهاية لإعادة تسمية جهاز (للمالك أو المدير) عبر POST /devices/{id}/rename
+/// Endpoint renaming a device (owner or admin) via POST /devices/{id}/rename
+#[post("/devices/{id}/rename")]
+pub async fn rename_device(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    bearer: BearerToken,
+    payload_bytes: web::Bytes,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+    let payload: DeviceRenameRequest = match parse_json_payload(&payload_bytes) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+    let name = payload.friendly_name.trim();
+    if name.is_empty() || name.chars().count() > MAX_FRIENDLY_NAME_LEN {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            "INVALID_FRIENDLY_NAME",
+            "Friendly name must be 1-64 characters",
+        );
+    }
+    let registry =
+        match owned_device_registry(&app_data, &claims, &path, DeviceAction::Manage).await {
+            Ok(registry) => registry,
+            Err(resp) => return resp,
+        };
+    device_response(&req, registry.rename(&path, name).await)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DeviceAction {
+    Manage,
+    SetTrust,
+}
+
+/// ترويسة السر المشترك التي يضيفها وكيل إنهاء TLS.
+/// Shared-secret header added by the TLS-terminating proxy.
+const TRANSPORT_PROXY_SECRET_HEADER: &str = "X-Transport-Proxy-Secret";
+
+/// مصدر بصمة النقل لطلب ما.
+/// Where a request's transport fingerprint comes from.
+enum ProxyTransport {
+    /// لم يصل الطلب عبر وكيل موثوق، فلا توجد بصمة يمكن الوثوق بها.
+    /// The request did not come through a trusted proxy, so no fingerprint can be trusted.
+    Untrusted,
+    /// وكيل موثوق لم يمرر البصمة.
+    /// A trusted proxy that forwarded no fingerprint.
+    Missing,
+    Present(TransportFingerprintInput),
+}
+
+/// يقرأ بصمة النقل من ترويسات الوكيل فقط: النظير ضمن `TRUSTED_PROXY_CIDRS`، ويطابق
+/// `X-Transport-Proxy-Secret` قيمة `TRANSPORT_PROXY_SECRET` عند ضبطها.
+/// Reads the transport fingerprint from proxy headers only: the peer must be within
+/// `TRUSTED_PROXY_CIDRS`, and `X-Transport-Proxy-Secret` must match
+/// `TRANSPORT_PROXY_SECRET` when it is set.
+fn proxy_transport(
+    app_data: &AppState,
+    req: &HttpRequest,
+) -> Result<ProxyTransport, ClientFingerprintError> {
+    let peer_trusted = req
+        .peer_addr()
+        .is_some_and(|peer| app_data.forwarding.is_trusted(&canonical_ip(peer.ip())));
+    let secret_matches = app_data
+        .transport_proxy_secret
+        .as_ref()
+        .is_none_or(|secret| {
+            req.headers()
+                .get(TRANSPORT_PROXY_SECRET_HEADER)
+                .and_then(|hv| hv.to_str().ok())
+                .is_some_and(|provided| {
+                    provided
+                        .trim()
+                        .as_bytes()
+                        .ct_eq(secret.expose().as_bytes())
+                        .into()
+                })
+        });
+    if !(peer_trusted && secret_matches) {
+        return Ok(ProxyTransport::Untrusted);
+    }
+    let headers = req
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)));
+    Ok(match TransportFingerprintInput::from_headers(headers)? {
+        Some(input) => ProxyTransport::Present(input),
+        None => ProxyTransport::Missing,
+    })
+}
+
+fn invalid_transport() -> HttpResponse {
+    api_error(
+        StatusCode::BAD_REQUEST,
+        "INVALID_TRANSPORT_FINGERPRINT",
+        "Malformed TLS ClientHello or HTTP/2 fingerprint",
+    )
+}
+
+async fn list_devices(
+    app_data: &AppState,
+    req: &HttpRequest,
+    claims: &Claims,
+    target_user_id: Uuid,
+) -> HttpResponse {
+    let action = Action::ReadUserData {
+        target_user_id: &target_user_id,
+    };
+    if !policy_allows(app_data, claims, &action).await {
+        return insufficient_permissions();
+    }
+    let Some(pool) = &app_data.db_pool else {
+        return database_disabled();
+    };
+    match DeviceRegistry::new(pool.clone())
+        .list_for_user(&target_user_id)
         .await
     {
-        Ok(result) => ok_json_with_trace(&req, result), // إعادة نتيجة التحليل بنجاح
-        // Return analysis result on success
+        Ok(devices) => ok_json_with_trace(req, devices),
         Err(_) => api_error(
             StatusCode::INTERNAL_SERVER_ERROR,
-            "DEVICE_FINGERPRINT_INTERNAL_ERROR",
-            "Internal error while processing device fingerprint",
+            "DEVICE_REGISTRY_INTERNAL_ERROR",
+            "Internal error while listing devices",
+        ),
+    }
+}
+
+/// يتحقق من صلاحية المستدعي على الجهاز قبل إعادة السجل. جهاز لا يملكه المستدعي يُرد
+/// عليه كجهاز غير موجود حتى لا تكشف الاستجابة وجود معرفات أجهزة الآخرين.
+/// Checks the caller's permission on the device before returning the registry. A device
+/// the caller does not own is answered as missing, so responses do not reveal which
+/// device ids exist for other users.
+async fn owned_device_registry(
+    app_data: &AppState,
+    claims: &Claims,
+    device_id: &Uuid,
+    action: DeviceAction,
+) -> Result<DeviceRegistry, HttpResponse> {
+    let Some(pool) = &app_data.db_pool else {
+        return Err(database_disabled());
+    };
+    if action == DeviceAction::SetTrust
+        && !policy_allows(app_data, claims, &Action::SetDeviceTrust).await
+    {
+        return Err(insufficient_permissions());
+    }
+    let registry = DeviceRegistry::new(pool.clone());
+    let device = match registry.get(device_id).await {
+        Ok(device) => device,
+        Err(e) => return Err(registry_error(&e)),
+    };
+    if action == DeviceAction::Manage
+        && !policy_allows(
+            app_data,
+            claims,
+            &Action::ManageDevice {
+                owner_id: &device.user_id,
+            },
+        )
+        .await
+    {
+        return Err(registry_error(&DeviceRegistryError::NotFound));
+    }
+    Ok(registry)
+}
+
+fn device_response(req: &HttpRequest, result: Result<Device, DeviceRegistryError>) -> HttpResponse {
+    match result {
+        Ok(device) => ok_json_with_trace(req, device),
+        Err(e) => registry_error(&e),
+    }
+}
+
+fn registry_error(error: &DeviceRegistryError) -> HttpResponse {
+    match error {
+        DeviceRegistryError::NotFound => api_error(
+            StatusCode::NOT_FOUND,
+            "DEVICE_NOT_FOUND",
+            "Device not found",
+        ),
+        DeviceRegistryError::InvalidTransition { .. } => api_error(
+            StatusCode::CONFLICT,
+            "INVALID_DEVICE_TRUST_TRANSITION",
+            "Device trust state cannot change this way",
+        ),
+        DeviceRegistryError::Database(_) | DeviceRegistryError::Match(_) => api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "DEVICE_REGISTRY_INTERNAL_ERROR",
+            "Internal error while updating device",
         ),
     }
 }
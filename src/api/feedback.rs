@@ -0,0 +1,237 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: feedback.rs
+    المسار: src/api/feedback.rs
+
+    وظيفة الملف:
+    حلقة تغذية راجعة من المحللين. تحليلات السلوك والتحقق المتقاطع تُخزن بمعرف (`analysis_id`)،
+    ويسجل المشرفون حكمهم (احتيال/شرعي) عليها عبر POST /analyses/{id}/verdict فيُحفظ في SQLite
+    ويُمرر إلى `LabeledHistory` الذي تستهلكه النماذج. GET /analyses/report يعيد الدقة
+    والاستدعاء لكل قاعدة ولكل حد درجة.
+
+    File name: feedback.rs
+    Path: src/api/feedback.rs
+
+    File purpose:
+    Analyst feedback loop. Behavior and cross-validation analyses are stored under an
+    `analysis_id`, and moderators record their verdict (fraud/legit) via
+    POST /analyses/{id}/verdict; it is persisted in SQLite and fed to the `LabeledHistory`
+    the models consume. GET /analyses/report returns precision and recall per rule and per
+    score threshold.
+******************************************************************************************/
+use crate::api::api_error;
+use crate::api::authorize_request;
+use crate::api::ok_json_with_trace;
+use crate::api::parse_json_payload;
+use crate::api::BearerToken;
+use crate::api::{database_disabled, insufficient_permissions, policy_allows};
+use crate::core::behavior_bio::BehaviorInput;
+use crate::core::behavior_feedback::{FeedbackReport, LabeledDecision, DEFAULT_THRESHOLDS};
+use crate::db::crud;
+use crate::db::models::{AnalysisVerdict, RiskAnalysis, Verdict};
+use crate::security::jwt::Claims;
+use crate::security::policy::Action;
+use crate::AppState;
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// أقصى طول لملاحظات المحلل.
+/// Maximum length of analyst notes.
+const MAX_NOTES_LEN: usize = 1024;
+
+/// نتيجة تحليل مع معرفها المخزن (غائب إذا كانت قاعدة البيانات معطلة).
+/// An analysis result with its stored id (absent when the database is disabled).
+#[derive(Serialize)]
+pub struct RecordedAnalysis<T: Serialize> {
+    #[serde(flatten)]
+    pub result: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis_id: Option<Uuid>,
+}
+
+/// يخزن قرار مخاطر ليُصنف لاحقاً. فشل الحفظ لا يُفشل التحليل نفسه.
+/// Stores a risk decision to be labeled later. A failed save does not fail the analysis.
+pub(crate) async fn record_analysis(
+    app_data: &AppState,
+    claims: &Claims,
+    source: &str,
+    input: &BehaviorInput,
+    risk_score: f32,
+    rules: Vec<String>,
+) -> Option<Uuid> {
+    let pool = app_data.db_pool.as_ref()?;
+    let analysis = RiskAnalysis {
+        id: Uuid::new_v4(),
+        requested_by: claims.sub,
+        entity_id: input.entity_id.clone(),
+        source: source.to_string(),
+        risk_score,
+        rules,
+        input: serde_json::to_value(input).ok()?,
+        created_at: chrono::Utc::now().naive_utc(),
+    };
+    match crud::insert_risk_analysis(pool, &analysis).await {
+        Ok(()) => Some(analysis.id),
+        Err(e) => {
+            log::warn!("failed to store {source} analysis: {e}");
+            None
+        }
+    }
+}
+
+/// نموذج الطلب لتسجيل حكم المحلل.
+/// Request model for recording an analyst verdict.
+#[derive(Deserialize)]
+pub struct VerdictRequest {
+    pub verdict: Verdict, // احتيال أو شرعي
+    // Fraud or legit
+    #[serde(default)]
+    pub notes: Option<String>, // ملاحظات المحلل (اختياري)
+                               // Analyst notes (optional)
+}
+
+/// معاملات تقرير الدقة والاستدعاء.
+/// Precision/recall report parameters.
+#[derive(Deserialize)]
+pub struct ReportQuery {
+    /// `behavior` أو `cross_validation`؛ بدونه تُجمع كل المصادر.
+    /// `behavior` or `cross_validation`; without it all sources are combined.
+    pub source: Option<String>,
+    /// حدود مفصولة بفواصل مثل `0.5,0.7`.
+    /// Comma-separated thresholds such as `0.5,0.7`.
+    pub thresholds: Option<String>,
+}
+
+/// نقطة نهاية لتسجيل حكم المحلل عبر POST /analyses/{id}/verdict
+/// Endpoint recording an analyst verdict via POST /analyses/{id}/verdict
+#[post("/analyses/{id}/verdict")]
+pub async fn record_verdict(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    bearer: BearerToken,
+    path: web::Path<Uuid>,
+    payload_bytes: web::Bytes,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(claims) => claims,
+        Err(resp) => return resp,
+    };
+    if !policy_allows(&app_data, &claims, &Action::LabelRiskDecision).await {
+        return insufficient_permissions();
+    }
+    let payload: VerdictRequest = match parse_json_payload(&payload_bytes) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+    if payload
+        .notes
+        .as_ref()
+        .is_some_and(|notes| notes.chars().count() > MAX_NOTES_LEN)
+    {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            "INVALID_NOTES",
+            "Notes must be at most 1024 characters",
+        );
+    }
+    let Some(pool) = &app_data.db_pool else {
+        return database_disabled();
+    };
+
+    let analysis = match crud::get_risk_analysis(pool, &path).await {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => {
+            return api_error(
+                StatusCode::NOT_FOUND,
+                "ANALYSIS_NOT_FOUND",
+                "Analysis not found",
+            )
+        }
+        Err(_) => return storage_error(),
+    };
+    let verdict = AnalysisVerdict {
+        analysis_id: analysis.id,
+        verdict: payload.verdict,
+        analyst_id: claims.sub,
+        notes: payload.notes,
+        created_at: chrono::Utc::now().naive_utc(),
+    };
+    if crud::upsert_analysis_verdict(pool, &verdict).await.is_err() {
+        return storage_error();
+    }
+    if let Ok(input) = serde_json::from_value::<BehaviorInput>(analysis.input) {
+        app_data
+            .labeled_history
+            .record(&input, verdict.verdict)
+            .await;
+    }
+    ok_json_with_trace(&req, verdict)
+}
+
+/// نقطة نهاية لتقرير الدقة والاستدعاء عبر GET /analyses/report
+/// Endpoint for the precision/recall report via GET /analyses/report
+#[get("/analyses/report")]
+pub async fn feedback_report(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    bearer: BearerToken,
+    query: web::Query<ReportQuery>,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &web::Bytes::new()).await {
+        Ok(claims) => claims,
+        Err(resp) => return resp,
+    };
+    if !policy_allows(&app_data, &claims, &Action::GenerateSecurityReport).await {
+        return insufficient_permissions();
+    }
+    let thresholds = match &query.thresholds {
+        Some(spec) => match spec
+            .split(',')
+            .map(|t| t.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(values) if values.iter().all(|t| (0.0..=1.0).contains(t)) => values,
+            _ => {
+                return api_error(
+                    StatusCode::BAD_REQUEST,
+                    "INVALID_THRESHOLDS",
+                    "Thresholds must be comma-separated numbers between 0 and 1",
+                )
+            }
+        },
+        None => DEFAULT_THRESHOLDS.to_vec(),
+    };
+    let Some(pool) = &app_data.db_pool else {
+        return database_disabled();
+    };
+
+    match crud::list_labeled_analyses(pool, query.source.as_deref()).await {
+        Ok(labeled) => {
+            let decisions: Vec<_> = labeled
+                .into_iter()
+                .map(|(analysis, verdict)| LabeledDecision {
+                    risk_score: analysis.risk_score,
+                    rules: analysis.rules,
+                    verdict,
+                })
+                .collect();
+            ok_json_with_trace(&req, FeedbackReport::build(&decisions, &thresholds))
+        }
+        Err(_) => storage_error(),
+    }
+}
+
+fn storage_error() -> HttpResponse {
+    api_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "FEEDBACK_STORAGE_FAILED",
+        "Failed to access analysis feedback",
+    )
+}
@@ -27,9 +27,11 @@
 ******************************************************************************************/
 use crate::api::api_error;
 use crate::api::authorize_request;
+use crate::api::feedback::{record_analysis, RecordedAnalysis};
 use crate::api::ok_json_with_trace;
 use crate::api::parse_json_payload;
 use crate::api::BearerToken;
+use crate::api::{attach_device_similarity, bind_entity};
 use crate::core::behavior_bio::BehaviorInput;
 use crate::core::cross_location::CrossValidationError;
 use crate::core::cross_location::CrossValidationInput;
@@ -50,6 +52,12 @@ pub struct GeoResolveRequest {
     // IP address (optional)
     pub gps_data: Option<(f64, f64, u8, f64)>, // بيانات GPS (اختياري)
     // GPS data (optional)
+    #[serde(default)]
+    pub sim_country: Option<String>, // دولة شريحة SIM (اختياري)
+    // SIM country (optional)
+    #[serde(default)]
+    pub locale: Option<String>, // منطقة اللغة مثل ar-SA (اختياري)
+    // Locale such as ar-SA (optional)
     pub os_info: String, // معلومات نظام التشغيل
     // Operating system info
     pub device_details: String, // تفاصيل الجهاز
@@ -69,14 +77,17 @@ pub async fn resolve_geo(
     bearer: BearerToken,
     payload_bytes: web::Bytes,
 ) -> impl Responder {
-    if let Err(resp) = authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
-        return resp;
-    }
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(claims) => claims,
+        Err(resp) => return resp,
+    };
 
-    let payload: GeoResolveRequest = match parse_json_payload(&payload_bytes) {
+    let mut payload: GeoResolveRequest = match parse_json_payload(&payload_bytes) {
         Ok(v) => v,
         Err(resp) => return resp,
     };
+    bind_entity(&app_data, &claims, &mut payload.behavior_input).await;
+    attach_device_similarity(&app_data, &claims, &mut payload.behavior_input).await;
 
     // --- تجميع المدخلات من الطلب ---
     // Collect inputs from the request
@@ -85,6 +96,8 @@ pub async fn resolve_geo(
         // IP address
         gps_data: payload.gps_data, // بيانات GPS
         // GPS data
+        sim_country: payload.sim_country.as_deref(),
+        locale: payload.locale.as_deref(),
         os_info: &payload.os_info, // معلومات نظام التشغيل
         // OS info
         device_details: &payload.device_details, // تفاصيل الجهاز
@@ -99,8 +112,35 @@ pub async fn resolve_geo(
     // Execute the analysis and return the result
     let engine = &app_data.x_engine;
     match engine.validate(input).await {
-        Ok(result) => ok_json_with_trace(&req, result), // إعادة نتيجة التحقق بنجاح
-        // Return validation result on success
+        Ok(result) => {
+            // تخزين القرار ليصنفه المحللون لاحقاً؛ الخطر هو عكس الثقة النهائية
+            // Store the decision so analysts can label it later; risk is the inverse of the final trust
+            let mut rules: Vec<String> = result
+                .behavior_analysis
+                .anomalies
+                .iter()
+                .map(|a| a.code.clone())
+                .collect();
+            if !result.is_trusted {
+                rules.push("untrusted".to_string());
+            }
+            let analysis_id = record_analysis(
+                &app_data,
+                &claims,
+                "cross_validation",
+                &payload.behavior_input,
+                1.0 - result.final_trust_score,
+                rules,
+            )
+            .await;
+            ok_json_with_trace(
+                &req,
+                RecordedAnalysis {
+                    result,
+                    analysis_id,
+                },
+            )
+        }
         Err(CrossValidationError::GeoResolutionFailed(msg)) => {
             // Treat expected lack-of-source scenarios as operational input issues (422), not server faults (500).
             let no_sources = msg.contains("لا توجد مصادر متاحة")
@@ -41,7 +41,12 @@ use subtle::ConstantTimeEq;
 use uuid::Uuid;
 use zeroize::Zeroize;
 
+use crate::core::behavior_bio::BehaviorInput;
+use crate::core::device_registry::DeviceRegistry;
+use crate::core::forwarding_chain::{ForwardingAnalysis, ForwardingHeaders};
+use crate::db::crud;
 use crate::security::jwt::Claims;
+use crate::security::policy::{Action, PolicyContext, PolicyEngine, Role, UserStatus};
 use crate::security::ratelimit::RateLimitError;
 use crate::security::request_guard::{validate_request_framing, RequestFramingError};
 use crate::AppState;
@@ -54,6 +59,7 @@ pub mod alerts;
 pub mod auth;
 pub mod behavior;
 pub mod device;
+pub mod feedback;
 pub mod geo;
 pub mod network;
 pub mod sensors;
@@ -109,31 +115,170 @@ pub fn ok_json_with_trace<T: Serialize>(req: &HttpRequest, data: T) -> HttpRespo
     }))
 }
 
-pub fn client_ip(req: &HttpRequest) -> IpAddr {
-    let trust_x_forwarded_for = std::env::var("TRUST_X_FORWARDED_FOR")
-        .ok()
-        .map(|value| {
-            matches!(
-                value.trim().to_ascii_lowercase().as_str(),
-                "1" | "true" | "yes" | "on"
-            )
+/// يحلل سلسلة التمرير للطلب بمحلل الحالة المشتركة (انظر `ForwardingChainAnalyzer::from_env`).
+/// Analyzes the request forwarding chain with the shared state's analyzer (see
+/// `ForwardingChainAnalyzer::from_env`).
+pub fn forwarding_analysis(app_state: &AppState, req: &HttpRequest) -> ForwardingAnalysis {
+    let header_values = |name: &str| -> Vec<String> {
+        req.headers()
+            .get_all(name)
+            .filter_map(|hv| hv.to_str().ok())
+            .map(ToString::to_string)
+            .collect()
+    };
+    let headers = ForwardingHeaders {
+        forwarded: header_values("Forwarded"),
+        x_forwarded_for: header_values("X-Forwarded-For"),
+        via: header_values("Via"),
+        x_real_ip: header_values("X-Real-IP").into_iter().next(),
+    };
+    let peer = req
+        .peer_addr()
+        .map(|a| a.ip())
+        .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+    app_state.forwarding.analyze(peer, &headers)
+}
+
+/// عدد التحليلات الأخيرة التي تُحسب منها درجة ثقة المستدعي.
+/// Recent analyses the caller's trust score is computed from.
+const TRUST_HISTORY: usize = 20;
+
+/// سياق سياسة المستدعي: أدواره من التوكن، وحالته ودرجة ثقته من قاعدة البيانات.
+/// The caller's policy context: roles from the token, status and trust score from the database.
+pub struct CallerPolicy {
+    user_id: Uuid,
+    roles: Vec<Role>,
+    status: UserStatus,
+    trust_score: f32,
+}
+
+impl CallerPolicy {
+    /// يحمّل حالة المستدعي ودرجة ثقته (عكس متوسط خطر تحليلاته الأخيرة). مستدعٍ بلا سجل
+    /// مستخدم يُعامل كموقوف؛ بدون قاعدة بيانات يُعامل كنشط بلا ثقة.
+    /// Loads the caller's status and trust score (the inverse of the mean risk of their
+    /// recent analyses). A caller without a user record is treated as suspended; without a
+    /// database they are treated as active with no trust.
+    ///
+    /// # Errors
+    /// Returns `tokio_rusqlite::Error` if the user or analyses cannot be read.
+    pub async fn load(
+        app_state: &AppState,
+        claims: &Claims,
+    ) -> Result<Self, tokio_rusqlite::Error> {
+        let roles = claims.roles.iter().filter_map(|r| r.parse().ok()).collect();
+        let Some(pool) = &app_state.db_pool else {
+            return Ok(Self {
+                user_id: claims.sub,
+                roles,
+                status: UserStatus::Active,
+                trust_score: 0.0,
+            });
+        };
+        let status = crud::get_user_by_id(pool, &claims.sub)
+            .await?
+            .map_or(UserStatus::Suspended, |user| {
+                UserStatus::from_record(&user.status)
+            });
+        let analyses =
+            crud::list_recent_analyses_for_entity(pool, &claims.sub.to_string(), TRUST_HISTORY)
+                .await?;
+        #[allow(clippy::cast_precision_loss)]
+        let trust_score = if analyses.is_empty() {
+            0.0
+        } else {
+            let mean_risk =
+                analyses.iter().map(|a| a.risk_score).sum::<f32>() / analyses.len() as f32;
+            (1.0 - mean_risk).clamp(0.0, 1.0)
+        };
+        Ok(Self {
+            user_id: claims.sub,
+            roles,
+            status,
+            trust_score,
         })
-        .unwrap_or(false);
+    }
 
-    if trust_x_forwarded_for {
-        return req
-            .headers()
-            .get("X-Forwarded-For")
-            .and_then(|hv| hv.to_str().ok())
-            .and_then(|v| v.split(',').next())
-            .and_then(|s| s.trim().parse::<IpAddr>().ok())
-            .or_else(|| req.peer_addr().map(|a| a.ip()))
-            .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+    #[must_use]
+    pub fn context(&self) -> PolicyContext<'_> {
+        PolicyContext {
+            user_id: self.user_id,
+            roles: &self.roles,
+            status: &self.status,
+            trust_score: self.trust_score,
+        }
     }
 
-    req.peer_addr()
-        .map(|a| a.ip())
-        .unwrap_or(IpAddr::from([0, 0, 0, 0]))
+    #[must_use]
+    pub fn allows(&self, action: &Action) -> bool {
+        PolicyEngine::can_execute(&self.context(), action).is_ok()
+    }
+}
+
+/// يتحقق من سماح السياسة للمستدعي بالإجراء؛ تعذر تحميل سياقه يعني الرفض.
+/// Checks the policy allows the caller the action; failing to load their context denies it.
+pub async fn policy_allows(app_state: &AppState, claims: &Claims, action: &Action<'_>) -> bool {
+    match CallerPolicy::load(app_state, claims).await {
+        Ok(caller) => caller.allows(action),
+        Err(e) => {
+            log::warn!("failed to load caller policy context: {e}");
+            false
+        }
+    }
+}
+
+pub fn insufficient_permissions() -> HttpResponse {
+    api_error(
+        StatusCode::FORBIDDEN,
+        "INSUFFICIENT_PERMISSIONS",
+        "Insufficient permissions",
+    )
+}
+
+pub fn database_disabled() -> HttpResponse {
+    api_error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "DATABASE_DISABLED",
+        "Database backend is disabled. Configure DATABASE_URL=sqlite://...",
+    )
+}
+
+/// يربط مدخل السلوك بالمستدعي: `entity_id` يصبح `claims.sub` ما لم يكن حساب خدمة يرسل
+/// باسم الآخرين، فلا تُقرأ أو تُلوث قوالب كيان آخر ولا خط أساسه.
+/// Binds the behavior input to the caller: `entity_id` becomes `claims.sub` unless a service
+/// account submits on behalf of others, so another entity's templates and baseline can be
+/// neither probed nor polluted.
+pub async fn bind_entity(app_state: &AppState, claims: &Claims, input: &mut BehaviorInput) {
+    let own_entity = claims.sub.to_string();
+    if input.entity_id != own_entity
+        && !policy_allows(app_state, claims, &Action::SubmitOnBehalf).await
+    {
+        input.entity_id = own_entity;
+    }
+}
+
+/// يملأ تشابه الجهاز في مدخل السلوك من أجهزة المستخدم المسجلة؛ يبقى فارغاً بدون قاعدة بيانات.
+/// Fills the behavior input's device similarity from the caller's registered devices; it
+/// stays empty without a database.
+pub async fn attach_device_similarity(
+    app_state: &AppState,
+    claims: &Claims,
+    input: &mut BehaviorInput,
+) {
+    input.device_similarity = None;
+    let Some(pool) = &app_state.db_pool else {
+        return;
+    };
+    match DeviceRegistry::new(pool.clone())
+        .device_similarity(&claims.sub, &input.device_fingerprint)
+        .await
+    {
+        Ok(similarity) => input.device_similarity = similarity,
+        Err(e) => log::warn!("device similarity lookup failed: {e}"),
+    }
+}
+
+pub fn client_ip(app_state: &AppState, req: &HttpRequest) -> IpAddr {
+    forwarding_analysis(app_state, req).client_ip
 }
 
 pub fn request_id(req: &HttpRequest) -> String {
@@ -181,7 +326,7 @@ pub async fn authorize_request(
     bearer: &BearerToken,
     payload_bytes: &web::Bytes,
 ) -> Result<Claims, HttpResponse> {
-    let ip = client_ip(req);
+    let ip = client_ip(app_state, req);
     let req_id = request_id(req);
     let path = req.path();
 
@@ -368,10 +513,19 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(auth::get_user)
             .service(geo::resolve_geo)
             .service(device::resolve_device)
+            .service(device::list_own_devices)
+            .service(device::list_user_devices)
+            .service(device::revoke_device)
+            .service(device::set_device_trust)
+            .service(device::rename_device)
             .service(behavior::analyze_behavior)
+            .service(behavior::record_access)
             .service(sensors::analyze_sensors)
             .service(network::analyze_network)
+            .service(network::decrypt_ip)
             .service(alerts::trigger_alert)
+            .service(feedback::record_verdict)
+            .service(feedback::feedback_report)
             .service(weather::weather_summary)
             .service(smart_access::smart_access_verify),
     );
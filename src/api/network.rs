@@ -27,19 +27,25 @@
 
 use crate::api::api_error;
 use crate::api::authorize_request;
+use crate::api::forwarding_analysis;
 use crate::api::ok_json_with_trace;
 use crate::api::parse_json_payload;
 use crate::api::BearerToken;
+use crate::api::{insufficient_permissions, CallerPolicy};
+use crate::core::forwarding_chain::ForwardingAnalysis;
 use crate::core::network_analyzer::{ConnectionType, NetworkInfoProvider};
+use crate::security::keyring::KeyringError;
+use crate::security::policy::Action;
 use crate::AppState;
 use actix_web::http::StatusCode;
 use actix_web::{post, web, HttpRequest, Responder};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 
 struct SimpleProvider {
     ip: IpAddr,
     conn_type: ConnectionType,
+    forwarding: Option<ForwardingAnalysis>,
 }
 
 #[async_trait::async_trait]
@@ -50,6 +56,9 @@ impl NetworkInfoProvider for SimpleProvider {
     async fn get_public_ip(&self) -> Option<IpAddr> {
         Some(self.ip)
     }
+    async fn get_forwarding_analysis(&self) -> Option<ForwardingAnalysis> {
+        self.forwarding.clone()
+    }
 }
 
 /// نموذج الطلب لتحليل الشبكة.
@@ -82,9 +91,13 @@ pub async fn analyze_network(
 
     // --- تمرير الطلب لمحرك core ---
     // Pass the request to the core network analysis engine
+    // سلسلة التمرير تخص عنوان الطلب نفسه فقط
+    // The forwarding chain only describes the requesting address itself
+    let forwarding = forwarding_analysis(&app_data, &req);
     let provider = SimpleProvider {
         ip: payload.ip,
         conn_type: payload.conn_type.clone(),
+        forwarding: (forwarding.client_ip == payload.ip).then_some(forwarding),
     };
     let engine = &app_data.x_engine.network_engine;
     match engine.analyze(&provider).await {
@@ -96,3 +109,68 @@ pub async fn analyze_network(
         ),
     }
 }
+
+/// نموذج طلب فك تشفير عنوان IP محمي.
+/// Request model for decrypting a protected IP address.
+#[derive(Deserialize)]
+pub struct DecryptIpRequest {
+    pub encrypted_ip: String,
+}
+
+#[derive(Serialize)]
+struct DecryptIpResponse {
+    ip: IpAddr,
+}
+
+/// نقطة نهاية إدارية لفك تشفير `encrypted_ip` عبر POST /network/decrypt_ip
+/// Admin endpoint to decrypt an `encrypted_ip` via POST /network/decrypt_ip
+#[post("/network/decrypt_ip")]
+pub async fn decrypt_ip(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    bearer: BearerToken,
+    payload_bytes: web::Bytes,
+) -> impl Responder {
+    let claims = match authorize_request(&app_data, &req, &bearer, &payload_bytes).await {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let payload: DecryptIpRequest = match parse_json_payload(&payload_bytes) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    let caller = match CallerPolicy::load(&app_data, &claims).await {
+        Ok(caller) => caller,
+        Err(e) => {
+            log::warn!("failed to load caller policy context: {e}");
+            return insufficient_permissions();
+        }
+    };
+    if !caller.allows(&Action::DecryptIpAddress) {
+        return insufficient_permissions();
+    }
+
+    let Some(keyring) = app_data.x_engine.network_engine.keyring() else {
+        return api_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "IP_KEYRING_UNAVAILABLE",
+            "IP keyring is not configured",
+        );
+    };
+    match keyring.decrypt_ip(payload.encrypted_ip.trim(), &caller.context()) {
+        Ok(ip) => ok_json_with_trace(&req, DecryptIpResponse { ip }),
+        Err(KeyringError::Unauthorized(_)) => insufficient_permissions(),
+        Err(KeyringError::LockFailed | KeyringError::Storage(_)) => api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "IP_KEYRING_INTERNAL_ERROR",
+            "Internal error while decrypting IP",
+        ),
+        Err(_) => api_error(
+            StatusCode::BAD_REQUEST,
+            "INVALID_ENCRYPTED_IP",
+            "Encrypted IP could not be decrypted",
+        ),
+    }
+}
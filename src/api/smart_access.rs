@@ -26,11 +26,16 @@ use crate::api::api_error;
 use crate::api::authorize_request;
 use crate::api::parse_json_payload;
 use crate::api::BearerToken;
+use crate::api::{attach_device_similarity, bind_entity};
 use crate::core::behavior_bio::BehaviorInput;
 use crate::AppState;
 use actix_web::http::StatusCode;
 use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 
+/// Arabic: خطر الإشارات (مثل تناقض IP و GPS) الذي يُرفض عنده الدخول
+/// English: Signal risk (such as an IP/GPS mismatch) at which access is denied
+const SMART_ACCESS_MAX_RISK: f32 = 0.8;
+
 /// Arabic: نموذج الطلب لنقطة نهاية التحقق المركب
 /// English: Request model for the composite verification endpoint
 #[derive(serde::Deserialize, Clone)]
@@ -51,14 +56,17 @@ pub async fn smart_access_verify(
     bearer: BearerToken,
     payload_bytes: web::Bytes,
 ) -> impl Responder {
-    if let Err(resp) = authorize_request(&data, &req, &bearer, &payload_bytes).await {
-        return resp;
-    }
+    let claims = match authorize_request(&data, &req, &bearer, &payload_bytes).await {
+        Ok(claims) => claims,
+        Err(resp) => return resp,
+    };
 
-    let payload: SmartAccessRequest = match parse_json_payload(&payload_bytes) {
+    let mut payload: SmartAccessRequest = match parse_json_payload(&payload_bytes) {
         Ok(v) => v,
         Err(resp) => return resp,
     };
+    bind_entity(&data, &claims, &mut payload.behavior_input).await;
+    attach_device_similarity(&data, &claims, &mut payload.behavior_input).await;
 
     // سياسات المناطق والأوقات (مثال، يمكن تخصيصها)
     let allowed_zones = vec!["Riyadh".to_string(), "Jeddah".to_string()];
@@ -80,8 +88,10 @@ pub async fn smart_access_verify(
         .await;
 
     match result {
-        Ok(true) => HttpResponse::Ok().body("Access granted"),
-        Ok(false) => api_error(
+        Ok(assessment) if assessment.risk() < SMART_ACCESS_MAX_RISK => {
+            HttpResponse::Ok().body("Access granted")
+        }
+        Ok(_) => api_error(
             StatusCode::FORBIDDEN,
             "SMART_ACCESS_DENIED",
             "Access denied",
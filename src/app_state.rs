@@ -1,5 +1,8 @@
+use crate::core::account_correlation::AccountCorrelator;
+use crate::core::behavior_feedback::LabeledHistory;
 use crate::core::composite_verification::CompositeVerifier;
 use crate::core::cross_location::CrossValidationEngine;
+use crate::core::forwarding_chain::ForwardingChainAnalyzer;
 use crate::core::weather_val::WeatherEngine;
 use crate::security::ai_guard::RequestAiGuard;
 use crate::security::jwt::JwtManager;
@@ -44,6 +47,15 @@ pub struct AppState {
     pub rate_limiter: Arc<RateLimiter>,
     pub ai_guard: Arc<RequestAiGuard>,
     pub api_key: Option<SecureString>,
+    /// محلل سلسلة التمرير، يُبنى مرة واحدة عند التشغيل.
+    /// Forwarding-chain analyzer, built once at startup.
+    pub forwarding: Arc<ForwardingChainAnalyzer>,
+    /// سر مشترك مع وكيل إنهاء TLS يُرسل في `X-Transport-Proxy-Secret` مع بصمة النقل.
+    /// Secret shared with the TLS-terminating proxy, sent in `X-Transport-Proxy-Secret`
+    /// alongside the transport fingerprint.
+    pub transport_proxy_secret: Option<SecureString>,
     pub alert_memory: Arc<AlertMemoryStore>,
+    pub access_correlator: Arc<AccountCorrelator>,
+    pub labeled_history: Arc<LabeledHistory>,
     pub db_pool: Option<DbPool>,
 }
@@ -0,0 +1,569 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: account_correlation.rs
+    المسار:    src/core/account_correlation.rs
+    دور الملف:
+    ربط أحداث الدخول عبر الحسابات لكشف مشاركة الحسابات وحشو بيانات الاعتماد: بصمة جهاز
+    أو IP يخدم حسابات كثيرة، وحساب نشط من عدة أجهزة أو مواقع متباعدة في الوقت نفسه،
+    ودفعات من محاولات الدخول الفاشلة على حسابات مختلفة من بادئة شبكة مشتركة (مع ASN
+    كإشارة ثانوية). كل نمط يُصدر `SecurityAlert` مع فترة تهدئة لكل مفتاح.
+    --------------------------------------------------------------
+    File Name: account_correlation.rs
+    Path:     src/core/account_correlation.rs
+    File Role:
+    Correlates access events across accounts to detect account sharing and credential
+    stuffing: one device fingerprint or IP serving many accounts, one account active from
+    several devices or distant locations at once, and bursts of failed logins across
+    accounts from a shared network prefix (with the ASN as a secondary signal). Each
+    pattern emits a `SecurityAlert`, with a cooldown per key.
+******************************************************************************************/
+
+use crate::core::behavior_pipeline::IpAttribution;
+use crate::db::models::SecurityAlert;
+use crate::utils::ip_prefix::{canonical_ip, IpPrefix};
+use crate::utils::precision::haversine_km;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// عدد الأحداث بين كل تنظيف كامل للمفاتيح المنتهية.
+/// Events between full sweeps of expired keys.
+const SWEEP_EVERY: u64 = 1024;
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// نتيجة محاولة الدخول.
+/// Outcome of an access attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessOutcome {
+    Success,
+    Failure,
+}
+
+/// حدث دخول لحساب.
+/// An access event for an account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessEvent {
+    pub account_id: Uuid,
+    pub ip: IpAddr,
+    #[serde(default)]
+    pub device_fingerprint: Option<String>,
+    #[serde(default)]
+    pub location: Option<(f64, f64)>,
+    pub outcome: AccessOutcome,
+    #[serde(default = "Utc::now")]
+    pub timestamp: DateTime<Utc>,
+}
+
+/// الأنماط التي يكشفها المحلل.
+/// Patterns detected by the analyzer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrelationPattern {
+    /// بصمة جهاز واحدة لحسابات كثيرة.
+    /// One device fingerprint for many accounts.
+    SharedDevice,
+    /// عنوان IP واحد لحسابات كثيرة.
+    /// One IP for many accounts.
+    SharedIp,
+    /// حساب واحد من عدة أجهزة في الوقت نفسه.
+    /// One account on several devices at once.
+    ConcurrentDevices,
+    /// حساب واحد من مواقع متباعدة في الوقت نفسه.
+    /// One account from distant locations at once.
+    ConcurrentLocations,
+    /// دفعة فشل دخول على حسابات مختلفة من بنية تحتية مشتركة.
+    /// A burst of failed logins across accounts from shared infrastructure.
+    CredentialStuffing,
+}
+
+impl CorrelationPattern {
+    /// قيمة `alert_type` في `SecurityAlert`.
+    /// The `alert_type` value of the `SecurityAlert`.
+    #[must_use]
+    pub const fn alert_type(self) -> &'static str {
+        match self {
+            Self::SharedDevice => "account_sharing_device",
+            Self::SharedIp => "account_sharing_ip",
+            Self::ConcurrentDevices => "concurrent_devices",
+            Self::ConcurrentLocations => "concurrent_locations",
+            Self::CredentialStuffing => "credential_stuffing",
+        }
+    }
+
+    const fn severity(self) -> &'static str {
+        match self {
+            Self::SharedIp | Self::ConcurrentDevices => "medium",
+            Self::SharedDevice | Self::ConcurrentLocations | Self::CredentialStuffing => "high",
+        }
+    }
+}
+
+/// إعدادات الربط بين الحسابات.
+/// Cross-account correlation settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorrelationConfig {
+    /// نافذة عد الحسابات لكل جهاز/IP ونافذة دفعات الفشل.
+    /// Window for accounts per device/IP and for failure bursts.
+    pub window_minutes: i64,
+    pub max_accounts_per_device: usize,
+    pub max_accounts_per_ip: usize,
+    /// الأحداث الناجحة الأقرب من هذا تُعد متزامنة.
+    /// Successful events closer than this count as concurrent.
+    pub concurrent_minutes: i64,
+    pub max_concurrent_devices: usize,
+    pub concurrent_distance_km: f64,
+    /// أدنى عدد من محاولات الفشل، وأدنى عدد من الحسابات المختلفة، لدفعة حشو.
+    /// Minimum failures, and minimum distinct accounts, for a stuffing burst.
+    pub stuffing_min_failures: usize,
+    pub stuffing_min_accounts: usize,
+    /// لا يتكرر التنبيه نفسه لنفس المفتاح خلال هذه المدة.
+    /// The same alert is not repeated for the same key within this period.
+    pub alert_cooldown_minutes: i64,
+    /// طوابع الأحداث تُحصر ضمن هذا الفرق عن وقت الخادم.
+    /// Event timestamps are clamped to within this much of server time.
+    pub max_clock_skew_minutes: i64,
+}
+
+impl Default for CorrelationConfig {
+    fn default() -> Self {
+        Self {
+            window_minutes: 60,
+            max_accounts_per_device: 3,
+            max_accounts_per_ip: 10,
+            concurrent_minutes: 15,
+            max_concurrent_devices: 2,
+            concurrent_distance_km: 500.0,
+            stuffing_min_failures: 20,
+            stuffing_min_accounts: 10,
+            alert_cooldown_minutes: 60,
+            max_clock_skew_minutes: 5,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CorrelationState {
+    observed: u64,
+    accounts_by_device: HashMap<String, VecDeque<(DateTime<Utc>, Uuid)>>,
+    accounts_by_ip: HashMap<IpAddr, VecDeque<(DateTime<Utc>, Uuid)>>,
+    sessions: HashMap<Uuid, VecDeque<AccessEvent>>,
+    failures_by_source: HashMap<String, VecDeque<(DateTime<Utc>, Uuid)>>,
+    last_alert: HashMap<(CorrelationPattern, String), DateTime<Utc>>,
+}
+
+fn prune<T>(events: &mut VecDeque<(DateTime<Utc>, T)>, cutoff: DateTime<Utc>) {
+    while events.front().is_some_and(|(at, _)| *at <= cutoff) {
+        events.pop_front();
+    }
+}
+
+/// يُدرج مع الحفاظ على ترتيب الطوابع حتى يبقى التقليم من البداية صحيحاً للأحداث المتأخرة.
+/// Inserts keeping timestamps ordered, so pruning from the front stays correct for late events.
+fn insert_ordered<T>(events: &mut VecDeque<(DateTime<Utc>, T)>, at: DateTime<Utc>, value: T) {
+    let index = events.partition_point(|(existing, _)| *existing <= at);
+    events.insert(index, (at, value));
+}
+
+fn minutes_before(at: DateTime<Utc>, minutes: i64) -> DateTime<Utc> {
+    at.checked_sub_signed(Duration::minutes(minutes))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+fn distinct_accounts(events: &VecDeque<(DateTime<Utc>, Uuid)>) -> Vec<Uuid> {
+    let mut seen = HashSet::new();
+    events
+        .iter()
+        .filter(|(_, account)| seen.insert(*account))
+        .map(|(_, account)| *account)
+        .collect()
+}
+
+// ================================================================
+// محلل الربط
+// Correlation Analyzer
+// ================================================================
+
+/// يربط أحداث الدخول عبر الحسابات في الذاكرة.
+/// Correlates access events across accounts, in memory.
+pub struct AccountCorrelator {
+    config: CorrelationConfig,
+    attribution: Option<Arc<dyn IpAttribution>>,
+    state: RwLock<CorrelationState>,
+}
+
+impl Default for AccountCorrelator {
+    fn default() -> Self {
+        Self::new(CorrelationConfig::default())
+    }
+}
+
+impl AccountCorrelator {
+    #[must_use]
+    pub fn new(config: CorrelationConfig) -> Self {
+        Self {
+            config,
+            attribution: None,
+            state: RwLock::new(CorrelationState::default()),
+        }
+    }
+
+    /// يضيف ASN المصدر إلى تنبيهات حشو بيانات الاعتماد كإشارة ثانوية.
+    /// Adds the source ASN to credential-stuffing alerts as a secondary signal.
+    #[must_use]
+    pub fn with_attribution(mut self, attribution: Arc<dyn IpAttribution>) -> Self {
+        self.attribution = Some(attribution);
+        self
+    }
+
+    #[must_use]
+    pub const fn config(&self) -> &CorrelationConfig {
+        &self.config
+    }
+
+    /// مفتاح المصدر: بادئة /24 لـ IPv4 أو /48 لـ IPv6. ASN وحده واسع جداً (مشغل جوال
+    /// كامل) فلا يُستخدم مفتاحاً.
+    /// Source key: the IPv4 /24 or IPv6 /48 prefix. An ASN alone is far too broad (a
+    /// whole mobile carrier), so it is not used as a key.
+    fn source_key(ip: IpAddr) -> String {
+        let ip = canonical_ip(ip);
+        let len = if ip.is_ipv4() { 24 } else { 48 };
+        IpPrefix::new(ip, len).map_or_else(|_| ip.to_string(), |prefix| prefix.to_string())
+    }
+
+    /// يسجل الحدث ويعيد التنبيهات التي أطلقها. طابع الحدث يأتي من المرسل فيُحصر حول
+    /// وقت الخادم.
+    /// Records the event and returns the alerts it raised. The event timestamp comes from
+    /// the sender, so it is clamped around server time.
+    pub async fn observe(&self, event: AccessEvent) -> Vec<SecurityAlert> {
+        self.observe_at(event, Utc::now()).await
+    }
+
+    async fn observe_at(&self, mut event: AccessEvent, now: DateTime<Utc>) -> Vec<SecurityAlert> {
+        let skew = self.config.max_clock_skew_minutes;
+        let earliest = minutes_before(now, skew);
+        let latest = now
+            .checked_add_signed(Duration::minutes(skew))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        event.timestamp = event.timestamp.clamp(earliest, latest);
+        let window_cutoff = minutes_before(event.timestamp, self.config.window_minutes);
+        let concurrent_cutoff = minutes_before(event.timestamp, self.config.concurrent_minutes);
+        let mut state = self.state.write().await;
+        state.observed += 1;
+        if state.observed.is_multiple_of(SWEEP_EVERY) {
+            Self::sweep(&mut state, window_cutoff.min(concurrent_cutoff));
+        }
+
+        let mut found = Vec::new();
+        match event.outcome {
+            AccessOutcome::Success => {
+                if let Some(device) = &event.device_fingerprint {
+                    let events = state.accounts_by_device.entry(device.clone()).or_default();
+                    insert_ordered(events, event.timestamp, event.account_id);
+                    prune(events, window_cutoff);
+                    let accounts = distinct_accounts(events);
+                    if accounts.len() > self.config.max_accounts_per_device {
+                        found.push((
+                            CorrelationPattern::SharedDevice,
+                            device.clone(),
+                            json!({ "device_fingerprint": device, "accounts": accounts }),
+                        ));
+                    }
+                }
+
+                let events = state.accounts_by_ip.entry(event.ip).or_default();
+                insert_ordered(events, event.timestamp, event.account_id);
+                prune(events, window_cutoff);
+                let accounts = distinct_accounts(events);
+                if accounts.len() > self.config.max_accounts_per_ip {
+                    found.push((
+                        CorrelationPattern::SharedIp,
+                        event.ip.to_string(),
+                        json!({ "ip": event.ip, "accounts": accounts }),
+                    ));
+                }
+
+                let sessions = state.sessions.entry(event.account_id).or_default();
+                while sessions
+                    .front()
+                    .is_some_and(|s| s.timestamp <= concurrent_cutoff)
+                {
+                    sessions.pop_front();
+                }
+                let devices: HashSet<_> = sessions
+                    .iter()
+                    .chain(std::iter::once(&event))
+                    .filter_map(|s| s.device_fingerprint.as_deref())
+                    .collect();
+                if devices.len() > self.config.max_concurrent_devices {
+                    found.push((
+                        CorrelationPattern::ConcurrentDevices,
+                        event.account_id.to_string(),
+                        json!({ "devices": devices }),
+                    ));
+                }
+                let farthest = event.location.and_then(|here| {
+                    sessions
+                        .iter()
+                        .filter_map(|s| s.location)
+                        .map(|there| (there, haversine_km(here.0, here.1, there.0, there.1)))
+                        .max_by(|a, b| a.1.total_cmp(&b.1))
+                });
+                if let Some((there, distance_km)) =
+                    farthest.filter(|(_, km)| *km > self.config.concurrent_distance_km)
+                {
+                    found.push((
+                        CorrelationPattern::ConcurrentLocations,
+                        event.account_id.to_string(),
+                        json!({
+                            "locations": [event.location, there],
+                            "distance_km": distance_km,
+                        }),
+                    ));
+                }
+                let index = sessions.partition_point(|s| s.timestamp <= event.timestamp);
+                sessions.insert(index, event.clone());
+            }
+            AccessOutcome::Failure => {
+                let source = Self::source_key(event.ip);
+                let events = state.failures_by_source.entry(source.clone()).or_default();
+                insert_ordered(events, event.timestamp, event.account_id);
+                prune(events, window_cutoff);
+                let failures = events.len();
+                let accounts = distinct_accounts(events);
+                if failures >= self.config.stuffing_min_failures
+                    && accounts.len() >= self.config.stuffing_min_accounts
+                {
+                    found.push((
+                        CorrelationPattern::CredentialStuffing,
+                        source.clone(),
+                        json!({
+                            "source": source,
+                            "asn": self.attribution.as_ref().and_then(|a| a.asn(event.ip)),
+                            "failures": failures,
+                            "accounts": accounts.len(),
+                        }),
+                    ));
+                }
+            }
+        }
+
+        let cooldown = Duration::minutes(self.config.alert_cooldown_minutes);
+        let mut alerts = Vec::new();
+        for (pattern, key, details) in found {
+            let last = state
+                .last_alert
+                .entry((pattern, key))
+                .or_insert(DateTime::<Utc>::MIN_UTC);
+            if event.timestamp - *last < cooldown {
+                continue;
+            }
+            *last = event.timestamp;
+            alerts.push(SecurityAlert {
+                id: Uuid::new_v4(),
+                user_id: event.account_id,
+                alert_type: pattern.alert_type().to_string(),
+                alert_data: json!({
+                    "entity_type": "user",
+                    "severity": pattern.severity(),
+                    "details": details,
+                    "window_minutes": self.config.window_minutes,
+                }),
+                created_at: event.timestamp.naive_utc(),
+            });
+        }
+        alerts
+    }
+
+    /// يحذف المفاتيح التي انتهت كل أحداثها.
+    /// Drops keys whose events have all expired.
+    fn sweep(state: &mut CorrelationState, cutoff: DateTime<Utc>) {
+        for events in state.accounts_by_device.values_mut() {
+            prune(events, cutoff);
+        }
+        state
+            .accounts_by_device
+            .retain(|_, events| !events.is_empty());
+        for events in state.accounts_by_ip.values_mut() {
+            prune(events, cutoff);
+        }
+        state.accounts_by_ip.retain(|_, events| !events.is_empty());
+        for events in state.failures_by_source.values_mut() {
+            prune(events, cutoff);
+        }
+        state
+            .failures_by_source
+            .retain(|_, events| !events.is_empty());
+        state
+            .sessions
+            .retain(|_, sessions| sessions.back().is_some_and(|s| s.timestamp > cutoff));
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // الاختبارات تمرر وقت الخادم مساوياً لطابع الحدث
+    // Tests pass server time equal to the event timestamp
+    async fn observe(correlator: &AccountCorrelator, event: AccessEvent) -> Vec<SecurityAlert> {
+        let now = event.timestamp;
+        correlator.observe_at(event, now).await
+    }
+
+    fn access(account: Uuid, ip: &str, device: &str, minute: i64) -> AccessEvent {
+        AccessEvent {
+            account_id: account,
+            ip: ip.parse().unwrap(),
+            device_fingerprint: Some(device.to_string()),
+            location: Some((24.7136, 46.6753)),
+            outcome: AccessOutcome::Success,
+            timestamp: Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap()
+                + Duration::minutes(minute),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_device_alerts_once_per_cooldown() {
+        let correlator = AccountCorrelator::default();
+        let mut raised = Vec::new();
+        for i in 0..6 {
+            let ip = format!("10.0.0.{i}");
+            raised
+                .extend(observe(&correlator, access(Uuid::new_v4(), &ip, "shared-phone", i)).await);
+        }
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].alert_type, "account_sharing_device");
+        assert_eq!(raised[0].alert_data["severity"], "high");
+        assert_eq!(
+            raised[0].alert_data["details"]["accounts"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        // خارج النافذة لا تُحسب الحسابات القديمة
+        // Accounts outside the window no longer count
+        let later = observe(
+            &correlator,
+            access(Uuid::new_v4(), "10.0.0.9", "shared-phone", 200),
+        )
+        .await;
+        assert!(later.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_devices_and_locations() {
+        let correlator = AccountCorrelator::default();
+        let account = Uuid::new_v4();
+        assert!(
+            observe(&correlator, access(account, "10.0.0.1", "phone", 0))
+                .await
+                .is_empty()
+        );
+        let mut far = access(account, "10.0.0.1", "phone", 5);
+        far.location = Some((51.5072, -0.1276));
+        let alerts = observe(&correlator, far).await;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].alert_type, "concurrent_locations");
+        assert_eq!(alerts[0].user_id, account);
+
+        observe(&correlator, access(account, "10.0.0.1", "laptop", 6)).await;
+        let alerts = observe(&correlator, access(account, "10.0.0.1", "tablet", 7)).await;
+        assert!(alerts.iter().any(|a| a.alert_type == "concurrent_devices"));
+    }
+
+    #[tokio::test]
+    async fn test_failure_burst_from_shared_prefix() {
+        let correlator = AccountCorrelator::new(CorrelationConfig {
+            stuffing_min_failures: 5,
+            stuffing_min_accounts: 5,
+            ..CorrelationConfig::default()
+        });
+        let mut raised = Vec::new();
+        for i in 0..8 {
+            let mut event = access(Uuid::new_v4(), &format!("203.0.113.{i}"), "bot", i);
+            event.outcome = AccessOutcome::Failure;
+            raised.extend(observe(&correlator, event).await);
+        }
+        // محاولات متكررة على حساب واحد ليست حشواً
+        // Repeated failures on one account are not stuffing
+        let victim = Uuid::new_v4();
+        for i in 0..8 {
+            let mut event = access(victim, "198.51.100.7", "bot", i);
+            event.outcome = AccessOutcome::Failure;
+            raised.extend(observe(&correlator, event).await);
+        }
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].alert_type, "credential_stuffing");
+        assert_eq!(raised[0].alert_data["details"]["source"], "203.0.113.0/24");
+        assert!(raised[0].alert_data["details"]["asn"].is_null());
+    }
+
+    #[test]
+    fn test_source_key_uses_network_prefix() {
+        assert_eq!(
+            AccountCorrelator::source_key("2001:db8:abcd:12::1".parse().unwrap()),
+            "2001:db8:abcd::/48"
+        );
+        assert_eq!(
+            AccountCorrelator::source_key("::ffff:198.51.100.9".parse().unwrap()),
+            "198.51.100.0/24"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_late_and_skewed_events_stay_in_the_window() {
+        let correlator = AccountCorrelator::default();
+        let now = access(Uuid::new_v4(), "10.0.0.1", "phone", 0).timestamp;
+        // حدث متأخر يصل بعد أحدث منه، ويُحسب مع البقية
+        // A late event arrives after a newer one and still counts
+        for minute in [3, 1, 2] {
+            let event = access(Uuid::new_v4(), "10.0.0.1", "shared", minute);
+            assert!(correlator.observe_at(event, now).await.is_empty());
+        }
+        let alerts = correlator
+            .observe_at(access(Uuid::new_v4(), "10.0.0.1", "shared", 0), now)
+            .await;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].alert_type, "account_sharing_device");
+
+        // طابع بعيد في المستقبل يُحصر ولا يطرد أحداث النافذة
+        // A far-future timestamp is clamped and does not evict the window
+        let state = correlator.state.read().await;
+        assert_eq!(state.accounts_by_device["shared"].len(), 4);
+        drop(state);
+        let mut future = access(Uuid::new_v4(), "10.0.0.2", "shared", 0);
+        future.timestamp = DateTime::<Utc>::MAX_UTC;
+        correlator.observe_at(future, now).await;
+        let state = correlator.state.read().await;
+        let events = &state.accounts_by_device["shared"];
+        assert_eq!(events.len(), 5);
+        assert_eq!(events.back().unwrap().0, now + Duration::minutes(5));
+        assert!(events
+            .iter()
+            .zip(events.iter().skip(1))
+            .all(|(a, b)| a.0 <= b.0));
+    }
+}
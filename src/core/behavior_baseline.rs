@@ -0,0 +1,545 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: behavior_baseline.rs
+    المسار:    src/core/behavior_baseline.rs
+    دور الملف:
+    خط أساس إحصائي لكل كيان. يتعلم الساعات المعتادة (بتوقيت الكيان المحلي)، وتجمعات
+    المواقع، والشبكات، والأجهزة، مع تلاشي أُسّي بنصف عمر قابل للضبط، ويقيس الانحراف
+    احتمالياً: كل قيمة تُقارن بأكثر قيمة معتادة للكيان. في البداية الباردة تُمزج النتيجة
+    مع نموذج احتياطي (عادة `DefaultBehavioralModel`) بحسب كمية التاريخ المتعلم.
+    --------------------------------------------------------------
+    File Name: behavior_baseline.rs
+    Path:     src/core/behavior_baseline.rs
+    File Role:
+    Statistical per-entity baseline. Learns usual hours (in the entity's local time),
+    location clusters, networks and devices with exponential decay at a configurable half
+    life, and scores deviations probabilistically: each value is compared with the
+    entity's most usual value. During cold start the result is blended with a fallback
+    model (normally `DefaultBehavioralModel`) according to how much history was learned.
+******************************************************************************************/
+
+use crate::core::behavior_bio::{BehaviorError, BehaviorInput, BehavioralModel};
+use crate::core::behavior_calendar::LocalMoment;
+use crate::utils::ip_prefix::{canonical_ip, IpPrefix};
+use crate::utils::precision::haversine_km;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// تنعيم لابلاس: يمنع أن تكون القيمة الجديدة مستحيلة تماماً.
+/// Laplace smoothing: keeps an unseen value from being strictly impossible.
+const SMOOTHING: f64 = 0.5;
+/// أقصى عدد من تجمعات المواقع لكل كيان.
+/// Maximum location clusters per entity.
+const MAX_CLUSTERS: usize = 16;
+/// القيم التي تلاشى وزنها دون هذا الحد تُحذف.
+/// Values whose weight decayed below this are dropped.
+const PRUNE_WEIGHT: f64 = 0.01;
+
+// ================================================================
+// الإعدادات
+// Configuration
+// ================================================================
+
+/// إعدادات خط الأساس.
+/// Baseline settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BaselineConfig {
+    /// نصف عمر الأحداث المتعلمة بالأيام.
+    /// Half life of learned events, in days.
+    pub half_life_days: f64,
+    /// الوزن المتعلم الذي تصبح عنده الثقة في خط الأساس كاملة.
+    /// Learned weight at which the baseline is fully trusted.
+    pub cold_start_events: f64,
+    /// نصف قطر تجمع المواقع (كم).
+    /// Location cluster radius (km).
+    pub cluster_radius_km: f64,
+    pub hour_weight: f32,
+    pub location_weight: f32,
+    pub network_weight: f32,
+    pub device_weight: f32,
+    /// الأحداث بدرجة خطر عند هذا الحد أو فوقه لا تُضاف إلى العادات.
+    /// Events scored at or above this risk are not folded into the habits.
+    pub learn_risk_threshold: f32,
+    /// أقصى فرق مقبول بين طابع وقت العميل ووقت الخادم (دقائق)؛ ما يتجاوزه يُقيد.
+    /// Maximum accepted drift between the client timestamp and server time (minutes);
+    /// anything beyond it is clamped.
+    pub max_clock_skew_minutes: i64,
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        Self {
+            half_life_days: 30.0,
+            cold_start_events: 10.0,
+            cluster_radius_km: 25.0,
+            hour_weight: 0.2,
+            location_weight: 0.3,
+            network_weight: 0.2,
+            device_weight: 0.3,
+            learn_risk_threshold: 0.8,
+            max_clock_skew_minutes: 5,
+        }
+    }
+}
+
+// ================================================================
+// خط أساس الكيان
+// Entity Baseline
+// ================================================================
+
+/// تجمع مواقع: مركز مرجح ووزن متلاشٍ.
+/// A location cluster: weighted centroid and decayed weight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationCluster {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub weight: f64,
+}
+
+/// الانحراف عن خط الأساس لكل بُعد (0.0 معتاد، 1.0 لم يُرَ من قبل).
+/// Per-dimension deviation from the baseline (0.0 usual, 1.0 never seen).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BaselineDeviation {
+    pub hour: f64,
+    pub location: f64,
+    pub network: f64,
+    pub device: f64,
+    /// 0.0 (لا تاريخ) إلى 1.0 (تاريخ كافٍ).
+    /// 0.0 (no history) to 1.0 (enough history).
+    pub confidence: f64,
+}
+
+/// العادات المتعلمة لكيان واحد.
+/// The learned habits of a single entity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityBaseline {
+    pub hours: [f64; 24],
+    pub clusters: Vec<LocationCluster>,
+    pub networks: HashMap<String, f64>,
+    pub devices: HashMap<String, f64>,
+    pub vpn: f64,
+    pub total: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// ندرة القيمة مقارنة بأكثر قيمة معتادة.
+/// Rarity of a value compared with the most usual value.
+fn rarity(weight: f64, max_weight: f64) -> f64 {
+    1.0 - (weight + SMOOTHING) / (max_weight.max(weight) + SMOOTHING)
+}
+
+fn max_weight<'a>(weights: impl Iterator<Item = &'a f64>) -> f64 {
+    weights.copied().fold(0.0, f64::max)
+}
+
+/// مفتاح الشبكة: نوع الاتصال مع بادئة /24 لـ IPv4 أو /48 لـ IPv6.
+/// Network key: connection type with the IPv4 /24 or IPv6 /48 prefix.
+fn network_key(input: &BehaviorInput) -> String {
+    let prefix = input
+        .network_info
+        .ip_address
+        .parse::<IpAddr>()
+        .ok()
+        .map(canonical_ip)
+        .and_then(|ip| IpPrefix::new(ip, if ip.is_ipv4() { 24 } else { 48 }).ok())
+        .map_or_else(
+            || input.network_info.ip_address.clone(),
+            |prefix| prefix.to_string(),
+        );
+    format!(
+        "{}|{prefix}",
+        input.network_info.connection_type.to_lowercase()
+    )
+}
+
+/// الساعة المحلية للحدث (المنطقة الزمنية من الملف أو من الموقع).
+/// Local hour of the event (timezone from the profile or from the location).
+fn local_hour(input: &BehaviorInput) -> usize {
+    LocalMoment::of(input).hour as usize % 24
+}
+
+impl EntityBaseline {
+    fn new(at: DateTime<Utc>) -> Self {
+        Self {
+            hours: [0.0; 24],
+            clusters: Vec::new(),
+            networks: HashMap::new(),
+            devices: HashMap::new(),
+            vpn: 0.0,
+            total: 0.0,
+            updated_at: at,
+        }
+    }
+
+    /// يطبق التلاشي حتى لحظة `at`.
+    /// Applies decay up to `at`.
+    fn decay_to(&mut self, at: DateTime<Utc>, config: &BaselineConfig) {
+        #[allow(clippy::cast_precision_loss)]
+        let days = (at - self.updated_at).num_seconds() as f64 / 86_400.0;
+        if days <= 0.0 || config.half_life_days <= 0.0 {
+            return;
+        }
+        let factor = 0.5_f64.powf(days / config.half_life_days);
+        self.hours.iter_mut().for_each(|h| *h *= factor);
+        self.clusters.iter_mut().for_each(|c| c.weight *= factor);
+        self.networks.values_mut().for_each(|w| *w *= factor);
+        self.devices.values_mut().for_each(|w| *w *= factor);
+        self.vpn *= factor;
+        self.total *= factor;
+        self.clusters.retain(|c| c.weight >= PRUNE_WEIGHT);
+        self.networks.retain(|_, w| *w >= PRUNE_WEIGHT);
+        self.devices.retain(|_, w| *w >= PRUNE_WEIGHT);
+        self.updated_at = at;
+    }
+
+    /// أقرب تجمع ضمن نصف القطر.
+    /// Closest cluster within the radius.
+    fn nearest_cluster(&self, location: (f64, f64), radius_km: f64) -> Option<usize> {
+        self.clusters
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                (
+                    i,
+                    haversine_km(c.latitude, c.longitude, location.0, location.1),
+                )
+            })
+            .filter(|(_, distance)| *distance <= radius_km)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// يقيس انحراف المُدخل عن العادات (بعد تطبيق التلاشي حتى وقته).
+    /// Measures the input's deviation from the habits (decayed to its time).
+    #[must_use]
+    pub fn deviation(&self, input: &BehaviorInput, config: &BaselineConfig) -> BaselineDeviation {
+        let mut decayed = self.clone();
+        decayed.decay_to(input.timestamp, config);
+        decayed.raw_deviation(input, config)
+    }
+
+    fn raw_deviation(&self, input: &BehaviorInput, config: &BaselineConfig) -> BaselineDeviation {
+        // الساعات المجاورة تحسب بنصف وزن حتى لا تُعاقب 09:00 لمن يعمل عادة 08:00
+        // Neighboring hours count at half weight so 09:00 is not penalized for an 08:00 user
+        let smoothed =
+            |h: usize| self.hours[h] + 0.5 * (self.hours[(h + 23) % 24] + self.hours[(h + 1) % 24]);
+        let hour_max = (0..24).map(smoothed).fold(0.0, f64::max);
+        let hour = rarity(smoothed(local_hour(input)), hour_max);
+
+        let cluster_max = max_weight(self.clusters.iter().map(|c| &c.weight));
+        let location = rarity(
+            self.nearest_cluster(input.location, config.cluster_radius_km)
+                .map_or(0.0, |i| self.clusters[i].weight),
+            cluster_max,
+        );
+
+        let network_rarity = rarity(
+            self.networks
+                .get(&network_key(input))
+                .copied()
+                .unwrap_or(0.0),
+            max_weight(self.networks.values()),
+        );
+        let vpn_rarity = if input.network_info.is_vpn {
+            rarity(self.vpn, self.total)
+        } else {
+            0.0
+        };
+
+        let device = rarity(
+            self.devices
+                .get(&input.device_fingerprint)
+                .copied()
+                .unwrap_or(0.0),
+            max_weight(self.devices.values()),
+        );
+
+        BaselineDeviation {
+            hour,
+            location,
+            network: network_rarity.max(vpn_rarity),
+            device,
+            confidence: if config.cold_start_events > 0.0 {
+                (self.total / config.cold_start_events).min(1.0)
+            } else {
+                1.0
+            },
+        }
+    }
+
+    /// يضيف المُدخل إلى العادات.
+    /// Folds the input into the habits.
+    fn learn(&mut self, input: &BehaviorInput, config: &BaselineConfig) {
+        self.hours[local_hour(input)] += 1.0;
+        match self.nearest_cluster(input.location, config.cluster_radius_km) {
+            Some(i) => {
+                let cluster = &mut self.clusters[i];
+                let weight = cluster.weight + 1.0;
+                cluster.latitude += (input.location.0 - cluster.latitude) / weight;
+                cluster.longitude += (input.location.1 - cluster.longitude) / weight;
+                cluster.weight = weight;
+            }
+            None => {
+                if self.clusters.len() >= MAX_CLUSTERS {
+                    if let Some(weakest) = self
+                        .clusters
+                        .iter()
+                        .enumerate()
+                        .min_by(|a, b| a.1.weight.total_cmp(&b.1.weight))
+                        .map(|(i, _)| i)
+                    {
+                        self.clusters.swap_remove(weakest);
+                    }
+                }
+                self.clusters.push(LocationCluster {
+                    latitude: input.location.0,
+                    longitude: input.location.1,
+                    weight: 1.0,
+                });
+            }
+        }
+        *self.networks.entry(network_key(input)).or_default() += 1.0;
+        *self
+            .devices
+            .entry(input.device_fingerprint.clone())
+            .or_default() += 1.0;
+        if input.network_info.is_vpn {
+            self.vpn += 1.0;
+        }
+        self.total += 1.0;
+    }
+}
+
+// ================================================================
+// النموذج السلوكي
+// Behavioral Model
+// ================================================================
+
+/// نموذج خط الأساس لكل كيان مع نموذج احتياطي للبداية الباردة.
+/// Per-entity baseline model with a cold-start fallback model.
+pub struct BaselineBehavioralModel {
+    fallback: Arc<dyn BehavioralModel>,
+    config: BaselineConfig,
+    baselines: RwLock<HashMap<String, EntityBaseline>>,
+}
+
+impl BaselineBehavioralModel {
+    #[must_use]
+    pub fn new(fallback: Arc<dyn BehavioralModel>) -> Self {
+        Self {
+            fallback,
+            config: BaselineConfig::default(),
+            baselines: RwLock::new(HashMap::new()),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_config(mut self, config: BaselineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// يحلل المُدخل بالنسبة إلى وقت الخادم `now`: طابع وقت العميل يُقيد ضمن
+    /// `now ± max_clock_skew_minutes` فلا يستطيع تزوير الساعة أو محو العادات بالتلاشي.
+    /// Analyzes the input against server time `now`: the client timestamp is clamped to
+    /// `now ± max_clock_skew_minutes`, so it cannot forge the hour or decay the habits away.
+    ///
+    /// # Errors
+    /// Returns the fallback model's error.
+    pub async fn analyze_at(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+        now: DateTime<Utc>,
+    ) -> Result<f32, BehaviorError> {
+        let config = &self.config;
+        let skew = Duration::minutes(config.max_clock_skew_minutes.max(0));
+        let mut current = current.clone();
+        current.timestamp = current.timestamp.clamp(now - skew, now + skew);
+        let current = &current;
+        let fallback = self.fallback.analyze(current, history).await?;
+
+        let mut baselines = self.baselines.write().await;
+        let baseline = baselines
+            .entry(current.entity_id.clone())
+            .or_insert_with(|| EntityBaseline::new(current.timestamp));
+        baseline.decay_to(current.timestamp, config);
+        let deviation = baseline.raw_deviation(current, config);
+
+        let weights = [
+            config.hour_weight,
+            config.location_weight,
+            config.network_weight,
+            config.device_weight,
+        ]
+        .map(f64::from);
+        let weight_sum: f64 = weights.iter().sum();
+        let learned = if weight_sum > 0.0 {
+            (weights[0] * deviation.hour
+                + weights[1] * deviation.location
+                + weights[2] * deviation.network
+                + weights[3] * deviation.device)
+                / weight_sum
+        } else {
+            0.0
+        };
+        let blended = deviation
+            .confidence
+            .mul_add(learned, (1.0 - deviation.confidence) * f64::from(fallback));
+        #[allow(clippy::cast_possible_truncation)]
+        let score = blended.clamp(0.0, 1.0) as f32;
+        // الأحداث الخطرة لا تُتعلم، وإلا صار الهجوم المتكرر عادة
+        // Risky events are not learned, or a repeated attack would become a habit
+        if score < config.learn_risk_threshold {
+            baseline.learn(current, config);
+        }
+        Ok(score)
+    }
+
+    /// خط الأساس الحالي للكيان إن وُجد.
+    /// The entity's current baseline, if any.
+    pub async fn baseline(&self, entity_id: &str) -> Option<EntityBaseline> {
+        self.baselines.read().await.get(entity_id).cloned()
+    }
+}
+
+#[async_trait]
+impl BehavioralModel for BaselineBehavioralModel {
+    async fn analyze(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<f32, BehaviorError> {
+        self.analyze_at(current, history, Utc::now()).await
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::behavior_bio::{DefaultBehavioralModel, NetworkInfo};
+    use chrono::{Duration, TimeZone};
+
+    struct FixedModel(f32);
+    #[async_trait]
+    impl BehavioralModel for FixedModel {
+        async fn analyze(
+            &self,
+            _: &BehaviorInput,
+            _: &VecDeque<BehaviorInput>,
+        ) -> Result<f32, BehaviorError> {
+            Ok(self.0)
+        }
+    }
+
+    /// مستخدم في الرياض يعمل ليلاً (23:00 بتوقيت الرياض = 20:00 UTC) عبر VPN.
+    /// A Riyadh user who works at night (23:00 Riyadh = 20:00 UTC) over a VPN.
+    fn night_shift(at: DateTime<Utc>) -> BehaviorInput {
+        BehaviorInput {
+            entity_id: "u1".to_string(),
+            timestamp: at,
+            location: (24.7136, 46.6753),
+            network_info: NetworkInfo {
+                ip_address: "185.23.10.44".to_string(),
+                is_vpn: true,
+                connection_type: "WiFi".to_string(),
+            },
+            device_fingerprint: "laptop".to_string(),
+            device_similarity: None,
+            keystrokes: None,
+            pointer_streams: Vec::new(),
+            timezone: Some(chrono_tz::Asia::Riyadh),
+        }
+    }
+
+    /// يحلل المُدخل وكأن ساعة الخادم تطابق طابع وقته.
+    /// Analyzes the input as if the server clock matched its timestamp.
+    async fn analyze_on_time(model: &BaselineBehavioralModel, input: &BehaviorInput) -> f32 {
+        model
+            .analyze_at(input, &VecDeque::new(), input.timestamp)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cold_start_uses_fallback() {
+        let model = BaselineBehavioralModel::new(Arc::new(FixedModel(0.42)));
+        let start = Utc.with_ymd_and_hms(2025, 3, 1, 20, 0, 0).unwrap();
+        let score = analyze_on_time(&model, &night_shift(start)).await;
+        assert!((score - 0.42).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_learned_habits_score_low_and_deviations_high() {
+        let model = BaselineBehavioralModel::new(Arc::new(DefaultBehavioralModel::default()));
+        let start = Utc.with_ymd_and_hms(2025, 3, 1, 20, 0, 0).unwrap();
+        for day in 0..20 {
+            analyze_on_time(&model, &night_shift(start + Duration::days(day))).await;
+        }
+        let baseline = model.baseline("u1").await.unwrap();
+        assert!(baseline.hours[23] > baseline.hours[20]);
+
+        // الوقت المعتاد مع VPN المعتاد: منخفض رغم أن VPN يُعاقب في النموذج الافتراضي
+        // Usual hour with the usual VPN: low although the default model penalizes VPN
+        let usual = analyze_on_time(&model, &night_shift(start + Duration::days(21))).await;
+        assert!(usual < 0.1, "{usual}");
+
+        let mut unusual = night_shift(start + Duration::days(22) + Duration::hours(12));
+        unusual.location = (21.4858, 39.1925); // Jeddah
+        unusual.network_info.ip_address = "5.1.2.3".to_string();
+        unusual.device_fingerprint = "unknown-phone".to_string();
+        let unusual = analyze_on_time(&model, &unusual).await;
+        assert!(unusual > 0.8, "{unusual}");
+        // الحدث الخطر لم يُضف إلى العادات
+        // The risky event was not folded into the habits
+        let after = model.baseline("u1").await.unwrap();
+        assert!(!after.devices.contains_key("unknown-phone"));
+    }
+
+    #[tokio::test]
+    async fn test_old_habits_decay() {
+        let config = BaselineConfig {
+            half_life_days: 7.0,
+            ..BaselineConfig::default()
+        };
+        let model = BaselineBehavioralModel::new(Arc::new(FixedModel(0.0))).with_config(config);
+        let start = Utc.with_ymd_and_hms(2025, 3, 1, 20, 0, 0).unwrap();
+        for day in 0..10 {
+            analyze_on_time(&model, &night_shift(start + Duration::days(day))).await;
+        }
+        let baseline = model.baseline("u1").await.unwrap();
+        let fresh = baseline.deviation(&night_shift(start + Duration::days(10)), &config);
+        let stale = baseline.deviation(&night_shift(start + Duration::days(100)), &config);
+        assert!(fresh.confidence > 0.5, "{fresh:?}");
+        assert!(stale.confidence < 0.01, "{stale:?}");
+
+        // طابع وقت مزور بعيد في المستقبل يُقيد بوقت الخادم فلا يمحو العادات
+        // A forged far-future timestamp is clamped to server time and does not erase the habits
+        let now = start + Duration::days(10);
+        model
+            .analyze_at(
+                &night_shift(start + Duration::days(100)),
+                &VecDeque::new(),
+                now,
+            )
+            .await
+            .unwrap();
+        let kept = model.baseline("u1").await.unwrap();
+        assert!(kept.total > baseline.total * 0.5, "{kept:?}");
+    }
+}
@@ -36,22 +36,28 @@
     5.  Design for testability and integration with all project systems and AI.
 ******************************************************************************************/
 
+use crate::core::behavior_calendar::{resolve_timezone, ActivityCalendar, LocalMoment};
+use crate::core::behavior_pipeline::IpAttribution;
+use crate::core::keystroke::KeystrokeSample;
+use crate::core::pointer::PointerStream;
 use crate::utils::precision::{speed_kmh, time_delta_secs};
 use async_trait::async_trait;
 #[cfg(test)]
 use chrono::TimeZone;
-use chrono::{DateTime, Timelike, Utc};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
+use tokio_rusqlite::Connection;
 use uuid::Uuid;
-// use sqlx::PgPool; // تم التعليق بعد التحويل إلى sea-orm
 
 // --- Local Imports ---
-use crate::db::models::User;
-use crate::security::policy::PolicyError;
+use crate::db::crud;
+use crate::db::models::{Device, User};
+use crate::security::policy::{Action, PolicyContext, PolicyEngine, PolicyError};
 
 // ================================================================
 // الأخطاء المخصصة للوحدة
@@ -93,6 +99,24 @@ pub struct BehaviorInput {
     pub location: (f64, f64), // (latitude, longitude)
     pub network_info: NetworkInfo,
     pub device_fingerprint: String,
+    /// تشابه الجهاز مع أقرب جهاز معروف، يحسبه الخادم من سجل الأجهزة ولا يُقبل من العميل.
+    /// Similarity of the device to the closest known device, computed by the server from
+    /// the device registry and never accepted from the client.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub device_similarity: Option<f32>,
+    /// توقيتات الكتابة لعبارة معروفة أو نص حر (انظر `KeystrokeDynamicsModel`).
+    /// Typing timings for a known phrase or free text (see `KeystrokeDynamicsModel`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keystrokes: Option<KeystrokeSample>,
+    /// تدفقات السحب وحركة الفأرة (انظر `PointerDynamicsModel`).
+    /// Swipe and mouse movement streams (see `PointerDynamicsModel`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pointer_streams: Vec<PointerStream>,
+    /// المنطقة الزمنية للكيان (IANA مثل "Asia/Riyadh") من ملفه؛ إذا غابت تُستنتج من الموقع.
+    /// The entity's timezone (IANA, e.g. "Asia/Riyadh") from its profile; when absent it
+    /// is derived from the location (see `behavior_calendar::resolve_timezone`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
 }
 
 /// معلومات الشبكة المرفقة مع كل سلوك.
@@ -112,6 +136,10 @@ pub struct AnalysisResult {
     pub risk_level: RiskLevel,
     pub anomaly_detected: bool,
     pub reasoning: String,
+    /// كل حالات الشذوذ المكتشفة بترتيب الكشف.
+    /// Every detected anomaly, in detection order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<Anomaly>,
 }
 
 impl PartialEq for AnalysisResult {
@@ -120,12 +148,44 @@ impl PartialEq for AnalysisResult {
             && self.risk_level == other.risk_level
             && self.anomaly_detected == other.anomaly_detected
             && self.reasoning == other.reasoning
+            && self.anomalies == other.anomalies
+    }
+}
+
+/// حالة شذوذ برمز ثابت وخطورة ورسالة.
+/// An anomaly with a stable code, severity and message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anomaly {
+    /// رمز ثابت مثل `impossible_travel` أو `new_country`.
+    /// Stable code such as `impossible_travel` or `new_country`.
+    pub code: String,
+    pub severity: RiskLevel,
+    pub message: String,
+    /// وزن الشذوذ في درجة الخطورة (0.0 = للإبلاغ فقط).
+    /// Weight of the anomaly in the risk score (0.0 = reported only).
+    #[serde(default)]
+    pub weight: f32,
+}
+
+impl Anomaly {
+    /// مساهمة الشذوذ في درجة الخطورة: الوزن × قيمة الخطورة.
+    /// The anomaly's contribution to the risk score: weight × severity value.
+    #[must_use]
+    pub fn risk(&self) -> f32 {
+        let severity = match self.severity {
+            RiskLevel::None => 0.0,
+            RiskLevel::Low => 0.25,
+            RiskLevel::Medium => 0.5,
+            RiskLevel::High => 0.75,
+            RiskLevel::Critical => 1.0,
+        };
+        (self.weight * severity).clamp(0.0, 1.0)
     }
 }
 
 /// مستويات الخطورة الممكنة.
 /// Possible risk levels.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RiskLevel {
     None,
     Low,
@@ -150,6 +210,14 @@ pub trait BehavioralModel: Send + Sync {
         current: &BehaviorInput,
         history: &VecDeque<BehaviorInput>,
     ) -> Result<f32, BehaviorError>;
+
+    /// هل في المُدخل بيانات يقيّمها النموذج؟ النماذج التي لا تجد بياناتها (مثل الكتابة
+    /// دون `keystrokes`) تُستبعد من أوزان `ModelEnsemble`.
+    /// Does the input carry data this model scores? Models that find none (such as typing
+    /// without `keystrokes`) are left out of the `ModelEnsemble` weights.
+    fn has_signal(&self, _current: &BehaviorInput) -> bool {
+        true
+    }
 }
 
 /// واجهة لكاشف الشذوذ.
@@ -163,6 +231,28 @@ pub trait AnomalyDetector: Send + Sync {
         current: &BehaviorInput,
         history: &VecDeque<BehaviorInput>,
     ) -> Result<Option<String>, BehaviorError>;
+
+    /// يعيد كل حالات الشذوذ برموزها وخطورتها. التطبيق الافتراضي يغلف `detect`
+    /// برمز `anomaly` وخطورة عالية ووزن صفري (للإبلاغ فقط).
+    /// Returns every anomaly with its code and severity. The default implementation
+    /// wraps `detect` with the `anomaly` code, high severity and zero weight (reported only).
+    async fn detect_all(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Vec<Anomaly>, BehaviorError> {
+        Ok(self
+            .detect(current, history)
+            .await?
+            .map(|message| Anomaly {
+                code: "anomaly".to_string(),
+                severity: RiskLevel::High,
+                message,
+                weight: 0.0,
+            })
+            .into_iter()
+            .collect())
+    }
 }
 
 // ================================================================
@@ -174,6 +264,7 @@ pub struct BehaviorEngine {
     detector: Arc<dyn AnomalyDetector>,
     history: RwLock<VecDeque<BehaviorInput>>,
     history_limit: usize,
+    attribution: Option<Arc<dyn IpAttribution>>,
 }
 
 impl BehaviorEngine {
@@ -189,36 +280,67 @@ impl BehaviorEngine {
             detector,
             history: RwLock::new(VecDeque::with_capacity(history_limit)),
             history_limit,
+            attribution: None,
         }
     }
 
+    /// Arabic: مصدر المنطقة الزمنية من سجل GeoIP، يُقدَّم على الاستنتاج من الموقع.
+    /// English: Source of the timezone from the GeoIP record, preferred over deriving it
+    /// from the location.
+    #[must_use]
+    pub fn with_attribution(mut self, attribution: Arc<dyn IpAttribution>) -> Self {
+        self.attribution = Some(attribution);
+        self
+    }
+
     /// تنفيذ تحليل كامل لسلوك واحد.
     /// Executes a full analysis for a single behavior.
     ///
     /// # Errors
     /// يعيد `BehaviorError` إذا فشلت مكونات الكشف أو النموذج أو الوصول إلى التاريخ.
     /// Returns `BehaviorError` if detector/model fail or history access fails.
-    pub async fn process(&self, input: BehaviorInput) -> Result<AnalysisResult, BehaviorError> {
+    pub async fn process(&self, mut input: BehaviorInput) -> Result<AnalysisResult, BehaviorError> {
+        // 0. تثبيت المنطقة الزمنية (الملف، ثم GeoIP، ثم الموقع) حتى تقيّم النماذج والتاريخ
+        //    بنفس التوقيت المحلي
+        // 0. Pin the timezone (profile, then GeoIP, then location) so models and history
+        //    share the same local time
+        if input.timezone.is_none() {
+            input.timezone = self.attribution.as_ref().and_then(|attribution| {
+                attribution.timezone(input.network_info.ip_address.parse().ok()?)
+            });
+        }
+        input.timezone = Some(resolve_timezone(&input));
         let history_guard = self.history.read().await;
 
         // 1. كشف الشذوذ
         // 1. Anomaly Detection
-        let anomaly = self.detector.detect(&input, &history_guard).await?;
+        let anomalies = self.detector.detect_all(&input, &history_guard).await?;
 
-        // 2. تحليل النموذج السلوكي لتحديد درجة الخطورة
-        // 2. Behavioral model analysis to determine risk score
-        let risk_score = self.model.analyze(&input, &history_guard).await?;
+        // 2. تحليل النموذج السلوكي لتحديد درجة الخطورة، ورفعها بمجموع أوزان الشذوذ
+        // 2. Behavioral model analysis to determine risk score, raised by weighted anomalies
+        let model_score = self.model.analyze(&input, &history_guard).await?;
+        let anomaly_score = anomalies.iter().map(Anomaly::risk).sum::<f32>().min(1.0);
+        let risk_score = model_score.max(anomaly_score);
 
         let risk_level = Self::score_to_level(risk_score);
 
         // 3. بناء النتيجة النهائية
         // 3. Construct the final result
+        let reasoning = if anomalies.is_empty() {
+            "Behavior is within normal parameters.".to_string()
+        } else {
+            anomalies
+                .iter()
+                .map(|a| a.message.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
         let result = AnalysisResult {
             risk_score,
             risk_level,
-            anomaly_detected: anomaly.is_some(),
-            reasoning: anomaly
-                .unwrap_or_else(|| "Behavior is within normal parameters.".to_string()),
+            anomaly_detected: !anomalies.is_empty(),
+            reasoning,
+            anomalies,
         };
 
         // 4. تحديث السجل التاريخي (بعد انتهاء القراءة)
@@ -253,7 +375,10 @@ impl BehaviorEngine {
 
 /// تطبيق افتراضي ذكي لنموذج تحليل السلوك.
 /// A smart default implementation for the behavioral model.
-pub struct DefaultBehavioralModel;
+#[derive(Debug, Clone, Default)]
+pub struct DefaultBehavioralModel {
+    calendar: ActivityCalendar,
+}
 
 #[async_trait]
 impl BehavioralModel for DefaultBehavioralModel {
@@ -265,7 +390,7 @@ impl BehavioralModel for DefaultBehavioralModel {
         // 1. حساب درجة المخاطرة الأولية بناءً على القواعد
         // 1. Calculate initial risk score based on rules
         let mut score: f32 = 0.0;
-        if Self::is_suspicious_time(current.timestamp) {
+        if self.is_suspicious_time(current) {
             score += 0.3;
         }
 
@@ -279,7 +404,12 @@ impl BehavioralModel for DefaultBehavioralModel {
         // 3. History factor: comparison with previous device fingerprint
         if let Some(prev) = history.back() {
             if prev.device_fingerprint != current.device_fingerprint {
-                score += 0.4;
+                // الانحراف البسيط (تحديث متصفح) يُخصم بقدر ما تغير فقط
+                // Minor drift (a browser update) only costs as much as what changed
+                let dissimilarity = current
+                    .device_similarity
+                    .map_or(1.0, |similarity| (1.0 - similarity).clamp(0.0, 1.0));
+                score += 0.4 * dissimilarity;
             }
         } else {
             // أول ظهور للكيان، يعتبر مخاطرة منخفضة
@@ -292,10 +422,28 @@ impl BehavioralModel for DefaultBehavioralModel {
 }
 
 impl DefaultBehavioralModel {
+    /// Arabic: نموذج بالتقويم الافتراضي (نهاية أسبوع الجمعة والسبت).
+    /// English: A model with the default calendar (a Friday/Saturday weekend).
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Arabic: تقويم النشاط المستخدم لساعات الهدوء (مثلاً السبت والأحد نهاية أسبوع في الإمارات).
+    /// English: The activity calendar used for quiet hours (e.g. a Saturday/Sunday weekend
+    /// in the UAE).
+    #[must_use]
+    pub fn with_calendar(mut self, calendar: ActivityCalendar) -> Self {
+        self.calendar = calendar;
+        self
+    }
+
     /// Checks if the event occurs at a suspicious time (e.g., late at night).
-    fn is_suspicious_time(timestamp: DateTime<Utc>) -> bool {
-        let hour = timestamp.hour();
-        (0..=5).contains(&hour) // Between midnight and 5 AM
+    /// يُقيّم بالتوقيت المحلي للكيان، مع ساعات هدوء مختلفة لنهاية الأسبوع ورمضان.
+    /// Evaluated in the entity's local time, with shifted quiet hours on the weekend and
+    /// during Ramadan (see `ActivityCalendar`).
+    fn is_suspicious_time(&self, input: &BehaviorInput) -> bool {
+        self.calendar.is_quiet(&LocalMoment::of(input))
     }
 }
 
@@ -337,6 +485,24 @@ impl AnomalyDetector for DefaultAnomalyDetector {
 
         Ok(None)
     }
+
+    async fn detect_all(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Vec<Anomaly>, BehaviorError> {
+        Ok(self
+            .detect(current, history)
+            .await?
+            .map(|message| Anomaly {
+                code: "impossible_travel".to_string(),
+                severity: RiskLevel::High,
+                message,
+                weight: 0.0,
+            })
+            .into_iter()
+            .collect())
+    }
 }
 
 /// دالة حساب المسافة بين نقطتين على الكرة الأرضية.
@@ -350,72 +516,144 @@ fn haversine_distance(p1: (f64, f64), p2: (f64, f64)) -> f64 {
 // User Service
 // ================================================================
 
+/// عدد التحليلات الحديثة في الملخص السلوكي.
+/// Recent analyses included in the behavioral summary.
+const RECENT_ANALYSES: usize = 10;
+/// عدد آخر المواقع في الملخص السلوكي.
+/// Last locations included in the behavioral summary.
+const RECENT_LOCATIONS: usize = 5;
+
+/// Arabic: موقع ظهر فيه المستخدم في تحليل مخزن.
+/// English: A location the user was seen at in a stored analysis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationSighting {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub seen_at: DateTime<Utc>,
+}
+
+/// Arabic: درجة مخاطر من تحليل مخزن.
+/// English: A risk score from a stored analysis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskSnapshot {
+    pub analysis_id: Uuid,
+    pub source: String,
+    pub risk_score: f32,
+    pub rules: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Arabic: ملخص سلوك المستخدم: أجهزته وآخر مواقعه وأحدث درجات مخاطره.
+/// English: Summary of a user's behavior: their devices, last locations and recent risk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BehavioralSummary {
+    pub devices: Vec<Device>,
+    pub last_locations: Vec<LocationSighting>,
+    pub recent_risk: Vec<RiskSnapshot>,
+    /// متوسط `recent_risk`؛ غائب إذا لم توجد تحليلات.
+    /// Mean of `recent_risk`; absent when there are no analyses.
+    pub average_recent_risk: Option<f32>,
+}
+
+/// Arabic: ملف المستخدم مع ملخصه السلوكي.
+/// English: A user profile with its behavioral summary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfileData {
+    #[serde(flatten)]
+    pub user: User,
+    pub behavior: BehavioralSummary,
+}
+
 /// Arabic: يوفر وظائف لإدارة المستخدمين والتفاعل معهم، مع تطبيق سياسات الأمان.
 /// English: Provides functions to manage and interact with users, applying security policies.
 pub struct UserService {
-    // pool: Arc<PgPool>, // تم التعليق بعد التحويل إلى sea-orm
-}
-
-impl Default for UserService {
-    fn default() -> Self {
-        Self::new()
-    }
+    pool: Connection,
 }
 
 impl UserService {
-    /// Arabic: إنشاء نسخة جديدة من خدمة المستخدم.
-    /// English: Creates a new instance of the user service.
+    /// Arabic: إنشاء نسخة جديدة من خدمة المستخدم فوق اتصال SQLite.
+    /// English: Creates a new instance of the user service over a SQLite connection.
     #[must_use]
-    pub const fn new(// pool: Arc<PgPool>, // تم التعليق بعد التحويل إلى sea-orm
-    ) -> Self {
-        Self {
-            // pool, // تم التعليق بعد التحويل إلى sea-orm
-        }
+    pub const fn new(pool: Connection) -> Self {
+        Self { pool }
     }
 
-    /// Arabic: جلب بيانات ملف شخصي لمستخدم مع التحقق من الصلاحيات.
-    /// English: Fetches a user's profile data, with permission checking.
+    /// Arabic: جلب بيانات ملف شخصي لمستخدم مع التحقق من الصلاحيات وفق سياق سياسة مقدم
+    /// الطلب (أدواره وحالته ودرجة ثقته). يعيد `None` إذا لم يوجد المستخدم المستهدف.
+    /// English: Fetches a user's profile data, checking permissions against the requester's
+    /// policy context (roles, status and trust score). Returns `None` if the target user
+    /// does not exist.
     ///
     /// # Errors
-    /// Returns `BehaviorError` when DB access or policy check fails (once implemented).
-    pub fn get_user_profile_data(
+    /// Returns `BehaviorError::PolicyError` when the policy denies the read and
+    /// `BehaviorError::DatabaseError` when DB access fails.
+    pub async fn get_user_profile_data(
         &self,
-        _requester_id: Uuid,
-        _target_user_id: Uuid,
-    ) -> Result<User, BehaviorError> {
-        // --- 1. جلب بيانات مُقدم الطلب ---
-        // --- 1. Fetch requester's data ---
-        // let requester = crud::get_user_by_id(&self.pool, requester_id).await?;
-
-        // --- 2. بناء سياق السياسة ---
-        // --- 2. Build the Policy Context ---
-        // let roles: Vec<Role> = requester.roles.iter()
-        //     .map(|r| Role::from_str(r).unwrap_or(Role::User))
-        //     .collect();
-
-        // let status = match requester.status.as_str() {
-        //     "Active" => UserStatus::Active,
-        //     "Suspended" => UserStatus::Suspended,
-        //     "Banned" => UserStatus::Banned,
-        //     _ => UserStatus::Suspended, // Default to suspended if status is unknown
-        // };
-
-        // let context = PolicyContext {
-        //     user_id: requester.id,
-        //     roles: &roles,
-        //     status: &status,
-        //     trust_score: requester.trust_score,
-        // };
-
-        // --- 3. التحقق من الصلاحيات ---
-        // --- 3. Check Permissions ---
-        // let action = Action::ReadUserData { target_user_id: &target_user_id };
-        // PolicyEngine::can_execute(&context, &action)?;
-
-        // --- 4. جلب بيانات المستخدم المستهدف ---
-        // --- 4. Fetch Target User's Data ---
-        // let target_user = crud::get_user_by_id(&self.pool, target_user_id).await?;
-        unimplemented!("Database access needs to be migrated to sea-orm");
+        requester: &PolicyContext<'_>,
+        target_user_id: Uuid,
+    ) -> Result<Option<UserProfileData>, BehaviorError> {
+        // --- 1. التحقق من الصلاحيات ---
+        // --- 1. Check Permissions ---
+        let action = Action::ReadUserProfile {
+            target_user_id: &target_user_id,
+        };
+        PolicyEngine::can_execute(requester, &action)?;
+
+        // --- 2. جلب بيانات المستخدم المستهدف وملخصه ---
+        // --- 2. Fetch Target User's Data and Summary ---
+        let Some(user) = crud::get_user_by_id(&self.pool, &target_user_id)
+            .await
+            .map_err(|e| BehaviorError::DatabaseError(e.into()))?
+        else {
+            return Ok(None);
+        };
+        let devices = crud::list_devices_for_user(&self.pool, &target_user_id)
+            .await
+            .map_err(|e| BehaviorError::DatabaseError(e.into()))?;
+        let analyses = crud::list_recent_analyses_for_entity(
+            &self.pool,
+            &target_user_id.to_string(),
+            RECENT_ANALYSES,
+        )
+        .await
+        .map_err(|e| BehaviorError::DatabaseError(e.into()))?;
+
+        let last_locations = analyses
+            .iter()
+            .filter_map(|analysis| {
+                let input: BehaviorInput = serde_json::from_value(analysis.input.clone()).ok()?;
+                Some(LocationSighting {
+                    latitude: input.location.0,
+                    longitude: input.location.1,
+                    seen_at: input.timestamp,
+                })
+            })
+            .take(RECENT_LOCATIONS)
+            .collect();
+        let recent_risk: Vec<RiskSnapshot> = analyses
+            .into_iter()
+            .map(|analysis| RiskSnapshot {
+                analysis_id: analysis.id,
+                source: analysis.source,
+                risk_score: analysis.risk_score,
+                rules: analysis.rules,
+                created_at: analysis.created_at,
+            })
+            .collect();
+        #[allow(clippy::cast_precision_loss)]
+        let average_recent_risk = (!recent_risk.is_empty()).then(|| {
+            recent_risk.iter().map(|r| r.risk_score).sum::<f32>() / recent_risk.len() as f32
+        });
+
+        Ok(Some(UserProfileData {
+            user,
+            behavior: BehavioralSummary {
+                devices,
+                last_locations,
+                recent_risk,
+                average_recent_risk,
+            },
+        }))
     }
 }
 
@@ -426,6 +664,7 @@ impl UserService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::security::policy::{Role, UserStatus};
     use std::time::Duration;
 
     // --- Mock Components for Precise Testing ---
@@ -465,13 +704,17 @@ mod tests {
                 connection_type: "WiFi".to_string(),
             },
             device_fingerprint: "fingerprint_123".to_string(),
+            device_similarity: None,
+            keystrokes: None,
+            pointer_streams: Vec::new(),
+            timezone: None,
         }
     }
 
     #[tokio::test]
     async fn test_engine_with_default_components() {
         let engine = BehaviorEngine::new(
-            Arc::new(DefaultBehavioralModel),
+            Arc::new(DefaultBehavioralModel::default()),
             Arc::new(DefaultAnomalyDetector {
                 max_speed_kmh: 1200.0,
             }),
@@ -507,7 +750,7 @@ mod tests {
     #[tokio::test]
     async fn test_engine_with_mocked_anomaly_detector() {
         let engine = BehaviorEngine::new(
-            Arc::new(DefaultBehavioralModel),
+            Arc::new(DefaultBehavioralModel::default()),
             Arc::new(MockTeleportDetector), // Inject mock detector
             10,
         );
@@ -521,7 +764,7 @@ mod tests {
     #[tokio::test]
     async fn test_impossible_travel_anomaly() {
         let engine = BehaviorEngine::new(
-            Arc::new(DefaultBehavioralModel),
+            Arc::new(DefaultBehavioralModel::default()),
             Arc::new(DefaultAnomalyDetector {
                 max_speed_kmh: 1200.0,
             }), // Supersonic jet speed
@@ -543,4 +786,151 @@ mod tests {
         assert!(result.anomaly_detected);
         assert!(result.reasoning.contains("Impossible travel speed"));
     }
+
+    #[tokio::test]
+    async fn test_suspicious_time_uses_local_time_and_ramadan() {
+        let input_at = |timestamp: DateTime<Utc>| {
+            let mut input = create_sample_input("riyadh-user");
+            input.location = (24.7136, 46.6753); // Riyadh, timezone derived from location
+            input.timestamp = timestamp;
+            input
+        };
+        let score_at = |timestamp: DateTime<Utc>| async move {
+            DefaultBehavioralModel::default()
+                .analyze(&input_at(timestamp), &VecDeque::new())
+                .await
+                .unwrap()
+        };
+
+        // 22:00 UTC يوم الثلاثاء = 01:00 الأربعاء بتوقيت الرياض: مشبوه
+        // 22:00 UTC on Tuesday = 01:00 Wednesday in Riyadh: suspicious
+        let night = score_at(Utc.with_ymd_and_hms(2025, 1, 14, 22, 0, 0).unwrap()).await;
+        assert!((night - 0.4).abs() < 1e-6);
+        // 04:00 UTC = 07:00 بتوقيت الرياض: صباح عادي
+        // 04:00 UTC = 07:00 in Riyadh: an ordinary morning
+        let morning = score_at(Utc.with_ymd_and_hms(2025, 1, 15, 4, 0, 0).unwrap()).await;
+        assert!((morning - 0.1).abs() < 1e-6);
+        // نفس الساعة الليلية في رمضان نشاط معتاد
+        // The same night hour during Ramadan is usual activity
+        let ramadan = score_at(Utc.with_ymd_and_hms(2025, 3, 11, 22, 0, 0).unwrap()).await;
+        assert!((ramadan - 0.1).abs() < 1e-6);
+
+        // الجمعة 01:00 سهرة نهاية أسبوع، إلا مع تقويم نهايته السبت والأحد
+        // Friday 01:00 is a weekend night, except with a Saturday/Sunday calendar
+        let friday_night = input_at(Utc.with_ymd_and_hms(2025, 1, 16, 22, 0, 0).unwrap());
+        let sat_sun = DefaultBehavioralModel::default().with_calendar(ActivityCalendar {
+            weekend: vec![chrono::Weekday::Sat, chrono::Weekday::Sun],
+            ..ActivityCalendar::default()
+        });
+        let default_score = DefaultBehavioralModel::default()
+            .analyze(&friday_night, &VecDeque::new())
+            .await
+            .unwrap();
+        let sat_sun_score = sat_sun
+            .analyze(&friday_night, &VecDeque::new())
+            .await
+            .unwrap();
+        assert!((default_score - 0.1).abs() < 1e-6);
+        assert!((sat_sun_score - 0.4).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_user_service_enforces_policy_and_summarizes() {
+        use crate::db::models::RiskAnalysis;
+
+        let pool = Connection::open_in_memory().await.unwrap();
+        crud::init_schema(&pool).await.unwrap();
+        let user = |status: &str| User {
+            id: Uuid::new_v4(),
+            username: format!("user-{status}-{}", Uuid::new_v4()),
+            email: format!("{}@example.local", Uuid::new_v4()),
+            password_hash: "hash".to_string(),
+            status: status.to_string(),
+            created_at: Utc::now().naive_utc(),
+            last_login_at: None,
+        };
+        let (target, other, banned) = (user("active"), user("active"), user("banned"));
+        for record in [&target, &other, &banned] {
+            crud::upsert_user(&pool, record).await.unwrap();
+        }
+
+        let input = create_sample_input(&target.id.to_string());
+        for score in [0.2, 0.6] {
+            let analysis = RiskAnalysis {
+                id: Uuid::new_v4(),
+                requested_by: target.id,
+                entity_id: target.id.to_string(),
+                source: "behavior".to_string(),
+                risk_score: score,
+                rules: Vec::new(),
+                input: serde_json::to_value(&input).unwrap(),
+                created_at: Utc::now().naive_utc(),
+            };
+            crud::insert_risk_analysis(&pool, &analysis).await.unwrap();
+        }
+
+        let service = UserService::new(pool);
+        let requester =
+            |user_id: Uuid, roles: &'static [Role], status: &'static UserStatus| PolicyContext {
+                user_id,
+                roles,
+                status,
+                trust_score: 0.5,
+            };
+        let own = service
+            .get_user_profile_data(
+                &requester(target.id, &[Role::User], &UserStatus::Active),
+                target.id,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(own.user.id, target.id);
+        assert_eq!(own.behavior.recent_risk.len(), 2);
+        assert!((own.behavior.last_locations[0].latitude - 40.7128).abs() < 1e-9);
+        assert!((own.behavior.average_recent_risk.unwrap() - 0.4).abs() < 1e-6);
+
+        // مستخدم آخر يُرفض وكذلك المشرف، والمدير يُسمح له، والمحظور يُرفض حتى لنفسه
+        // Another user and a moderator are denied, an admin is allowed, a banned requester
+        // is denied even for self
+        assert!(matches!(
+            service
+                .get_user_profile_data(
+                    &requester(other.id, &[Role::User], &UserStatus::Active),
+                    target.id
+                )
+                .await,
+            Err(BehaviorError::PolicyError(
+                PolicyError::InsufficientPermissions
+            ))
+        ));
+        assert!(matches!(
+            service
+                .get_user_profile_data(
+                    &requester(other.id, &[Role::Moderator], &UserStatus::Active),
+                    target.id
+                )
+                .await,
+            Err(BehaviorError::PolicyError(
+                PolicyError::InsufficientPermissions
+            ))
+        ));
+        assert!(service
+            .get_user_profile_data(
+                &requester(other.id, &[Role::Admin], &UserStatus::Active),
+                target.id
+            )
+            .await
+            .unwrap()
+            .is_some());
+        assert!(matches!(
+            service
+                .get_user_profile_data(
+                    &requester(banned.id, &[Role::User], &UserStatus::Banned),
+                    banned.id
+                )
+                .await,
+            Err(BehaviorError::PolicyError(PolicyError::UserBanned))
+        ));
+    }
 }
@@ -0,0 +1,325 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: behavior_calendar.rs
+    المسار:    src/core/behavior_calendar.rs
+    دور الملف:
+    الوقت المحلي لأحداث السلوك. يحدد المنطقة الزمنية للكيان (من ملفه إن وُجدت، ثم من سجل
+    GeoIP، وإلا من موقعه كملاذ أخير)، ويحول التاريخ إلى التقويم الهجري (الجدولي)، ويعرّف ساعات الهدوء المعتادة
+    بحسب الفترة: الأيام العادية، ونهاية الأسبوع، ورمضان حيث يمتد النشاط إلى السحور
+    وينتقل النوم إلى الصباح.
+    --------------------------------------------------------------
+    File Name: behavior_calendar.rs
+    Path:     src/core/behavior_calendar.rs
+    File Role:
+    Local time of behavior events. Resolves the entity's timezone (from its profile when
+    given, then from the GeoIP record, otherwise from its location as a last resort), converts dates to the (tabular) Hijri calendar,
+    and defines the usual quiet hours per period: regular days, the weekend, and Ramadan,
+    when activity runs until suhoor and sleep moves to the morning.
+******************************************************************************************/
+
+use crate::core::behavior_bio::BehaviorInput;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// رقم اليوم اليولياني لـ 0001-01-01 ناقص واحد.
+/// Julian day number of 0001-01-01, minus one.
+const JDN_CE_OFFSET: i32 = 1_721_425;
+/// رقم اليوم اليولياني لبداية التقويم الهجري (الحقبة المدنية).
+/// Julian day number of the Hijri epoch (civil reckoning).
+const HIJRI_EPOCH_JDN: i32 = 1_948_440;
+/// رقم شهر رمضان في التقويم الهجري.
+/// Month number of Ramadan in the Hijri calendar.
+pub const RAMADAN: u32 = 9;
+
+// ================================================================
+// المنطقة الزمنية
+// Timezone
+// ================================================================
+
+/// مناطق تقريبية للمنطقة الأساسية للمنصة: (جنوب، شمال، غرب، شرق، المنطقة).
+/// الأصغر أولاً لأن المربعات تتداخل، والحدود تتجنب إيران والعراق والأردن وفلسطين.
+/// Coarse regions for the platform's home region: (south, north, west, east, zone).
+/// Smaller boxes first since the boxes overlap; the edges stay clear of Iran, Iraq,
+/// Jordan and Palestine.
+const REGIONS: &[(f64, f64, f64, f64, Tz)] = &[
+    (24.4, 26.2, 50.7, 51.7, chrono_tz::Asia::Qatar),
+    (25.5, 26.4, 50.3, 50.7, chrono_tz::Asia::Bahrain),
+    (28.5, 30.1, 46.5, 48.5, chrono_tz::Asia::Kuwait),
+    (25.6, 26.5, 56.0, 56.5, chrono_tz::Asia::Muscat),
+    (22.5, 26.1, 51.7, 56.4, chrono_tz::Asia::Dubai),
+    (16.6, 26.5, 55.7, 59.9, chrono_tz::Asia::Muscat),
+    (16.6, 19.5, 53.1, 55.7, chrono_tz::Asia::Muscat),
+    (16.3, 29.1, 38.5, 50.3, chrono_tz::Asia::Riyadh),
+    (23.0, 29.1, 35.5, 38.5, chrono_tz::Asia::Riyadh),
+    (19.0, 24.3, 50.3, 55.7, chrono_tz::Asia::Riyadh),
+    (22.0, 31.7, 24.7, 34.2, chrono_tz::Africa::Cairo),
+];
+
+/// يستنتج المنطقة الزمنية من الإحداثيات كملاذ أخير حين لا يوجد سجل GeoIP: جدول تقريبي
+/// للخليج ومصر، وخارجه الإزاحة البحرية من خط الطول (`Etc/GMT±N`، بلا توقيت صيفي ولا
+/// مناطق نصف الساعة).
+/// Derives a timezone from coordinates, as a last resort when there is no GeoIP record:
+/// a coarse table for the Gulf and Egypt, and elsewhere the nautical offset from the
+/// longitude (`Etc/GMT±N`, with neither daylight saving nor half-hour zones).
+#[must_use]
+pub fn timezone_for_location(location: (f64, f64)) -> Tz {
+    let (latitude, longitude) = location;
+    if let Some((.., zone)) = REGIONS.iter().find(|(south, north, west, east, _)| {
+        (*south..=*north).contains(&latitude) && (*west..=*east).contains(&longitude)
+    }) {
+        return *zone;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let offset = (longitude.clamp(-180.0, 180.0) / 15.0).round() as i32;
+    if offset == 0 {
+        return Tz::UTC;
+    }
+    // أسماء Etc معكوسة الإشارة: Etc/GMT-3 تعني UTC+3
+    // Etc names have an inverted sign: Etc/GMT-3 means UTC+3
+    format!("Etc/GMT{:+}", -offset).parse().unwrap_or(Tz::UTC)
+}
+
+/// المنطقة الزمنية للمُدخل: المحددة مسبقاً أولاً (من الملف أو من سجل GeoIP عبر
+/// `BehaviorEngine`)، وإلا المستنتجة من الموقع.
+/// The input's timezone: the one already set first (from the profile, or from the GeoIP
+/// record via `BehaviorEngine`), otherwise derived from the location.
+#[must_use]
+pub fn resolve_timezone(input: &BehaviorInput) -> Tz {
+    input
+        .timezone
+        .unwrap_or_else(|| timezone_for_location(input.location))
+}
+
+// ================================================================
+// التقويم الهجري
+// Hijri calendar
+// ================================================================
+
+/// تاريخ هجري وفق التقويم الجدولي (قد يختلف عن أم القرى بيوم أو يومين).
+/// A Hijri date in the tabular calendar (may differ from Umm al-Qura by a day or two).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HijriDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl HijriDate {
+    /// يحول تاريخاً ميلادياً إلى هجري.
+    /// Converts a Gregorian date to Hijri.
+    #[must_use]
+    pub fn from_gregorian(date: NaiveDate) -> Self {
+        let days = date.num_days_from_ce() + JDN_CE_OFFSET - HIJRI_EPOCH_JDN + 10_632;
+        let cycles = (days - 1) / 10_631;
+        let days = days - 10_631 * cycles + 354;
+        let year_in_cycle = ((10_985 - days) / 5_316) * ((50 * days) / 17_719)
+            + (days / 5_670) * ((43 * days) / 15_238);
+        let days = days
+            - ((30 - year_in_cycle) / 15) * ((17_719 * year_in_cycle) / 50)
+            - (year_in_cycle / 16) * ((15_238 * year_in_cycle) / 43)
+            + 29;
+        let month = (24 * days) / 709;
+        let day = days - (709 * month) / 24;
+        #[allow(clippy::cast_sign_loss)]
+        Self {
+            year: 30 * cycles + year_in_cycle - 30,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    #[must_use]
+    pub fn is_ramadan(&self) -> bool {
+        self.month == RAMADAN
+    }
+}
+
+// ================================================================
+// اللحظة المحلية وساعات الهدوء
+// Local moment and quiet hours
+// ================================================================
+
+/// سمات الوقت المحلي لحدث ما.
+/// Local-time features of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalMoment {
+    pub hour: u32,
+    pub weekday: Weekday,
+    pub hijri: HijriDate,
+}
+
+impl LocalMoment {
+    #[must_use]
+    pub fn at(timestamp: DateTime<Utc>, timezone: Tz) -> Self {
+        let local = timestamp.with_timezone(&timezone);
+        Self {
+            hour: local.hour(),
+            weekday: local.weekday(),
+            hijri: HijriDate::from_gregorian(local.date_naive()),
+        }
+    }
+
+    /// اللحظة المحلية لمُدخل سلوك (انظر `resolve_timezone`).
+    /// The local moment of a behavior input (see `resolve_timezone`).
+    #[must_use]
+    pub fn of(input: &BehaviorInput) -> Self {
+        Self::at(input.timestamp, resolve_timezone(input))
+    }
+}
+
+/// نافذة ساعات محلية `[start, end)`، تلتف بعد منتصف الليل إذا كانت `start > end`.
+/// A local hour window `[start, end)`, wrapping past midnight when `start > end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietHours {
+    #[must_use]
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+/// تقويم النشاط: ساعات الهدوء المعتادة لكل فترة. رمضان يتقدم على نهاية الأسبوع.
+/// Activity calendar: the usual quiet hours per period. Ramadan takes precedence over
+/// the weekend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivityCalendar {
+    /// أيام نهاية الأسبوع المحلية (الجمعة والسبت في المملكة).
+    /// Local weekend days (Friday and Saturday in the Kingdom).
+    pub weekend: Vec<Weekday>,
+    pub regular: QuietHours,
+    /// ليالي نهاية الأسبوع تمتد أكثر فيتأخر الهدوء.
+    /// Weekend nights run later, so the quiet window starts later.
+    pub weekend_quiet: QuietHours,
+    /// في رمضان يمتد النشاط حتى السحور والنوم يكون صباحاً.
+    /// In Ramadan activity runs until suhoor and sleep happens in the morning.
+    pub ramadan: QuietHours,
+}
+
+impl Default for ActivityCalendar {
+    fn default() -> Self {
+        Self {
+            weekend: vec![Weekday::Fri, Weekday::Sat],
+            regular: QuietHours { start: 0, end: 6 },
+            weekend_quiet: QuietHours { start: 2, end: 8 },
+            ramadan: QuietHours { start: 5, end: 10 },
+        }
+    }
+}
+
+impl ActivityCalendar {
+    /// ساعات الهدوء السارية في لحظة محلية.
+    /// The quiet hours in effect at a local moment.
+    #[must_use]
+    pub fn quiet_hours(&self, moment: &LocalMoment) -> QuietHours {
+        if moment.hijri.is_ramadan() {
+            self.ramadan
+        } else if self.weekend.contains(&moment.weekday) {
+            self.weekend_quiet
+        } else {
+            self.regular
+        }
+    }
+
+    #[must_use]
+    pub fn is_quiet(&self, moment: &LocalMoment) -> bool {
+        self.quiet_hours(moment).contains(moment.hour)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn converts_gregorian_dates_to_hijri() {
+        let mid_ramadan = HijriDate::from_gregorian(date(2025, 3, 15));
+        assert_eq!((mid_ramadan.year, mid_ramadan.month), (1446, RAMADAN));
+        assert!(mid_ramadan.is_ramadan());
+        let rajab = HijriDate::from_gregorian(date(2025, 1, 15));
+        assert_eq!((rajab.year, rajab.month), (1446, 7));
+        let new_year = HijriDate::from_gregorian(date(2023, 7, 20));
+        assert_eq!((new_year.year, new_year.month), (1445, 1));
+    }
+
+    #[test]
+    fn derives_timezone_from_location() {
+        assert_eq!(
+            timezone_for_location((24.7136, 46.6753)),
+            chrono_tz::Asia::Riyadh
+        );
+        assert_eq!(
+            timezone_for_location((25.2048, 55.2708)),
+            chrono_tz::Asia::Dubai
+        );
+        assert_eq!(
+            timezone_for_location((25.2854, 51.5310)),
+            chrono_tz::Asia::Qatar
+        );
+        // شيراز وإيلات خارج مربعات الخليج
+        // Shiraz and Eilat fall outside the Gulf boxes
+        assert_ne!(
+            timezone_for_location((29.5918, 52.5837)),
+            chrono_tz::Asia::Riyadh
+        );
+        assert_ne!(
+            timezone_for_location((29.5577, 34.9519)),
+            chrono_tz::Asia::Riyadh
+        );
+        assert_eq!(
+            timezone_for_location((40.7128, -74.0060)),
+            chrono_tz::Etc::GMTPlus5
+        );
+        assert_eq!(timezone_for_location((51.5, -0.12)), Tz::UTC);
+    }
+
+    #[test]
+    fn ramadan_and_weekend_shift_quiet_hours() {
+        let calendar = ActivityCalendar::default();
+        let riyadh = chrono_tz::Asia::Riyadh;
+        // الأربعاء 00:30 بتوقيت الرياض، خارج رمضان
+        // Wednesday 00:30 Riyadh time, outside Ramadan
+        let weekday_night = LocalMoment::at(
+            Utc.with_ymd_and_hms(2025, 1, 14, 21, 30, 0).unwrap(),
+            riyadh,
+        );
+        assert!(calendar.is_quiet(&weekday_night));
+        // الجمعة 00:30: سهرة نهاية الأسبوع
+        // Friday 00:30: a weekend night out
+        let weekend_night = LocalMoment::at(
+            Utc.with_ymd_and_hms(2025, 1, 16, 21, 30, 0).unwrap(),
+            riyadh,
+        );
+        assert_eq!(weekend_night.weekday, Weekday::Fri);
+        assert!(!calendar.is_quiet(&weekend_night));
+        // منتصف رمضان: 00:30 نشاط معتاد و07:00 وقت نوم
+        // Mid Ramadan: 00:30 is usual activity and 07:00 is sleep time
+        let ramadan_night = LocalMoment::at(
+            Utc.with_ymd_and_hms(2025, 3, 11, 21, 30, 0).unwrap(),
+            riyadh,
+        );
+        assert!(!calendar.is_quiet(&ramadan_night));
+        let ramadan_morning =
+            LocalMoment::at(Utc.with_ymd_and_hms(2025, 3, 12, 4, 0, 0).unwrap(), riyadh);
+        assert!(calendar.is_quiet(&ramadan_morning));
+    }
+}
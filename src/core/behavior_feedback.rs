@@ -0,0 +1,433 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: behavior_feedback.rs
+    المسار:    src/core/behavior_feedback.rs
+    دور الملف:
+    حلقة تغذية راجعة من المحللين لقرارات المخاطر. `FeedbackReport` يحسب الدقة والاستدعاء
+    لكل قاعدة (رمز شذوذ) ولكل حد درجة من التحليلات المصنفة، و`LabeledHistory` يحفظ
+    الأجهزة والمواقع المؤكدة لكل كيان، و`FeedbackAwareModel` يخفض الدرجة للجهاز أو
+    الموقع الذي أكده محلل كشرعي ويرفعها للجهاز المؤكد كاحتيال.
+    --------------------------------------------------------------
+    File Name: behavior_feedback.rs
+    Path:     src/core/behavior_feedback.rs
+    File Role:
+    Analyst feedback loop for risk decisions. `FeedbackReport` computes precision and recall
+    per rule (anomaly code) and per score threshold from labeled analyses, `LabeledHistory`
+    keeps the confirmed devices and locations per entity, and `FeedbackAwareModel` lowers
+    the score for a device or location an analyst confirmed as legit and raises it for a
+    device confirmed as fraud.
+******************************************************************************************/
+
+use crate::core::behavior_bio::{BehaviorError, BehaviorInput, BehavioralModel};
+use crate::db::models::Verdict;
+use crate::utils::precision::haversine_km;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// الحدود الافتراضية لتقرير الدقة والاستدعاء.
+/// Default thresholds of the precision/recall report.
+pub const DEFAULT_THRESHOLDS: [f32; 5] = [0.3, 0.5, 0.6, 0.7, 0.9];
+
+/// أقصى عدد من المواقع المؤكدة لكل كيان.
+/// Maximum confirmed locations per entity.
+const MAX_LOCATIONS: usize = 32;
+
+// ================================================================
+// تقرير الدقة والاستدعاء
+// Precision/Recall Report
+// ================================================================
+
+/// قرار مصنف: درجته والقواعد التي أطلقها وحكم المحلل.
+/// A labeled decision: its score, the rules it fired, and the analyst verdict.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabeledDecision {
+    pub risk_score: f32,
+    pub rules: Vec<String>,
+    pub verdict: Verdict,
+}
+
+/// مصفوفة الالتباس لقاعدة أو حد.
+/// Confusion counts for a rule or threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Confusion {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+}
+
+impl Confusion {
+    fn add(&mut self, flagged: bool, verdict: Verdict) {
+        match (flagged, verdict) {
+            (true, Verdict::Fraud) => self.true_positives += 1,
+            (true, Verdict::Legit) => self.false_positives += 1,
+            (false, Verdict::Fraud) => self.false_negatives += 1,
+            (false, Verdict::Legit) => self.true_negatives += 1,
+        }
+    }
+
+    /// غائبة إذا لم يُطلَق أي قرار.
+    /// Absent when nothing was flagged.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn precision(&self) -> Option<f64> {
+        let flagged = self.true_positives + self.false_positives;
+        (flagged > 0).then(|| self.true_positives as f64 / flagged as f64)
+    }
+
+    /// غائب إذا لم يوجد احتيال مؤكد.
+    /// Absent when there is no confirmed fraud.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn recall(&self) -> Option<f64> {
+        let fraud = self.true_positives + self.false_negatives;
+        (fraud > 0).then(|| self.true_positives as f64 / fraud as f64)
+    }
+}
+
+/// مقاييس حد درجة: القرار يُعد احتيالاً إذا بلغت درجته الحد.
+/// Metrics of a score threshold: a decision counts as fraud when its score reaches it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdMetrics {
+    pub threshold: f32,
+    #[serde(flatten)]
+    pub confusion: Confusion,
+    pub precision: Option<f64>,
+    pub recall: Option<f64>,
+}
+
+/// مقاييس قاعدة: القرار يُعد احتيالاً إذا أطلق القاعدة.
+/// Metrics of a rule: a decision counts as fraud when it fired the rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleMetrics {
+    pub rule: String,
+    #[serde(flatten)]
+    pub confusion: Confusion,
+    pub precision: Option<f64>,
+    pub recall: Option<f64>,
+}
+
+/// تقرير الدقة والاستدعاء للقرارات المصنفة.
+/// Precision/recall report over labeled decisions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackReport {
+    pub labeled: usize,
+    pub fraud: usize,
+    pub legit: usize,
+    pub thresholds: Vec<ThresholdMetrics>,
+    pub rules: Vec<RuleMetrics>,
+}
+
+impl FeedbackReport {
+    /// يبني التقرير؛ القواعد مرتبة أبجدياً.
+    /// Builds the report; rules are sorted alphabetically.
+    #[must_use]
+    pub fn build(decisions: &[LabeledDecision], thresholds: &[f32]) -> Self {
+        let thresholds = thresholds
+            .iter()
+            .map(|&threshold| {
+                let mut confusion = Confusion::default();
+                for decision in decisions {
+                    confusion.add(decision.risk_score >= threshold, decision.verdict);
+                }
+                ThresholdMetrics {
+                    threshold,
+                    confusion,
+                    precision: confusion.precision(),
+                    recall: confusion.recall(),
+                }
+            })
+            .collect();
+
+        let names: HashSet<&str> = decisions
+            .iter()
+            .flat_map(|d| d.rules.iter().map(String::as_str))
+            .collect();
+        let mut by_rule: BTreeMap<&str, Confusion> = BTreeMap::new();
+        for name in names {
+            let confusion = by_rule.entry(name).or_default();
+            for decision in decisions {
+                confusion.add(decision.rules.iter().any(|r| r == name), decision.verdict);
+            }
+        }
+        let rules = by_rule
+            .into_iter()
+            .map(|(rule, confusion)| RuleMetrics {
+                rule: rule.to_string(),
+                confusion,
+                precision: confusion.precision(),
+                recall: confusion.recall(),
+            })
+            .collect();
+
+        let fraud = decisions
+            .iter()
+            .filter(|d| d.verdict == Verdict::Fraud)
+            .count();
+        Self {
+            labeled: decisions.len(),
+            fraud,
+            legit: decisions.len() - fraud,
+            thresholds,
+            rules,
+        }
+    }
+}
+
+// ================================================================
+// السجل المصنف
+// Labeled History
+// ================================================================
+
+#[derive(Default)]
+struct EntityLabels {
+    legit_devices: HashSet<String>,
+    fraud_devices: HashSet<String>,
+    legit_locations: VecDeque<(f64, f64)>,
+}
+
+/// الأجهزة والمواقع التي صنفها المحللون لكل كيان، في الذاكرة.
+/// Devices and locations labeled by analysts per entity, in memory.
+#[derive(Default)]
+pub struct LabeledHistory {
+    entities: RwLock<HashMap<String, EntityLabels>>,
+}
+
+/// ما يعرفه السجل عن مدخل.
+/// What the history knows about an input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LabelMatch {
+    pub legit_device: bool,
+    pub fraud_device: bool,
+    pub legit_location: bool,
+}
+
+impl LabeledHistory {
+    /// يطبق حكماً على مدخل: الشرعي يضيف الجهاز والموقع إلى القائمة البيضاء، والاحتيال
+    /// ينقل الجهاز إلى قائمة الاحتيال.
+    /// Applies a verdict to an input: legit whitelists the device and location, fraud moves
+    /// the device to the fraud list.
+    pub async fn record(&self, input: &BehaviorInput, verdict: Verdict) {
+        let mut entities = self.entities.write().await;
+        let labels = entities.entry(input.entity_id.clone()).or_default();
+        let device = input.device_fingerprint.clone();
+        match verdict {
+            Verdict::Legit => {
+                labels.fraud_devices.remove(&device);
+                labels.legit_devices.insert(device);
+                if labels.legit_locations.len() >= MAX_LOCATIONS {
+                    labels.legit_locations.pop_front();
+                }
+                labels.legit_locations.push_back(input.location);
+            }
+            Verdict::Fraud => {
+                labels.legit_devices.remove(&device);
+                labels.fraud_devices.insert(device);
+            }
+        }
+    }
+
+    /// يطابق المدخل مع التصنيفات؛ الموقع يطابق إذا كان ضمن `radius_km` من موقع مؤكد.
+    /// Matches the input against the labels; the location matches within `radius_km` of a
+    /// confirmed one.
+    pub async fn lookup(&self, input: &BehaviorInput, radius_km: f64) -> LabelMatch {
+        let entities = self.entities.read().await;
+        let Some(labels) = entities.get(&input.entity_id) else {
+            return LabelMatch::default();
+        };
+        let (lat, lon) = input.location;
+        LabelMatch {
+            legit_device: labels.legit_devices.contains(&input.device_fingerprint),
+            fraud_device: labels.fraud_devices.contains(&input.device_fingerprint),
+            legit_location: labels
+                .legit_locations
+                .iter()
+                .any(|&(la, lo)| haversine_km(lat, lon, la, lo) <= radius_km),
+        }
+    }
+}
+
+// ================================================================
+// النموذج المستفيد من التصنيفات
+// Feedback-Aware Model
+// ================================================================
+
+/// إعدادات تعديل الدرجة من التصنيفات.
+/// Score adjustments from labels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedbackConfig {
+    /// معامل ضرب الدرجة لجهاز مؤكد كشرعي.
+    /// Score multiplier for a device confirmed as legit.
+    pub legit_device_factor: f32,
+    /// معامل ضرب الدرجة لموقع مؤكد كشرعي.
+    /// Score multiplier for a location confirmed as legit.
+    pub legit_location_factor: f32,
+    pub location_radius_km: f64,
+    /// أدنى درجة لجهاز مؤكد كاحتيال.
+    /// Minimum score for a device confirmed as fraud.
+    pub fraud_floor: f32,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            legit_device_factor: 0.5,
+            legit_location_factor: 0.6,
+            location_radius_km: 25.0,
+            fraud_floor: 0.9,
+        }
+    }
+}
+
+/// يعدّل درجة النموذج الداخلي حسب تصنيفات المحللين.
+/// Adjusts the inner model's score by analyst labels.
+pub struct FeedbackAwareModel {
+    inner: Arc<dyn BehavioralModel>,
+    history: Arc<LabeledHistory>,
+    config: FeedbackConfig,
+}
+
+impl FeedbackAwareModel {
+    #[must_use]
+    pub fn new(inner: Arc<dyn BehavioralModel>, history: Arc<LabeledHistory>) -> Self {
+        Self {
+            inner,
+            history,
+            config: FeedbackConfig::default(),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_config(mut self, config: FeedbackConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+#[async_trait]
+impl BehavioralModel for FeedbackAwareModel {
+    async fn analyze(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<f32, BehaviorError> {
+        let score = self.inner.analyze(current, history).await?;
+        let labels = self
+            .history
+            .lookup(current, self.config.location_radius_km)
+            .await;
+        if labels.fraud_device {
+            return Ok(score.max(self.config.fraud_floor));
+        }
+        let mut adjusted = score;
+        if labels.legit_device {
+            adjusted *= self.config.legit_device_factor;
+        }
+        if labels.legit_location {
+            adjusted *= self.config.legit_location_factor;
+        }
+        Ok(adjusted.clamp(0.0, 1.0))
+    }
+
+    fn has_signal(&self, current: &BehaviorInput) -> bool {
+        self.inner.has_signal(current)
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::behavior_bio::{DefaultBehavioralModel, NetworkInfo};
+    use chrono::{TimeZone, Utc};
+
+    fn decision(score: f32, rules: &[&str], verdict: Verdict) -> LabeledDecision {
+        LabeledDecision {
+            risk_score: score,
+            rules: rules.iter().map(ToString::to_string).collect(),
+            verdict,
+        }
+    }
+
+    #[test]
+    fn test_precision_recall_per_rule_and_threshold() {
+        let decisions = [
+            decision(0.9, &["impossible_travel"], Verdict::Fraud),
+            decision(0.8, &["new_country"], Verdict::Legit),
+            decision(0.6, &["new_country", "new_asn"], Verdict::Fraud),
+            decision(0.2, &[], Verdict::Fraud),
+            decision(0.1, &[], Verdict::Legit),
+        ];
+        let report = FeedbackReport::build(&decisions, &[0.5, 0.85]);
+        assert_eq!((report.labeled, report.fraud, report.legit), (5, 3, 2));
+
+        let at_half = &report.thresholds[0];
+        assert_eq!(at_half.confusion.true_positives, 2);
+        assert_eq!(at_half.confusion.false_positives, 1);
+        assert!((at_half.precision.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert!((at_half.recall.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.thresholds[1].precision, Some(1.0));
+
+        let names: Vec<_> = report.rules.iter().map(|r| r.rule.as_str()).collect();
+        assert_eq!(names, ["impossible_travel", "new_asn", "new_country"]);
+        assert_eq!(report.rules[2].precision, Some(0.5));
+        assert!((report.rules[2].recall.unwrap() - 1.0 / 3.0).abs() < 1e-9);
+
+        let empty = FeedbackReport::build(&[], &DEFAULT_THRESHOLDS);
+        assert_eq!(empty.thresholds[0].precision, None);
+        assert_eq!(empty.thresholds[0].recall, None);
+    }
+
+    #[tokio::test]
+    async fn test_model_consumes_labels() {
+        // ساعة متأخرة من جهاز جديد: درجة القواعد أعلى من الصفر
+        // Late hour from a new device: the rule score is above zero
+        let input = BehaviorInput {
+            entity_id: "u1".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 15, 3, 0, 0).unwrap(),
+            location: (24.7136, 46.6753),
+            network_info: NetworkInfo {
+                ip_address: "10.0.0.1".to_string(),
+                is_vpn: true,
+                connection_type: "WiFi".to_string(),
+            },
+            device_fingerprint: "new-laptop".to_string(),
+            device_similarity: None,
+            keystrokes: None,
+            pointer_streams: Vec::new(),
+            timezone: None,
+        };
+        let labels = Arc::new(LabeledHistory::default());
+        let model = FeedbackAwareModel::new(
+            Arc::new(DefaultBehavioralModel::default()),
+            Arc::clone(&labels),
+        );
+        let history = VecDeque::new();
+        let before = model.analyze(&input, &history).await.unwrap();
+        assert!(before > 0.0);
+
+        labels.record(&input, Verdict::Legit).await;
+        let whitelisted = model.analyze(&input, &history).await.unwrap();
+        assert!((whitelisted - before * 0.5 * 0.6).abs() < 1e-6);
+
+        let mut elsewhere = input.clone();
+        elsewhere.location = (21.4858, 39.1925);
+        let device_only = model.analyze(&elsewhere, &history).await.unwrap();
+        assert!((device_only - before * 0.5).abs() < 1e-6);
+
+        labels.record(&input, Verdict::Fraud).await;
+        assert!(model.analyze(&input, &history).await.unwrap() >= 0.9);
+    }
+}
@@ -0,0 +1,971 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: behavior_pipeline.rs
+    المسار:    src/core/behavior_pipeline.rs
+    دور الملف:
+    خط معالجة قابل للتركيب لـ `BehaviorEngine`. `DetectorPipeline` يشغل سلسلة كواشف
+    بأوزان لكل كاشف وقواعد إيقاف مبكر ويجمع كل حالات الشذوذ برموزها وخطورتها، و
+    `ModelEnsemble` يجمع عدة نماذج بأوزان. `PipelineConfig` يبني الاثنين من ملف JSON
+    (السرعة، ASN جديد، دولة جديدة، تبدل الأجهزة، الحركة المبرمجة) دون إعادة ترجمة.
+    --------------------------------------------------------------
+    File Name: behavior_pipeline.rs
+    Path:     src/core/behavior_pipeline.rs
+    File Role:
+    Composable pipeline for `BehaviorEngine`. `DetectorPipeline` runs a chain of detectors
+    with per-detector weights and short-circuit rules and collects every anomaly with its
+    code and severity; `ModelEnsemble` combines several weighted models. `PipelineConfig`
+    builds both from a JSON file (velocity, new ASN, new country, device churn, scripted
+    pointer) without recompiling.
+******************************************************************************************/
+
+use crate::core::behavior_baseline::{BaselineBehavioralModel, BaselineConfig};
+use crate::core::behavior_bio::{
+    Anomaly, AnomalyDetector, BehaviorError, BehaviorInput, BehavioralModel,
+    DefaultAnomalyDetector, DefaultBehavioralModel, RiskLevel,
+};
+use crate::core::behavior_calendar::ActivityCalendar;
+use crate::core::biometric_template::TemplateBackend;
+use crate::core::geo_db::GeoDbManager;
+use crate::core::keystroke::{KeystrokeDynamicsModel, KeystrokeProfiles};
+use crate::core::pointer::{PointerDynamicsModel, PointerProfiles, SyntheticPointerDetector};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    #[error("Failed to read pipeline config: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed pipeline config: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Invalid pipeline config: {0}")]
+    Invalid(String),
+}
+
+// ================================================================
+// مصدر نسب الشبكة
+// Network Attribution Source
+// ================================================================
+
+/// يحدد ASN والدولة والمنطقة الزمنية لعنوان IP.
+/// Resolves the ASN, country and timezone of an IP.
+pub trait IpAttribution: Send + Sync {
+    fn asn(&self, ip: IpAddr) -> Option<u32>;
+    fn country(&self, ip: IpAddr) -> Option<String>;
+
+    /// منطقة IANA الزمنية لعنوان IP إن كانت معروفة.
+    /// The IANA timezone of an IP, when known.
+    fn timezone(&self, _ip: IpAddr) -> Option<Tz> {
+        None
+    }
+}
+
+impl IpAttribution for GeoDbManager {
+    fn asn(&self, ip: IpAddr) -> Option<u32> {
+        self.lookup_network(ip).and_then(|network| network.asn)
+    }
+
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        let reader = self.city_reader();
+        let city = reader.lookup_city(ip).ok()??;
+        city.country.iso_code.map(ToString::to_string)
+    }
+
+    fn timezone(&self, ip: IpAddr) -> Option<Tz> {
+        let reader = self.city_reader();
+        let city = reader.lookup_city(ip).ok()??;
+        city.location.time_zone?.parse().ok()
+    }
+}
+
+// ================================================================
+// الكواشف الجديدة
+// New Detectors
+// ================================================================
+
+/// الخاصية التي يتتبعها `NewValueDetector`.
+/// The attribute tracked by `NewValueDetector`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackedValue {
+    Asn,
+    Country,
+}
+
+#[derive(Default)]
+struct SeenValues {
+    events: u32,
+    values: HashSet<String>,
+}
+
+/// يبلغ عن ASN أو دولة لم يظهر بها الكيان من قبل، بعد `min_history` حدثاً.
+/// Reports an ASN or country the entity has never been seen on, after `min_history` events.
+pub struct NewValueDetector {
+    tracked: TrackedValue,
+    attribution: Arc<dyn IpAttribution>,
+    min_history: u32,
+    seen: RwLock<HashMap<String, SeenValues>>,
+}
+
+impl NewValueDetector {
+    #[must_use]
+    pub fn new(
+        tracked: TrackedValue,
+        attribution: Arc<dyn IpAttribution>,
+        min_history: u32,
+    ) -> Self {
+        Self {
+            tracked,
+            attribution,
+            min_history,
+            seen: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn resolve(&self, input: &BehaviorInput) -> Option<String> {
+        let ip = input.network_info.ip_address.parse().ok()?;
+        match self.tracked {
+            TrackedValue::Asn => self.attribution.asn(ip).map(|asn| format!("AS{asn}")),
+            TrackedValue::Country => self.attribution.country(ip),
+        }
+    }
+}
+
+#[async_trait]
+impl AnomalyDetector for NewValueDetector {
+    async fn detect(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Option<String>, BehaviorError> {
+        Ok(self
+            .detect_all(current, history)
+            .await?
+            .into_iter()
+            .next()
+            .map(|a| a.message))
+    }
+
+    async fn detect_all(
+        &self,
+        current: &BehaviorInput,
+        _history: &VecDeque<BehaviorInput>,
+    ) -> Result<Vec<Anomaly>, BehaviorError> {
+        let Some(value) = self.resolve(current) else {
+            return Ok(Vec::new());
+        };
+        let mut seen = self.seen.write().await;
+        let entity = seen.entry(current.entity_id.clone()).or_default();
+        let is_new = entity.events >= self.min_history && !entity.values.contains(&value);
+        entity.events = entity.events.saturating_add(1);
+        entity.values.insert(value.clone());
+        drop(seen);
+
+        let (code, label) = match self.tracked {
+            TrackedValue::Asn => ("new_asn", "network (ASN)"),
+            TrackedValue::Country => ("new_country", "country"),
+        };
+        Ok(if is_new {
+            vec![Anomaly {
+                code: code.to_string(),
+                severity: RiskLevel::Medium,
+                message: format!("Anomaly detected: First activity from {label} {value}."),
+                weight: 1.0,
+            }]
+        } else {
+            Vec::new()
+        })
+    }
+}
+
+/// الأجهزة المستخدمة داخل النافذة مع وقت كل حدث.
+/// Devices used within the window with each event's time.
+type DeviceWindow = VecDeque<(DateTime<Utc>, String)>;
+
+/// يبلغ إذا استخدم الكيان أكثر من `max_devices` بصمة جهاز خلال النافذة.
+/// Reports when the entity used more than `max_devices` device fingerprints in the window.
+pub struct DeviceChurnDetector {
+    window: Duration,
+    max_devices: usize,
+    seen: RwLock<HashMap<String, DeviceWindow>>,
+}
+
+impl DeviceChurnDetector {
+    #[must_use]
+    pub fn new(window: Duration, max_devices: usize) -> Self {
+        Self {
+            window,
+            max_devices,
+            seen: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl AnomalyDetector for DeviceChurnDetector {
+    async fn detect(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Option<String>, BehaviorError> {
+        Ok(self
+            .detect_all(current, history)
+            .await?
+            .into_iter()
+            .next()
+            .map(|a| a.message))
+    }
+
+    async fn detect_all(
+        &self,
+        current: &BehaviorInput,
+        _history: &VecDeque<BehaviorInput>,
+    ) -> Result<Vec<Anomaly>, BehaviorError> {
+        let mut seen = self.seen.write().await;
+        let events = seen.entry(current.entity_id.clone()).or_default();
+        events.push_back((current.timestamp, current.device_fingerprint.clone()));
+        let cutoff = current.timestamp - self.window;
+        events.retain(|(at, _)| *at > cutoff);
+        let devices = events
+            .iter()
+            .map(|(_, device)| device.as_str())
+            .collect::<HashSet<_>>()
+            .len();
+        drop(seen);
+
+        Ok(if devices > self.max_devices {
+            vec![Anomaly {
+                code: "device_churn".to_string(),
+                severity: RiskLevel::Medium,
+                message: format!(
+                    "Anomaly detected: {devices} devices used within {} hours.",
+                    self.window.num_hours()
+                ),
+                weight: 1.0,
+            }]
+        } else {
+            Vec::new()
+        })
+    }
+}
+
+/// كاشف لا يبلغ عن شيء (أساس لكواشف مغلِّفة في الإعدادات).
+/// A detector that reports nothing (base for wrapping detectors in the config).
+pub struct NoAnomalyDetector;
+
+#[async_trait]
+impl AnomalyDetector for NoAnomalyDetector {
+    async fn detect(
+        &self,
+        _: &BehaviorInput,
+        _: &VecDeque<BehaviorInput>,
+    ) -> Result<Option<String>, BehaviorError> {
+        Ok(None)
+    }
+}
+
+/// نموذج يعيد صفراً دائماً (أساس لنماذج مغلِّفة في الإعدادات).
+/// A model that always returns zero (base for wrapping models in the config).
+pub struct NeutralModel;
+
+#[async_trait]
+impl BehavioralModel for NeutralModel {
+    async fn analyze(
+        &self,
+        _: &BehaviorInput,
+        _: &VecDeque<BehaviorInput>,
+    ) -> Result<f32, BehaviorError> {
+        Ok(0.0)
+    }
+
+    fn has_signal(&self, _: &BehaviorInput) -> bool {
+        false
+    }
+}
+
+// ================================================================
+// خط الكواشف ومجموعة النماذج
+// Detector Pipeline and Model Ensemble
+// ================================================================
+
+/// مرحلة في خط الكواشف.
+/// A stage in the detector pipeline.
+pub struct DetectorStage {
+    pub detector: Arc<dyn AnomalyDetector>,
+    /// وزن حالات الشذوذ من هذه المرحلة (يستبدل وزن الكاشف).
+    /// Weight of this stage's anomalies (replaces the detector's own weight).
+    pub weight: Option<f32>,
+    /// خطورة تستبدل خطورة الكاشف.
+    /// Severity that replaces the detector's own.
+    pub severity: Option<RiskLevel>,
+    /// إيقاف الخط إذا أبلغت هذه المرحلة عن شذوذ.
+    /// Stop the pipeline if this stage reports an anomaly.
+    pub stop_on_trigger: bool,
+}
+
+impl DetectorStage {
+    #[must_use]
+    pub fn new(detector: Arc<dyn AnomalyDetector>) -> Self {
+        Self {
+            detector,
+            weight: None,
+            severity: None,
+            stop_on_trigger: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_severity(mut self, severity: RiskLevel) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    #[must_use]
+    pub const fn stop_on_trigger(mut self) -> Self {
+        self.stop_on_trigger = true;
+        self
+    }
+}
+
+/// سلسلة كواشف تجمع كل حالات الشذوذ.
+/// A chain of detectors that collects every anomaly.
+#[derive(Default)]
+pub struct DetectorPipeline {
+    stages: Vec<DetectorStage>,
+    stop_at_severity: Option<RiskLevel>,
+}
+
+impl DetectorPipeline {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_stage(mut self, stage: DetectorStage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// إيقاف الخط عند أول شذوذ بهذه الخطورة أو أعلى.
+    /// Stop the pipeline at the first anomaly of this severity or higher.
+    #[must_use]
+    pub const fn with_stop_at_severity(mut self, severity: RiskLevel) -> Self {
+        self.stop_at_severity = Some(severity);
+        self
+    }
+}
+
+#[async_trait]
+impl AnomalyDetector for DetectorPipeline {
+    async fn detect(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Option<String>, BehaviorError> {
+        Ok(self
+            .detect_all(current, history)
+            .await?
+            .into_iter()
+            .next()
+            .map(|a| a.message))
+    }
+
+    async fn detect_all(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Vec<Anomaly>, BehaviorError> {
+        let mut anomalies = Vec::new();
+        for stage in &self.stages {
+            let mut found = stage.detector.detect_all(current, history).await?;
+            for anomaly in &mut found {
+                if let Some(weight) = stage.weight {
+                    anomaly.weight = weight;
+                }
+                if let Some(severity) = &stage.severity {
+                    anomaly.severity = severity.clone();
+                }
+            }
+            let triggered = !found.is_empty();
+            let severe = self
+                .stop_at_severity
+                .as_ref()
+                .is_some_and(|limit| found.iter().any(|a| a.severity >= *limit));
+            anomalies.extend(found);
+            if (triggered && stage.stop_on_trigger) || severe {
+                break;
+            }
+        }
+        Ok(anomalies)
+    }
+}
+
+/// طريقة دمج درجات النماذج.
+/// How model scores are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Combine {
+    /// المتوسط المرجح.
+    /// Weighted mean.
+    #[default]
+    Mean,
+    /// أعلى درجة مرجحة.
+    /// Highest weighted score.
+    Max,
+}
+
+/// مجموعة نماذج بأوزان.
+/// A weighted ensemble of models.
+#[derive(Default)]
+pub struct ModelEnsemble {
+    members: Vec<(Arc<dyn BehavioralModel>, f32)>,
+    combine: Combine,
+    short_circuit_score: Option<f32>,
+}
+
+impl ModelEnsemble {
+    #[must_use]
+    pub fn new(combine: Combine) -> Self {
+        Self {
+            combine,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_member(mut self, model: Arc<dyn BehavioralModel>, weight: f32) -> Self {
+        self.members.push((model, weight));
+        self
+    }
+
+    /// إذا بلغت درجة عضو هذا الحد تُعاد بدلاً من الدرجة المجمعة. كل الأعضاء تعمل مع ذلك،
+    /// فتستمر النماذج ذات الحالة في التعلم.
+    /// When a member scores at least this, its score is returned instead of the combined
+    /// one. Every member still runs, so stateful models keep learning.
+    #[must_use]
+    pub const fn with_short_circuit_score(mut self, score: f32) -> Self {
+        self.short_circuit_score = Some(score);
+        self
+    }
+}
+
+#[async_trait]
+impl BehavioralModel for ModelEnsemble {
+    fn has_signal(&self, current: &BehaviorInput) -> bool {
+        self.members
+            .iter()
+            .any(|(model, _)| model.has_signal(current))
+    }
+
+    async fn analyze(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<f32, BehaviorError> {
+        let (mut total, mut weights, mut max) = (0.0_f32, 0.0_f32, 0.0_f32);
+        let mut short_circuit: Option<f32> = None;
+        for (model, weight) in &self.members {
+            let score = model.analyze(current, history).await?;
+            if self.short_circuit_score.is_some_and(|limit| score >= limit) {
+                short_circuit = Some(short_circuit.map_or(score, |s| s.max(score)));
+            }
+            // عضو بلا بيانات لا يسحب المتوسط نحو الصفر
+            // A member without data does not drag the mean towards zero
+            if !model.has_signal(current) {
+                continue;
+            }
+            total += weight * score;
+            weights += weight;
+            max = max.max(weight * score);
+        }
+        if let Some(score) = short_circuit {
+            return Ok(score.min(1.0));
+        }
+        let score = match self.combine {
+            Combine::Mean if weights > 0.0 => total / weights,
+            Combine::Mean => 0.0,
+            Combine::Max => max,
+        };
+        Ok(score.clamp(0.0, 1.0))
+    }
+}
+
+/// النموذج والكاشف اللذان يُمرران إلى `BehaviorEngine::new`.
+/// The model and detector handed to `BehaviorEngine::new`.
+pub type BehaviorComponents = (Arc<dyn BehavioralModel>, Arc<dyn AnomalyDetector>);
+
+// ================================================================
+// الإعدادات من ملف
+// File Configuration
+// ================================================================
+
+const fn default_weight() -> f32 {
+    1.0
+}
+
+const fn default_min_history() -> u32 {
+    5
+}
+
+/// نوع الكاشف ومعاملاته.
+/// Detector kind and parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DetectorKind {
+    Velocity {
+        max_speed_kmh: f64,
+    },
+    NewAsn {
+        #[serde(default = "default_min_history")]
+        min_history: u32,
+    },
+    NewCountry {
+        #[serde(default = "default_min_history")]
+        min_history: u32,
+    },
+    DeviceChurn {
+        window_hours: i64,
+        max_devices: usize,
+    },
+    ScriptedPointer,
+}
+
+/// مرحلة كاشف في ملف الإعدادات.
+/// A detector stage in the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectorSpec {
+    #[serde(flatten)]
+    pub kind: DetectorKind,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub severity: Option<RiskLevel>,
+    #[serde(default)]
+    pub stop_on_trigger: bool,
+}
+
+/// نوع النموذج ومعاملاته. نماذج المفاتيح والمؤشر تُقيَّم وحدها هنا.
+/// Model kind and parameters. Keystroke and pointer models are scored on their own here.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModelKind {
+    Rules,
+    Baseline {
+        #[serde(default)]
+        config: BaselineConfig,
+    },
+    Keystroke,
+    Pointer,
+}
+
+/// عضو نموذج في ملف الإعدادات.
+/// A model member in the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelSpec {
+    #[serde(flatten)]
+    pub kind: ModelKind,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+/// إعدادات خط السلوك.
+/// Behavior pipeline configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    pub detectors: Vec<DetectorSpec>,
+    pub models: Vec<ModelSpec>,
+    #[serde(default)]
+    pub combine: Combine,
+    #[serde(default)]
+    pub stop_at_severity: Option<RiskLevel>,
+    #[serde(default)]
+    pub short_circuit_score: Option<f32>,
+    /// تقويم النشاط لنماذج القواعد والخط الأساسي (نهاية الأسبوع وساعات الهدوء).
+    /// Activity calendar for the rules and baseline models (weekend and quiet hours).
+    #[serde(default)]
+    pub calendar: ActivityCalendar,
+}
+
+impl PipelineConfig {
+    /// # Errors
+    /// يعيد `PipelineError` إذا كان JSON غير صالح أو الإعدادات غير متسقة.
+    /// Returns `PipelineError` if the JSON is malformed or the config is inconsistent.
+    pub fn from_json(json: &str) -> Result<Self, PipelineError> {
+        let config: Self = serde_json::from_str(json)?;
+        if config.models.is_empty() {
+            return Err(PipelineError::Invalid(
+                "at least one model is required".into(),
+            ));
+        }
+        if let Some(spec) = config
+            .detectors
+            .iter()
+            .find(|spec| !spec.weight.is_finite() || spec.weight < 0.0)
+        {
+            return Err(PipelineError::Invalid(format!(
+                "detector weight must be non-negative: {:?}",
+                spec.kind
+            )));
+        }
+        if config
+            .models
+            .iter()
+            .any(|spec| !spec.weight.is_finite() || spec.weight < 0.0)
+        {
+            return Err(PipelineError::Invalid(
+                "model weights must be non-negative".into(),
+            ));
+        }
+        Ok(config)
+    }
+
+    /// # Errors
+    /// يعيد `PipelineError` إذا تعذرت قراءة الملف أو تحليله.
+    /// Returns `PipelineError` if the file cannot be read or parsed.
+    pub fn load_file(path: &Path) -> Result<Self, PipelineError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// يبني الكاشف والنموذج. كواشف ASN والدولة تحتاج `attribution`.
+    /// Builds the detector and the model. ASN and country detectors need `attribution`.
+    ///
+    /// # Errors
+    /// يعيد `PipelineError::Invalid` إذا طُلب كاشف ASN أو دولة دون مصدر نسب.
+    /// Returns `PipelineError::Invalid` if an ASN or country detector has no attribution.
+    pub fn build(
+        &self,
+        attribution: Option<Arc<dyn IpAttribution>>,
+    ) -> Result<BehaviorComponents, PipelineError> {
+        self.build_with_templates(attribution, None)
+    }
+
+    /// مثل `build`، مع حفظ قوالب الكتابة والمؤشر في `templates`.
+    /// Like `build`, persisting keystroke and pointer templates to `templates`.
+    ///
+    /// # Errors
+    /// يعيد `PipelineError::Invalid` إذا طُلب كاشف ASN أو دولة دون مصدر نسب.
+    /// Returns `PipelineError::Invalid` if an ASN or country detector has no attribution.
+    pub fn build_with_templates(
+        &self,
+        attribution: Option<Arc<dyn IpAttribution>>,
+        templates: Option<Arc<dyn TemplateBackend>>,
+    ) -> Result<BehaviorComponents, PipelineError> {
+        let mut pipeline = DetectorPipeline::new();
+        if let Some(severity) = &self.stop_at_severity {
+            pipeline = pipeline.with_stop_at_severity(severity.clone());
+        }
+        for spec in &self.detectors {
+            let detector: Arc<dyn AnomalyDetector> = match &spec.kind {
+                DetectorKind::Velocity { max_speed_kmh } => Arc::new(DefaultAnomalyDetector {
+                    max_speed_kmh: *max_speed_kmh,
+                }),
+                DetectorKind::NewAsn { min_history } | DetectorKind::NewCountry { min_history } => {
+                    let attribution = attribution.clone().ok_or_else(|| {
+                        PipelineError::Invalid(
+                            "new_asn and new_country detectors need a GeoIP database".into(),
+                        )
+                    })?;
+                    let tracked = if matches!(spec.kind, DetectorKind::NewAsn { .. }) {
+                        TrackedValue::Asn
+                    } else {
+                        TrackedValue::Country
+                    };
+                    Arc::new(NewValueDetector::new(tracked, attribution, *min_history))
+                }
+                DetectorKind::DeviceChurn {
+                    window_hours,
+                    max_devices,
+                } => Arc::new(DeviceChurnDetector::new(
+                    Duration::hours(*window_hours),
+                    *max_devices,
+                )),
+                DetectorKind::ScriptedPointer => {
+                    Arc::new(SyntheticPointerDetector::new(Arc::new(NoAnomalyDetector)))
+                }
+            };
+            let mut stage = DetectorStage::new(detector).with_weight(spec.weight);
+            if let Some(severity) = &spec.severity {
+                stage = stage.with_severity(severity.clone());
+            }
+            if spec.stop_on_trigger {
+                stage = stage.stop_on_trigger();
+            }
+            pipeline = pipeline.with_stage(stage);
+        }
+
+        let mut ensemble = ModelEnsemble::new(self.combine);
+        if let Some(score) = self.short_circuit_score {
+            ensemble = ensemble.with_short_circuit_score(score);
+        }
+        let rules = DefaultBehavioralModel::default().with_calendar(self.calendar.clone());
+        for spec in &self.models {
+            let model: Arc<dyn BehavioralModel> = match &spec.kind {
+                ModelKind::Rules => Arc::new(rules.clone()),
+                ModelKind::Baseline { config } => Arc::new(
+                    BaselineBehavioralModel::new(Arc::new(rules.clone())).with_config(*config),
+                ),
+                ModelKind::Keystroke => {
+                    let mut profiles = KeystrokeProfiles::default();
+                    if let Some(backend) = &templates {
+                        profiles = profiles.with_backend(Arc::clone(backend));
+                    }
+                    Arc::new(
+                        KeystrokeDynamicsModel::new(Arc::new(NeutralModel))
+                            .with_profiles(Arc::new(profiles))
+                            .with_weight(1.0),
+                    )
+                }
+                ModelKind::Pointer => {
+                    let mut profiles = PointerProfiles::default();
+                    if let Some(backend) = &templates {
+                        profiles = profiles.with_backend(Arc::clone(backend));
+                    }
+                    Arc::new(
+                        PointerDynamicsModel::new(Arc::new(NeutralModel))
+                            .with_profiles(Arc::new(profiles))
+                            .with_weight(1.0),
+                    )
+                }
+            };
+            ensemble = ensemble.with_member(model, spec.weight);
+        }
+        Ok((Arc::new(ensemble), Arc::new(pipeline)))
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::behavior_bio::{BehaviorEngine, NetworkInfo};
+    use chrono::TimeZone;
+
+    /// نسب ثابت: 10.1.x.x في SA/AS1، وأي عنوان آخر في AE/AS2.
+    /// Fixed attribution: 10.1.x.x is SA/AS1, anything else AE/AS2.
+    struct FixedAttribution;
+    impl IpAttribution for FixedAttribution {
+        fn asn(&self, ip: IpAddr) -> Option<u32> {
+            Some(if ip.to_string().starts_with("10.1.") {
+                1
+            } else {
+                2
+            })
+        }
+        fn country(&self, ip: IpAddr) -> Option<String> {
+            Some(
+                if ip.to_string().starts_with("10.1.") {
+                    "SA"
+                } else {
+                    "AE"
+                }
+                .to_string(),
+            )
+        }
+        fn timezone(&self, ip: IpAddr) -> Option<Tz> {
+            (!ip.to_string().starts_with("10.1.")).then_some(chrono_tz::Asia::Dubai)
+        }
+    }
+
+    fn event(minute: i64, ip: &str, device: &str) -> BehaviorInput {
+        BehaviorInput {
+            entity_id: "u1".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap()
+                + Duration::minutes(minute),
+            location: (24.7136, 46.6753),
+            network_info: NetworkInfo {
+                ip_address: ip.to_string(),
+                is_vpn: false,
+                connection_type: "WiFi".to_string(),
+            },
+            device_fingerprint: device.to_string(),
+            device_similarity: None,
+            keystrokes: None,
+            pointer_streams: Vec::new(),
+            timezone: None,
+        }
+    }
+
+    const CONFIG: &str = r#"{
+        "detectors": [
+            {"type": "velocity", "max_speed_kmh": 1200},
+            {"type": "new_country", "min_history": 3, "weight": 0.8, "severity": "High"},
+            {"type": "new_asn", "min_history": 3, "weight": 0.4},
+            {"type": "device_churn", "window_hours": 24, "max_devices": 2, "weight": 0.6}
+        ],
+        "models": [{"type": "rules"}]
+    }"#;
+
+    #[tokio::test]
+    async fn test_config_pipeline_collects_all_anomalies() {
+        let config = PipelineConfig::from_json(CONFIG).unwrap();
+        let (model, detector) = config.build(Some(Arc::new(FixedAttribution))).unwrap();
+        let engine = BehaviorEngine::new(model, detector, 10);
+        for minute in 0..3 {
+            let result = engine
+                .process(event(minute, "10.1.0.7", "phone"))
+                .await
+                .unwrap();
+            assert!(!result.anomaly_detected, "{result:?}");
+        }
+
+        let result = engine
+            .process(event(10, "185.1.2.3", "laptop"))
+            .await
+            .unwrap();
+        let codes: Vec<_> = result.anomalies.iter().map(|a| a.code.as_str()).collect();
+        assert_eq!(codes, ["new_country", "new_asn"]);
+        assert_eq!(result.anomalies[0].severity, RiskLevel::High);
+        // 0.8 × 0.75 + 0.4 × 0.5
+        assert!((result.risk_score - 0.8).abs() < 1e-6, "{result:?}");
+        assert_eq!(result.risk_level, RiskLevel::High);
+
+        let result = engine
+            .process(event(20, "10.1.0.7", "tablet"))
+            .await
+            .unwrap();
+        assert_eq!(result.anomalies.len(), 1);
+        assert_eq!(result.anomalies[0].code, "device_churn");
+    }
+
+    #[tokio::test]
+    async fn test_engine_prefers_geoip_timezone() {
+        let engine = || {
+            BehaviorEngine::new(
+                Arc::new(DefaultBehavioralModel::default()),
+                Arc::new(DefaultAnomalyDetector {
+                    max_speed_kmh: 1200.0,
+                }),
+                10,
+            )
+        };
+        // 20:30 UTC = 23:30 بتوقيت الرياض (عادي) = 00:30 بتوقيت دبي (ساعات هدوء)
+        // 20:30 UTC = 23:30 in Riyadh (ordinary) = 00:30 in Dubai (quiet hours)
+        let from_location = engine()
+            .process(event(510, "185.1.2.3", "phone"))
+            .await
+            .unwrap();
+        let from_geoip = engine()
+            .with_attribution(Arc::new(FixedAttribution))
+            .process(event(510, "185.1.2.3", "phone"))
+            .await
+            .unwrap();
+        assert!(from_geoip.risk_score > from_location.risk_score);
+
+        let city = GeoDbManager::load(&crate::core::geo_db::GeoDbConfig {
+            city_path: Some("GeoLite2-City-Test.mmdb".into()),
+            ..Default::default()
+        });
+        if let Ok(city) = city {
+            assert_eq!(
+                city.timezone("81.2.69.142".parse().unwrap()),
+                Some(chrono_tz::Europe::London)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_short_circuit_rules() {
+        let pipeline = DetectorPipeline::new()
+            .with_stage(
+                DetectorStage::new(Arc::new(DeviceChurnDetector::new(Duration::hours(1), 0)))
+                    .stop_on_trigger(),
+            )
+            .with_stage(DetectorStage::new(Arc::new(DeviceChurnDetector::new(
+                Duration::hours(1),
+                0,
+            ))));
+        let found = pipeline
+            .detect_all(&event(0, "10.1.0.7", "phone"), &VecDeque::new())
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+
+        struct Fixed(f32);
+        #[async_trait]
+        impl BehavioralModel for Fixed {
+            async fn analyze(
+                &self,
+                _: &BehaviorInput,
+                _: &VecDeque<BehaviorInput>,
+            ) -> Result<f32, BehaviorError> {
+                Ok(self.0)
+            }
+        }
+        let history = VecDeque::new();
+        let mean = ModelEnsemble::new(Combine::Mean)
+            .with_member(Arc::new(Fixed(0.2)), 1.0)
+            .with_member(Arc::new(Fixed(0.8)), 3.0);
+        let score = mean
+            .analyze(&event(0, "10.1.0.7", "phone"), &history)
+            .await
+            .unwrap();
+        assert!((score - 0.65).abs() < 1e-6);
+        // عضو الكتابة بلا `keystrokes` لا يدخل في المتوسط
+        // The typing member without `keystrokes` stays out of the mean
+        let mean = mean.with_member(
+            Arc::new(KeystrokeDynamicsModel::new(Arc::new(NeutralModel))),
+            4.0,
+        );
+        let score = mean
+            .analyze(&event(0, "10.1.0.7", "phone"), &history)
+            .await
+            .unwrap();
+        assert!((score - 0.65).abs() < 1e-6, "{score}");
+
+        // كل الأعضاء تعمل، وتُعاد أعلى درجة تجاوزت الحد
+        // Every member runs, and the highest score past the limit is returned
+        let short = mean.with_short_circuit_score(0.1);
+        let score = short
+            .analyze(&event(0, "10.1.0.7", "phone"), &history)
+            .await
+            .unwrap();
+        assert!((score - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(matches!(
+            PipelineConfig::from_json(r#"{"detectors": [], "models": []}"#),
+            Err(PipelineError::Invalid(_))
+        ));
+        assert!(matches!(
+            PipelineConfig::from_json(
+                r#"{"detectors": [{"type": "teleport"}], "models": [{"type": "rules"}]}"#
+            ),
+            Err(PipelineError::Parse(_))
+        ));
+        let config = PipelineConfig::from_json(
+            r#"{"detectors": [{"type": "new_asn"}], "models": [{"type": "rules"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(config.build(None), Err(PipelineError::Invalid(_))));
+    }
+}
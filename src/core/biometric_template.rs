@@ -0,0 +1,471 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: biometric_template.rs
+    المسار:    src/core/biometric_template.rs
+    دور الملف:
+    قوالب بيومترية مشتركة بين ديناميكيات المفاتيح والمؤشر: متوسط وانحراف مطلق لكل
+    ميزة، ومسافة مانهاتن المقاسة، ومخزن قوالب لكل مفتاح (مستخدم/عبارة/جهاز) يسجل
+    العينات الأولى ثم يتكيف مع العينات منخفضة الخطر فقط. المخزن محدود العدد (الأقدم
+    استخداماً يُطرد) ويُسقط القوالب الخاملة، ويمكن حفظه في قاعدة البيانات.
+    --------------------------------------------------------------
+    File Name: biometric_template.rs
+    Path:     src/core/biometric_template.rs
+    File Role:
+    Biometric templates shared by keystroke and pointer dynamics: per-feature mean and
+    mean absolute deviation, the scaled Manhattan distance, and a template store per key
+    (user/phrase/device) that enrolls the first samples and then adapts only to low-risk
+    samples. The store is capped (least recently used profiles are evicted), drops idle
+    profiles, and can be persisted to the database.
+******************************************************************************************/
+
+use crate::app_state::DbPool;
+use crate::db::crud;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Sample has {actual} features but the template has {expected}")]
+    DimensionMismatch { expected: usize, actual: usize },
+
+    #[error("Template storage error: {0}")]
+    Storage(String),
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// إعدادات التسجيل والمقارنة لنوع من العينات.
+/// Enrollment and scoring settings for one kind of sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateSettings {
+    /// عدد العينات المطلوبة قبل بناء القالب.
+    /// Samples required before a template is built.
+    pub min_enrollment: usize,
+    /// أقصى عدد من العينات المحفوظة (الأقدم يُستبدل).
+    /// Maximum samples kept (the oldest is replaced).
+    pub max_samples: usize,
+    /// أدنى انحراف مطلق، وأدنى انحراف كنسبة من المتوسط، حتى لا تضخم ميزة ثابتة المسافة.
+    /// Absolute deviation floor, and floor as a fraction of the mean, so a very stable
+    /// feature does not blow up the distance.
+    pub absolute_floor: f64,
+    pub relative_floor: f64,
+    /// المسافة (لكل ميزة) التي يبدأ عندها الخطر، والمسافة التي يبلغ عندها 1.0.
+    /// Per-feature distance where risk starts, and where it reaches 1.0.
+    pub genuine_distance: f64,
+    pub impostor_distance: f64,
+    /// العينات الأقل خطراً من هذا الحد تُضاف إلى القالب ليتكيف مع الوقت.
+    /// Samples below this risk are folded into the template so it adapts over time.
+    pub adapt_below_risk: f32,
+    /// أقصى عدد من القوالب في الذاكرة؛ الأقدم استخداماً يُطرد عند الامتلاء.
+    /// Maximum templates in memory; the least recently used is evicted when full.
+    pub max_profiles: usize,
+    /// القوالب غير المستخدمة لهذه المدة (أيام) تُسقط ولا تُحمّل من قاعدة البيانات.
+    /// Templates unused for this long (days) are dropped and not loaded from the database.
+    pub idle_days: i64,
+}
+
+impl TemplateSettings {
+    /// تحويل المسافة إلى خطر بين 0.0 و 1.0.
+    /// Maps a distance to a risk between 0.0 and 1.0.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn risk(&self, distance: f64) -> f32 {
+        ((distance - self.genuine_distance) / (self.impostor_distance - self.genuine_distance))
+            .clamp(0.0, 1.0) as f32
+    }
+}
+
+/// قالب ميزات: متوسط كل ميزة ومتوسط انحرافها المطلق.
+/// A feature template: each feature's mean and mean absolute deviation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureTemplate {
+    pub means: Vec<f64>,
+    pub deviations: Vec<f64>,
+    pub samples: usize,
+}
+
+impl FeatureTemplate {
+    /// يبني القالب من متجهات ميزات متساوية الطول.
+    /// Builds the template from equal-length feature vectors.
+    #[must_use]
+    pub fn from_vectors<'a>(vectors: impl ExactSizeIterator<Item = &'a Vec<f64>> + Clone) -> Self {
+        let samples = vectors.len();
+        #[allow(clippy::cast_precision_loss)]
+        let n = samples.max(1) as f64;
+        let dims = vectors.clone().next().map_or(0, Vec::len);
+        let mut means = vec![0.0; dims];
+        for vector in vectors.clone() {
+            for (mean, value) in means.iter_mut().zip(vector) {
+                *mean += value / n;
+            }
+        }
+        let mut deviations = vec![0.0; dims];
+        for vector in vectors {
+            for ((deviation, mean), value) in deviations.iter_mut().zip(&means).zip(vector) {
+                *deviation += (value - mean).abs() / n;
+            }
+        }
+        Self {
+            means,
+            deviations,
+            samples,
+        }
+    }
+
+    /// مسافة مانهاتن المقاسة مقسومة على عدد الميزات: 1.0 تقريباً لصاحب القالب.
+    /// Scaled Manhattan distance divided by the feature count: about 1.0 for the owner.
+    ///
+    /// # Errors
+    /// يعيد `TemplateError::DimensionMismatch` إذا اختلف عدد الميزات.
+    /// Returns `TemplateError::DimensionMismatch` if the feature count differs.
+    pub fn distance(
+        &self,
+        features: &[f64],
+        settings: &TemplateSettings,
+    ) -> Result<f64, TemplateError> {
+        if features.len() != self.means.len() || features.is_empty() {
+            return Err(TemplateError::DimensionMismatch {
+                expected: self.means.len(),
+                actual: features.len(),
+            });
+        }
+        let total: f64 = features
+            .iter()
+            .zip(&self.means)
+            .zip(&self.deviations)
+            .map(|((value, mean), deviation)| {
+                let floor = settings
+                    .absolute_floor
+                    .max(settings.relative_floor * mean.abs());
+                (value - mean).abs() / deviation.max(floor)
+            })
+            .sum();
+        #[allow(clippy::cast_precision_loss)]
+        Ok(total / features.len() as f64)
+    }
+}
+
+/// نتيجة مقارنة عينة بقالب المستخدم.
+/// Result of comparing a sample with the user's template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateScore {
+    /// غائبة أثناء التسجيل (لم يُبنَ القالب بعد).
+    /// Absent during enrollment (no template yet).
+    pub distance: Option<f64>,
+    pub risk: f32,
+    pub enrolled: bool,
+}
+
+struct Profile {
+    vectors: VecDeque<Vec<f64>>,
+    template: Option<FeatureTemplate>,
+    last_seen: DateTime<Utc>,
+}
+
+impl Profile {
+    fn new(vectors: Vec<Vec<f64>>, settings: &TemplateSettings, now: DateTime<Utc>) -> Self {
+        let skip = vectors.len().saturating_sub(settings.max_samples);
+        let vectors: VecDeque<_> = vectors.into_iter().skip(skip).collect();
+        let template = (vectors.len() >= settings.min_enrollment)
+            .then(|| FeatureTemplate::from_vectors(vectors.iter()));
+        Self {
+            vectors,
+            template,
+            last_seen: now,
+        }
+    }
+}
+
+// ================================================================
+// التخزين الدائم
+// Persistent Storage
+// ================================================================
+
+/// مخزن دائم لعينات القوالب، مفتاحه (نوع القالب، مفتاح مسلسل).
+/// Persistent storage of template samples, keyed by (template kind, serialized key).
+#[async_trait]
+pub trait TemplateBackend: Send + Sync {
+    async fn load(
+        &self,
+        kind: &str,
+        key: &str,
+        not_before: DateTime<Utc>,
+    ) -> Result<Option<Vec<Vec<f64>>>, TemplateError>;
+    async fn save(&self, kind: &str, key: &str, samples: &[Vec<f64>]) -> Result<(), TemplateError>;
+}
+
+/// يحفظ عينات القوالب في جدول `biometric_templates`.
+/// Stores template samples in the `biometric_templates` table.
+pub struct SqliteTemplateBackend {
+    pool: DbPool,
+}
+
+impl SqliteTemplateBackend {
+    #[must_use]
+    pub const fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TemplateBackend for SqliteTemplateBackend {
+    async fn load(
+        &self,
+        kind: &str,
+        key: &str,
+        not_before: DateTime<Utc>,
+    ) -> Result<Option<Vec<Vec<f64>>>, TemplateError> {
+        crud::get_biometric_samples(&self.pool, kind, key, not_before.naive_utc())
+            .await
+            .map_err(|e| TemplateError::Storage(e.to_string()))
+    }
+
+    async fn save(&self, kind: &str, key: &str, samples: &[Vec<f64>]) -> Result<(), TemplateError> {
+        crud::upsert_biometric_samples(&self.pool, kind, key, samples, Utc::now().naive_utc())
+            .await
+            .map_err(|e| TemplateError::Storage(e.to_string()))
+    }
+}
+
+// ================================================================
+// مخزن القوالب
+// Template Store
+// ================================================================
+
+/// قوالب الميزات لكل مفتاح، في الذاكرة مع حفظ اختياري في قاعدة البيانات.
+/// Feature templates per key, in memory with optional database persistence.
+pub struct TemplateStore<K> {
+    settings: TemplateSettings,
+    backend: Option<(&'static str, Arc<dyn TemplateBackend>)>,
+    profiles: RwLock<HashMap<K, Profile>>,
+}
+
+impl<K: Eq + Hash + Clone + Serialize> TemplateStore<K> {
+    #[must_use]
+    pub fn new(settings: TemplateSettings) -> Self {
+        Self {
+            settings,
+            backend: None,
+            profiles: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// يحفظ القوالب في مخزن دائم تحت نوع `kind` ويحمّلها منه عند أول استخدام.
+    /// Persists templates to a backend under `kind` and loads them from it on first use.
+    #[must_use]
+    pub fn with_backend(mut self, kind: &'static str, backend: Arc<dyn TemplateBackend>) -> Self {
+        self.backend = Some((kind, backend));
+        self
+    }
+
+    #[must_use]
+    pub const fn settings(&self) -> &TemplateSettings {
+        &self.settings
+    }
+
+    /// يقيّم المتجه مقابل قالب المفتاح ثم يضيفه إليه أثناء التسجيل أو إذا كان
+    /// منخفض الخطر، فلا تُلوث عينات المنتحل القالب.
+    /// Scores the vector against the key's template, then folds it in during enrollment
+    /// or when it is low risk, so impostor samples do not pollute the template.
+    ///
+    /// # Errors
+    /// يعيد `TemplateError::DimensionMismatch` إذا اختلف طول المتجه عن عينات المفتاح.
+    /// Returns `TemplateError::DimensionMismatch` if the vector length differs from the
+    /// key's samples.
+    pub async fn observe(
+        &self,
+        key: K,
+        features: Vec<f64>,
+    ) -> Result<TemplateScore, TemplateError> {
+        let now = Utc::now();
+        let idle_cutoff = now - Duration::days(self.settings.idle_days);
+        let stored_key = self
+            .backend
+            .as_ref()
+            .and_then(|_| serde_json::to_string(&key).ok());
+
+        // التحميل من قاعدة البيانات خارج القفل
+        // Loading from the database happens outside the lock
+        let mut loaded = None;
+        if let (Some((kind, backend)), Some(stored_key)) = (&self.backend, &stored_key) {
+            let cached = self
+                .profiles
+                .read()
+                .await
+                .get(&key)
+                .is_some_and(|profile| profile.last_seen >= idle_cutoff);
+            if !cached {
+                loaded = backend
+                    .load(kind, stored_key, idle_cutoff)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::warn!("failed to load {kind} template: {e}");
+                        None
+                    });
+            }
+        }
+
+        let mut profiles = self.profiles.write().await;
+        if profiles
+            .get(&key)
+            .is_some_and(|profile| profile.last_seen < idle_cutoff)
+        {
+            profiles.remove(&key);
+        }
+        if !profiles.contains_key(&key) {
+            self.evict(&mut profiles, idle_cutoff);
+        }
+        let profile = profiles
+            .entry(key)
+            .or_insert_with(|| Profile::new(loaded.unwrap_or_default(), &self.settings, now));
+        profile.last_seen = now;
+
+        let score = match &profile.template {
+            Some(template) => {
+                let distance = template.distance(&features, &self.settings)?;
+                TemplateScore {
+                    distance: Some(distance),
+                    risk: self.settings.risk(distance),
+                    enrolled: true,
+                }
+            }
+            None => {
+                if let Some(first) = profile.vectors.front() {
+                    if first.len() != features.len() {
+                        return Err(TemplateError::DimensionMismatch {
+                            expected: first.len(),
+                            actual: features.len(),
+                        });
+                    }
+                }
+                TemplateScore {
+                    distance: None,
+                    risk: 0.0,
+                    enrolled: false,
+                }
+            }
+        };
+
+        if !score.enrolled || score.risk < self.settings.adapt_below_risk {
+            if profile.vectors.len() >= self.settings.max_samples {
+                profile.vectors.pop_front();
+            }
+            profile.vectors.push_back(features);
+            if profile.vectors.len() >= self.settings.min_enrollment {
+                profile.template = Some(FeatureTemplate::from_vectors(profile.vectors.iter()));
+            }
+            let samples: Vec<Vec<f64>> = profile.vectors.iter().cloned().collect();
+            drop(profiles);
+            if let (Some((kind, backend)), Some(stored_key)) = (&self.backend, &stored_key) {
+                if let Err(e) = backend.save(kind, stored_key, &samples).await {
+                    log::warn!("failed to store {kind} template: {e}");
+                }
+            }
+        }
+        Ok(score)
+    }
+
+    /// يُسقط القوالب الخاملة ثم الأقدم استخداماً حتى يتسع مكان لقالب جديد.
+    /// Drops idle templates, then the least recently used, to make room for a new one.
+    fn evict(&self, profiles: &mut HashMap<K, Profile>, idle_cutoff: DateTime<Utc>) {
+        if profiles.len() < self.settings.max_profiles {
+            return;
+        }
+        profiles.retain(|_, profile| profile.last_seen >= idle_cutoff);
+        while profiles.len() >= self.settings.max_profiles.max(1) {
+            let Some(oldest) = profiles
+                .iter()
+                .min_by_key(|(_, profile)| profile.last_seen)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            profiles.remove(&oldest);
+        }
+    }
+
+    /// قالب المفتاح الحالي إن وُجد.
+    /// The key's current template, if any.
+    pub async fn template(&self, key: &K) -> Option<FeatureTemplate> {
+        self.profiles
+            .read()
+            .await
+            .get(key)
+            .and_then(|profile| profile.template.clone())
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SETTINGS: TemplateSettings = TemplateSettings {
+        min_enrollment: 2,
+        max_samples: 5,
+        absolute_floor: 1.0,
+        relative_floor: 0.0,
+        genuine_distance: 1.5,
+        impostor_distance: 3.5,
+        adapt_below_risk: 0.3,
+        max_profiles: 2,
+        idle_days: 30,
+    };
+
+    #[tokio::test]
+    async fn test_least_recently_used_profile_is_evicted() {
+        let store = TemplateStore::new(TEST_SETTINGS);
+        for user in ["a", "b"] {
+            for _ in 0..2 {
+                store.observe(user.to_string(), vec![10.0]).await.unwrap();
+            }
+        }
+        // "a" استُخدم أخيراً، فيُطرد "b" عند وصول "c"
+        // "a" was used last, so "b" is evicted when "c" arrives
+        store.observe("a".to_string(), vec![10.0]).await.unwrap();
+        store.observe("c".to_string(), vec![10.0]).await.unwrap();
+        assert!(store.template(&"a".to_string()).await.is_some());
+        assert!(store.template(&"b".to_string()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_templates_survive_a_restart_through_the_database() {
+        let db = tokio_rusqlite::Connection::open_in_memory().await.unwrap();
+        crud::init_schema(&db).await.unwrap();
+        let backend: Arc<dyn TemplateBackend> = Arc::new(SqliteTemplateBackend::new(db));
+
+        let store = TemplateStore::new(TEST_SETTINGS).with_backend("test", Arc::clone(&backend));
+        for value in [10.0, 12.0] {
+            store.observe("a".to_string(), vec![value]).await.unwrap();
+        }
+
+        let restarted = TemplateStore::new(TEST_SETTINGS).with_backend("test", backend);
+        let score = restarted
+            .observe("a".to_string(), vec![11.0])
+            .await
+            .unwrap();
+        assert!(score.enrolled);
+        assert!(score.risk < 0.3, "{score:?}");
+    }
+}
@@ -0,0 +1,1188 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: client_fingerprint.rs
+    المسار:    src/core/client_fingerprint.rs
+    دور الملف:
+    بصمة عميل النقل: تحليل رسالة TLS ClientHello الخام وحساب بصمتي JA3 و JA4،
+    وبصمة HTTP/2 بأسلوب Akamai من إطارات SETTINGS/WINDOW_UPDATE/PRIORITY،
+    ومقارنتها بقاعدة بصمات عملاء معروفة لكشف التناقض بين وكيل المستخدم المعلن
+    ومكدس TLS الفعلي (مثل متصفح "Chrome" بمكتبة Python).
+    تُرسل هذه القيم من وكيل إنهاء TLS لأن العميل لا يستطيع تزويرها بسهولة.
+    --------------------------------------------------------------
+    File Name: client_fingerprint.rs
+    Path:     src/core/client_fingerprint.rs
+    File Role:
+    Transport client fingerprinting: parses a raw TLS ClientHello into JA3 and JA4
+    fingerprints, builds an Akamai-style HTTP/2 fingerprint from the SETTINGS,
+    WINDOW_UPDATE and PRIORITY frames, and compares both against a database of known
+    clients to flag a claimed user agent that disagrees with the real TLS stack (such
+    as a "Chrome" user agent on a Python TLS library).
+    The values come from the TLS-terminating proxy, since clients cannot forge them easily.
+******************************************************************************************/
+
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum ClientFingerprintError {
+    #[error("Malformed TLS ClientHello: {0}")]
+    MalformedClientHello(&'static str),
+    #[error("Malformed HTTP/2 fingerprint: {0}")]
+    MalformedHttp2(String),
+    #[error("Malformed transport header: {0}")]
+    MalformedHeader(&'static str),
+    #[error("Failed to read known-client file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Malformed known-client file: {0}")]
+    MalformedDatabase(#[from] serde_json::Error),
+}
+
+// ================================================================
+// ثوابت TLS
+// TLS constants
+// ================================================================
+const TLS_RECORD_HANDSHAKE: u8 = 0x16;
+const TLS_HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// خصم مستوى الأمان عند تناقض بصمة النقل مع وكيل المستخدم.
+/// Security level penalty when the transport fingerprint contradicts the user agent.
+pub const CLIENT_MISMATCH_PENALTY: u8 = 4;
+
+/// قيم GREASE (RFC 8701) من الشكل `0x?a?a` ويتجاهلها JA3 و JA4.
+/// GREASE values (RFC 8701) of the form `0x?a?a`, ignored by JA3 and JA4.
+#[must_use]
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// قارئ بايتات بسيط يعيد خطأ عند الاقتطاع بدل الذعر.
+/// Minimal byte reader that errors on truncation instead of panicking.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize, what: &'static str) -> Result<&'a [u8], ClientFingerprintError> {
+        if self.buf.len() < n {
+            return Err(ClientFingerprintError::MalformedClientHello(what));
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self, what: &'static str) -> Result<u8, ClientFingerprintError> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    fn u16(&mut self, what: &'static str) -> Result<u16, ClientFingerprintError> {
+        let b = self.take(2, what)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self, what: &'static str) -> Result<usize, ClientFingerprintError> {
+        let b = self.take(3, what)?;
+        Ok(usize::from(b[0]) << 16 | usize::from(b[1]) << 8 | usize::from(b[2]))
+    }
+
+    /// متجه مسبوق بطول من بايت واحد.
+    /// A vector prefixed with a one-byte length.
+    fn vec8(&mut self, what: &'static str) -> Result<Reader<'a>, ClientFingerprintError> {
+        let len = usize::from(self.u8(what)?);
+        Ok(Reader {
+            buf: self.take(len, what)?,
+        })
+    }
+
+    /// متجه مسبوق بطول من بايتين.
+    /// A vector prefixed with a two-byte length.
+    fn vec16(&mut self, what: &'static str) -> Result<Reader<'a>, ClientFingerprintError> {
+        let len = usize::from(self.u16(what)?);
+        Ok(Reader {
+            buf: self.take(len, what)?,
+        })
+    }
+
+    fn u16_list(mut self, what: &'static str) -> Result<Vec<u16>, ClientFingerprintError> {
+        let mut out = Vec::with_capacity(self.buf.len() / 2);
+        while !self.buf.is_empty() {
+            out.push(self.u16(what)?);
+        }
+        Ok(out)
+    }
+}
+
+// ================================================================
+// TLS ClientHello و JA3/JA4
+// TLS ClientHello and JA3/JA4
+// ================================================================
+
+/// الحقول التي تحتاجها البصمات من رسالة ClientHello، بترتيب الإرسال ومع قيم GREASE.
+/// The ClientHello fields the fingerprints need, in wire order and including GREASE values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientHello {
+    pub legacy_version: u16,
+    pub cipher_suites: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub alpn: Vec<String>,
+    pub supported_versions: Vec<u16>,
+    pub server_name: Option<String>,
+}
+
+impl ClientHello {
+    /// يحلل ClientHello خاماً، مع ترويسة سجل TLS أو بدونها (رسالة المصافحة وحدها).
+    /// Parses a raw ClientHello, with or without the TLS record header (the bare handshake message).
+    pub fn parse(bytes: &[u8]) -> Result<Self, ClientFingerprintError> {
+        let mut input = Reader { buf: bytes };
+        if bytes.first() == Some(&TLS_RECORD_HANDSHAKE) {
+            input.take(3, "record header")?;
+            input = input.vec16("record length")?;
+        }
+        if input.u8("handshake type")? != TLS_HANDSHAKE_CLIENT_HELLO {
+            return Err(ClientFingerprintError::MalformedClientHello(
+                "not a ClientHello handshake",
+            ));
+        }
+        let len = input.u24("handshake length")?;
+        let mut body = Reader {
+            buf: input.take(len, "handshake body")?,
+        };
+
+        let mut hello = Self {
+            legacy_version: body.u16("legacy version")?,
+            ..Self::default()
+        };
+        body.take(32, "random")?;
+        body.vec8("session id")?;
+        hello.cipher_suites = body.vec16("cipher suites")?.u16_list("cipher suites")?;
+        body.vec8("compression methods")?;
+        if body.buf.is_empty() {
+            return Ok(hello);
+        }
+
+        let mut extensions = body.vec16("extensions")?;
+        while !extensions.buf.is_empty() {
+            let ext_type = extensions.u16("extension type")?;
+            let mut data = extensions.vec16("extension data")?;
+            hello.extensions.push(ext_type);
+            match ext_type {
+                EXT_SERVER_NAME => {
+                    let mut names = data.vec16("server name list")?;
+                    while !names.buf.is_empty() {
+                        let name_type = names.u8("server name type")?;
+                        let name = names.vec16("server name")?;
+                        if name_type == 0 && hello.server_name.is_none() {
+                            hello.server_name =
+                                Some(String::from_utf8_lossy(name.buf).into_owned());
+                        }
+                    }
+                }
+                EXT_SUPPORTED_GROUPS => {
+                    hello.supported_groups = data
+                        .vec16("supported groups")?
+                        .u16_list("supported groups")?;
+                }
+                EXT_EC_POINT_FORMATS => {
+                    hello.ec_point_formats = data.vec8("ec point formats")?.buf.to_vec();
+                }
+                EXT_SIGNATURE_ALGORITHMS => {
+                    hello.signature_algorithms = data
+                        .vec16("signature algorithms")?
+                        .u16_list("signature algorithms")?;
+                }
+                EXT_ALPN => {
+                    let mut protocols = data.vec16("alpn list")?;
+                    while !protocols.buf.is_empty() {
+                        let protocol = protocols.vec8("alpn protocol")?;
+                        hello
+                            .alpn
+                            .push(String::from_utf8_lossy(protocol.buf).into_owned());
+                    }
+                }
+                EXT_SUPPORTED_VERSIONS => {
+                    hello.supported_versions = data
+                        .vec8("supported versions")?
+                        .u16_list("supported versions")?;
+                }
+                _ => {}
+            }
+        }
+        Ok(hello)
+    }
+
+    /// هل يرسل العميل قيم GREASE (سمة متصفحات Chromium و Safari).
+    /// Whether the client sends GREASE values (a trait of Chromium and Safari browsers).
+    #[must_use]
+    pub fn uses_grease(&self) -> bool {
+        self.cipher_suites
+            .iter()
+            .chain(&self.extensions)
+            .any(|v| is_grease(*v))
+    }
+
+    /// أعلى إصدار TLS معروض: من `supported_versions` إن وجد وإلا الإصدار القديم.
+    /// Highest offered TLS version: from `supported_versions` when present, else the legacy version.
+    #[must_use]
+    pub fn max_version(&self) -> u16 {
+        self.supported_versions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .max()
+            .unwrap_or(self.legacy_version)
+    }
+
+    /// سمات المكدس التي لا تشبه المتصفح المعلن (فارغة للعائلات غير المعروفة).
+    /// Stack traits that do not look like the claimed browser (empty for unknown families).
+    #[must_use]
+    pub fn browser_mismatches(&self, claimed_family: &str) -> Vec<ClientMismatch> {
+        let mut mismatches = Vec::new();
+        if !is_modern_browser(claimed_family) {
+            return mismatches;
+        }
+        if !self.alpn.iter().any(|p| p == "h2") {
+            mismatches.push(ClientMismatch::BrowserWithoutH2);
+        }
+        if self.max_version() < 0x0304 {
+            mismatches.push(ClientMismatch::BrowserWithoutTls13);
+        }
+        if claimed_family != "firefox" && !self.uses_grease() {
+            mismatches.push(ClientMismatch::MissingGrease);
+        }
+        mismatches
+    }
+
+    /// نص JA3: `version,ciphers,extensions,groups,point_formats` بالأرقام العشرية دون GREASE.
+    /// The JA3 string: `version,ciphers,extensions,groups,point_formats` in decimal, without GREASE.
+    #[must_use]
+    pub fn ja3_string(&self) -> String {
+        fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+            values.map(|v| v.to_string()).collect::<Vec<_>>().join("-")
+        }
+        let no_grease = |v: &&u16| !is_grease(**v);
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version,
+            join(self.cipher_suites.iter().filter(no_grease)),
+            join(self.extensions.iter().filter(no_grease)),
+            join(self.supported_groups.iter().filter(no_grease)),
+            join(self.ec_point_formats.iter()),
+        )
+    }
+
+    /// بصمة JA3: MD5 لنص JA3 بصيغة hex.
+    /// The JA3 fingerprint: hex MD5 of the JA3 string.
+    #[must_use]
+    pub fn ja3(&self) -> String {
+        hex::encode(Md5::digest(self.ja3_string().as_bytes()))
+    }
+
+    /// بصمة JA4 (TCP): `t<ver><sni><ciphers><exts><alpn>_<ciphers hash>_<extensions hash>`.
+    /// الأجزاء مرتبة فلا تتأثر بخلط ترتيب الامتدادات الذي تستخدمه المتصفحات الحديثة.
+    /// The JA4 (TCP) fingerprint: `t<ver><sni><ciphers><exts><alpn>_<ciphers hash>_<extensions hash>`.
+    /// Lists are sorted, so the extension-order shuffling of modern browsers does not affect it.
+    #[must_use]
+    pub fn ja4(&self) -> String {
+        let ciphers: Vec<u16> = self
+            .cipher_suites
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect();
+        let extensions: Vec<u16> = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect();
+        let version = match self.max_version() {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+        let sni = if extensions.contains(&EXT_SERVER_NAME) {
+            'd'
+        } else {
+            'i'
+        };
+        let ja4_a = format!(
+            "t{version}{sni}{:02}{:02}{}",
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            ja4_alpn(self.alpn.first().map(String::as_bytes)),
+        );
+
+        let mut sorted_ciphers = ciphers;
+        sorted_ciphers.sort_unstable();
+        let ja4_b = truncated_sha256(&hex_list(&sorted_ciphers));
+
+        let mut sorted_extensions: Vec<u16> = extensions
+            .into_iter()
+            .filter(|v| *v != EXT_SERVER_NAME && *v != EXT_ALPN)
+            .collect();
+        sorted_extensions.sort_unstable();
+        let mut ja4_c_input = hex_list(&sorted_extensions);
+        let signature_algorithms: Vec<u16> = self
+            .signature_algorithms
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect();
+        if !signature_algorithms.is_empty() {
+            ja4_c_input.push('_');
+            ja4_c_input.push_str(&hex_list(&signature_algorithms));
+        }
+        let ja4_c = truncated_sha256(&ja4_c_input);
+        format!("{ja4_a}_{ja4_b}_{ja4_c}")
+    }
+}
+
+/// أول وآخر محرف من أول بروتوكول ALPN، أو `00` عند غيابه.
+/// First and last character of the first ALPN protocol, or `00` when absent.
+fn ja4_alpn(protocol: Option<&[u8]>) -> String {
+    match protocol {
+        Some(protocol) if !protocol.is_empty() => {
+            let (first, last) = (&protocol[0], &protocol[protocol.len() - 1]);
+            if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                format!("{}{}", char::from(*first), char::from(*last))
+            } else {
+                let first_hex = format!("{first:02x}");
+                let last_hex = format!("{last:02x}");
+                format!("{}{}", &first_hex[..1], &last_hex[1..])
+            }
+        }
+        _ => "00".to_string(),
+    }
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{v:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// أول 12 محرف hex من SHA-256، أو أصفار لقائمة فارغة كما يحدد JA4.
+/// First 12 hex characters of SHA-256, or zeros for an empty list as JA4 specifies.
+fn truncated_sha256(input: &str) -> String {
+    if input.is_empty() {
+        return "0".repeat(12);
+    }
+    let mut digest = hex::encode(Sha256::digest(input.as_bytes()));
+    digest.truncate(12);
+    digest
+}
+
+// ================================================================
+// بصمة HTTP/2
+// HTTP/2 fingerprint
+// ================================================================
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const H2_FRAME_HEADERS: u8 = 0x1;
+const H2_FRAME_PRIORITY: u8 = 0x2;
+const H2_FRAME_SETTINGS: u8 = 0x4;
+const H2_FRAME_WINDOW_UPDATE: u8 = 0x8;
+const H2_FLAG_ACK: u8 = 0x1;
+const H2_FLAG_PADDED: u8 = 0x8;
+const H2_FLAG_PRIORITY: u8 = 0x20;
+
+/// أولوية تدفق HTTP/2 (الوزن بالصيغة المعروضة 1-256).
+/// An HTTP/2 stream priority (weight in its displayed 1-256 form).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Http2Priority {
+    pub stream_id: u32,
+    pub exclusive: bool,
+    pub depends_on: u32,
+    pub weight: u16,
+}
+
+/// بصمة HTTP/2 بأسلوب Akamai: `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`.
+/// An Akamai-style HTTP/2 fingerprint: `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Http2Fingerprint {
+    pub settings: Vec<(u16, u32)>,
+    pub window_update: u32,
+    pub priorities: Vec<Http2Priority>,
+    /// ترتيب الترويسات الزائفة بحروفها الأولى (`m,a,s,p`).
+    /// Pseudo-header order by first letter (`m,a,s,p`).
+    pub pseudo_header_order: Vec<char>,
+}
+
+impl Http2Fingerprint {
+    /// يبني البصمة من إطارات العميل الأولى (مع مقدمة الاتصال أو بدونها).
+    /// ترتيب الترويسات الزائفة يحتاج فك HPACK فيمرره الوكيل كما رآه (`:method` أو `m`).
+    /// Builds the fingerprint from the client's first frames (with or without the connection
+    /// preface). The pseudo-header order needs HPACK decoding, so the proxy passes it as seen
+    /// (`:method` or `m`).
+    pub fn from_frames(
+        frames: &[u8],
+        pseudo_headers: &[String],
+    ) -> Result<Self, ClientFingerprintError> {
+        let malformed = |what: &str| ClientFingerprintError::MalformedHttp2(what.to_string());
+        let mut input = frames.strip_prefix(H2_PREFACE).unwrap_or(frames);
+        let mut fingerprint = Self {
+            pseudo_header_order: pseudo_headers
+                .iter()
+                .filter_map(|name| name.trim_start_matches(':').chars().next())
+                .collect(),
+            ..Self::default()
+        };
+        let mut settings_seen = false;
+        while !input.is_empty() {
+            if input.len() < 9 {
+                return Err(malformed("truncated frame header"));
+            }
+            let len =
+                usize::from(input[0]) << 16 | usize::from(input[1]) << 8 | usize::from(input[2]);
+            let (frame_type, flags) = (input[3], input[4]);
+            let stream_id =
+                u32::from_be_bytes([input[5], input[6], input[7], input[8]]) & 0x7fff_ffff;
+            let payload = input
+                .get(9..9 + len)
+                .ok_or_else(|| malformed("truncated frame payload"))?;
+            input = &input[9 + len..];
+
+            match frame_type {
+                H2_FRAME_SETTINGS if flags & H2_FLAG_ACK == 0 && !settings_seen => {
+                    settings_seen = true;
+                    if payload.len() % 6 != 0 {
+                        return Err(malformed("SETTINGS payload is not a multiple of 6"));
+                    }
+                    fingerprint.settings = payload
+                        .chunks_exact(6)
+                        .map(|s| {
+                            (
+                                u16::from_be_bytes([s[0], s[1]]),
+                                u32::from_be_bytes([s[2], s[3], s[4], s[5]]),
+                            )
+                        })
+                        .collect();
+                }
+                H2_FRAME_WINDOW_UPDATE if stream_id == 0 && fingerprint.window_update == 0 => {
+                    let increment = payload
+                        .get(..4)
+                        .ok_or_else(|| malformed("short WINDOW_UPDATE"))?;
+                    fingerprint.window_update = u32::from_be_bytes([
+                        increment[0],
+                        increment[1],
+                        increment[2],
+                        increment[3],
+                    ]) & 0x7fff_ffff;
+                }
+                H2_FRAME_PRIORITY => {
+                    fingerprint.priorities.push(
+                        parse_priority(stream_id, payload)
+                            .ok_or_else(|| malformed("short PRIORITY"))?,
+                    );
+                }
+                H2_FRAME_HEADERS if flags & H2_FLAG_PRIORITY != 0 => {
+                    let pad = usize::from(flags & H2_FLAG_PADDED != 0);
+                    fingerprint.priorities.push(
+                        payload
+                            .get(pad..)
+                            .and_then(|p| parse_priority(stream_id, p))
+                            .ok_or_else(|| malformed("short HEADERS priority"))?,
+                    );
+                }
+                _ => {}
+            }
+        }
+        Ok(fingerprint)
+    }
+}
+
+fn parse_priority(stream_id: u32, payload: &[u8]) -> Option<Http2Priority> {
+    let p = payload.get(..5)?;
+    let dependency = u32::from_be_bytes([p[0], p[1], p[2], p[3]]);
+    Some(Http2Priority {
+        stream_id,
+        exclusive: dependency & 0x8000_0000 != 0,
+        depends_on: dependency & 0x7fff_ffff,
+        weight: u16::from(p[4]) + 1,
+    })
+}
+
+impl fmt::Display for Http2Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let settings: Vec<String> = self
+            .settings
+            .iter()
+            .map(|(id, value)| format!("{id}:{value}"))
+            .collect();
+        let window_update = if self.window_update == 0 {
+            "00".to_string()
+        } else {
+            self.window_update.to_string()
+        };
+        let priorities: Vec<String> = self
+            .priorities
+            .iter()
+            .map(|p| {
+                format!(
+                    "{}:{}:{}:{}",
+                    p.stream_id,
+                    u8::from(p.exclusive),
+                    p.depends_on,
+                    p.weight
+                )
+            })
+            .collect();
+        let priorities = if priorities.is_empty() {
+            "0".to_string()
+        } else {
+            priorities.join(",")
+        };
+        let pseudo: Vec<String> = self
+            .pseudo_header_order
+            .iter()
+            .map(char::to_string)
+            .collect();
+        write!(
+            f,
+            "{}|{window_update}|{priorities}|{}",
+            settings.join(";"),
+            pseudo.join(",")
+        )
+    }
+}
+
+impl FromStr for Http2Fingerprint {
+    type Err = ClientFingerprintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = |what: &str| ClientFingerprintError::MalformedHttp2(format!("{what}: {s}"));
+        let parts: Vec<&str> = s.trim().split('|').collect();
+        let [settings, window_update, priorities, pseudo] = parts.as_slice() else {
+            return Err(malformed("expected four '|' separated parts"));
+        };
+        let settings = settings
+            .split(';')
+            .filter(|p| !p.is_empty())
+            .map(|pair| {
+                let (id, value) = pair.split_once(':')?;
+                Some((id.parse().ok()?, value.parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| malformed("bad SETTINGS"))?;
+        let window_update = window_update
+            .parse()
+            .map_err(|_| malformed("bad WINDOW_UPDATE"))?;
+        let priorities = if *priorities == "0" {
+            Vec::new()
+        } else {
+            priorities
+                .split(',')
+                .map(|p| {
+                    let fields: Vec<&str> = p.split(':').collect();
+                    let [stream_id, exclusive, depends_on, weight] = fields.as_slice() else {
+                        return None;
+                    };
+                    Some(Http2Priority {
+                        stream_id: stream_id.parse().ok()?,
+                        exclusive: *exclusive == "1",
+                        depends_on: depends_on.parse().ok()?,
+                        weight: weight.parse().ok()?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| malformed("bad PRIORITY"))?
+        };
+        let pseudo_header_order = pseudo
+            .split(',')
+            .filter_map(|p| p.trim().chars().next())
+            .collect();
+        Ok(Self {
+            settings,
+            window_update,
+            priorities,
+            pseudo_header_order,
+        })
+    }
+}
+
+// ================================================================
+// قاعدة العملاء المعروفين
+// Known-client database
+// ================================================================
+
+/// نوع العميل المعروف.
+/// Known client kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientKind {
+    Browser,
+    Library,
+    Bot,
+}
+
+/// مدخل في قاعدة العملاء المعروفين؛ يكفي حقل بصمة واحد للمطابقة.
+/// A known-client entry; any single fingerprint field is enough to match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownClient {
+    /// وصف مقروء، مثل `python-requests 2.31`.
+    /// Human-readable label, such as `python-requests 2.31`.
+    pub label: String,
+    /// عائلة العميل بنفس أسماء `BrowserInfo::name` للمتصفحات (`chrome`, `firefox`...).
+    /// Client family, using the `BrowserInfo::name` names for browsers (`chrome`, `firefox`...).
+    pub family: String,
+    pub kind: ClientKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ja3: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ja4: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2: Option<String>,
+}
+
+/// قاعدة بصمات العملاء المعروفين، تُحمّل من ملف JSON (مصفوفة `KnownClient`).
+/// Known client fingerprint database, loaded from a JSON file (an array of `KnownClient`).
+#[derive(Debug, Clone, Default)]
+pub struct KnownClientDb {
+    clients: Vec<KnownClient>,
+}
+
+impl KnownClientDb {
+    #[must_use]
+    pub fn new(clients: Vec<KnownClient>) -> Self {
+        Self { clients }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ClientFingerprintError> {
+        Ok(Self::new(serde_json::from_str(json)?))
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, ClientFingerprintError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// يبحث بالأولوية JA4 ثم JA3 ثم HTTP/2؛ JA4 أثبت لأنه لا يتأثر بترتيب الامتدادات.
+    /// Looks up by JA4, then JA3, then HTTP/2; JA4 is the most stable as extension order does not affect it.
+    #[must_use]
+    pub fn lookup(
+        &self,
+        ja4: Option<&str>,
+        ja3: Option<&str>,
+        http2: Option<&str>,
+    ) -> Option<&KnownClient> {
+        let by = |value: Option<&str>, field: fn(&KnownClient) -> Option<&str>| {
+            let value = value?;
+            self.clients
+                .iter()
+                .find(|c| field(c).is_some_and(|f| f.eq_ignore_ascii_case(value)))
+        };
+        by(ja4, |c| c.ja4.as_deref())
+            .or_else(|| by(ja3, |c| c.ja3.as_deref()))
+            .or_else(|| by(http2, |c| c.http2.as_deref()))
+    }
+}
+
+// ================================================================
+// التحليل وكشف التناقض
+// Analysis and mismatch detection
+// ================================================================
+
+/// مدخلات النقل كما يرسلها وكيل إنهاء TLS. الـ ClientHello الخام يتقدم على القيم المحسوبة مسبقاً.
+/// Transport inputs as sent by the TLS-terminating proxy. A raw ClientHello takes precedence over precomputed values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransportFingerprintInput {
+    /// بايتات ClientHello الخام.
+    /// Raw ClientHello bytes.
+    #[serde(skip_serializing_if = "Option::is_none", with = "base64_bytes")]
+    pub client_hello: Option<Vec<u8>>,
+    /// بصمة JA3 (hex MD5) محسوبة في الوكيل.
+    /// JA3 fingerprint (hex MD5) computed by the proxy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja3: Option<String>,
+    /// بصمة JA4 محسوبة في الوكيل.
+    /// JA4 fingerprint computed by the proxy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja4: Option<String>,
+    /// بصمة HTTP/2 بصيغة Akamai.
+    /// Akamai-format HTTP/2 fingerprint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2: Option<String>,
+    /// إطارات HTTP/2 الأولى الخام مع ترتيب الترويسات الزائفة.
+    /// The raw first HTTP/2 frames with the pseudo-header order.
+    #[serde(skip_serializing_if = "Option::is_none", with = "base64_bytes")]
+    pub http2_frames: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub http2_pseudo_headers: Vec<String>,
+}
+
+/// ترويسات النقل التي يضعها وكيل إنهاء TLS (ويحذف ما أرسله العميل منها).
+/// Transport headers set by the TLS-terminating proxy (which strips any client-sent copies).
+pub const CLIENT_HELLO_HEADER: &str = "x-tls-clienthello";
+pub const JA3_HEADER: &str = "x-ja3-fingerprint";
+pub const JA4_HEADER: &str = "x-ja4-fingerprint";
+pub const HTTP2_HEADER: &str = "x-http2-fingerprint";
+pub const HTTP2_FRAMES_HEADER: &str = "x-http2-frames";
+pub const HTTP2_PSEUDO_HEADERS_HEADER: &str = "x-http2-pseudo-headers";
+
+impl TransportFingerprintInput {
+    /// يبني المدخلات من ترويسات الوكيل؛ البايتات الخام بترميز base64 القياسي.
+    /// يعيد `None` إذا لم تُرسل أي ترويسة نقل.
+    /// Builds the input from the proxy headers; raw bytes are standard base64.
+    /// Returns `None` when no transport header was sent.
+    ///
+    /// # Errors
+    /// يعيد `MalformedHeader` عند تلف ترميز base64.
+    /// Returns `MalformedHeader` for invalid base64.
+    pub fn from_headers<'a>(
+        headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Option<Self>, ClientFingerprintError> {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+
+        let decode = |value: &str, header: &'static str| {
+            STANDARD
+                .decode(value)
+                .map_err(|_| ClientFingerprintError::MalformedHeader(header))
+        };
+        let mut input = Self::default();
+        for (name, value) in headers {
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                CLIENT_HELLO_HEADER => {
+                    input.client_hello = Some(decode(value, CLIENT_HELLO_HEADER)?);
+                }
+                JA3_HEADER => input.ja3 = Some(value.to_string()),
+                JA4_HEADER => input.ja4 = Some(value.to_string()),
+                HTTP2_HEADER => input.http2 = Some(value.to_string()),
+                HTTP2_FRAMES_HEADER => {
+                    input.http2_frames = Some(decode(value, HTTP2_FRAMES_HEADER)?);
+                }
+                HTTP2_PSEUDO_HEADERS_HEADER => {
+                    input.http2_pseudo_headers = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|h| !h.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok((input != Self::default()).then_some(input))
+    }
+}
+
+/// البايتات الخام تُنقل في JSON بترميز base64 القياسي.
+/// Raw bytes travel in JSON as standard base64.
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => s.serialize_str(&STANDARD.encode(bytes)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|text| {
+                STANDARD
+                    .decode(text.trim())
+                    .map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+/// سبب تناقض بصمة النقل مع وكيل المستخدم.
+/// Why a transport fingerprint contradicts the user agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientMismatch {
+    /// البصمة تطابق عميلاً معروفاً من عائلة أخرى.
+    /// The fingerprint matches a known client of another family.
+    KnownClientFamily,
+    /// متصفح معلن لا يعرض HTTP/2 في ALPN.
+    /// A claimed browser that does not offer HTTP/2 in ALPN.
+    BrowserWithoutH2,
+    /// متصفح معلن لا يعرض TLS 1.3.
+    /// A claimed browser that does not offer TLS 1.3.
+    BrowserWithoutTls13,
+    /// متصفح Chromium أو Safari معلن بلا قيم GREASE.
+    /// A claimed Chromium or Safari browser without GREASE values.
+    MissingGrease,
+    /// الطلب مر عبر الوكيل الموثوق لكنه لم يمرر بصمة النقل.
+    /// The request came through the trusted proxy but it forwarded no transport fingerprint.
+    MissingTransportFingerprint,
+}
+
+/// نتيجة تحليل بصمة النقل.
+/// Transport fingerprint analysis result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientFingerprintReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja3: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja4: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub known_client: Option<KnownClient>,
+    /// عائلة المتصفح المعلنة في وكيل المستخدم.
+    /// Browser family claimed by the user agent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claimed_family: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<ClientMismatch>,
+}
+
+impl ClientFingerprintReport {
+    #[must_use]
+    pub fn is_mismatch(&self) -> bool {
+        !self.mismatches.is_empty()
+    }
+
+    /// تقرير لطلب وصل عبر الوكيل الموثوق بلا بصمة نقل؛ يُعامل كتناقض.
+    /// Report for a request that came through the trusted proxy without a transport
+    /// fingerprint; treated as a mismatch.
+    #[must_use]
+    pub fn missing(claimed_family: Option<&str>) -> Self {
+        Self {
+            ja3: None,
+            ja4: None,
+            http2: None,
+            known_client: None,
+            claimed_family: claimed_family.map(str::to_ascii_lowercase),
+            mismatches: vec![ClientMismatch::MissingTransportFingerprint],
+        }
+    }
+
+    /// يخصم `CLIENT_MISMATCH_PENALTY` من مستوى الأمان عند التناقض.
+    /// Subtracts `CLIENT_MISMATCH_PENALTY` from the security level on a mismatch.
+    #[must_use]
+    pub fn adjust_security_level(&self, level: u8) -> u8 {
+        if self.is_mismatch() {
+            level.saturating_sub(CLIENT_MISMATCH_PENALTY)
+        } else {
+            level
+        }
+    }
+}
+
+/// يحسب بصمات النقل ويقارنها بالعائلة المعلنة وبقاعدة العملاء المعروفين.
+/// Computes transport fingerprints and checks them against the claimed family and known clients.
+pub fn analyze_transport(
+    input: &TransportFingerprintInput,
+    claimed_family: Option<&str>,
+    known: &KnownClientDb,
+) -> Result<ClientFingerprintReport, ClientFingerprintError> {
+    let hello = input
+        .client_hello
+        .as_deref()
+        .map(ClientHello::parse)
+        .transpose()?;
+    let http2 = match (&input.http2_frames, &input.http2) {
+        (Some(frames), _) => {
+            Some(Http2Fingerprint::from_frames(frames, &input.http2_pseudo_headers)?.to_string())
+        }
+        (None, Some(akamai)) => Some(akamai.parse::<Http2Fingerprint>()?.to_string()),
+        (None, None) => None,
+    };
+    let ja3 = hello
+        .as_ref()
+        .map(ClientHello::ja3)
+        .or_else(|| input.ja3.clone());
+    let ja4 = hello
+        .as_ref()
+        .map(ClientHello::ja4)
+        .or_else(|| input.ja4.clone());
+    let known_client = known
+        .lookup(ja4.as_deref(), ja3.as_deref(), http2.as_deref())
+        .cloned();
+
+    let claimed_family = claimed_family.map(str::to_ascii_lowercase);
+    let mut mismatches = Vec::new();
+    if let Some(claimed) = claimed_family.as_deref() {
+        if known_client
+            .as_ref()
+            .is_some_and(|client| !client.family.eq_ignore_ascii_case(claimed))
+        {
+            mismatches.push(ClientMismatch::KnownClientFamily);
+        }
+        if let Some(hello) = &hello {
+            mismatches.extend(hello.browser_mismatches(claimed));
+        }
+    }
+
+    Ok(ClientFingerprintReport {
+        ja3,
+        ja4,
+        http2,
+        known_client,
+        claimed_family,
+        mismatches,
+    })
+}
+
+/// المتصفحات التي تعرض كل إصداراتها الحديثة TLS 1.3 و HTTP/2.
+/// Browsers whose current releases all offer TLS 1.3 and HTTP/2.
+fn is_modern_browser(family: &str) -> bool {
+    matches!(
+        family,
+        "chrome" | "edge" | "opera" | "samsung" | "firefox" | "safari"
+    )
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ext(ext_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut out = ext_type.to_be_bytes().to_vec();
+        out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn u16_vec(values: &[u16]) -> Vec<u8> {
+        let mut out = ((values.len() * 2) as u16).to_be_bytes().to_vec();
+        values
+            .iter()
+            .for_each(|v| out.extend_from_slice(&v.to_be_bytes()));
+        out
+    }
+
+    /// ClientHello مطابق لمثال مواصفة JA4 لمتصفح Chrome، مع GREASE وترتيب امتدادات قابل للخلط.
+    /// A ClientHello matching the JA4 spec's Chrome example, with GREASE and a shuffleable extension order.
+    fn chrome_like_hello(reverse_extensions: bool) -> Vec<u8> {
+        let ciphers = [
+            0x0a0a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013,
+            0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+        ];
+        let mut sni = vec![0, 14, 0, 0, 11];
+        sni.extend_from_slice(b"example.com");
+        let mut alpn = vec![0, 12, 2];
+        alpn.extend_from_slice(b"h2");
+        alpn.push(8);
+        alpn.extend_from_slice(b"http/1.1");
+        let mut extensions = vec![
+            ext(0x1a1a, &[]),
+            ext(EXT_SERVER_NAME, &sni),
+            ext(0x0017, &[]),
+            ext(0xff01, &[0]),
+            ext(
+                EXT_SUPPORTED_GROUPS,
+                &u16_vec(&[0x2a2a, 0x001d, 0x0017, 0x0018]),
+            ),
+            ext(EXT_EC_POINT_FORMATS, &[1, 0]),
+            ext(0x0023, &[]),
+            ext(EXT_ALPN, &alpn),
+            ext(0x0005, &[1, 0, 0, 0, 0]),
+            ext(
+                EXT_SIGNATURE_ALGORITHMS,
+                &u16_vec(&[
+                    0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+                ]),
+            ),
+            ext(0x0012, &[]),
+            ext(0x0033, &[0, 0]),
+            ext(0x002d, &[1, 1]),
+            ext(
+                EXT_SUPPORTED_VERSIONS,
+                &[6, 0x3a, 0x3a, 0x03, 0x04, 0x03, 0x03],
+            ),
+            ext(0x001b, &[2, 0, 2]),
+            ext(0x4469, &[]),
+            ext(0x0015, &[0, 0]),
+        ];
+        if reverse_extensions {
+            extensions.reverse();
+        }
+        let extensions: Vec<u8> = extensions.concat();
+
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[7; 32]);
+        body.push(0);
+        body.extend_from_slice(&u16_vec(&ciphers));
+        body.extend_from_slice(&[1, 0]);
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut handshake = vec![TLS_HANDSHAKE_CLIENT_HELLO];
+        handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(&body);
+        let mut record = vec![TLS_RECORD_HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn test_ja3_and_ja4_match_published_vectors() {
+        let ja3_example = ClientHello {
+            legacy_version: 769,
+            cipher_suites: vec![47, 53, 5, 10, 49161, 49162, 49171, 49172, 50, 56, 19, 4],
+            extensions: vec![0x0a0a, 0, 10, 11],
+            supported_groups: vec![23, 24, 25],
+            ec_point_formats: vec![0],
+            ..ClientHello::default()
+        };
+        assert_eq!(ja3_example.ja3(), "ada70206e40642a3e4461f35503241d5");
+
+        let hello = ClientHello::parse(&chrome_like_hello(false)).expect("parse");
+        assert_eq!(hello.server_name.as_deref(), Some("example.com"));
+        assert_eq!(hello.alpn, vec!["h2", "http/1.1"]);
+        assert_eq!(hello.max_version(), 0x0304);
+        assert!(hello.uses_grease());
+        assert_eq!(hello.ja4(), "t13d1516h2_8daaf6152771_e5627efa2ab1");
+
+        // خلط الامتدادات يغير JA3 ولا يغير JA4
+        // Shuffling extensions changes JA3 but not JA4
+        let shuffled = ClientHello::parse(&chrome_like_hello(true)).expect("parse");
+        assert_eq!(shuffled.ja4(), hello.ja4());
+        assert_ne!(shuffled.ja3(), hello.ja3());
+
+        let truncated = chrome_like_hello(false);
+        assert!(ClientHello::parse(&truncated[..truncated.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn test_http2_fingerprint_from_frames_and_akamai_string() {
+        let mut frames = H2_PREFACE.to_vec();
+        let settings: Vec<u8> = [(1u16, 65536u32), (2, 0), (4, 6_291_456), (6, 262_144)]
+            .iter()
+            .flat_map(|(id, value)| {
+                [id.to_be_bytes().to_vec(), value.to_be_bytes().to_vec()].concat()
+            })
+            .collect();
+        frames.extend_from_slice(&[0, 0, settings.len() as u8, H2_FRAME_SETTINGS, 0, 0, 0, 0, 0]);
+        frames.extend_from_slice(&settings);
+        frames.extend_from_slice(&[0, 0, 4, H2_FRAME_WINDOW_UPDATE, 0, 0, 0, 0, 0]);
+        frames.extend_from_slice(&15_663_105u32.to_be_bytes());
+        frames.extend_from_slice(&[0, 0, 5, H2_FRAME_PRIORITY, 0, 0, 0, 0, 3]);
+        frames.extend_from_slice(&[0, 0, 0, 0, 200]);
+        let pseudo = [":method", ":authority", ":scheme", ":path"].map(String::from);
+
+        let fingerprint = Http2Fingerprint::from_frames(&frames, &pseudo).expect("frames");
+        let akamai = "1:65536;2:0;4:6291456;6:262144|15663105|3:0:0:201|m,a,s,p";
+        assert_eq!(fingerprint.to_string(), akamai);
+        assert_eq!(
+            akamai.parse::<Http2Fingerprint>().expect("parse"),
+            fingerprint
+        );
+        assert!("1:65536|00".parse::<Http2Fingerprint>().is_err());
+        assert!(Http2Fingerprint::from_frames(&frames[..frames.len() - 2], &pseudo).is_err());
+    }
+
+    #[test]
+    fn test_chrome_user_agent_on_python_stack_is_a_mismatch() {
+        let hello = ClientHello::parse(&chrome_like_hello(false)).expect("parse");
+        let python = KnownClient {
+            label: "python-requests".to_string(),
+            family: "python".to_string(),
+            kind: ClientKind::Library,
+            ja3: Some("ada70206e40642a3e4461f35503241d5".to_string()),
+            ja4: None,
+            http2: None,
+        };
+        let browser = KnownClient {
+            label: "Chrome 120".to_string(),
+            family: "chrome".to_string(),
+            kind: ClientKind::Browser,
+            ja3: None,
+            ja4: Some(hello.ja4()),
+            http2: None,
+        };
+        let known = KnownClientDb::new(vec![python, browser]);
+
+        let genuine = TransportFingerprintInput {
+            client_hello: Some(chrome_like_hello(false)),
+            ..Default::default()
+        };
+        let report = analyze_transport(&genuine, Some("chrome"), &known).expect("analyze");
+        assert!(!report.is_mismatch());
+        assert_eq!(
+            report.known_client.map(|c| c.kind),
+            Some(ClientKind::Browser)
+        );
+        assert_eq!(
+            report.ja4.as_deref(),
+            Some("t13d1516h2_8daaf6152771_e5627efa2ab1")
+        );
+
+        let forged = TransportFingerprintInput {
+            ja3: Some("ADA70206E40642A3E4461F35503241D5".to_string()),
+            ..Default::default()
+        };
+        let report = analyze_transport(&forged, Some("chrome"), &known).expect("analyze");
+        assert_eq!(report.mismatches, vec![ClientMismatch::KnownClientFamily]);
+        assert_eq!(report.adjust_security_level(9), 9 - CLIENT_MISMATCH_PENALTY);
+
+        // بلا قاعدة: مكدس TLS 1.2 بلا h2 ولا GREASE لا يشبه Chrome
+        // Without a database: a TLS 1.2 stack without h2 or GREASE does not look like Chrome
+        let bare = ClientHello {
+            legacy_version: 0x0303,
+            cipher_suites: vec![0xc02f],
+            ..ClientHello::default()
+        };
+        assert_eq!(
+            bare.ja4(),
+            format!("t12i010000_{}_000000000000", truncated_sha256("c02f"))
+        );
+        assert_eq!(
+            bare.browser_mismatches("chrome"),
+            vec![
+                ClientMismatch::BrowserWithoutH2,
+                ClientMismatch::BrowserWithoutTls13,
+                ClientMismatch::MissingGrease
+            ]
+        );
+        assert_eq!(
+            bare.browser_mismatches("firefox"),
+            vec![
+                ClientMismatch::BrowserWithoutH2,
+                ClientMismatch::BrowserWithoutTls13
+            ]
+        );
+        assert!(bare.browser_mismatches("curl").is_empty());
+    }
+}
@@ -25,10 +25,40 @@
 use crate::core::behavior_bio::{BehaviorEngine, BehaviorInput};
 use crate::core::device_fp::AdaptiveFingerprintEngine;
 use crate::core::geo_resolver::{GeoResolver, ResolveParams};
+use crate::core::location_consistency::{DeviceLocationHints, LocationConsistencyChecker};
 use crate::core::network_analyzer::NetworkAnalyzer;
 use chrono::Timelike;
+use serde::Serialize;
 use std::sync::Arc;
 
+/// Arabic: إشارة خطر من التحقق المركب بوزن بين 0.0 و 1.0؛ السياسة تقرر ما يكفي للرفض.
+/// English: A composite verification risk signal weighted 0.0 to 1.0; policy decides what is enough to deny.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SmartAccessSignal {
+    pub code: &'static str,
+    pub weight: f32,
+}
+
+/// Arabic: نتيجة تحقق مركب اجتاز الشروط الصارمة، مع إشارات الخطر التي وُجدت
+/// English: Result of a composite verification that passed the hard checks, with the risk signals found
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SmartAccessAssessment {
+    pub signals: Vec<SmartAccessSignal>,
+}
+
+impl SmartAccessAssessment {
+    /// Arabic: مجموع أوزان الإشارات، بحد أقصى 1.0
+    /// English: Sum of the signal weights, capped at 1.0
+    #[must_use]
+    pub fn risk(&self) -> f32 {
+        self.signals
+            .iter()
+            .map(|signal| signal.weight)
+            .sum::<f32>()
+            .min(1.0)
+    }
+}
+
 /// Arabic: هيكل التحقق المركب يجمع كل المحركات المتخصصة
 /// English: CompositeVerifier struct aggregates all specialized engines
 pub struct CompositeVerifier {
@@ -36,6 +66,7 @@ pub struct CompositeVerifier {
     pub behavior: Arc<BehaviorEngine>,
     pub device_fp: Arc<AdaptiveFingerprintEngine>,
     pub network: Arc<NetworkAnalyzer>,
+    pub location_consistency: LocationConsistencyChecker,
 }
 
 impl CompositeVerifier {
@@ -48,7 +79,8 @@ impl CompositeVerifier {
         device_info: (&str, &str, &str),
         allowed_zones: &[String],
         allowed_hours: Option<(u8, u8)>,
-    ) -> Result<bool, String> {
+    ) -> Result<SmartAccessAssessment, String> {
+        let mut assessment = SmartAccessAssessment::default();
         // 1. تحقق جغرافي
         let geo_location = match &geo_input {
             Some((ip, gps)) => self
@@ -73,6 +105,27 @@ impl CompositeVerifier {
         } else {
             return Err("Geo location city missing".to_string());
         }
+        // 1.1 تحقق اتساق موقع IP مع GPS: التناقض إشارة خطر وليس رفضاً (VPN الشركات، التجوال)
+        // 1.1 IP-vs-GPS location consistency check: a mismatch is a risk signal, not a denial
+        // (corporate VPNs, roaming)
+        if let Some((ip, (lat, lng, _, accuracy))) = &geo_input {
+            if let Some(ip_location) = self.network.geolocate_ip(ip) {
+                let report = self.location_consistency.check(
+                    &ip_location,
+                    &DeviceLocationHints {
+                        gps: Some((*lat, *lng)),
+                        gps_accuracy_m: Some(*accuracy),
+                        ..DeviceLocationHints::default()
+                    },
+                );
+                if report.is_mismatch {
+                    assessment.signals.push(SmartAccessSignal {
+                        code: "ip_gps_mismatch",
+                        weight: report.mismatch_score,
+                    });
+                }
+            }
+        }
         if let Some((start, end)) = allowed_hours {
             let hour = chrono::Utc::now().hour() as u8;
             if hour < start || hour > end {
@@ -103,6 +156,6 @@ impl CompositeVerifier {
         // let network_result = self.network.analyze(...).await?;
         // if network_result.security_score < 0.5 { return Err("Access denied: network not trusted".to_string()); }
         // 5. إذا نجحت كل الشروط
-        Ok(true)
+        Ok(assessment)
     }
 }
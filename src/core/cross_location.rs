@@ -37,6 +37,9 @@
 use crate::core::behavior_bio::{AnalysisResult as BehaviorResult, BehaviorEngine, BehaviorInput};
 use crate::core::device_fp::{AdaptiveFingerprint, AdaptiveFingerprintEngine};
 use crate::core::geo_resolver::{GeoLocation, GeoResolver};
+use crate::core::location_consistency::{
+    DeviceLocationHints, LocationConsistencyChecker, LocationConsistencyReport,
+};
 use crate::core::network_analyzer::NetworkAnalyzer;
 use crate::core::sensors_analyzer::SensorsAnalyzerEngine;
 
@@ -77,6 +80,10 @@ pub struct CrossValidationInput<'a> {
     // Inputs for GeoResolver
     pub ip_address: Option<std::net::IpAddr>,
     pub gps_data: Option<(f64, f64, u8, f64)>,
+    // تلميحات الدولة من الجهاز (لمقارنتها بدولة IP)
+    // Device country hints (compared with the IP country)
+    pub sim_country: Option<&'a str>,
+    pub locale: Option<&'a str>,
 
     // Inputs for DeviceFPEngine
     pub os_info: &'a str,
@@ -96,6 +103,10 @@ pub struct ValidationResult {
     pub geo_location: GeoLocation,
     pub device_fingerprint: AdaptiveFingerprint,
     pub behavior_analysis: BehaviorResult,
+    /// اتساق موقع IP مع موقع الجهاز.
+    /// Consistency of the IP location with the device location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_consistency: Option<LocationConsistencyReport>,
     pub signature: String,
     pub timestamp: i64,
 }
@@ -128,6 +139,7 @@ pub struct CrossValidationEngine {
     pub network_engine: Arc<NetworkAnalyzer>,
     pub scoring_strategy: Arc<dyn ScoringStrategy>,
     pub signing_key: SecureBytes,
+    pub location_consistency: LocationConsistencyChecker,
 }
 
 impl CrossValidationEngine {
@@ -150,9 +162,18 @@ impl CrossValidationEngine {
             network_engine,
             scoring_strategy,
             signing_key,
+            location_consistency: LocationConsistencyChecker::default(),
         }
     }
 
+    /// استبدال إعدادات مدقق اتساق الموقع الافتراضية.
+    /// Replaces the default location consistency checker.
+    #[must_use]
+    pub fn with_location_consistency(mut self, checker: LocationConsistencyChecker) -> Self {
+        self.location_consistency = checker;
+        self
+    }
+
     /// تنفيذ عملية التحقق والتنسيق الكاملة.
     /// Executes the full validation and orchestration process.
     ///
@@ -193,11 +214,24 @@ impl CrossValidationEngine {
 
         // 2. حساب درجة الثقة النهائية باستخدام الاستراتيجية المحقونة
         // 2. Calculate the final trust score using the injected strategy
-        let final_trust_score = self
+        let score = self
             .scoring_strategy
             .calculate_score(&geo_location, &device_fingerprint, &behavior_analysis)
             .await;
 
+        // 2.1 مقارنة موقع IP بموقع الجهاز وخصم الثقة عند التناقض
+        // 2.1 Compare the IP location with the device location and deduct trust on mismatch
+        let (final_trust_score, location_consistency) = self.apply_location_consistency(
+            score,
+            input.ip_address,
+            &DeviceLocationHints {
+                gps: input.gps_data.map(|(lat, lng, _, _)| (lat, lng)),
+                gps_accuracy_m: input.gps_data.map(|(_, _, _, accuracy)| accuracy),
+                sim_country: input.sim_country,
+                locale: input.locale,
+            },
+        );
+
         // 3. بناء الحكم النهائي
         // 3. Construct the final verdict
         let mut result = ValidationResult {
@@ -206,6 +240,7 @@ impl CrossValidationEngine {
             geo_location,
             device_fingerprint,
             behavior_analysis,
+            location_consistency,
             signature: String::new(), // سيتم ملؤها لاحقًا
             timestamp: chrono::Utc::now().timestamp(),
         };
@@ -218,6 +253,25 @@ impl CrossValidationEngine {
         Ok(result)
     }
 
+    /// يقارن موقع IP بتلميحات الجهاز ويعيد الدرجة بعد الخصم مع التقرير؛ بدون موقع IP
+    /// تبقى الدرجة كما هي.
+    /// Compares the IP location with the device hints and returns the penalized score with
+    /// the report; without an IP location the score is unchanged.
+    fn apply_location_consistency(
+        &self,
+        score: f32,
+        ip: Option<std::net::IpAddr>,
+        hints: &DeviceLocationHints<'_>,
+    ) -> (f32, Option<LocationConsistencyReport>) {
+        let report = ip
+            .and_then(|ip| self.network_engine.geolocate_ip(&ip))
+            .map(|ip_location| self.location_consistency.check(&ip_location, hints));
+        let score = report.as_ref().map_or(score, |report| {
+            self.location_consistency.penalize(score, report)
+        });
+        (score, report)
+    }
+
     /// يوقع على بيانات الحكم باستخدام مفتاح HMAC-SHA512.
     /// Signs the verdict data using an HMAC-SHA512 key.
     fn sign_verdict(&self, result: &ValidationResult) -> Result<String, CrossValidationError> {
@@ -326,7 +380,7 @@ mod tests {
 
         // 3. Build BehaviorEngine
         let behavior_engine = Arc::new(BehaviorEngine::new(
-            Arc::new(DefaultBehavioralModel),
+            Arc::new(DefaultBehavioralModel::default()),
             Arc::new(DefaultAnomalyDetector {
                 max_speed_kmh: 1200.0,
             }),
@@ -389,6 +443,8 @@ mod tests {
         let input = CrossValidationInput {
             ip_address: Some("8.8.8.8".parse().unwrap()),
             gps_data: Some((34.05, -118.24, 95, 5.0)),
+            sim_country: None,
+            locale: None,
             os_info: "Windows 11",
             device_details: "Dell XPS",
             environment_context: "desktop",
@@ -402,6 +458,10 @@ mod tests {
                     connection_type: "WiFi".to_string(),
                 },
                 device_fingerprint: "initial_fp".to_string(),
+                device_similarity: None,
+                keystrokes: None,
+                pointer_streams: Vec::new(),
+                timezone: None,
             },
         };
         let Ok(result) = engine.validate(input).await else {
@@ -418,4 +478,30 @@ mod tests {
         mac.update(&serialized);
         assert!(mac.verify_slice(&signature_bytes).is_ok());
     }
+
+    #[test]
+    fn test_location_mismatch_penalizes_trust() {
+        let engine = setup_full_engine();
+        // IP في لندن بينما GPS وشريحة الاتصال في الرياض
+        // A London IP while the GPS fix and SIM are in Riyadh
+        let riyadh = DeviceLocationHints {
+            gps: Some((24.7136, 46.6753)),
+            gps_accuracy_m: Some(10.0),
+            sim_country: Some("SA"),
+            locale: Some("ar-SA"),
+        };
+        let london_ip = Some("81.2.69.142".parse().unwrap());
+        let (score, report) = engine.apply_location_consistency(0.9, london_ip, &riyadh);
+        let report = report.unwrap();
+        assert!(report.is_mismatch);
+        assert!(report.distance_km.unwrap() > 4000.0);
+        assert!(score < 0.9);
+        assert!((score - engine.location_consistency.penalize(0.9, &report)).abs() < 1e-6);
+
+        // بدون عنوان IP لا يوجد تقرير ولا خصم
+        // Without an IP there is no report and no penalty
+        let (score, report) = engine.apply_location_consistency(0.9, None, &riyadh);
+        assert!(report.is_none());
+        assert!((score - 0.9).abs() < f32::EPSILON);
+    }
 }
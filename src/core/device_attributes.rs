@@ -0,0 +1,834 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: device_attributes.rs
+    المسار:    src/core/device_attributes.rs
+    دور الملف:
+    نموذج منظم لخصائص الجهاز (عائلة النظام وإصداره، الطراز، الشاشة، اللغة، المنطقة
+    الزمنية، عدد الأنوية، وكيل المستخدم، تلميحات العميل، إصدار التطبيق) مع محلل
+    لوكلاء المستخدم الشائعة وترويسات Client Hints، وتطبيع الخصائص وأوزانها التي
+    يحسب `AdaptiveFingerprintEngine` البصمة عليها.
+    --------------------------------------------------------------
+    File Name: device_attributes.rs
+    Path:     src/core/device_attributes.rs
+    File Role:
+    Typed device-attributes model (OS family/version, model, screen, locale, timezone,
+    hardware concurrency, user agent, client hints, app version) with a parser for
+    common user agents and Client Hints headers, plus the normalized, weighted
+    attributes `AdaptiveFingerprintEngine` computes fingerprints over.
+******************************************************************************************/
+
+use crate::core::device_integrity::DeviceSignals;
+use serde::{Deserialize, Serialize};
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// عائلة نظام التشغيل.
+/// Operating-system family.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OsFamily {
+    Windows,
+    MacOs,
+    Ios,
+    Android,
+    Linux,
+    ChromeOs,
+    #[default]
+    Unknown,
+}
+
+impl OsFamily {
+    /// يتعرف على العائلة من اسم حر مثل `Windows 11` أو `macOS` أو `iPadOS`.
+    /// Recognizes the family from a free-form name such as `Windows 11`, `macOS` or `iPadOS`.
+    #[must_use]
+    pub fn parse(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if name.contains("android") {
+            Self::Android
+        } else if name.contains("chrome os")
+            || name.contains("chromeos")
+            || name.contains("chromium os")
+        {
+            Self::ChromeOs
+        } else if name.contains("ios") || name.contains("iphone") || name.contains("ipad") {
+            Self::Ios
+        } else if name.contains("windows") || name.starts_with("win") {
+            Self::Windows
+        } else if name.contains("macos")
+            || name.contains("mac os")
+            || name.contains("macintosh")
+            || name.contains("os x")
+        {
+            Self::MacOs
+        } else if name.contains("linux") || name.contains("ubuntu") || name.contains("fedora") {
+            Self::Linux
+        } else {
+            Self::Unknown
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Windows => "windows",
+            Self::MacOs => "macos",
+            Self::Ios => "ios",
+            Self::Android => "android",
+            Self::Linux => "linux",
+            Self::ChromeOs => "chromeos",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// فئة الجهاز التي تحدد ملف تعريف البيئة.
+/// Device class, which selects the environment profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceClass {
+    Mobile,
+    Tablet,
+    Desktop,
+    Iot,
+    Server,
+    #[default]
+    Unknown,
+}
+
+impl DeviceClass {
+    /// مفتاح ملف تعريف البيئة في `AdaptiveFingerprintEngine`.
+    /// Environment-profile key in `AdaptiveFingerprintEngine`.
+    #[must_use]
+    pub const fn profile_key(&self) -> &'static str {
+        match self {
+            Self::Mobile | Self::Tablet => "mobile",
+            Self::Iot => "iot",
+            Self::Server => "server",
+            Self::Desktop | Self::Unknown => "desktop",
+        }
+    }
+
+    /// يستنتج الفئة من كلمات وصف البيئة (`mobile-wifi`، `datacenter`، ...).
+    /// Infers the class from environment description words (`mobile-wifi`, `datacenter`, ...).
+    #[must_use]
+    pub fn from_environment_hint(data: &str) -> Self {
+        let lower = data.to_ascii_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let has = |options: &[&str]| words.iter().any(|w| options.contains(w));
+        if has(&["mobile", "android", "ios", "phone", "cellular"]) {
+            Self::Mobile
+        } else if has(&["tablet", "ipad"]) {
+            Self::Tablet
+        } else if has(&["iot", "embedded", "tv", "wearable"]) {
+            Self::Iot
+        } else if has(&["server", "datacenter", "headless"]) {
+            Self::Server
+        } else if has(&["desktop", "laptop", "workstation", "pc"]) {
+            Self::Desktop
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// أبعاد الشاشة.
+/// Screen dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScreenInfo {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixel_ratio: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_depth: Option<u8>,
+}
+
+/// المتصفح المستخرج من وكيل المستخدم.
+/// Browser extracted from the user agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrowserInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub major_version: Option<u32>,
+}
+
+/// ترويسات User-Agent Client Hints.
+/// User-Agent Client Hints headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientHints {
+    /// `Sec-CH-UA`: قائمة `(brand, version)`.
+    /// `Sec-CH-UA`: list of `(brand, version)`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub brands: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mobile: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitness: Option<String>,
+}
+
+impl ClientHints {
+    /// يبني التلميحات من أزواج `(اسم الترويسة, القيمة)`؛ الأسماء غير حساسة لحالة الأحرف.
+    /// Builds hints from `(header name, value)` pairs; names are case-insensitive.
+    pub fn from_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut hints = Self::default();
+        for (name, value) in headers {
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "sec-ch-ua-full-version-list" => hints.brands = parse_brand_list(value),
+                "sec-ch-ua" if hints.brands.is_empty() => hints.brands = parse_brand_list(value),
+                "sec-ch-ua-mobile" => hints.mobile = Some(value == "?1"),
+                "sec-ch-ua-platform" => hints.platform = non_empty(unquote(value)),
+                "sec-ch-ua-platform-version" => hints.platform_version = non_empty(unquote(value)),
+                "sec-ch-ua-model" => hints.model = non_empty(unquote(value)),
+                "sec-ch-ua-arch" => hints.architecture = non_empty(unquote(value)),
+                "sec-ch-ua-bitness" => hints.bitness = non_empty(unquote(value)),
+                _ => {}
+            }
+        }
+        hints
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// أول علامة حقيقية (تتجاهل علامات GREASE مثل `Not A;Brand`).
+    /// First real brand (skips GREASE brands such as `Not A;Brand`).
+    fn primary_brand(&self) -> Option<&(String, String)> {
+        let real = |(brand, _): &&(String, String)| {
+            let lower = brand.to_ascii_lowercase();
+            !lower.contains("not") && !lower.contains("brand")
+        };
+        self.brands
+            .iter()
+            .filter(real)
+            .find(|(brand, _)| brand != "Chromium")
+            .or_else(|| self.brands.iter().find(|b| real(b)))
+    }
+}
+
+/// خصائص الجهاز المنظمة.
+/// Typed device attributes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceAttributes {
+    pub os_family: OsFamily,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub device_class: DeviceClass,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screen: Option<ScreenInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hardware_concurrency: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_hints: Option<ClientHints>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub browser: Option<BrowserInfo>,
+    /// إشارات سلامة البيئة (لا تدخل في البصمة).
+    /// Environment integrity signals (not part of the fingerprint).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signals: Option<DeviceSignals>,
+}
+
+/// أوزان الخصائص في البصمة (وزن 0 يستبعد الخاصية).
+/// Attribute weights in the fingerprint (a weight of 0 excludes the attribute).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributeWeights {
+    pub os_family: u8,
+    pub os_version: u8,
+    pub model: u8,
+    pub screen: u8,
+    pub hardware_concurrency: u8,
+    pub timezone: u8,
+    pub locale: u8,
+    pub browser: u8,
+    pub app_version: u8,
+}
+
+impl Default for AttributeWeights {
+    fn default() -> Self {
+        Self {
+            os_family: 20,
+            os_version: 10,
+            model: 20,
+            screen: 15,
+            hardware_concurrency: 10,
+            timezone: 10,
+            locale: 5,
+            browser: 5,
+            app_version: 5,
+        }
+    }
+}
+
+/// خاصية بعد التطبيع مع وزنها.
+/// A normalized attribute with its weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedAttribute {
+    pub name: &'static str,
+    pub value: String,
+    pub weight: u8,
+}
+
+impl DeviceAttributes {
+    /// يبني الخصائص من وكيل المستخدم وحده.
+    /// Builds attributes from a user agent alone.
+    #[must_use]
+    pub fn from_user_agent(user_agent: &str) -> Self {
+        Self {
+            user_agent: Some(user_agent.to_string()),
+            ..Self::default()
+        }
+        .resolved()
+    }
+
+    /// يبني الخصائص من ترويسات HTTP (`User-Agent` و `Sec-CH-UA-*`).
+    /// Builds attributes from HTTP headers (`User-Agent` and `Sec-CH-UA-*`).
+    pub fn from_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let headers: Vec<(&str, &str)> = headers.into_iter().collect();
+        let user_agent = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("user-agent"))
+            .map(|(_, value)| (*value).to_string());
+        let hints = ClientHints::from_headers(headers.iter().copied());
+        Self {
+            user_agent,
+            client_hints: (!hints.is_empty()).then_some(hints),
+            ..Self::default()
+        }
+        .resolved()
+    }
+
+    /// يحول المدخلات النصية القديمة `(os, device_info, environment_data)` إلى خصائص.
+    /// Converts the legacy `(os, device_info, environment_data)` strings into attributes.
+    #[must_use]
+    pub fn from_legacy(os: &str, device_info: &str, environment_data: &str) -> Self {
+        let device_info = device_info.trim();
+        let mut attrs = if device_info.starts_with("Mozilla/") {
+            Self::from_user_agent(device_info)
+        } else {
+            Self {
+                model: non_empty(device_info),
+                ..Self::default()
+            }
+        };
+        let family = OsFamily::parse(os);
+        if family != OsFamily::Unknown {
+            attrs.os_family = family;
+        }
+        if let Some(version) = os
+            .split_whitespace()
+            .find(|t| t.starts_with(char::is_numeric))
+        {
+            attrs.os_version = Some(version.to_string());
+        }
+        let class = DeviceClass::from_environment_hint(environment_data);
+        if class != DeviceClass::Unknown {
+            attrs.device_class = class;
+        }
+        attrs.resolved()
+    }
+
+    /// يملأ الحقول الناقصة من وكيل المستخدم وتلميحات العميل ويطبع القيم.
+    /// التلميحات أدق من وكيل المستخدم فتتقدم عليه، والقيم الصريحة تتقدم على الاثنين.
+    /// Fills missing fields from the user agent and client hints and normalizes values.
+    /// Hints are more precise than the user agent and win over it; explicit values win over both.
+    #[must_use]
+    pub fn resolved(mut self) -> Self {
+        let parsed = self.user_agent.as_deref().map(parse_user_agent);
+        let hints = self.client_hints.clone().unwrap_or_default();
+
+        if self.os_family == OsFamily::Unknown {
+            self.os_family = hints
+                .platform
+                .as_deref()
+                .map(OsFamily::parse)
+                .filter(|f| *f != OsFamily::Unknown)
+                .or(parsed.as_ref().map(|p| p.os_family))
+                .unwrap_or_default();
+        }
+        if self.os_version.is_none() {
+            self.os_version = hints
+                .platform_version
+                .as_deref()
+                .map(|v| normalize_platform_version(self.os_family, v))
+                .or_else(|| parsed.as_ref().and_then(|p| p.os_version.clone()));
+        }
+        if self.model.is_none() {
+            self.model = hints
+                .model
+                .clone()
+                .or_else(|| parsed.as_ref().and_then(|p| p.model.clone()));
+        }
+        if self.browser.is_none() {
+            self.browser = hints
+                .primary_brand()
+                .map(|(brand, version)| BrowserInfo {
+                    name: normalize_browser_name(brand),
+                    major_version: major_of(version),
+                })
+                .or_else(|| parsed.as_ref().and_then(|p| p.browser.clone()));
+        }
+        if self.device_class == DeviceClass::Unknown {
+            self.device_class = match (hints.mobile, &parsed) {
+                (Some(true), _) => DeviceClass::Mobile,
+                (_, Some(p)) if p.device_class != DeviceClass::Unknown => p.device_class,
+                _ => match self.os_family {
+                    OsFamily::Android | OsFamily::Ios => DeviceClass::Mobile,
+                    OsFamily::Unknown => DeviceClass::Unknown,
+                    _ => DeviceClass::Desktop,
+                },
+            };
+        }
+        self.locale = self.locale.as_deref().and_then(normalize_locale);
+        self.timezone = self.timezone.as_deref().and_then(|tz| non_empty(tz.trim()));
+        self.model = self.model.as_deref().and_then(|m| non_empty(m.trim()));
+        self.app_version = self
+            .app_version
+            .as_deref()
+            .and_then(|v| non_empty(v.trim()));
+        self
+    }
+
+    /// القيم المطبعة والموزونة التي تُحسب عليها البصمة، مرتبة بالاسم.
+    /// The normalized, weighted values the fingerprint is computed over, sorted by name.
+    #[must_use]
+    pub fn normalized(&self, weights: &AttributeWeights) -> Vec<NormalizedAttribute> {
+        let mut out = Vec::new();
+        let mut push = |name: &'static str, value: Option<String>, weight: u8| {
+            if let (Some(value), true) = (value, weight > 0) {
+                out.push(NormalizedAttribute {
+                    name,
+                    value,
+                    weight,
+                });
+            }
+        };
+        push(
+            "app_version",
+            self.app_version.as_deref().map(major_minor),
+            weights.app_version,
+        );
+        push(
+            "browser",
+            self.browser.as_ref().map(|b| b.name.to_ascii_lowercase()),
+            weights.browser,
+        );
+        push(
+            "hardware_concurrency",
+            self.hardware_concurrency.map(|n| n.to_string()),
+            weights.hardware_concurrency,
+        );
+        push(
+            "locale",
+            self.locale.as_deref().map(str::to_ascii_lowercase),
+            weights.locale,
+        );
+        push(
+            "model",
+            self.model
+                .as_deref()
+                .map(|m| collapse_whitespace(&m.to_ascii_lowercase())),
+            weights.model,
+        );
+        push(
+            "os_family",
+            (self.os_family != OsFamily::Unknown).then(|| self.os_family.as_str().to_string()),
+            weights.os_family,
+        );
+        push(
+            "os_version",
+            self.os_version.as_deref().map(major_of_str),
+            weights.os_version,
+        );
+        // الأبعاد مرتبة حتى لا يغير تدوير الشاشة البصمة
+        // Dimensions are sorted so screen rotation does not change the fingerprint
+        push(
+            "screen",
+            self.screen.map(|s| {
+                let (short, long) = (s.width.min(s.height), s.width.max(s.height));
+                match s.pixel_ratio {
+                    Some(ratio) => format!("{short}x{long}@{ratio:.1}"),
+                    None => format!("{short}x{long}"),
+                }
+            }),
+            weights.screen,
+        );
+        push(
+            "timezone",
+            self.timezone.as_deref().map(str::to_ascii_lowercase),
+            weights.timezone,
+        );
+        out
+    }
+
+    /// النصوص المستخدمة في الفحص الأمني `(os, device_info)`.
+    /// Strings passed to the security scan `(os, device_info)`.
+    #[must_use]
+    pub fn scan_strings(&self) -> (String, String) {
+        let os = match &self.os_version {
+            Some(version) => format!("{} {version}", self.os_family.as_str()),
+            None => self.os_family.as_str().to_string(),
+        };
+        let device = [self.model.as_deref(), self.user_agent.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        (os, device)
+    }
+}
+
+// ================================================================
+// محلل وكيل المستخدم
+// User-Agent Parser
+// ================================================================
+
+/// نتيجة تحليل وكيل المستخدم.
+/// Parsed user-agent result.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedUserAgent {
+    pub os_family: OsFamily,
+    pub os_version: Option<String>,
+    pub model: Option<String>,
+    pub device_class: DeviceClass,
+    pub browser: Option<BrowserInfo>,
+}
+
+/// يحلل وكلاء المستخدم الشائعة (المتصفحات الرئيسية على Windows/macOS/iOS/Android/Linux/ChromeOS).
+/// Parses common user agents (major browsers on Windows/macOS/iOS/Android/Linux/ChromeOS).
+#[must_use]
+pub fn parse_user_agent(ua: &str) -> ParsedUserAgent {
+    let mut out = ParsedUserAgent::default();
+    // الجزء بين القوسين الأولين يحمل النظام والطراز
+    // The first parenthesized section carries the platform and model
+    let platform = ua
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map_or("", |(inside, _)| inside);
+    let tokens: Vec<&str> = platform.split(';').map(str::trim).collect();
+
+    if let Some(token) = tokens.iter().find(|t| t.starts_with("Android")) {
+        out.os_family = OsFamily::Android;
+        out.os_version = token
+            .strip_prefix("Android")
+            .map(str::trim)
+            .and_then(non_empty);
+        // الطراز هو الرمز التالي لإصدار Android بعد حذف "Build/..."
+        // The model is the token after the Android version, minus "Build/..."
+        let model = tokens
+            .iter()
+            .skip_while(|t| !t.starts_with("Android"))
+            .nth(1)
+            .map(|t| t.split(" Build/").next().unwrap_or(t).trim());
+        out.model = model
+            .filter(|m| *m != "K" && *m != "wv")
+            .and_then(non_empty);
+        out.device_class = if ua.contains("Mobile") {
+            DeviceClass::Mobile
+        } else {
+            DeviceClass::Tablet
+        };
+    } else if tokens
+        .iter()
+        .any(|t| *t == "iPhone" || *t == "iPad" || *t == "iPod touch")
+    {
+        out.os_family = OsFamily::Ios;
+        out.model = tokens
+            .iter()
+            .find(|t| t.starts_with("iP"))
+            .map(|t| (*t).to_string());
+        out.os_version = version_after(platform, " OS ").map(|v| v.replace('_', "."));
+        out.device_class = if out.model.as_deref() == Some("iPad") {
+            DeviceClass::Tablet
+        } else {
+            DeviceClass::Mobile
+        };
+    } else if let Some(token) = tokens.iter().find(|t| t.starts_with("Windows NT")) {
+        out.os_family = OsFamily::Windows;
+        out.os_version = token
+            .strip_prefix("Windows NT")
+            .map(str::trim)
+            .map(|nt| match nt {
+                "6.1" => "7".to_string(),
+                "6.2" => "8".to_string(),
+                "6.3" => "8.1".to_string(),
+                "10.0" => "10".to_string(),
+                other => other.to_string(),
+            });
+        out.device_class = DeviceClass::Desktop;
+    } else if platform.contains("Mac OS X") {
+        out.os_family = OsFamily::MacOs;
+        out.os_version = version_after(platform, "Mac OS X ").map(|v| v.replace('_', "."));
+        out.device_class = DeviceClass::Desktop;
+    } else if platform.contains("CrOS") {
+        out.os_family = OsFamily::ChromeOs;
+        out.device_class = DeviceClass::Desktop;
+    } else if platform.contains("Linux") || platform.contains("X11") {
+        out.os_family = OsFamily::Linux;
+        out.device_class = DeviceClass::Desktop;
+    }
+
+    let lower = ua.to_ascii_lowercase();
+    if ["smart-tv", "smarttv", "tizen", "web0s", "crkey", "appletv"]
+        .iter()
+        .any(|marker| lower.contains(marker))
+    {
+        out.device_class = DeviceClass::Iot;
+    }
+
+    out.browser = parse_browser(ua);
+    out
+}
+
+fn parse_browser(ua: &str) -> Option<BrowserInfo> {
+    // الترتيب مهم: Edge و Opera و Samsung تحمل أيضاً "Chrome/"، و Chrome يحمل "Safari/"
+    // Order matters: Edge, Opera and Samsung also carry "Chrome/", and Chrome carries "Safari/"
+    const MARKERS: [(&str, &str); 9] = [
+        ("Edg/", "edge"),
+        ("EdgA/", "edge"),
+        ("OPR/", "opera"),
+        ("SamsungBrowser/", "samsung"),
+        ("Firefox/", "firefox"),
+        ("FxiOS/", "firefox"),
+        ("CriOS/", "chrome"),
+        ("Chrome/", "chrome"),
+        ("Version/", "safari"),
+    ];
+    for (marker, name) in MARKERS {
+        if let Some(version) = version_after(ua, marker) {
+            if name == "safari" && !ua.contains("Safari/") {
+                continue;
+            }
+            return Some(BrowserInfo {
+                name: name.to_string(),
+                major_version: major_of(&version),
+            });
+        }
+    }
+    None
+}
+
+// ================================================================
+// دوال مساعدة للتطبيع
+// Normalization Helpers
+// ================================================================
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches('"')
+}
+
+/// `"Chromium";v="124", "Google Chrome";v="124"`
+fn parse_brand_list(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|item| {
+            let (brand, version) = item.split_once(";v=")?;
+            Some((unquote(brand).to_string(), unquote(version).to_string()))
+        })
+        .collect()
+}
+
+fn version_after(haystack: &str, marker: &str) -> Option<String> {
+    let rest = &haystack[haystack.find(marker)? + marker.len()..];
+    let version: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_')
+        .collect();
+    non_empty(&version)
+}
+
+fn major_of(version: &str) -> Option<u32> {
+    version.split(['.', '_']).next()?.parse().ok()
+}
+
+fn major_of_str(version: &str) -> String {
+    version
+        .split(['.', '_'])
+        .next()
+        .unwrap_or(version)
+        .to_string()
+}
+
+fn major_minor(version: &str) -> String {
+    version.split('.').take(2).collect::<Vec<_>>().join(".")
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Windows يبلغ عبر Client Hints عن إصدار المنصة (13+ تعني Windows 11).
+/// Windows reports the platform version via Client Hints (13+ means Windows 11).
+fn normalize_platform_version(family: OsFamily, version: &str) -> String {
+    match (family, major_of(version)) {
+        (OsFamily::Windows, Some(major)) if major >= 13 => "11".to_string(),
+        (OsFamily::Windows, Some(major)) if major > 0 => "10".to_string(),
+        _ => version.to_string(),
+    }
+}
+
+fn normalize_browser_name(brand: &str) -> String {
+    let lower = brand.to_ascii_lowercase();
+    if lower.contains("edge") {
+        "edge".to_string()
+    } else if lower.contains("opera") {
+        "opera".to_string()
+    } else if lower.contains("chrome") || lower.contains("chromium") {
+        "chrome".to_string()
+    } else {
+        lower
+    }
+}
+
+/// `ar_SA.UTF-8` أو `AR-sa` تصبح `ar-SA`.
+/// `ar_SA.UTF-8` or `AR-sa` become `ar-SA`.
+fn normalize_locale(locale: &str) -> Option<String> {
+    let base = locale.trim().split('.').next().unwrap_or_default();
+    let mut parts = base.split(['-', '_']).filter(|p| !p.is_empty());
+    let language = parts.next()?.to_ascii_lowercase();
+    Some(match parts.next() {
+        Some(region) if region.len() == 2 => format!("{language}-{}", region.to_ascii_uppercase()),
+        Some(other) => format!("{language}-{other}"),
+        None => language,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_common_user_agents() {
+        let android = parse_user_agent(
+            "Mozilla/5.0 (Linux; Android 14; Pixel 8 Build/UD1A.230803.041) AppleWebKit/537.36 \
+             (KHTML, like Gecko) Chrome/124.0.6367.82 Mobile Safari/537.36",
+        );
+        assert_eq!(android.os_family, OsFamily::Android);
+        assert_eq!(android.os_version.as_deref(), Some("14"));
+        assert_eq!(android.model.as_deref(), Some("Pixel 8"));
+        assert_eq!(android.device_class, DeviceClass::Mobile);
+        assert_eq!(android.browser.unwrap().name, "chrome");
+
+        let iphone = parse_user_agent(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1",
+        );
+        assert_eq!(iphone.os_family, OsFamily::Ios);
+        assert_eq!(iphone.os_version.as_deref(), Some("17.4"));
+        assert_eq!(iphone.browser.unwrap().name, "safari");
+
+        let edge = parse_user_agent(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+             Chrome/124.0.0.0 Safari/537.36 Edg/124.0.2478.51",
+        );
+        assert_eq!(edge.os_family, OsFamily::Windows);
+        assert_eq!(edge.os_version.as_deref(), Some("10"));
+        assert_eq!(
+            edge.browser,
+            Some(BrowserInfo {
+                name: "edge".to_string(),
+                major_version: Some(124)
+            })
+        );
+    }
+
+    #[test]
+    fn test_client_hints_override_user_agent() {
+        let attrs = DeviceAttributes::from_headers([
+            (
+                "User-Agent",
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) Chrome/124.0.0.0 Safari/537.36",
+            ),
+            (
+                "Sec-CH-UA",
+                "\"Not-A.Brand\";v=\"99\", \"Chromium\";v=\"124\", \"Google Chrome\";v=\"124\"",
+            ),
+            ("Sec-CH-UA-Platform", "\"Windows\""),
+            ("Sec-CH-UA-Platform-Version", "\"15.0.0\""),
+            ("Sec-CH-UA-Mobile", "?0"),
+        ]);
+        assert_eq!(attrs.os_family, OsFamily::Windows);
+        assert_eq!(attrs.os_version.as_deref(), Some("11"));
+        assert_eq!(attrs.device_class, DeviceClass::Desktop);
+        assert_eq!(attrs.browser.unwrap().name, "chrome");
+    }
+
+    #[test]
+    fn test_normalization_is_stable_across_formatting() {
+        let weights = AttributeWeights::default();
+        let a = DeviceAttributes {
+            os_family: OsFamily::Android,
+            os_version: Some("14.1".to_string()),
+            model: Some(" Pixel  8 ".to_string()),
+            screen: Some(ScreenInfo {
+                width: 1080,
+                height: 2400,
+                pixel_ratio: Some(2.625),
+                color_depth: None,
+            }),
+            locale: Some("ar_SA.UTF-8".to_string()),
+            ..DeviceAttributes::default()
+        }
+        .resolved();
+        let b = DeviceAttributes {
+            os_family: OsFamily::Android,
+            os_version: Some("14".to_string()),
+            model: Some("pixel 8".to_string()),
+            screen: Some(ScreenInfo {
+                width: 2400,
+                height: 1080,
+                pixel_ratio: Some(2.625),
+                color_depth: Some(24),
+            }),
+            locale: Some("AR-sa".to_string()),
+            ..DeviceAttributes::default()
+        }
+        .resolved();
+        assert_eq!(a.normalized(&weights), b.normalized(&weights));
+        assert_eq!(a.device_class, DeviceClass::Mobile);
+
+        let no_locale = AttributeWeights {
+            locale: 0,
+            ..AttributeWeights::default()
+        };
+        assert!(a
+            .normalized(&no_locale)
+            .iter()
+            .all(|attr| attr.name != "locale"));
+    }
+}
@@ -44,12 +44,22 @@
     clippy::cast_sign_loss
 )]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::{CStr, CString};
 use std::sync::Arc;
 use std::time::Instant;
 
+use crate::core::client_fingerprint::{
+    analyze_transport, ClientFingerprintError, ClientFingerprintReport, KnownClientDb,
+    TransportFingerprintInput,
+};
+use crate::core::device_attributes::{AttributeWeights, DeviceAttributes};
+use crate::core::device_integrity::{
+    IntegrityDetector, IntegrityIndicator, IntegrityReport, IntegrityThreat,
+};
+use crate::security::fingerprint_keys::FingerprintKeyring;
 use crate::security::secret::SecureBytes;
+use crate::security::threat_signatures::{ThreatSignatureDb, ThreatSignatureError};
 use async_trait::async_trait;
 use blake3::Hasher;
 use futures::executor;
@@ -88,13 +98,26 @@ pub enum FingerprintError {
 
     #[error("Security threat detected: {0}")]
     SecurityThreat(String),
+
+    #[error("Fingerprint key error: {0}")]
+    Keys(#[from] crate::security::fingerprint_keys::FingerprintKeyError),
+
+    #[error("Threat signature error: {0}")]
+    ThreatSignatures(#[from] ThreatSignatureError),
+
+    #[error("Transport fingerprint error: {0}")]
+    ClientFingerprint(#[from] ClientFingerprintError),
 }
 
+/// إصدار صيغة البصمة الحالية: `fp2.<key_id>.<hex>`.
+/// Current fingerprint format version: `fp2.<key_id>.<hex>`.
+pub const FINGERPRINT_FORMAT_VERSION: u8 = 2;
+
 // ================================================================
 // الهياكل الرئيسية (دون تغيير جوهري)
 // Main Structures (No fundamental change)
 // ================================================================
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AdaptiveFingerprint {
     pub base_fp: String,
     pub adaptive_fp: String,
@@ -104,6 +127,109 @@ pub struct AdaptiveFingerprint {
     pub environment_profile: EnvironmentProfile,
     pub quantum_resistant: bool,
     pub generation_time_us: u64,
+    /// بصمات الخصائص المنفردة مع أوزانها (للمطابقة التقريبية).
+    /// Per-attribute digests with their weights (for fuzzy matching).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<FingerprintComponent>,
+    /// بصمات الخصائص نفسها تحت مفاتيح الحلقة الأخرى، لمطابقة أجهزة سُجلت قبل تدوير المفتاح.
+    /// The same per-attribute digests under the keyring's other keys, to match devices
+    /// registered before a key rotation.
+    #[serde(skip)]
+    pub rekeyed_components: BTreeMap<u32, Vec<FingerprintComponent>>,
+    /// معرف مفتاح البصمة (غائب عند استخدام مفتاح العملية المؤقت).
+    /// Fingerprint key id (absent when the ephemeral per-process key is used).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<u32>,
+    /// تقرير سلامة البيئة عند وجود مؤشرات تلاعب.
+    /// Environment integrity report when tampering indicators were found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<IntegrityReport>,
+    /// إصدار قاعدة توقيعات التهديد التي فُحص بها الجهاز.
+    /// Version of the threat signature database the device was scanned with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threat_signatures_version: Option<u64>,
+    /// بصمة النقل (JA3/JA4/HTTP2) عند إرسالها من وكيل إنهاء TLS.
+    /// Transport fingerprint (JA3/JA4/HTTP2) when sent by the TLS-terminating proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<ClientFingerprintReport>,
+}
+
+impl AdaptiveFingerprint {
+    /// بصمات الخصائص تحت مفتاح معين، أو `None` إذا لم يعد المفتاح في الحلقة.
+    /// The per-attribute digests under a given key, or `None` when the key is no longer
+    /// in the keyring.
+    #[must_use]
+    pub fn components_for_key(&self, key_id: Option<u32>) -> Option<&[FingerprintComponent]> {
+        if key_id == self.key_id {
+            return Some(&self.components);
+        }
+        key_id
+            .and_then(|id| self.rekeyed_components.get(&id))
+            .map(Vec::as_slice)
+    }
+}
+
+/// صيغ البصمة الأساسية المعروفة.
+/// Known base-fingerprint formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FingerprintVersion {
+    /// hex بدون بادئة: `BLAKE3(os || device_info || key)` بمفتاح غير معروف المعرف.
+    /// Unprefixed hex: `BLAKE3(os || device_info || key)` under an unidentified key.
+    V1,
+    /// `fp2.<key_id>.<hex>` فوق الخصائص المطبعة والموزونة.
+    /// `fp2.<key_id>.<hex>` over normalized, weighted attributes.
+    V2,
+}
+
+/// بصمة أساسية مخزنة بعد التحليل.
+/// A parsed stored base fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedFingerprint {
+    pub version: FingerprintVersion,
+    pub key_id: Option<u32>,
+    pub digest: blake3::Hash,
+}
+
+impl ParsedFingerprint {
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if let Some(rest) = value.strip_prefix("fp2.") {
+            let (key_id, digest) = rest.split_once('.')?;
+            return Some(Self {
+                version: FingerprintVersion::V2,
+                key_id: Some(key_id.parse().ok()?),
+                digest: blake3::Hash::from_hex(digest).ok()?,
+            });
+        }
+        Some(Self {
+            version: FingerprintVersion::V1,
+            key_id: None,
+            digest: blake3::Hash::from_hex(value).ok()?,
+        })
+    }
+}
+
+/// نتيجة مطابقة بصمة مخزنة مع الجهاز الحالي.
+/// Result of matching a stored fingerprint against the current device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredFingerprintMatch {
+    pub matched: bool,
+    pub stored_version: Option<FingerprintVersion>,
+    pub stored_key_id: Option<u32>,
+    /// البصمة المخزنة بصيغة قديمة أو بمفتاح متقاعد ويجب استبدالها بـ `current_fp`.
+    /// The stored fingerprint uses an old format or a retired key and should be replaced by `current_fp`.
+    pub needs_migration: bool,
+    pub current_fp: String,
+}
+
+/// بصمة خاصية واحدة بعد التطبيع.
+/// Digest of a single normalized attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FingerprintComponent {
+    pub name: String,
+    pub digest: String,
+    pub weight: u8,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -114,12 +240,83 @@ pub struct EnvironmentProfile {
     pub resource_constraints: ResourceConstraints,
 }
 
+/// مستوى التهديد الذي لا يخفض ما دونه مستوى الأمان.
+/// Threat level at or below which the security level is not lowered.
+pub const BASELINE_THREAT_LEVEL: u8 = 5;
+
+impl EnvironmentProfile {
+    /// ملفات البيئات الافتراضية لفئات الأجهزة (mobile, desktop, iot, server).
+    /// Default environment profiles for the device classes (mobile, desktop, iot, server).
+    #[must_use]
+    pub fn defaults() -> HashMap<String, Self> {
+        [
+            ("mobile", "Mobile", "Phone/Tablet", 6, 512, 5_000),
+            ("desktop", "Desktop", "PC/Workstation", 4, 2_048, 10_000),
+            ("iot", "IoT", "Embedded", 7, 256, 4_000),
+            ("server", "Server", "Datacenter Node", 8, 8_192, 15_000),
+        ]
+        .into_iter()
+        .map(
+            |(key, os_type, device_category, threat_level, max_memory_kb, max_processing_us)| {
+                (
+                    key.to_string(),
+                    Self {
+                        os_type: os_type.to_string(),
+                        device_category: device_category.to_string(),
+                        threat_level,
+                        resource_constraints: ResourceConstraints {
+                            max_memory_kb,
+                            max_processing_us,
+                        },
+                    },
+                )
+            },
+        )
+        .collect()
+    }
+
+    /// يخفض مستوى الأمان بمقدار تجاوز مستوى تهديد البيئة للحد الأساسي.
+    /// Lowers the security level by how far the environment threat level exceeds the baseline.
+    #[must_use]
+    pub const fn adjust_security_level(&self, level: u8) -> u8 {
+        level.saturating_sub(self.threat_level.saturating_sub(BASELINE_THREAT_LEVEL))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ResourceConstraints {
     pub max_memory_kb: u64,
+    /// ميزانية زمن التوليد بالميكروثانية (0 بلا حد).
+    /// Generation time budget in microseconds (0 means unlimited).
     pub max_processing_us: u64,
 }
 
+impl ResourceConstraints {
+    /// مستوى الأداء من 1 (استهلك الميزانية كاملة) إلى 10 (لم يستهلك شيئاً).
+    /// Performance level from 1 (whole budget used) to 10 (none used).
+    #[must_use]
+    pub fn performance_level(&self, elapsed_us: u64) -> u8 {
+        if self.max_processing_us == 0 {
+            return 10;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let used = (elapsed_us as f64 / self.max_processing_us as f64).clamp(0.0, 1.0);
+        10 - (used * 9.0).round() as u8
+    }
+
+    /// # Errors
+    /// Returns `FingerprintError::ResourceExceeded` when `elapsed_us` exceeds the budget.
+    pub fn check_processing_budget(&self, elapsed_us: u64) -> Result<(), FingerprintError> {
+        if self.max_processing_us > 0 && elapsed_us > self.max_processing_us {
+            return Err(FingerprintError::ResourceExceeded(format!(
+                "fingerprint generation took {elapsed_us}us, over the {}us budget",
+                self.max_processing_us
+            )));
+        }
+        Ok(())
+    }
+}
+
 // ================================================================
 // الواجهات (Traits) للمكونات القابلة للحقن
 // Traits for Injectable Components
@@ -130,6 +327,22 @@ pub trait SecurityMonitor: Send + Sync {
     async fn scan_environment(&self, os: &str, device_info: &str) -> Result<(), FingerprintError>;
     async fn update_threat_database(&self, threat_data: &str) -> Result<(), FingerprintError>;
     async fn current_security_level(&self) -> u8;
+
+    /// يقيّم سلامة بيئة الجهاز (محاكي، روت، أتمتة)؛ التطبيق الافتراضي لا يقيّم شيئاً.
+    /// Assesses the device environment integrity (emulator, root, automation); the
+    /// default implementation assesses nothing.
+    async fn assess_integrity(
+        &self,
+        _attributes: &DeviceAttributes,
+    ) -> Result<Option<IntegrityReport>, FingerprintError> {
+        Ok(None)
+    }
+
+    /// إصدار قاعدة توقيعات التهديد المحمّلة، إن وجدت.
+    /// Version of the loaded threat signature database, if any.
+    async fn threat_signatures_version(&self) -> Option<u64> {
+        None
+    }
 }
 
 #[async_trait]
@@ -157,6 +370,10 @@ pub struct AdaptiveFingerprintEngine {
     quantum: Arc<dyn QuantumEngine>,
     ai: Arc<dyn AiProcessor>,
     env_profiles: Arc<RwLock<HashMap<String, EnvironmentProfile>>>,
+    weights: AttributeWeights,
+    keys: Option<Arc<FingerprintKeyring>>,
+    enforce_processing_budget: bool,
+    known_clients: Arc<KnownClientDb>,
 }
 
 impl AdaptiveFingerprintEngine {
@@ -173,11 +390,51 @@ impl AdaptiveFingerprintEngine {
             quantum,
             ai,
             env_profiles,
+            weights: AttributeWeights::default(),
+            keys: None,
+            enforce_processing_budget: false,
+            known_clients: Arc::new(KnownClientDb::default()),
         }
     }
 
-    /// توليد بصمة متطورة
-    /// Generate an advanced fingerprint
+    /// تفعيل أو تعطيل رفض البصمات التي تتجاوز `max_processing_us`. افتراضياً يُسجَّل
+    /// التجاوز كتحذير فقط.
+    /// Enables or disables rejecting fingerprints over `max_processing_us`. By default an
+    /// overrun is only logged as a warning.
+    #[must_use]
+    pub const fn with_processing_budget_enforcement(mut self, enforce: bool) -> Self {
+        self.enforce_processing_budget = enforce;
+        self
+    }
+
+    /// استخدام مفاتيح بصمة ثابتة بدل مفتاح `QuantumEngine` المؤقت، لتبقى البصمة
+    /// نفسها عبر إعادة التشغيل والنسخ.
+    /// Uses stable fingerprint keys instead of the ephemeral `QuantumEngine` key, so the
+    /// fingerprint stays the same across restarts and replicas.
+    #[must_use]
+    pub fn with_fingerprint_keys(mut self, keys: Arc<FingerprintKeyring>) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// قاعدة بصمات العملاء المعروفين لمقارنة بصمات النقل (فارغة افتراضياً).
+    /// Known client fingerprints to compare transport fingerprints against (empty by default).
+    #[must_use]
+    pub fn with_known_clients(mut self, known_clients: Arc<KnownClientDb>) -> Self {
+        self.known_clients = known_clients;
+        self
+    }
+
+    /// استبدال أوزان الخصائص الافتراضية.
+    /// Replaces the default attribute weights.
+    #[must_use]
+    pub fn with_attribute_weights(mut self, weights: AttributeWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// توليد بصمة متطورة من المدخلات النصية القديمة (تُحوَّل إلى `DeviceAttributes`).
+    /// Generate an advanced fingerprint from the legacy strings (converted to `DeviceAttributes`).
     ///
     /// # Errors
     /// يعيد `FingerprintError` عند فشل الفحص الأمني، توليد البصمات، أو التوقيع بالذكاء الاصطناعي.
@@ -187,27 +444,67 @@ impl AdaptiveFingerprintEngine {
         os: &str,
         device_info: &str,
         environment_data: &str,
+    ) -> Result<AdaptiveFingerprint, FingerprintError> {
+        let attributes = DeviceAttributes::from_legacy(os, device_info, environment_data);
+        self.generate(os, device_info, &attributes).await
+    }
+
+    /// توليد بصمة من خصائص الجهاز المنظمة.
+    /// Generate a fingerprint from typed device attributes.
+    ///
+    /// # Errors
+    /// يعيد `FingerprintError` عند فشل الفحص الأمني، توليد البصمات، أو التوقيع بالذكاء الاصطناعي.
+    /// Returns `FingerprintError` if security scan, fingerprint generation, or AI signature fails.
+    pub async fn generate_fingerprint_from_attributes(
+        &self,
+        attributes: &DeviceAttributes,
+    ) -> Result<AdaptiveFingerprint, FingerprintError> {
+        let attributes = attributes.clone().resolved();
+        let (os, device_info) = attributes.scan_strings();
+        self.generate(&os, &device_info, &attributes).await
+    }
+
+    async fn generate(
+        &self,
+        scan_os: &str,
+        scan_device_info: &str,
+        attributes: &DeviceAttributes,
     ) -> Result<AdaptiveFingerprint, FingerprintError> {
         let start_time = Instant::now();
 
         // 1. الفحص الأمني الأولي
         // 1. Initial security scan
-        self.security.scan_environment(os, device_info).await?;
+        self.security
+            .scan_environment(scan_os, scan_device_info)
+            .await?;
+        let integrity = self.security.assess_integrity(attributes).await?;
 
-        // 2. تحليل البيئة وتحديد ملف التعريف
-        // 2. Analyze environment and determine profile
-        let env_type = Self::detect_environment_type(environment_data);
+        // 2. تحديد ملف تعريف البيئة من فئة الجهاز
+        // 2. Determine the environment profile from the device class
+        let env_type = attributes.device_class.profile_key();
         let env_profile = self
             .env_profiles
             .read()
             .await
-            .get(&env_type)
+            .get(env_type)
             .cloned()
-            .ok_or_else(|| FingerprintError::UnsupportedEnvironment(env_type.clone()))?;
-
-        // 3. إنشاء البصمة الأساسية
-        // 3. Create the base fingerprint
-        let base_fp = self.create_base_fingerprint(os, device_info, &env_profile);
+            .ok_or_else(|| FingerprintError::UnsupportedEnvironment(env_type.to_string()))?;
+
+        // 3. إنشاء البصمة الأساسية من الخصائص المطبعة والموزونة
+        // 3. Create the base fingerprint from the normalized, weighted attributes
+        let (key_id, key) = self.active_key();
+        let (digest, components) = Self::digest_attributes(key, attributes, &self.weights);
+        let base_fp = format_base_fingerprint(key_id, &digest);
+        let rekeyed_components = self
+            .all_keys()
+            .filter(|(id, _)| *id != key_id)
+            .map(|(id, key)| {
+                (
+                    id,
+                    Self::digest_attributes(key, attributes, &self.weights).1,
+                )
+            })
+            .collect();
 
         // 4. إنشاء البصمة التكيفية
         // 4. Create the adaptive fingerprint
@@ -220,37 +517,192 @@ impl AdaptiveFingerprintEngine {
             .generate_ai_signature(&base_fp, &adaptive_fp, &env_profile)
             .await?;
 
+        // 6. مستوى الأمان: مستوى المراقب، ثم خصم تهديد البيئة، ثم خصم مؤشرات التلاعب
+        // 6. Security level: the monitor level, minus the environment threat, minus tampering indicators
+        let security_level =
+            env_profile.adjust_security_level(self.security.current_security_level().await);
+        let security_level = integrity.as_ref().map_or(security_level, |report| {
+            report.adjust_security_level(security_level)
+        });
+
+        // 7. مستوى الأداء من الزمن المقاس مقابل ميزانية البيئة
+        // 7. Performance level from the measured time against the environment budget
+        let generation_time_us = start_time.elapsed().as_micros() as u64;
+        let constraints = &env_profile.resource_constraints;
+        if let Err(e) = constraints.check_processing_budget(generation_time_us) {
+            if self.enforce_processing_budget {
+                return Err(e);
+            }
+            log::warn!("Fingerprint processing budget exceeded (report-only): {e}");
+        }
+        let performance_level = constraints.performance_level(generation_time_us);
+
         Ok(AdaptiveFingerprint {
             base_fp,
             adaptive_fp,
             ai_signature,
-            security_level: self.security.current_security_level().await,
-            performance_level: 8, // Placeholder, can be dynamic
+            security_level,
+            performance_level,
             environment_profile: env_profile,
             quantum_resistant: self.quantum.is_quantum_resistant(),
-            generation_time_us: start_time.elapsed().as_micros() as u64,
+            generation_time_us,
+            components,
+            rekeyed_components,
+            key_id: self.keys.as_ref().map(|keys| keys.active_key_id()),
+            integrity,
+            threat_signatures_version: self.security.threat_signatures_version().await,
+            transport: None,
         })
     }
 
-    // --- وظائف مساعدة داخلية ---
-    // --- Internal helper functions ---
+    /// يرفق بصمة النقل بالبصمة ويخصم من مستوى الأمان عند تناقضها مع المتصفح المعلن.
+    /// Attaches the transport fingerprint and lowers the security level when it
+    /// contradicts the claimed browser family.
+    ///
+    /// # Errors
+    /// يعيد `FingerprintError::ClientFingerprint` عند تلف ClientHello أو بصمة HTTP/2.
+    /// Returns `FingerprintError::ClientFingerprint` for a malformed ClientHello or HTTP/2 fingerprint.
+    pub fn assess_transport(
+        &self,
+        fingerprint: &mut AdaptiveFingerprint,
+        claimed_family: Option<&str>,
+        input: &TransportFingerprintInput,
+    ) -> Result<(), FingerprintError> {
+        let report = analyze_transport(input, claimed_family, &self.known_clients)?;
+        Self::attach_transport(fingerprint, report);
+        Ok(())
+    }
 
-    fn create_base_fingerprint(
+    /// يعاقب طلباً وصل عبر وكيل TLS الموثوق دون أن يمرر بصمة النقل.
+    /// Penalizes a request that came through the trusted TLS proxy without a transport
+    /// fingerprint.
+    pub fn flag_missing_transport(
         &self,
-        os: &str,
-        device_info: &str,
-        _profile: &EnvironmentProfile,
-    ) -> String {
-        let mut hasher = Hasher::new();
-        hasher.update(os.as_bytes());
-        hasher.update(device_info.as_bytes());
+        fingerprint: &mut AdaptiveFingerprint,
+        claimed_family: Option<&str>,
+    ) {
+        Self::attach_transport(
+            fingerprint,
+            ClientFingerprintReport::missing(claimed_family),
+        );
+    }
 
+    fn attach_transport(fingerprint: &mut AdaptiveFingerprint, report: ClientFingerprintReport) {
+        fingerprint.security_level = report.adjust_security_level(fingerprint.security_level);
+        fingerprint.transport = Some(report);
+    }
+
+    /// يطابق بصمة أساسية مخزنة مع خصائص الجهاز الحالية عبر كل المفاتيح المعروفة.
+    /// بصمات V1 تحتاج المدخلات القديمة `(os, device_info)` لإعادة حسابها.
+    /// عند التطابق بصيغة أو مفتاح قديم يُطلب الترحيل إلى `current_fp`.
+    /// Matches a stored base fingerprint with the current device attributes across every
+    /// known key. V1 fingerprints need the legacy `(os, device_info)` to be recomputed.
+    /// A match under an old format or key asks for migration to `current_fp`.
+    #[must_use]
+    pub fn match_stored_fingerprint(
+        &self,
+        stored: &str,
+        attributes: &DeviceAttributes,
+        legacy: Option<(&str, &str)>,
+    ) -> StoredFingerprintMatch {
+        let attributes = attributes.clone().resolved();
+        let (active_id, active_key) = self.active_key();
+        let (current, _) = Self::digest_attributes(active_key, &attributes, &self.weights);
+        let current_fp = format_base_fingerprint(active_id, &current);
+
+        let Some(parsed) = ParsedFingerprint::parse(stored) else {
+            return StoredFingerprintMatch {
+                matched: false,
+                stored_version: None,
+                stored_key_id: None,
+                needs_migration: false,
+                current_fp,
+            };
+        };
+        // مقارنة blake3::Hash تتم بزمن ثابت
+        // blake3::Hash comparison is constant-time
+        let matched = match parsed.version {
+            FingerprintVersion::V2 => {
+                parsed
+                    .key_id
+                    .and_then(|id| self.key(id))
+                    .is_some_and(|key| {
+                        Self::digest_attributes(key, &attributes, &self.weights).0 == parsed.digest
+                    })
+            }
+            FingerprintVersion::V1 => legacy.is_some_and(|(os, device_info)| {
+                self.all_keys()
+                    .any(|(_, key)| legacy_v1_digest(key, os, device_info) == parsed.digest)
+            }),
+        };
+        StoredFingerprintMatch {
+            matched,
+            stored_version: Some(parsed.version),
+            stored_key_id: parsed.key_id,
+            needs_migration: matched
+                && (parsed.version != FingerprintVersion::V2 || parsed.key_id != Some(active_id)),
+            current_fp,
+        }
+    }
+
+    /// المفتاح النشط ومعرفه (0 لمفتاح العملية المؤقت).
+    /// The active key and its id (0 for the ephemeral per-process key).
+    fn active_key(&self) -> (u32, &SecureBytes) {
+        self.keys.as_ref().map_or_else(
+            || (0, self.quantum.get_secure_key()),
+            |keys| (keys.active_key_id(), keys.active_key()),
+        )
+    }
+
+    fn key(&self, key_id: u32) -> Option<&SecureBytes> {
+        match &self.keys {
+            Some(keys) => keys.key(key_id),
+            None => (key_id == 0).then(|| self.quantum.get_secure_key()),
+        }
+    }
+
+    fn all_keys(&self) -> Box<dyn Iterator<Item = (u32, &SecureBytes)> + '_> {
+        match &self.keys {
+            Some(keys) => Box::new(keys.keys()),
+            None => Box::new(std::iter::once((0, self.quantum.get_secure_key()))),
+        }
+    }
+
+    // --- وظائف مساعدة داخلية ---
+    // --- Internal helper functions ---
+
+    fn digest_attributes(
+        key: &SecureBytes,
+        attributes: &DeviceAttributes,
+        weights: &AttributeWeights,
+    ) -> (blake3::Hash, Vec<FingerprintComponent>) {
         // استخدام مفتاح سري لتكون البصمة فريدة لكل نظام
         // Use a secret key to make the fingerprint unique per system
-        let key = self.quantum.get_secure_key();
-        hasher.update(key.expose());
-
-        hasher.finalize().to_hex().to_string()
+        let keyed_hasher = || {
+            let mut hasher = Hasher::new();
+            hasher.update(key.expose());
+            hasher
+        };
+
+        let mut hasher = keyed_hasher();
+        let mut components = Vec::new();
+        for attr in attributes.normalized(weights) {
+            // فواصل صريحة تمنع تداخل الأسماء والقيم
+            // Explicit separators prevent name/value ambiguity
+            let encoded = format!("{}={}\u{1f}{}\u{1e}", attr.name, attr.value, attr.weight);
+            hasher.update(encoded.as_bytes());
+
+            let mut component = keyed_hasher();
+            component.update(attr.name.as_bytes());
+            component.update(b"=");
+            component.update(attr.value.as_bytes());
+            components.push(FingerprintComponent {
+                name: attr.name.to_string(),
+                digest: component.finalize().to_hex().to_string(),
+                weight: attr.weight,
+            });
+        }
+        (hasher.finalize(), components)
     }
 
     fn create_adaptive_fingerprint(base_fp: &str, profile: &EnvironmentProfile) -> String {
@@ -263,18 +715,23 @@ impl AdaptiveFingerprintEngine {
 
         hasher.finalize().to_hex().to_string()
     }
+}
 
-    fn detect_environment_type(data: &str) -> String {
-        if data.contains("mobile") || data.contains("android") || data.contains("ios") {
-            "mobile".to_string()
-        } else if data.contains("iot") || data.contains("embedded") {
-            "iot".to_string()
-        } else if data.contains("server") || data.contains("datacenter") {
-            "server".to_string()
-        } else {
-            "desktop".to_string()
-        }
-    }
+fn format_base_fingerprint(key_id: u32, digest: &blake3::Hash) -> String {
+    format!(
+        "fp{FINGERPRINT_FORMAT_VERSION}.{key_id}.{}",
+        digest.to_hex()
+    )
+}
+
+/// صيغة V1 الأصلية، مطلوبة فقط لمطابقة البصمات المخزنة قبل الترحيل.
+/// The original V1 formula, only needed to match fingerprints stored before migration.
+fn legacy_v1_digest(key: &SecureBytes, os: &str, device_info: &str) -> blake3::Hash {
+    let mut hasher = Hasher::new();
+    hasher.update(os.as_bytes());
+    hasher.update(device_info.as_bytes());
+    hasher.update(key.expose());
+    hasher.finalize()
 }
 
 // ================================================================
@@ -284,8 +741,11 @@ impl AdaptiveFingerprintEngine {
 
 // --- SecurityMonitor ---
 pub struct DefaultSecurityMonitor {
-    threat_database: RwLock<HashMap<String, u8>>,
+    threat_database: RwLock<ThreatSignatureDb>,
+    signature_key: Option<SecureBytes>,
+    block_severity: u8,
     security_level: RwLock<u8>,
+    integrity: IntegrityDetector,
 }
 
 impl Default for DefaultSecurityMonitor {
@@ -297,37 +757,98 @@ impl Default for DefaultSecurityMonitor {
 impl DefaultSecurityMonitor {
     #[must_use]
     pub fn new() -> Self {
-        let mut db = HashMap::new();
-        db.insert("rootkit".to_string(), 9);
-        db.insert("memory_scrape".to_string(), 7);
         Self {
-            threat_database: RwLock::new(db),
+            threat_database: RwLock::new(ThreatSignatureDb::builtin()),
+            signature_key: None,
+            block_severity: 7,
             security_level: RwLock::new(8),
+            integrity: IntegrityDetector::default(),
         }
     }
+
+    /// مفتاح التحقق من ملفات التوقيعات؛ بدونه تُرفض كل التحديثات.
+    /// Key verifying signature files; without it every update is rejected.
+    #[must_use]
+    pub fn with_signature_key(mut self, key: SecureBytes) -> Self {
+        self.signature_key = Some(key);
+        self
+    }
+
+    /// الخطورة التي يُرفض عندها الجهاز؛ التوقيعات الأدنى تخفض مستوى الأمان فقط.
+    /// Severity at which the device is rejected; lower signatures only lower the security level.
+    #[must_use]
+    pub const fn with_block_severity(mut self, severity: u8) -> Self {
+        self.block_severity = severity;
+        self
+    }
+
+    /// استبدال كاشف سلامة البيئة الافتراضي.
+    /// Replaces the default environment integrity detector.
+    #[must_use]
+    pub fn with_integrity_detector(mut self, integrity: IntegrityDetector) -> Self {
+        self.integrity = integrity;
+        self
+    }
 }
 
 #[async_trait]
 impl SecurityMonitor for DefaultSecurityMonitor {
     async fn scan_environment(&self, os: &str, device_info: &str) -> Result<(), FingerprintError> {
-        for (threat, _) in self.threat_database.read().await.iter() {
-            if os.contains(threat) || device_info.contains(threat) {
-                return Err(FingerprintError::SecurityThreat(format!(
-                    "{threat} detected"
-                )));
-            }
+        match self.threat_database.read().await.scan(os, device_info) {
+            Some(hit) if hit.severity >= self.block_severity => Err(
+                FingerprintError::SecurityThreat(format!("{} detected", hit.id)),
+            ),
+            _ => Ok(()),
         }
-        Ok(())
     }
 
-    async fn update_threat_database(&self, _threat_data: &str) -> Result<(), FingerprintError> {
-        // ... (logic to parse and update db)
+    async fn update_threat_database(&self, threat_data: &str) -> Result<(), FingerprintError> {
+        let key = self
+            .signature_key
+            .as_ref()
+            .ok_or(ThreatSignatureError::InvalidSignature)?;
+        // التحقق والتجميع على نسخة، ثم الاستبدال دفعة واحدة
+        // Verify and compile on a copy, then swap in one step
+        let mut updated = self.threat_database.read().await.clone();
+        updated.apply_signed(threat_data, key)?;
+        *self.threat_database.write().await = updated;
         Ok(())
     }
 
     async fn current_security_level(&self) -> u8 {
         *self.security_level.read().await
     }
+
+    async fn assess_integrity(
+        &self,
+        attributes: &DeviceAttributes,
+    ) -> Result<Option<IntegrityReport>, FingerprintError> {
+        // التوقيعات دون حد الرفض تدخل كمؤشرات تخفض مستوى الأمان
+        // Signatures below the block severity become indicators lowering the security level
+        let (os, device_info) = attributes.scan_strings();
+        let signature_indicators = self
+            .threat_database
+            .read()
+            .await
+            .scan_all(&os, &device_info)
+            .into_iter()
+            .filter(|hit| hit.severity < self.block_severity)
+            .map(|hit| IntegrityIndicator {
+                id: format!("signature:{}", hit.id),
+                threat: IntegrityThreat::KnownSignature,
+                weight: f32::from(hit.severity) / 10.0,
+                evidence: hit.id,
+            })
+            .collect();
+        let report = self
+            .integrity
+            .evaluate_with(attributes, signature_indicators);
+        Ok((!report.indicators.is_empty()).then_some(report))
+    }
+
+    async fn threat_signatures_version(&self) -> Option<u64> {
+        Some(self.threat_database.read().await.version())
+    }
 }
 
 // --- QuantumEngine ---
@@ -396,40 +917,16 @@ struct FullEngine {
 
 impl FullEngine {
     fn new() -> Result<Self, FingerprintError> {
-        // --- تهيئة البيئات ---
-        // --- Initialize Environments ---
-        let mut profiles = HashMap::new();
-        profiles.insert(
-            "mobile".to_string(),
-            EnvironmentProfile {
-                os_type: "Mobile".to_string(),
-                device_category: "Phone/Tablet".to_string(),
-                threat_level: 6,
-                resource_constraints: ResourceConstraints {
-                    max_memory_kb: 512,
-                    max_processing_us: 5000,
-                },
-            },
-        );
-        profiles.insert(
-            "desktop".to_string(),
-            EnvironmentProfile {
-                os_type: "Desktop".to_string(),
-                device_category: "PC/Workstation".to_string(),
-                threat_level: 4,
-                resource_constraints: ResourceConstraints {
-                    max_memory_kb: 2048,
-                    max_processing_us: 10000,
-                },
-            },
-        );
-
         let engine = AdaptiveFingerprintEngine::new(
             Arc::new(DefaultSecurityMonitor::new()),
             Arc::new(DefaultQuantumEngine::new()?),
             Arc::new(DefaultAiProcessor),
-            Arc::new(RwLock::new(profiles)),
+            Arc::new(RwLock::new(EnvironmentProfile::defaults())),
         );
+        let engine = match FingerprintKeyring::from_env()? {
+            Some(keys) => engine.with_fingerprint_keys(Arc::new(keys)),
+            None => engine,
+        };
         Ok(Self { engine })
     }
 }
@@ -487,6 +984,54 @@ pub unsafe extern "C" fn free_fingerprint_string(ptr: *mut std::ffi::c_char) {
     }
 }
 
+/// أدوات مشتركة لاختبارات مطابقة الأجهزة وسجلها.
+/// Shared helpers for the device matching and registry tests.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::{
+        AdaptiveFingerprint, EnvironmentProfile, FingerprintComponent, ResourceConstraints,
+    };
+    use std::collections::BTreeMap;
+
+    pub(crate) fn component(name: &str, digest: &str, weight: u8) -> FingerprintComponent {
+        FingerprintComponent {
+            name: name.to_string(),
+            digest: digest.to_string(),
+            weight,
+        }
+    }
+
+    pub(crate) fn fingerprint(
+        base_fp: &str,
+        components: Vec<FingerprintComponent>,
+    ) -> AdaptiveFingerprint {
+        AdaptiveFingerprint {
+            base_fp: base_fp.to_string(),
+            adaptive_fp: String::new(),
+            ai_signature: String::new(),
+            security_level: 8,
+            performance_level: 8,
+            environment_profile: EnvironmentProfile {
+                os_type: "Desktop".to_string(),
+                device_category: "PC/Workstation".to_string(),
+                threat_level: 4,
+                resource_constraints: ResourceConstraints {
+                    max_memory_kb: 2048,
+                    max_processing_us: 10_000,
+                },
+            },
+            quantum_resistant: true,
+            generation_time_us: 0,
+            components,
+            rekeyed_components: BTreeMap::new(),
+            key_id: Some(1),
+            integrity: None,
+            threat_signatures_version: None,
+            transport: None,
+        }
+    }
+}
+
 // ================================================================
 // اختبارات شاملة (محدثة بالكامل)
 // Comprehensive Tests (Fully Updated)
@@ -595,6 +1140,105 @@ mod tests {
         assert!(fp.quantum_resistant);
     }
 
+    #[tokio::test]
+    async fn test_attribute_fingerprint_ignores_formatting_and_zero_weights() {
+        use crate::core::device_attributes::{DeviceClass, OsFamily};
+        let sec_monitor = Arc::new(MockSecurityMonitor {
+            level: AtomicU8::new(9),
+            should_fail: false,
+        });
+        let engine = setup_test_engine(sec_monitor);
+        let attrs = DeviceAttributes {
+            os_family: OsFamily::Windows,
+            os_version: Some("11".to_string()),
+            model: Some("Dell XPS".to_string()),
+            device_class: DeviceClass::Desktop,
+            timezone: Some("Asia/Riyadh".to_string()),
+            ..DeviceAttributes::default()
+        };
+        let fp = engine
+            .generate_fingerprint_from_attributes(&attrs)
+            .await
+            .unwrap();
+        let reformatted = DeviceAttributes {
+            model: Some("  dell   xps ".to_string()),
+            ..attrs.clone()
+        };
+        let fp2 = engine
+            .generate_fingerprint_from_attributes(&reformatted)
+            .await
+            .unwrap();
+        assert_eq!(fp.base_fp, fp2.base_fp);
+        assert_eq!(fp.components.len(), 4);
+
+        let engine = engine.with_attribute_weights(AttributeWeights {
+            timezone: 0,
+            ..AttributeWeights::default()
+        });
+        let moved = DeviceAttributes {
+            timezone: Some("Europe/London".to_string()),
+            ..attrs.clone()
+        };
+        let a = engine
+            .generate_fingerprint_from_attributes(&attrs)
+            .await
+            .unwrap();
+        let b = engine
+            .generate_fingerprint_from_attributes(&moved)
+            .await
+            .unwrap();
+        assert_eq!(a.base_fp, b.base_fp);
+        assert_ne!(a.base_fp, fp.base_fp);
+    }
+
+    #[tokio::test]
+    async fn test_stable_keys_and_migration_from_retired_key_and_v1() {
+        let spec = format!("1:{},2:{}", "11".repeat(32), "22".repeat(32));
+        let old_keys = Arc::new(FingerprintKeyring::from_spec(&spec, Some(1)).unwrap());
+        let new_keys = Arc::new(FingerprintKeyring::from_spec(&spec, Some(2)).unwrap());
+        let monitor = || {
+            Arc::new(MockSecurityMonitor {
+                level: AtomicU8::new(9),
+                should_fail: false,
+            })
+        };
+
+        // نسختان بالمفتاح نفسه تعطيان البصمة نفسها
+        // Two instances with the same key produce the same fingerprint
+        let replica_a = setup_test_engine(monitor()).with_fingerprint_keys(Arc::clone(&old_keys));
+        let replica_b = setup_test_engine(monitor()).with_fingerprint_keys(old_keys);
+        let fp_a = replica_a
+            .generate_fingerprint("Windows 11", "Dell XPS", "desktop")
+            .await
+            .unwrap();
+        let fp_b = replica_b
+            .generate_fingerprint("Windows 11", "Dell XPS", "desktop")
+            .await
+            .unwrap();
+        assert_eq!(fp_a.base_fp, fp_b.base_fp);
+        assert!(fp_a.base_fp.starts_with("fp2.1."));
+
+        // بعد التدوير تطابق البصمة القديمة وتُطلب ترقيتها
+        // After rotation the old fingerprint still matches and asks for migration
+        let rotated = setup_test_engine(monitor()).with_fingerprint_keys(new_keys);
+        let attrs = DeviceAttributes::from_legacy("Windows 11", "Dell XPS", "desktop");
+        let outcome = rotated.match_stored_fingerprint(&fp_a.base_fp, &attrs, None);
+        assert!(outcome.matched && outcome.needs_migration);
+        assert_eq!(outcome.stored_key_id, Some(1));
+        assert!(outcome.current_fp.starts_with("fp2.2."));
+        let again = rotated.match_stored_fingerprint(&outcome.current_fp, &attrs, None);
+        assert!(again.matched && !again.needs_migration);
+
+        let v1 = legacy_v1_digest(&SecureBytes::new(vec![0x11; 32]), "Windows 11", "Dell XPS")
+            .to_hex()
+            .to_string();
+        let legacy =
+            rotated.match_stored_fingerprint(&v1, &attrs, Some(("Windows 11", "Dell XPS")));
+        assert_eq!(legacy.stored_version, Some(FingerprintVersion::V1));
+        assert!(legacy.matched && legacy.needs_migration);
+        assert!(!rotated.match_stored_fingerprint(&v1, &attrs, None).matched);
+    }
+
     #[tokio::test]
     async fn test_security_threat_scenario() {
         let sec_monitor = Arc::new(MockSecurityMonitor {
@@ -609,4 +1253,96 @@ mod tests {
 
         assert!(matches!(result, Err(FingerprintError::SecurityThreat(_))));
     }
+
+    #[tokio::test]
+    async fn test_automation_signals_lower_security_level() {
+        let engine = setup_test_engine(Arc::new(DefaultSecurityMonitor::new()));
+        let mut attrs = DeviceAttributes::from_user_agent(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+        );
+        let clean = engine
+            .generate_fingerprint_from_attributes(&attrs)
+            .await
+            .unwrap();
+        assert_eq!(clean.security_level, 8);
+        assert!(clean.integrity.is_none());
+        assert_eq!(clean.threat_signatures_version, Some(0));
+
+        attrs.signals = Some(crate::core::device_integrity::DeviceSignals {
+            webdriver: Some(true),
+            ..Default::default()
+        });
+        let automated = engine
+            .generate_fingerprint_from_attributes(&attrs)
+            .await
+            .unwrap();
+        assert!(automated.security_level < 5);
+        assert_eq!(automated.base_fp, clean.base_fp);
+        assert!(automated.integrity.is_some());
+    }
+
+    struct SlowAiProcessor;
+    #[async_trait]
+    impl AiProcessor for SlowAiProcessor {
+        async fn generate_ai_signature(
+            &self,
+            _base_fp: &str,
+            _adaptive_fp: &str,
+            _env_profile: &EnvironmentProfile,
+        ) -> Result<String, FingerprintError> {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            Ok("slow".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processing_budget_is_enforced() {
+        let mut profiles = HashMap::new();
+        profiles.insert(
+            "desktop".to_string(),
+            EnvironmentProfile {
+                os_type: "Desktop".to_string(),
+                device_category: "PC".to_string(),
+                threat_level: 7,
+                resource_constraints: ResourceConstraints {
+                    max_memory_kb: 2048,
+                    max_processing_us: 1_000,
+                },
+            },
+        );
+        let engine = AdaptiveFingerprintEngine::new(
+            Arc::new(DefaultSecurityMonitor::new()),
+            Arc::new(MockQuantumEngine),
+            Arc::new(SlowAiProcessor),
+            Arc::new(RwLock::new(profiles)),
+        );
+        // افتراضياً يُبلَّغ عن التجاوز فقط
+        // By default an overrun is only reported
+        let fp = engine
+            .generate_fingerprint("Windows", "Dell XPS", "desktop")
+            .await
+            .unwrap();
+        assert_eq!(fp.performance_level, 1);
+        // 8 من المراقب ناقص تجاوز مستوى التهديد 7 للحد الأساسي 5
+        // 8 from the monitor minus threat level 7 exceeding the baseline of 5
+        assert_eq!(fp.security_level, 6);
+
+        let result = engine
+            .with_processing_budget_enforcement(true)
+            .generate_fingerprint("Windows", "Dell XPS", "desktop")
+            .await;
+        assert!(matches!(result, Err(FingerprintError::ResourceExceeded(_))));
+    }
+
+    #[test]
+    fn test_performance_level_scales_with_budget_use() {
+        let constraints = ResourceConstraints {
+            max_memory_kb: 0,
+            max_processing_us: 10_000,
+        };
+        assert_eq!(constraints.performance_level(0), 10);
+        assert_eq!(constraints.performance_level(5_000), 5);
+        assert_eq!(constraints.performance_level(50_000), 1);
+        assert!(constraints.check_processing_budget(10_000).is_ok());
+    }
 }
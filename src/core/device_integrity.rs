@@ -0,0 +1,574 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: device_integrity.rs
+    المسار:    src/core/device_integrity.rs
+    دور الملف:
+    كاشف سلامة بيئة الجهاز: المحاكيات، الروت/كسر الحماية، أطر الحقن، الأتمتة
+    (`navigator.webdriver`)، المتصفحات بلا واجهة، وتناقض المعالج الرسومي مع المنصة.
+    كل مؤشر له وزن، وتُجمع المؤشرات بصيغة "أو الضوضائية" لكل فئة وإجمالاً،
+    والثقة الناتجة تخفض `security_level` في البصمة.
+    --------------------------------------------------------------
+    File Name: device_integrity.rs
+    Path:     src/core/device_integrity.rs
+    File Role:
+    Device environment integrity detector: emulators, root/jailbreak, hooking frameworks,
+    automation (`navigator.webdriver`), headless browsers and GPU/platform contradictions.
+    Every indicator carries a weight; indicators are combined with a noisy-OR per category
+    and overall, and the resulting confidence lowers the fingerprint `security_level`.
+******************************************************************************************/
+
+use crate::core::device_attributes::{DeviceAttributes, OsFamily};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// إشارات البيئة التي يرسلها الجهاز (كلها اختيارية).
+/// Environment signals submitted by the device (all optional).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSignals {
+    /// خصائص بناء أندرويد مثل `ro.kernel.qemu` و `ro.build.tags`.
+    /// Android build properties such as `ro.kernel.qemu` and `ro.build.tags`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_props: BTreeMap<String, String>,
+    /// مسارات أو حزم موجودة على الجهاز (`/system/xbin/su`، `com.topjohnwu.magisk`).
+    /// Paths or packages present on the device (`/system/xbin/su`, `com.topjohnwu.magisk`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webdriver: Option<bool>,
+    /// متغيرات عامة خاصة بأطر الأتمتة وُجدت في `window`.
+    /// Automation-framework globals found on `window`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub automation_globals: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages: Option<Vec<String>>,
+    /// قيمة `navigator.platform`.
+    /// Value of `navigator.platform`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webgl_vendor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webgl_renderer: Option<String>,
+}
+
+/// فئة التهديد.
+/// Threat category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityThreat {
+    Emulator,
+    Rooted,
+    Jailbroken,
+    Hooked,
+    Automation,
+    Headless,
+    Inconsistent,
+    /// توقيع تهديد معروف دون حد الرفض.
+    /// A known threat signature below the block severity.
+    KnownSignature,
+}
+
+/// مؤشر مكتشف مع وزنه والدليل.
+/// A detected indicator with its weight and evidence.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityIndicator {
+    pub id: String,
+    pub threat: IntegrityThreat,
+    pub weight: f32,
+    pub evidence: String,
+}
+
+/// تقرير السلامة.
+/// Integrity report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// الفئات التي تجاوزت ثقتها حد الاكتشاف.
+    /// Categories whose confidence crossed the detection threshold.
+    pub threats: Vec<IntegrityThreat>,
+    /// 0.0 (بيئة سليمة) إلى 1.0 (تلاعب مؤكد).
+    /// 0.0 (clean environment) to 1.0 (certain tampering).
+    pub confidence: f32,
+    pub indicators: Vec<IntegrityIndicator>,
+}
+
+impl IntegrityReport {
+    /// يخفض مستوى الأمان بنسبة الثقة.
+    /// Lowers the security level in proportion to the confidence.
+    #[must_use]
+    pub fn adjust_security_level(&self, level: u8) -> u8 {
+        let penalty = (f32::from(level) * self.confidence.clamp(0.0, 1.0)).round();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let penalty = penalty as u8;
+        level.saturating_sub(penalty)
+    }
+}
+
+/// إعدادات الكاشف.
+/// Detector configuration.
+#[derive(Debug, Clone)]
+pub struct IntegrityConfig {
+    /// ثقة الفئة التي تُعتبر عندها مكتشفة.
+    /// Category confidence at which it is reported as detected.
+    pub detection_threshold: f32,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            detection_threshold: 0.5,
+        }
+    }
+}
+
+// ================================================================
+// جداول المؤشرات
+// Indicator Tables
+// ================================================================
+
+/// `(الخاصية، القيمة أو جزء منها، الفئة، الوزن)`؛ القيم الرقمية تُطابق كاملة.
+/// `(property, value or fragment, category, weight)`; numeric values must match exactly.
+const BUILD_PROP_RULES: &[(&str, &str, IntegrityThreat, f32)] = &[
+    ("ro.kernel.qemu", "1", IntegrityThreat::Emulator, 0.9),
+    ("ro.hardware", "goldfish", IntegrityThreat::Emulator, 0.8),
+    ("ro.hardware", "ranchu", IntegrityThreat::Emulator, 0.8),
+    ("ro.hardware", "vbox86", IntegrityThreat::Emulator, 0.8),
+    (
+        "ro.product.model",
+        "sdk_gphone",
+        IntegrityThreat::Emulator,
+        0.7,
+    ),
+    (
+        "ro.product.model",
+        "emulator",
+        IntegrityThreat::Emulator,
+        0.6,
+    ),
+    (
+        "ro.product.manufacturer",
+        "genymotion",
+        IntegrityThreat::Emulator,
+        0.8,
+    ),
+    (
+        "ro.build.fingerprint",
+        "generic",
+        IntegrityThreat::Emulator,
+        0.4,
+    ),
+    ("ro.build.tags", "test-keys", IntegrityThreat::Rooted, 0.4),
+    ("ro.debuggable", "1", IntegrityThreat::Rooted, 0.3),
+    ("ro.secure", "0", IntegrityThreat::Rooted, 0.6),
+];
+
+/// `(جزء من المسار أو الحزمة، الفئة، الوزن)`.
+/// `(path or package fragment, category, weight)`.
+const ARTIFACT_RULES: &[(&str, IntegrityThreat, f32)] = &[
+    ("bin/su", IntegrityThreat::Rooted, 0.7),
+    ("magisk", IntegrityThreat::Rooted, 0.8),
+    ("supersu", IntegrityThreat::Rooted, 0.8),
+    ("busybox", IntegrityThreat::Rooted, 0.3),
+    ("cydia", IntegrityThreat::Jailbroken, 0.8),
+    ("mobilesubstrate", IntegrityThreat::Jailbroken, 0.8),
+    ("sileo", IntegrityThreat::Jailbroken, 0.7),
+    ("/etc/apt", IntegrityThreat::Jailbroken, 0.6),
+    ("/usr/sbin/sshd", IntegrityThreat::Jailbroken, 0.5),
+    ("frida", IntegrityThreat::Hooked, 0.8),
+    ("xposed", IntegrityThreat::Hooked, 0.7),
+    ("substrate", IntegrityThreat::Hooked, 0.5),
+];
+
+const AUTOMATION_GLOBALS: &[&str] = &[
+    "__selenium",
+    "__webdriver",
+    "__driver_evaluate",
+    "cdc_",
+    "_phantom",
+    "callphantom",
+    "__nightmare",
+    "__playwright",
+    "__puppeteer",
+    "domautomation",
+];
+
+const HEADLESS_USER_AGENTS: &[(&str, f32)] = &[
+    ("headlesschrome", 0.95),
+    ("phantomjs", 0.9),
+    ("slimerjs", 0.9),
+];
+
+const SOFTWARE_RENDERERS: &[&str] = &["swiftshader", "llvmpipe", "mesa offscreen", "softpipe"];
+
+// ================================================================
+// كاشف السلامة
+// Integrity Detector
+// ================================================================
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityDetector {
+    pub config: IntegrityConfig,
+}
+
+impl IntegrityDetector {
+    #[must_use]
+    pub const fn new(config: IntegrityConfig) -> Self {
+        Self { config }
+    }
+
+    /// يقيّم إشارات الجهاز وخصائصه المحلولة.
+    /// Evaluates the device signals and its resolved attributes.
+    #[must_use]
+    pub fn evaluate(&self, attributes: &DeviceAttributes) -> IntegrityReport {
+        self.evaluate_with(attributes, Vec::new())
+    }
+
+    /// مثل `evaluate` مع مؤشرات إضافية من مصادر أخرى (مثل توقيعات التهديد).
+    /// Like `evaluate` with extra indicators from other sources (such as threat signatures).
+    #[must_use]
+    pub fn evaluate_with(
+        &self,
+        attributes: &DeviceAttributes,
+        mut indicators: Vec<IntegrityIndicator>,
+    ) -> IntegrityReport {
+        let signals = attributes.signals.clone().unwrap_or_default();
+
+        Self::build_props(&signals, &mut indicators);
+        Self::artifacts(&signals, &mut indicators);
+        Self::automation(attributes, &signals, &mut indicators);
+        Self::consistency(attributes, &signals, &mut indicators);
+
+        let mut per_threat: BTreeMap<IntegrityThreat, f32> = BTreeMap::new();
+        for indicator in &indicators {
+            let entry = per_threat.entry(indicator.threat).or_insert(0.0);
+            *entry = noisy_or(*entry, indicator.weight);
+        }
+        let threats = per_threat
+            .iter()
+            .filter(|(_, confidence)| **confidence >= self.config.detection_threshold)
+            .map(|(threat, _)| *threat)
+            .collect();
+        let confidence = indicators
+            .iter()
+            .fold(0.0, |acc, indicator| noisy_or(acc, indicator.weight));
+        IntegrityReport {
+            threats,
+            confidence,
+            indicators,
+        }
+    }
+
+    fn build_props(signals: &DeviceSignals, out: &mut Vec<IntegrityIndicator>) {
+        for (prop, fragment, threat, weight) in BUILD_PROP_RULES {
+            let Some(value) = signals.build_props.get(*prop) else {
+                continue;
+            };
+            let value = value.trim().to_ascii_lowercase();
+            let hit = if fragment.chars().all(|c| c.is_ascii_digit()) {
+                value == *fragment
+            } else {
+                value.contains(fragment)
+            };
+            if hit {
+                out.push(indicator(
+                    &format!("build_prop:{prop}"),
+                    *threat,
+                    *weight,
+                    format!("{prop}={value}"),
+                ));
+            }
+        }
+    }
+
+    fn artifacts(signals: &DeviceSignals, out: &mut Vec<IntegrityIndicator>) {
+        // مؤشر واحد لكل قاعدة مهما تكرر الأثر
+        // One indicator per rule however often the artefact repeats
+        let mut seen = BTreeSet::new();
+        for artifact in &signals.artifacts {
+            let lowered = artifact.to_ascii_lowercase();
+            let matched: Vec<_> = ARTIFACT_RULES
+                .iter()
+                .filter(|(fragment, ..)| lowered.contains(fragment))
+                .collect();
+            for (fragment, threat, weight) in &matched {
+                // القاعدة الأخص فقط: "mobilesubstrate" لا تُحتسب "substrate" أيضاً
+                // The most specific rule only: "mobilesubstrate" does not also count as "substrate"
+                let shadowed = matched
+                    .iter()
+                    .any(|(other, ..)| other.len() > fragment.len() && other.contains(fragment));
+                if !shadowed && seen.insert(*fragment) {
+                    out.push(indicator(
+                        &format!("artifact:{fragment}"),
+                        *threat,
+                        *weight,
+                        artifact.clone(),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn automation(
+        attributes: &DeviceAttributes,
+        signals: &DeviceSignals,
+        out: &mut Vec<IntegrityIndicator>,
+    ) {
+        if signals.webdriver == Some(true) {
+            out.push(indicator(
+                "navigator.webdriver",
+                IntegrityThreat::Automation,
+                0.95,
+                "navigator.webdriver=true".to_string(),
+            ));
+        }
+        for global in &signals.automation_globals {
+            let lowered = global.to_ascii_lowercase();
+            if AUTOMATION_GLOBALS.iter().any(|g| lowered.starts_with(g)) {
+                out.push(indicator(
+                    "automation_global",
+                    IntegrityThreat::Automation,
+                    0.9,
+                    global.clone(),
+                ));
+                break;
+            }
+        }
+
+        let user_agent = attributes
+            .user_agent
+            .as_deref()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if let Some((marker, weight)) = HEADLESS_USER_AGENTS
+            .iter()
+            .find(|(marker, _)| user_agent.contains(marker))
+        {
+            out.push(indicator(
+                "headless_user_agent",
+                IntegrityThreat::Headless,
+                *weight,
+                (*marker).to_string(),
+            ));
+        }
+        let desktop = matches!(
+            attributes.os_family,
+            OsFamily::Windows | OsFamily::MacOs | OsFamily::Linux
+        );
+        if desktop && signals.plugins_count == Some(0) && user_agent.contains("chrome") {
+            out.push(indicator(
+                "no_plugins",
+                IntegrityThreat::Headless,
+                0.3,
+                "desktop Chrome without plugins".to_string(),
+            ));
+        }
+        if signals.languages.as_ref().is_some_and(Vec::is_empty) {
+            out.push(indicator(
+                "no_languages",
+                IntegrityThreat::Headless,
+                0.4,
+                "navigator.languages is empty".to_string(),
+            ));
+        }
+    }
+
+    fn consistency(
+        attributes: &DeviceAttributes,
+        signals: &DeviceSignals,
+        out: &mut Vec<IntegrityIndicator>,
+    ) {
+        let os = attributes.os_family;
+        let renderer = format!(
+            "{} {}",
+            signals.webgl_vendor.as_deref().unwrap_or_default(),
+            signals.webgl_renderer.as_deref().unwrap_or_default()
+        )
+        .to_ascii_lowercase();
+
+        if SOFTWARE_RENDERERS.iter().any(|r| renderer.contains(r)) {
+            let (threat, weight) = if matches!(os, OsFamily::Android | OsFamily::Ios) {
+                (IntegrityThreat::Emulator, 0.6)
+            } else {
+                (IntegrityThreat::Headless, 0.5)
+            };
+            out.push(indicator(
+                "software_renderer",
+                threat,
+                weight,
+                renderer.trim().to_string(),
+            ));
+        }
+
+        let renderer_conflict = !renderer.trim().is_empty()
+            && match os {
+                OsFamily::Ios => !renderer.contains("apple"),
+                OsFamily::Android => renderer.contains("apple") || renderer.contains("direct3d"),
+                OsFamily::Windows => renderer.contains("apple"),
+                OsFamily::MacOs | OsFamily::Linux | OsFamily::ChromeOs => {
+                    renderer.contains("direct3d")
+                }
+                OsFamily::Unknown => false,
+            };
+        if renderer_conflict {
+            out.push(indicator(
+                "gpu_platform_mismatch",
+                IntegrityThreat::Inconsistent,
+                0.6,
+                format!("{} on {}", renderer.trim(), os.as_str()),
+            ));
+        }
+
+        if let Some(platform) = signals.platform.as_deref() {
+            let lowered = platform.to_ascii_lowercase();
+            let platform_os = if lowered.starts_with("win") {
+                Some(&[OsFamily::Windows][..])
+            } else if lowered.starts_with("mac") {
+                // iPadOS بوضع سطح المكتب يبلغ عن MacIntel
+                // iPadOS in desktop mode reports MacIntel
+                Some(&[OsFamily::MacOs, OsFamily::Ios][..])
+            } else if lowered.starts_with("iphone") || lowered.starts_with("ipad") {
+                Some(&[OsFamily::Ios][..])
+            } else if lowered.starts_with("linux") {
+                Some(&[OsFamily::Linux, OsFamily::Android, OsFamily::ChromeOs][..])
+            } else {
+                None
+            };
+            if let Some(expected) = platform_os {
+                if os != OsFamily::Unknown && !expected.contains(&os) {
+                    out.push(indicator(
+                        "platform_mismatch",
+                        IntegrityThreat::Inconsistent,
+                        0.5,
+                        format!("navigator.platform={platform} on {}", os.as_str()),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn indicator(
+    id: &str,
+    threat: IntegrityThreat,
+    weight: f32,
+    evidence: String,
+) -> IntegrityIndicator {
+    IntegrityIndicator {
+        id: id.to_string(),
+        threat,
+        weight,
+        evidence,
+    }
+}
+
+/// دمج احتمالين مستقلين: `1 - (1 - a)(1 - b)`.
+/// Combines two independent probabilities: `1 - (1 - a)(1 - b)`.
+fn noisy_or(a: f32, b: f32) -> f32 {
+    1.0 - (1.0 - a) * (1.0 - b.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_device_has_no_threats() {
+        let attributes = DeviceAttributes {
+            os_family: OsFamily::Ios,
+            user_agent: Some("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X)".to_string()),
+            signals: Some(DeviceSignals {
+                webdriver: Some(false),
+                platform: Some("iPhone".to_string()),
+                webgl_vendor: Some("Apple Inc.".to_string()),
+                webgl_renderer: Some("Apple GPU".to_string()),
+                languages: Some(vec!["ar-SA".to_string()]),
+                ..DeviceSignals::default()
+            }),
+            ..DeviceAttributes::default()
+        };
+        let report = IntegrityDetector::default().evaluate(&attributes);
+        assert!(report.indicators.is_empty());
+        assert_eq!(report.adjust_security_level(8), 8);
+    }
+
+    #[test]
+    fn test_rooted_emulator_is_detected() {
+        let attributes = DeviceAttributes {
+            os_family: OsFamily::Android,
+            signals: Some(DeviceSignals {
+                build_props: BTreeMap::from([
+                    ("ro.kernel.qemu".to_string(), "1".to_string()),
+                    ("ro.hardware".to_string(), "ranchu".to_string()),
+                    ("ro.build.tags".to_string(), "test-keys".to_string()),
+                ]),
+                artifacts: vec![
+                    "/system/xbin/su".to_string(),
+                    "com.topjohnwu.magisk".to_string(),
+                ],
+                webgl_renderer: Some("Google SwiftShader".to_string()),
+                ..DeviceSignals::default()
+            }),
+            ..DeviceAttributes::default()
+        };
+        let report = IntegrityDetector::default().evaluate(&attributes);
+        assert_eq!(
+            report.threats,
+            vec![IntegrityThreat::Emulator, IntegrityThreat::Rooted]
+        );
+        assert!(report.confidence > 0.95);
+        assert!(report.adjust_security_level(8) <= 1);
+    }
+
+    #[test]
+    fn test_artifact_matches_most_specific_rule() {
+        let attributes = DeviceAttributes {
+            os_family: OsFamily::Ios,
+            signals: Some(DeviceSignals {
+                artifacts: vec!["/Library/MobileSubstrate/MobileSubstrate.dylib".to_string()],
+                ..DeviceSignals::default()
+            }),
+            ..DeviceAttributes::default()
+        };
+        let report = IntegrityDetector::default().evaluate(&attributes);
+        let ids: Vec<_> = report.indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["artifact:mobilesubstrate"]);
+        assert_eq!(report.threats, vec![IntegrityThreat::Jailbroken]);
+    }
+
+    #[test]
+    fn test_headless_automation_and_platform_mismatch() {
+        let attributes = DeviceAttributes {
+            os_family: OsFamily::Windows,
+            user_agent: Some(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) HeadlessChrome/120.0.0.0".to_string(),
+            ),
+            signals: Some(DeviceSignals {
+                webdriver: Some(true),
+                platform: Some("Linux x86_64".to_string()),
+                plugins_count: Some(0),
+                ..DeviceSignals::default()
+            }),
+            ..DeviceAttributes::default()
+        };
+        let report = IntegrityDetector::default().evaluate(&attributes);
+        assert_eq!(
+            report.threats,
+            vec![
+                IntegrityThreat::Automation,
+                IntegrityThreat::Headless,
+                IntegrityThreat::Inconsistent
+            ]
+        );
+    }
+}
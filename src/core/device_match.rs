@@ -0,0 +1,490 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: device_match.rs
+    المسار:    src/core/device_match.rs
+    دور الملف:
+    مطابقة تقريبية للأجهزة. تقارن بصمات الخصائص المنفردة (`FingerprintComponent`)
+    بأوزانها بدل مقارنة البصمة الكاملة، فيُتسامح مع الانحراف البسيط (تحديث المتصفح)
+    ويُعلَّم التغيير الشامل. `match_device` يعيد أقرب جهاز معروف للمستخدم مع درجة
+    التشابه والخصائص التي تغيرت.
+    --------------------------------------------------------------
+    File Name: device_match.rs
+    Path:     src/core/device_match.rs
+    File Role:
+    Fuzzy device matching. It compares weighted per-attribute digests
+    (`FingerprintComponent`) instead of the whole fingerprint, so minor drift (a browser
+    update) is tolerated while wholesale changes are flagged. `match_device` returns the
+    user's closest known device with a similarity score and the attributes that changed.
+******************************************************************************************/
+
+use crate::core::device_fp::{AdaptiveFingerprint, FingerprintComponent};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum DeviceMatchError {
+    #[error("Device store error: {0}")]
+    Store(String),
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// جهاز معروف لمستخدم مع بصمات خصائصه.
+/// A user's known device with its attribute digests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownDevice {
+    pub device_id: String,
+    pub base_fp: String,
+    pub components: Vec<FingerprintComponent>,
+    /// مفتاح البصمة الذي حُسبت به المكونات.
+    /// Fingerprint key the components were computed with.
+    #[serde(default)]
+    pub key_id: Option<u32>,
+}
+
+/// حكم المطابقة.
+/// Match verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceMatchVerdict {
+    /// كل الخصائص متطابقة.
+    /// Every attribute matches.
+    Exact,
+    /// الجهاز نفسه مع انحراف بسيط (تحديث متصفح أو نظام).
+    /// Same device with minor drift (browser or OS update).
+    MinorDrift,
+    /// تشابه جزئي فقط: تغيير شامل يستحق التدقيق.
+    /// Only partial similarity: a wholesale change worth reviewing.
+    MajorChange,
+}
+
+/// أقرب جهاز معروف مع درجة التشابه والخصائص المتغيرة.
+/// Closest known device with the similarity score and changed attributes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceMatch {
+    pub device_id: String,
+    /// 0.0 (مختلف تماماً) إلى 1.0 (مطابق).
+    /// 0.0 (completely different) to 1.0 (identical).
+    pub similarity: f32,
+    pub changed_attributes: Vec<String>,
+    pub verdict: DeviceMatchVerdict,
+}
+
+impl DeviceMatch {
+    /// هل يجب تعليم التغيير للمراجعة؟
+    /// Should the change be flagged for review?
+    #[must_use]
+    pub fn is_flagged(&self) -> bool {
+        self.verdict == DeviceMatchVerdict::MajorChange
+    }
+}
+
+/// حدود التشابه.
+/// Similarity thresholds.
+#[derive(Debug, Clone)]
+pub struct MatchThresholds {
+    /// الحد الأدنى لاعتبار الجهاز نفسه مع انحراف بسيط.
+    /// Minimum similarity to treat it as the same device with minor drift.
+    pub same_device: f32,
+    /// تحت هذا الحد لا يُعتبر الجهاز مرتبطاً بأي جهاز معروف.
+    /// Below this the device is not related to any known device.
+    pub related: f32,
+    /// وزن الخاصية الموجودة في جانب واحد فقط (نقص معلومة أضعف من التناقض).
+    /// Weight factor for an attribute present on one side only (missing data is weaker than a contradiction).
+    pub missing_attribute_factor: f32,
+}
+
+impl Default for MatchThresholds {
+    fn default() -> Self {
+        Self {
+            same_device: 0.8,
+            related: 0.4,
+            missing_attribute_factor: 0.5,
+        }
+    }
+}
+
+// ================================================================
+// واجهات (Traits) للمكونات القابلة للحقن
+// Traits for Injectable Components
+// ================================================================
+
+/// مخزن الأجهزة المعروفة لكل مستخدم.
+/// Store of each user's known devices.
+#[async_trait]
+pub trait DeviceStore: Send + Sync {
+    async fn known_devices(&self, user_id: &str) -> Result<Vec<KnownDevice>, DeviceMatchError>;
+}
+
+/// مخزن في الذاكرة (للاختبار والتشغيل بدون قاعدة بيانات).
+/// In-memory store (for tests and running without a database).
+#[derive(Default)]
+pub struct InMemoryDeviceStore {
+    devices: RwLock<HashMap<String, Vec<KnownDevice>>>,
+}
+
+impl InMemoryDeviceStore {
+    pub async fn remember(&self, user_id: &str, device: KnownDevice) {
+        let mut devices = self.devices.write().await;
+        let list = devices.entry(user_id.to_string()).or_default();
+        list.retain(|d| d.device_id != device.device_id);
+        list.push(device);
+    }
+}
+
+#[async_trait]
+impl DeviceStore for InMemoryDeviceStore {
+    async fn known_devices(&self, user_id: &str) -> Result<Vec<KnownDevice>, DeviceMatchError> {
+        Ok(self
+            .devices
+            .read()
+            .await
+            .get(user_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+// ================================================================
+// محرك المطابقة
+// Matching Engine
+// ================================================================
+pub struct DeviceMatcher {
+    store: Arc<dyn DeviceStore>,
+    thresholds: MatchThresholds,
+}
+
+impl DeviceMatcher {
+    pub fn new(store: Arc<dyn DeviceStore>, thresholds: MatchThresholds) -> Self {
+        Self { store, thresholds }
+    }
+
+    /// يعيد أقرب جهاز معروف للمستخدم، أو `None` إن لم يكن أي جهاز مرتبطاً بما يكفي.
+    /// Returns the user's closest known device, or `None` when none is related enough.
+    ///
+    /// # Errors
+    /// Returns `DeviceMatchError::Store` if the known devices cannot be loaded.
+    pub async fn match_device(
+        &self,
+        user_id: &str,
+        fingerprint: &AdaptiveFingerprint,
+    ) -> Result<Option<DeviceMatch>, DeviceMatchError> {
+        let known = self.store.known_devices(user_id).await?;
+        Ok(known
+            .iter()
+            .map(|device| self.compare(device, fingerprint))
+            .filter(|m| m.similarity >= self.thresholds.related)
+            .max_by(|a, b| a.similarity.total_cmp(&b.similarity)))
+    }
+
+    /// تشابه جهاز معروف للمستخدم (بالبصمة الأساسية) مع أقرب أجهزته الأخرى، أو `None` إن
+    /// لم تكن البصمة لجهاز معروف أو لم يكن له أجهزة أخرى.
+    /// Similarity of one of the user's known devices (by base fingerprint) to the closest of
+    /// their other devices, or `None` when the fingerprint is not a known device or there is
+    /// nothing to compare it with.
+    ///
+    /// # Errors
+    /// Returns `DeviceMatchError::Store` if the known devices cannot be loaded.
+    pub async fn known_device_similarity(
+        &self,
+        user_id: &str,
+        base_fp: &str,
+    ) -> Result<Option<f32>, DeviceMatchError> {
+        let known = self.store.known_devices(user_id).await?;
+        let Some(current) = known.iter().find(|device| device.base_fp == base_fp) else {
+            return Ok(None);
+        };
+        Ok(known
+            .iter()
+            .filter(|device| device.device_id != current.device_id)
+            .map(|device| {
+                let components =
+                    (device.key_id == current.key_id).then_some(current.components.as_slice());
+                self.compare_components(device, &current.base_fp, components)
+                    .similarity
+            })
+            .max_by(f32::total_cmp))
+    }
+
+    /// يقارن جهازاً معروفاً ببصمة حالية. المكونات تُقارن تحت مفتاح الجهاز المخزن فقط،
+    /// فلا يبدو كل جهاز معروف مختلفاً بعد تدوير المفتاح.
+    /// Compares a known device with a current fingerprint. Components are only compared
+    /// under the stored device's key, so known devices do not all look different after a
+    /// key rotation.
+    #[must_use]
+    pub fn compare(&self, device: &KnownDevice, fingerprint: &AdaptiveFingerprint) -> DeviceMatch {
+        self.compare_components(
+            device,
+            &fingerprint.base_fp,
+            fingerprint.components_for_key(device.key_id),
+        )
+    }
+
+    fn compare_components(
+        &self,
+        device: &KnownDevice,
+        current_fp: &str,
+        current: Option<&[FingerprintComponent]>,
+    ) -> DeviceMatch {
+        // البصمات بلا مكونات (صيغ قديمة) أو بمفتاح غير معروف تُقارن كاملة
+        // Fingerprints without components (older formats) or under an unknown key are
+        // compared whole
+        let (similarity, changed_attributes) = match current {
+            Some(current) if !device.components.is_empty() && !current.is_empty() => {
+                component_similarity(
+                    &device.components,
+                    current,
+                    self.thresholds.missing_attribute_factor,
+                )
+            }
+            _ if device.base_fp == current_fp => (1.0, Vec::new()),
+            _ => (0.0, Vec::new()),
+        };
+        let verdict = if changed_attributes.is_empty() && similarity >= 1.0 {
+            DeviceMatchVerdict::Exact
+        } else if similarity >= self.thresholds.same_device {
+            DeviceMatchVerdict::MinorDrift
+        } else {
+            DeviceMatchVerdict::MajorChange
+        };
+        DeviceMatch {
+            device_id: device.device_id.clone(),
+            similarity,
+            changed_attributes,
+            verdict,
+        }
+    }
+}
+
+/// تشابه جاكارد الموزون بين مجموعتي مكونات، مع الخصائص المتغيرة.
+/// Weighted Jaccard similarity between two component sets, with the changed attributes.
+#[must_use]
+pub fn component_similarity(
+    known: &[FingerprintComponent],
+    current: &[FingerprintComponent],
+    missing_attribute_factor: f32,
+) -> (f32, Vec<String>) {
+    let names: BTreeSet<&str> = known
+        .iter()
+        .chain(current)
+        .map(|c| c.name.as_str())
+        .collect();
+    let find = |list: &'_ [FingerprintComponent], name: &str| -> Option<(String, u8)> {
+        list.iter()
+            .find(|c| c.name == name)
+            .map(|c| (c.digest.clone(), c.weight))
+    };
+    let mut matched = 0.0_f32;
+    let mut total = 0.0_f32;
+    let mut changed = Vec::new();
+    for name in names {
+        match (find(known, name), find(current, name)) {
+            (Some((a, wa)), Some((b, wb))) => {
+                let weight = f32::from(wa.max(wb));
+                total += weight;
+                if a == b {
+                    matched += weight;
+                } else {
+                    changed.push(name.to_string());
+                }
+            }
+            (Some((_, w)), None) | (None, Some((_, w))) => {
+                total += f32::from(w) * missing_attribute_factor;
+                changed.push(name.to_string());
+            }
+            (None, None) => {}
+        }
+    }
+    let similarity = if total > 0.0 { matched / total } else { 0.0 };
+    (similarity, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device_fp::test_support::{component, fingerprint};
+
+    fn laptop() -> Vec<FingerprintComponent> {
+        vec![
+            component("os_family", "win", 20),
+            component("os_version", "11", 10),
+            component("model", "xps", 20),
+            component("screen", "1080x1920", 15),
+            component("hardware_concurrency", "8", 10),
+            component("timezone", "riyadh", 10),
+            component("locale", "ar-sa", 5),
+            component("browser", "chrome", 5),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_minor_drift_is_tolerated_and_wholesale_change_flagged() {
+        let store = Arc::new(InMemoryDeviceStore::default());
+        store
+            .remember(
+                "user-1",
+                KnownDevice {
+                    device_id: "laptop".to_string(),
+                    base_fp: "fp2.1.old".to_string(),
+                    components: laptop(),
+                    key_id: Some(1),
+                },
+            )
+            .await;
+        let matcher = DeviceMatcher::new(store, MatchThresholds::default());
+
+        // تغيير المتصفح فقط
+        // Only the browser changed
+        let mut drifted = laptop();
+        drifted[7] = component("browser", "edge", 5);
+        let found = matcher
+            .match_device("user-1", &fingerprint("fp2.1.x", drifted))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.verdict, DeviceMatchVerdict::MinorDrift);
+        assert_eq!(found.changed_attributes, vec!["browser".to_string()]);
+        assert!(found.similarity > 0.9);
+
+        // نفس النظام فقط مع كل شيء آخر مختلف
+        // Same OS only, everything else different
+        let mut replaced = laptop();
+        for c in replaced.iter_mut().skip(2) {
+            c.digest.push_str("-other");
+        }
+        let found = matcher
+            .match_device("user-1", &fingerprint("fp2.1.x", replaced))
+            .await
+            .unwrap();
+        assert!(found.is_none());
+
+        // تغيير الطراز والشاشة والمعالج: تغيير شامل يُعلَّم
+        // Model, screen and CPU changed: a wholesale change that gets flagged
+        let mut swapped = laptop();
+        for c in &mut swapped[2..5] {
+            c.digest.push_str("-other");
+        }
+        let found = matcher
+            .match_device("user-1", &fingerprint("fp2.1.x", swapped))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(found.is_flagged());
+        assert_eq!(found.changed_attributes.len(), 3);
+
+        let exact = matcher
+            .match_device("user-1", &fingerprint("fp2.1.x", laptop()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(exact.verdict, DeviceMatchVerdict::Exact);
+        assert!(matcher
+            .match_device("user-2", &fingerprint("fp2.1.x", laptop()))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_known_device_similarity_uses_registered_devices() {
+        let store = Arc::new(InMemoryDeviceStore::default());
+        for (device_id, base_fp, components) in [
+            ("laptop", "fp-laptop", laptop()),
+            ("laptop-updated", "fp-laptop-updated", {
+                let mut c = laptop();
+                c[7].digest = "browser-v2".to_string();
+                c
+            }),
+        ] {
+            store
+                .remember(
+                    "user-1",
+                    KnownDevice {
+                        device_id: device_id.to_string(),
+                        base_fp: base_fp.to_string(),
+                        components,
+                        key_id: Some(1),
+                    },
+                )
+                .await;
+        }
+        let matcher = DeviceMatcher::new(store, MatchThresholds::default());
+
+        let similarity = matcher
+            .known_device_similarity("user-1", "fp-laptop-updated")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(similarity > 0.8 && similarity < 1.0, "{similarity}");
+        // بصمة لم يسجلها الخادم لا تحصل على تشابه
+        // A fingerprint the server never registered gets no similarity
+        assert!(matcher
+            .known_device_similarity("user-1", "fp-unknown")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_devices_still_match_after_key_rotation() {
+        let store = Arc::new(InMemoryDeviceStore::default());
+        store
+            .remember(
+                "user-1",
+                KnownDevice {
+                    device_id: "laptop".to_string(),
+                    base_fp: "fp2.1.old".to_string(),
+                    components: laptop(),
+                    key_id: Some(1),
+                },
+            )
+            .await;
+        let matcher = DeviceMatcher::new(store, MatchThresholds::default());
+
+        // المفتاح النشط أصبح 2: المكونات الحالية مختلفة، والمقارنة تتم تحت المفتاح 1
+        // The active key is now 2: the current components differ, and the comparison runs under key 1
+        let mut rotated = fingerprint(
+            "fp2.1.x",
+            laptop()
+                .into_iter()
+                .map(|mut c| {
+                    c.digest.push_str("-key2");
+                    c
+                })
+                .collect(),
+        );
+        rotated.key_id = Some(2);
+        rotated.rekeyed_components.insert(1, laptop());
+        let found = matcher
+            .match_device("user-1", &rotated)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.verdict, DeviceMatchVerdict::Exact);
+
+        // مفتاح الجهاز خرج من الحلقة: لا يمكن مقارنة المكونات
+        // The device's key left the keyring: the components cannot be compared
+        rotated.rekeyed_components.clear();
+        assert!(matcher
+            .match_device("user-1", &rotated)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,410 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: device_registry.rs
+    المسار:    src/core/device_registry.rs
+    دور الملف:
+    سجل الأجهزة فوق جدول `devices`. يربط الجهاز بالمستخدم عند أول ظهور، ويحدّث
+    وقت آخر ظهور، ويتتبع دورة الثقة (جديد، موثوق، مشبوه، ملغى). الانحراف البسيط
+    في البصمة يُنسب للجهاز نفسه عبر `DeviceMatcher` فلا يتهرب جهاز ملغى بتحديث متصفحه،
+    والتغيير الشامل أو البيئة المتلاعب بها يسجل جهازاً جديداً مشبوهاً.
+    --------------------------------------------------------------
+    File Name: device_registry.rs
+    Path:     src/core/device_registry.rs
+    File Role:
+    Device registry over the `devices` table. It binds a device to its user on first
+    sight, refreshes the last-seen time and tracks the trust lifecycle (new, trusted,
+    suspicious, revoked). Minor fingerprint drift is attributed to the same device through
+    `DeviceMatcher`, so a revoked device cannot escape by updating its browser, while a
+    wholesale change or a tampered environment registers a new, suspicious device.
+******************************************************************************************/
+
+use crate::app_state::DbPool;
+use crate::core::device_fp::{AdaptiveFingerprint, FingerprintComponent};
+use crate::core::device_match::{
+    DeviceMatch, DeviceMatchError, DeviceMatchVerdict, DeviceMatcher, DeviceStore, KnownDevice,
+    MatchThresholds,
+};
+use crate::db::crud;
+use crate::db::models::{Device, DeviceTrustState};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum DeviceRegistryError {
+    #[error("Device database error: {0}")]
+    Database(#[from] tokio_rusqlite::Error),
+    #[error(transparent)]
+    Match(#[from] DeviceMatchError),
+    #[error("Device not found")]
+    NotFound,
+    #[error("Device trust cannot change from {from:?} to {to:?}")]
+    InvalidTransition {
+        from: DeviceTrustState,
+        to: DeviceTrustState,
+    },
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// نتيجة تسجيل جهاز أو رؤيته مجدداً.
+/// Outcome of registering or re-seeing a device.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceRegistration {
+    pub device: Device,
+    /// هل سُجل الجهاز للتو؟
+    /// Was the device registered just now?
+    pub is_new: bool,
+    /// أقرب جهاز معروف عند عدم تطابق البصمة حرفياً.
+    /// Closest known device when the fingerprint did not match verbatim.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched: Option<DeviceMatch>,
+}
+
+/// البيانات الوصفية المخزنة مع الجهاز في عمود `metadata`.
+/// Metadata stored with the device in the `metadata` column.
+fn device_metadata(fingerprint: &AdaptiveFingerprint) -> serde_json::Value {
+    serde_json::json!({
+        "components": fingerprint.components,
+        "key_id": fingerprint.key_id,
+        "os_type": fingerprint.environment_profile.os_type,
+        "device_category": fingerprint.environment_profile.device_category,
+    })
+}
+
+/// اسم افتراضي للجهاز من ملف بيئته.
+/// Default device name from its environment profile.
+fn default_friendly_name(fingerprint: &AdaptiveFingerprint) -> String {
+    let profile = &fingerprint.environment_profile;
+    format!("{} ({})", profile.os_type, profile.device_category)
+}
+
+// ================================================================
+// مخزن الأجهزة فوق SQLite
+// SQLite Device Store
+// ================================================================
+
+/// يقرأ الأجهزة المعروفة من جدول `devices` للمطابقة التقريبية، بما فيها الملغاة.
+/// Reads known devices from the `devices` table for fuzzy matching, revoked ones included.
+pub struct SqliteDeviceStore {
+    pool: DbPool,
+}
+
+impl SqliteDeviceStore {
+    #[must_use]
+    pub const fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DeviceStore for SqliteDeviceStore {
+    async fn known_devices(&self, user_id: &str) -> Result<Vec<KnownDevice>, DeviceMatchError> {
+        let user_id =
+            Uuid::parse_str(user_id).map_err(|e| DeviceMatchError::Store(e.to_string()))?;
+        let devices = crud::list_devices_for_user(&self.pool, &user_id)
+            .await
+            .map_err(|e| DeviceMatchError::Store(e.to_string()))?;
+        Ok(devices
+            .into_iter()
+            .map(|device| KnownDevice {
+                device_id: device.id.to_string(),
+                components: device
+                    .metadata
+                    .get("components")
+                    .cloned()
+                    .and_then(|c| serde_json::from_value::<Vec<FingerprintComponent>>(c).ok())
+                    .unwrap_or_default(),
+                key_id: device
+                    .metadata
+                    .get("key_id")
+                    .and_then(serde_json::Value::as_u64)
+                    .and_then(|id| u32::try_from(id).ok()),
+                base_fp: device.device_fingerprint,
+            })
+            .collect())
+    }
+}
+
+// ================================================================
+// سجل الأجهزة
+// Device Registry
+// ================================================================
+pub struct DeviceRegistry {
+    pool: DbPool,
+    matcher: DeviceMatcher,
+}
+
+impl DeviceRegistry {
+    #[must_use]
+    pub fn new(pool: DbPool) -> Self {
+        Self::with_thresholds(pool, MatchThresholds::default())
+    }
+
+    #[must_use]
+    pub fn with_thresholds(pool: DbPool, thresholds: MatchThresholds) -> Self {
+        let store = Arc::new(SqliteDeviceStore::new(pool.clone()));
+        Self {
+            pool,
+            matcher: DeviceMatcher::new(store, thresholds),
+        }
+    }
+
+    /// يربط البصمة بالمستخدم: تطابق حرفي أو انحراف بسيط يحدّث الجهاز الموجود،
+    /// وغير ذلك يسجل جهازاً جديداً (مشبوهاً إن كان تغييراً شاملاً لجهاز معروف).
+    /// Binds the fingerprint to the user: a verbatim match or minor drift refreshes the
+    /// existing device, anything else registers a new one (suspicious when it is a
+    /// wholesale change of a known device).
+    ///
+    /// # Errors
+    /// Returns `DeviceRegistryError` if the database or the device store fails.
+    pub async fn register_or_touch(
+        &self,
+        user_id: &Uuid,
+        fingerprint: &AdaptiveFingerprint,
+    ) -> Result<DeviceRegistration, DeviceRegistryError> {
+        let now = Utc::now().naive_utc();
+        let metadata = device_metadata(fingerprint);
+
+        if let Some(device) =
+            crud::get_device_by_fingerprint(&self.pool, user_id, &fingerprint.base_fp).await?
+        {
+            crud::touch_device(&self.pool, &device.id, &fingerprint.base_fp, &metadata, now)
+                .await?;
+            return self.reload(device.id, false, None).await;
+        }
+
+        let matched = self
+            .matcher
+            .match_device(&user_id.to_string(), fingerprint)
+            .await?;
+        if let Some(m) = matched.as_ref().filter(|m| !m.is_flagged()) {
+            if let Ok(device_id) = Uuid::parse_str(&m.device_id) {
+                crud::touch_device(&self.pool, &device_id, &fingerprint.base_fp, &metadata, now)
+                    .await?;
+                return self.reload(device_id, false, matched).await;
+            }
+        }
+
+        // تغيير شامل لجهاز معروف، أو بيئة متلاعب بها، أو مكدس TLS يناقض المتصفح يبدأ الجهاز مشبوهاً
+        // A wholesale change of a known device, a tampered environment or a TLS stack
+        // contradicting the browser starts suspicious
+        let tampered = fingerprint
+            .integrity
+            .as_ref()
+            .is_some_and(|report| !report.threats.is_empty())
+            || fingerprint
+                .transport
+                .as_ref()
+                .is_some_and(|report| report.is_mismatch());
+        let trust_state = match matched.as_ref().map(|m| m.verdict) {
+            Some(DeviceMatchVerdict::MajorChange) => DeviceTrustState::Suspicious,
+            _ if tampered => DeviceTrustState::Suspicious,
+            _ => DeviceTrustState::New,
+        };
+        let device = Device {
+            id: Uuid::new_v4(),
+            user_id: *user_id,
+            device_fingerprint: fingerprint.base_fp.clone(),
+            friendly_name: default_friendly_name(fingerprint),
+            metadata,
+            created_at: now,
+            last_seen_at: now,
+            trust_state,
+        };
+        crud::insert_device(&self.pool, &device).await?;
+        // طلب متزامن ربما سجل البصمة نفسها أولاً؛ نعيد ما في الجدول
+        // A concurrent request may have bound the same fingerprint first; return what is stored
+        let stored =
+            crud::get_device_by_fingerprint(&self.pool, user_id, &fingerprint.base_fp).await?;
+        let stored = stored.ok_or(DeviceRegistryError::NotFound)?;
+        Ok(DeviceRegistration {
+            is_new: stored.id == device.id,
+            device: stored,
+            matched,
+        })
+    }
+
+    /// تشابه جهاز المستخدم المسجل بهذه البصمة مع أقرب أجهزته الأخرى (انظر
+    /// `DeviceMatcher::known_device_similarity`).
+    /// Similarity of the user's device registered under this fingerprint to the closest of
+    /// their other devices (see `DeviceMatcher::known_device_similarity`).
+    ///
+    /// # Errors
+    /// Returns `DeviceRegistryError::Match` if the known devices cannot be loaded.
+    pub async fn device_similarity(
+        &self,
+        user_id: &Uuid,
+        device_fingerprint: &str,
+    ) -> Result<Option<f32>, DeviceRegistryError> {
+        Ok(self
+            .matcher
+            .known_device_similarity(&user_id.to_string(), device_fingerprint)
+            .await?)
+    }
+
+    /// # Errors
+    /// Returns `DeviceRegistryError::Database` if the query fails.
+    pub async fn list_for_user(&self, user_id: &Uuid) -> Result<Vec<Device>, DeviceRegistryError> {
+        Ok(crud::list_devices_for_user(&self.pool, user_id).await?)
+    }
+
+    /// # Errors
+    /// Returns `DeviceRegistryError::NotFound` if the device does not exist.
+    pub async fn get(&self, device_id: &Uuid) -> Result<Device, DeviceRegistryError> {
+        crud::get_device_by_id(&self.pool, device_id)
+            .await?
+            .ok_or(DeviceRegistryError::NotFound)
+    }
+
+    /// ينقل الجهاز إلى حالة ثقة جديدة وفق `DeviceTrustState::can_transition_to`.
+    /// Moves the device to a new trust state subject to `DeviceTrustState::can_transition_to`.
+    ///
+    /// # Errors
+    /// Returns `DeviceRegistryError::InvalidTransition` for a forbidden transition.
+    pub async fn set_trust_state(
+        &self,
+        device_id: &Uuid,
+        trust_state: DeviceTrustState,
+    ) -> Result<Device, DeviceRegistryError> {
+        let mut device = self.get(device_id).await?;
+        if !device.trust_state.can_transition_to(trust_state) {
+            return Err(DeviceRegistryError::InvalidTransition {
+                from: device.trust_state,
+                to: trust_state,
+            });
+        }
+        crud::update_device_trust_state(&self.pool, device_id, trust_state).await?;
+        device.trust_state = trust_state;
+        Ok(device)
+    }
+
+    /// # Errors
+    /// Returns `DeviceRegistryError::NotFound` if the device does not exist.
+    pub async fn revoke(&self, device_id: &Uuid) -> Result<Device, DeviceRegistryError> {
+        self.set_trust_state(device_id, DeviceTrustState::Revoked)
+            .await
+    }
+
+    /// # Errors
+    /// Returns `DeviceRegistryError::NotFound` if the device does not exist.
+    pub async fn rename(
+        &self,
+        device_id: &Uuid,
+        friendly_name: &str,
+    ) -> Result<Device, DeviceRegistryError> {
+        if !crud::rename_device(&self.pool, device_id, friendly_name).await? {
+            return Err(DeviceRegistryError::NotFound);
+        }
+        self.get(device_id).await
+    }
+
+    async fn reload(
+        &self,
+        device_id: Uuid,
+        is_new: bool,
+        matched: Option<DeviceMatch>,
+    ) -> Result<DeviceRegistration, DeviceRegistryError> {
+        Ok(DeviceRegistration {
+            device: self.get(&device_id).await?,
+            is_new,
+            matched,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::device_fp::test_support::{self, component};
+
+    fn fingerprint(base_fp: &str, browser: &str, model: &str) -> AdaptiveFingerprint {
+        test_support::fingerprint(
+            base_fp,
+            vec![
+                component("os_family", "win", 20),
+                component("model", model, 20),
+                component("screen", "1080x1920", 15),
+                component("timezone", "riyadh", 10),
+                component("browser", browser, 5),
+            ],
+        )
+    }
+
+    async fn registry() -> DeviceRegistry {
+        let pool = tokio_rusqlite::Connection::open_in_memory().await.unwrap();
+        crud::init_schema(&pool).await.unwrap();
+        DeviceRegistry::new(pool)
+    }
+
+    #[tokio::test]
+    async fn test_first_sight_binds_and_drift_touches_same_device() {
+        let registry = registry().await;
+        let user = Uuid::new_v4();
+
+        let first = registry
+            .register_or_touch(&user, &fingerprint("fp2.1.a", "chrome-120", "xps"))
+            .await
+            .unwrap();
+        assert!(first.is_new);
+        assert_eq!(first.device.trust_state, DeviceTrustState::New);
+
+        let again = registry
+            .register_or_touch(&user, &fingerprint("fp2.1.a", "chrome-120", "xps"))
+            .await
+            .unwrap();
+        assert!(!again.is_new);
+        assert_eq!(again.device.id, first.device.id);
+
+        registry.revoke(&first.device.id).await.unwrap();
+        let drifted = registry
+            .register_or_touch(&user, &fingerprint("fp2.1.b", "chrome-121", "xps"))
+            .await
+            .unwrap();
+        assert_eq!(drifted.device.id, first.device.id);
+        assert_eq!(drifted.device.device_fingerprint, "fp2.1.b");
+        assert_eq!(drifted.device.trust_state, DeviceTrustState::Revoked);
+        assert_eq!(registry.list_for_user(&user).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wholesale_change_is_new_suspicious_device_and_revocation_is_final() {
+        let registry = registry().await;
+        let user = Uuid::new_v4();
+        let laptop = registry
+            .register_or_touch(&user, &fingerprint("fp2.1.a", "chrome", "xps"))
+            .await
+            .unwrap();
+
+        let changed = registry
+            .register_or_touch(&user, &fingerprint("fp2.1.c", "firefox", "thinkpad"))
+            .await
+            .unwrap();
+        assert!(changed.is_new);
+        assert_ne!(changed.device.id, laptop.device.id);
+        assert_eq!(changed.device.trust_state, DeviceTrustState::Suspicious);
+
+        registry.revoke(&changed.device.id).await.unwrap();
+        assert!(matches!(
+            registry
+                .set_trust_state(&changed.device.id, DeviceTrustState::Trusted)
+                .await,
+            Err(DeviceRegistryError::InvalidTransition { .. })
+        ));
+    }
+}
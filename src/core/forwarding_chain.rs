@@ -0,0 +1,464 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: forwarding_chain.rs
+    المسار:    src/core/forwarding_chain.rs
+    دور الملف:
+    محلل سلسلة التمرير لرؤوس HTTP (`Forwarded` و `X-Forwarded-For` و `Via` و `X-Real-IP`).
+    يختار عنوان العميل من اليمين إلى اليسار متجاوزاً البروكسيات الموثوقة فقط،
+    ويرصد السلاسل المزورة أو المتناقضة ومؤشرات البروكسي المفتوح.
+    --------------------------------------------------------------
+    File Name: forwarding_chain.rs
+    Path:     src/core/forwarding_chain.rs
+    File Role:
+    Forwarding-chain analyzer for HTTP headers (`Forwarded`, `X-Forwarded-For`,
+    `Via`, `X-Real-IP`). It picks the client address right-to-left, skipping
+    only trusted proxies, and reports spoofed or inconsistent chains and
+    open-proxy indicators.
+******************************************************************************************/
+
+use crate::utils::ip_prefix::{canonical_ip, IpPrefix, PrefixTable};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// قيم رؤوس التمرير كما وردت في الطلب (قد يتكرر الرأس الواحد).
+/// Forwarding header values as received (a header may repeat).
+#[derive(Debug, Clone, Default)]
+pub struct ForwardingHeaders {
+    pub forwarded: Vec<String>,
+    pub x_forwarded_for: Vec<String>,
+    pub via: Vec<String>,
+    pub x_real_ip: Option<String>,
+}
+
+impl ForwardingHeaders {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.forwarded.is_empty()
+            && self.x_forwarded_for.is_empty()
+            && self.via.is_empty()
+            && self.x_real_ip.is_none()
+    }
+}
+
+/// مؤشر مستخرج من سلسلة التمرير.
+/// An indicator extracted from the forwarding chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum ForwardingIndicator {
+    /// رؤوس تمرير وصلت من نظير غير موثوق.
+    /// Forwarding headers arrived from an untrusted peer.
+    UntrustedForwardingHeaders,
+    /// عناصر إضافية يسار العميل المختار (بروكسي سابق أو قيم أدخلها العميل).
+    /// Extra hops left of the chosen client (an upstream proxy or client-supplied values).
+    UpstreamProxyHops(usize),
+    /// عدد عناصر `Via` يتجاوز عدد البروكسيات الموثوقة.
+    /// More `Via` entries than trusted proxies.
+    UntrustedVia(usize),
+    /// عنصر غير قابل للتحليل في السلسلة.
+    /// An unparseable element in the chain.
+    MalformedHop(String),
+    /// عنوان العميل المستخرج من الرؤوس غير عام.
+    /// The header-derived client address is not public.
+    NonPublicClient(String),
+    /// `Forwarded` و `X-Forwarded-For` يصفان سلسلتين مختلفتين.
+    /// `Forwarded` and `X-Forwarded-For` describe different chains.
+    ForwardedMismatch,
+    /// `X-Real-IP` لا يطابق العميل المختار.
+    /// `X-Real-IP` does not match the chosen client.
+    RealIpMismatch(String),
+}
+
+impl ForwardingIndicator {
+    #[must_use]
+    pub const fn is_spoofing(&self) -> bool {
+        matches!(self, Self::MalformedHop(_) | Self::NonPublicClient(_))
+    }
+
+    #[must_use]
+    pub const fn is_inconsistency(&self) -> bool {
+        matches!(self, Self::ForwardedMismatch | Self::RealIpMismatch(_))
+    }
+
+    #[must_use]
+    pub const fn is_open_proxy(&self) -> bool {
+        matches!(
+            self,
+            Self::UntrustedForwardingHeaders | Self::UpstreamProxyHops(_) | Self::UntrustedVia(_)
+        )
+    }
+}
+
+/// نتيجة تحليل سلسلة التمرير.
+/// Result of a forwarding-chain analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardingAnalysis {
+    pub client_ip: IpAddr,
+    pub peer_ip: IpAddr,
+    /// السلسلة كما وردت (من اليسار إلى اليمين).
+    /// The chain as received (left to right).
+    pub chain: Vec<String>,
+    /// عدد البروكسيات الموثوقة التي تم تجاوزها (يشمل النظير).
+    /// Number of trusted proxies skipped (including the peer).
+    pub trusted_hops: usize,
+    pub indicators: Vec<ForwardingIndicator>,
+}
+
+impl ForwardingAnalysis {
+    #[must_use]
+    pub fn is_spoofed(&self) -> bool {
+        self.indicators.iter().any(ForwardingIndicator::is_spoofing)
+    }
+
+    #[must_use]
+    pub fn is_inconsistent(&self) -> bool {
+        self.indicators
+            .iter()
+            .any(ForwardingIndicator::is_inconsistency)
+    }
+
+    #[must_use]
+    pub fn has_open_proxy_indicators(&self) -> bool {
+        self.indicators
+            .iter()
+            .any(ForwardingIndicator::is_open_proxy)
+    }
+}
+
+// ================================================================
+// محلل سلسلة التمرير
+// Forwarding-Chain Analyzer
+// ================================================================
+#[derive(Default)]
+pub struct ForwardingChainAnalyzer {
+    trusted: PrefixTable<()>,
+    trust_peer: bool,
+}
+
+impl ForwardingChainAnalyzer {
+    /// ينشئ محللاً بقائمة نطاقات البروكسي الموثوقة.
+    /// Creates an analyzer with the trusted proxy ranges.
+    #[must_use]
+    pub fn new(trusted_proxies: impl IntoIterator<Item = IpPrefix>) -> Self {
+        let mut trusted = PrefixTable::new();
+        for prefix in trusted_proxies {
+            trusted.insert(prefix, ());
+        }
+        Self {
+            trusted,
+            trust_peer: false,
+        }
+    }
+
+    /// يثق في النظير المباشر كبروكسي واحد حتى بدون قائمة نطاقات.
+    /// Trusts the immediate peer as a single proxy even without a range list.
+    #[must_use]
+    pub const fn trusting_peer(mut self) -> Self {
+        self.trust_peer = true;
+        self
+    }
+
+    /// يقرأ `TRUSTED_PROXY_CIDRS` (مفصولة بفواصل) و `TRUST_X_FORWARDED_FOR`.
+    /// عند تفعيل الأخير بدون نطاقات يُعتبر النظير المباشر فقط موثوقاً.
+    /// Reads `TRUSTED_PROXY_CIDRS` (comma-separated) and `TRUST_X_FORWARDED_FOR`.
+    /// When the latter is on without ranges, only the immediate peer is trusted.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let prefixes: Vec<IpPrefix> = std::env::var("TRUSTED_PROXY_CIDRS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|p| p.trim().parse().ok())
+            .collect();
+        let trust_forwarded = std::env::var("TRUST_X_FORWARDED_FOR").is_ok_and(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        });
+        let analyzer = Self::new(prefixes);
+        if trust_forwarded && analyzer.trusted.is_empty() {
+            analyzer.trusting_peer()
+        } else {
+            analyzer
+        }
+    }
+
+    #[must_use]
+    pub fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted.longest_match(ip).is_some()
+    }
+
+    /// يحلل الرؤوس ويختار عنوان العميل.
+    /// Analyzes the headers and picks the client address.
+    #[must_use]
+    pub fn analyze(&self, peer: IpAddr, headers: &ForwardingHeaders) -> ForwardingAnalysis {
+        let peer = canonical_ip(peer);
+        let mut indicators = Vec::new();
+
+        let forwarded = parse_forwarded(&headers.forwarded);
+        let xff = parse_list(&headers.x_forwarded_for);
+        if !forwarded.is_empty()
+            && !xff.is_empty()
+            && forwarded.iter().map(|h| h.1).ne(xff.iter().map(|h| h.1))
+        {
+            indicators.push(ForwardingIndicator::ForwardedMismatch);
+        }
+        let chain = if forwarded.is_empty() { xff } else { forwarded };
+        let via_hops = headers
+            .via
+            .iter()
+            .flat_map(|v| v.split(','))
+            .filter(|v| !v.trim().is_empty())
+            .count();
+
+        let mut analysis = ForwardingAnalysis {
+            client_ip: peer,
+            peer_ip: peer,
+            chain: chain.iter().map(|h| h.0.clone()).collect(),
+            trusted_hops: 0,
+            indicators: Vec::new(),
+        };
+
+        if !(self.trust_peer || self.is_trusted(&peer)) {
+            if !headers.is_empty() {
+                indicators.push(ForwardingIndicator::UntrustedForwardingHeaders);
+            }
+            analysis.indicators = indicators;
+            return analysis;
+        }
+
+        // المشي من اليمين: كل عنصر موثوق يُتجاوز، وأول عنصر غير موثوق هو العميل
+        // Walk from the right: trusted hops are skipped, the first untrusted hop is the client
+        analysis.trusted_hops = 1;
+        let mut client_index = None;
+        let mut opaque_client = false;
+        for (index, (raw, ip)) in chain.iter().enumerate().rev() {
+            let Some(ip) = ip else {
+                // `unknown` والمعرفات المموهة (`_x`) صالحة في RFC 7239 لكنها تخفي العميل فتنهي المشي
+                // RFC 7239 `unknown` and obfuscated (`_x`) nodes are valid but hide the client, ending the walk
+                if is_opaque_node(raw) {
+                    client_index = Some(index);
+                    opaque_client = true;
+                } else {
+                    indicators.push(ForwardingIndicator::MalformedHop(raw.clone()));
+                }
+                break;
+            };
+            analysis.client_ip = *ip;
+            client_index = Some(index);
+            if !self.is_trusted(ip) {
+                break;
+            }
+            analysis.trusted_hops += 1;
+        }
+
+        if let Some(index) = client_index.filter(|i| *i > 0) {
+            indicators.push(ForwardingIndicator::UpstreamProxyHops(index));
+        }
+        if via_hops > analysis.trusted_hops {
+            indicators.push(ForwardingIndicator::UntrustedVia(
+                via_hops - analysis.trusted_hops,
+            ));
+        }
+        if client_index.is_some() && !opaque_client && !is_public(&analysis.client_ip) {
+            indicators.push(ForwardingIndicator::NonPublicClient(
+                analysis.client_ip.to_string(),
+            ));
+        }
+        if let Some(raw) = headers.x_real_ip.as_deref() {
+            match parse_node(raw) {
+                Some(real) if real == analysis.client_ip => {}
+                Some(_) => {
+                    indicators.push(ForwardingIndicator::RealIpMismatch(raw.trim().to_string()))
+                }
+                None if is_opaque_node(raw) => {}
+                None => indicators.push(ForwardingIndicator::MalformedHop(raw.trim().to_string())),
+            }
+        }
+
+        analysis.indicators = indicators;
+        analysis
+    }
+}
+
+type Hop = (String, Option<IpAddr>);
+
+/// يحلل عقدة: `1.2.3.4` أو `1.2.3.4:80` أو `[2001:db8::1]:443` أو `"..."`.
+/// Parses a node: `1.2.3.4`, `1.2.3.4:80`, `[2001:db8::1]:443` or `"..."`.
+fn parse_node(raw: &str) -> Option<IpAddr> {
+    let value = raw.trim().trim_matches('"');
+    let host = if let Some(rest) = value.strip_prefix('[') {
+        rest.split(']').next()?
+    } else if value.matches(':').count() == 1 {
+        value.split(':').next()?
+    } else {
+        value
+    };
+    host.parse::<IpAddr>().ok().map(canonical_ip)
+}
+
+/// هل العقدة `unknown` أو معرف مموه يبدأ بـ `_` (RFC 7239، القسم 6)؟
+/// Whether the node is `unknown` or an obfuscated `_`-prefixed identifier (RFC 7239, section 6).
+fn is_opaque_node(raw: &str) -> bool {
+    let value = raw.trim().trim_matches('"');
+    let name = value.split(':').next().unwrap_or(value);
+    name.eq_ignore_ascii_case("unknown")
+        || name.strip_prefix('_').is_some_and(|rest| {
+            !rest.is_empty()
+                && rest
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        })
+}
+
+fn parse_list(values: &[String]) -> Vec<Hop> {
+    values
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| (v.to_string(), parse_node(v)))
+        .collect()
+}
+
+/// يستخرج قيم `for=` من رأس `Forwarded` (RFC 7239).
+/// Extracts the `for=` values from the `Forwarded` header (RFC 7239).
+fn parse_forwarded(values: &[String]) -> Vec<Hop> {
+    values
+        .iter()
+        .flat_map(|v| v.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| (value.trim().to_string(), parse_node(value)))
+            })
+        })
+        .collect()
+}
+
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast())
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyzer() -> ForwardingChainAnalyzer {
+        ForwardingChainAnalyzer::new(["10.0.0.0/8".parse().unwrap()])
+    }
+
+    #[test]
+    fn test_right_most_untrusted_is_client() {
+        let headers = ForwardingHeaders {
+            x_forwarded_for: vec!["6.6.6.6, 203.0.113.9".into(), "10.0.0.2".into()],
+            x_real_ip: Some("203.0.113.9".into()),
+            ..ForwardingHeaders::default()
+        };
+        let result = analyzer().analyze("10.0.0.1".parse().unwrap(), &headers);
+        assert_eq!(result.client_ip, "203.0.113.9".parse::<IpAddr>().unwrap());
+        assert_eq!(result.trusted_hops, 2);
+        assert_eq!(
+            result.indicators,
+            vec![ForwardingIndicator::UpstreamProxyHops(1)]
+        );
+        assert!(result.has_open_proxy_indicators());
+        assert!(!result.is_spoofed());
+    }
+
+    #[test]
+    fn test_untrusted_peer_keeps_socket_address() {
+        let headers = ForwardingHeaders {
+            x_forwarded_for: vec!["198.51.100.10".into()],
+            ..ForwardingHeaders::default()
+        };
+        let result = analyzer().analyze("192.0.2.50".parse().unwrap(), &headers);
+        assert_eq!(result.client_ip, "192.0.2.50".parse::<IpAddr>().unwrap());
+        assert_eq!(
+            result.indicators,
+            vec![ForwardingIndicator::UntrustedForwardingHeaders]
+        );
+    }
+
+    #[test]
+    fn test_forwarded_header_and_inconsistencies() {
+        let headers = ForwardingHeaders {
+            forwarded: vec![r#"for="[2001:db8:cafe::17]:4711";proto=https, for=10.0.0.3"#.into()],
+            x_forwarded_for: vec!["192.168.1.5".into()],
+            via: vec!["1.1 edge, 1.1 squid, 1.0 unknown".into()],
+            x_real_ip: Some("garbage".into()),
+        };
+        let result = analyzer().analyze("10.0.0.1".parse().unwrap(), &headers);
+        assert_eq!(
+            result.client_ip,
+            "2001:db8:cafe::17".parse::<IpAddr>().unwrap()
+        );
+        assert!(result.is_inconsistent());
+        assert!(result
+            .indicators
+            .contains(&ForwardingIndicator::UntrustedVia(1)));
+        assert!(result.is_spoofed());
+
+        let spoofed = ForwardingHeaders {
+            x_forwarded_for: vec!["127.0.0.1".into()],
+            ..ForwardingHeaders::default()
+        };
+        let result = analyzer()
+            .trusting_peer()
+            .analyze("198.51.100.1".parse().unwrap(), &spoofed);
+        assert_eq!(result.client_ip, "127.0.0.1".parse::<IpAddr>().unwrap());
+        assert!(result.is_spoofed());
+    }
+
+    #[test]
+    fn test_unknown_and_obfuscated_hops_end_the_walk() {
+        let unknown = ForwardingHeaders {
+            forwarded: vec!["for=unknown, for=10.0.0.3".into()],
+            ..ForwardingHeaders::default()
+        };
+        let result = analyzer().analyze("10.0.0.1".parse().unwrap(), &unknown);
+        assert_eq!(result.client_ip, "10.0.0.3".parse::<IpAddr>().unwrap());
+        assert_eq!(result.trusted_hops, 2);
+        assert!(result.indicators.is_empty());
+        assert!(!result.is_spoofed());
+
+        let obfuscated = ForwardingHeaders {
+            forwarded: vec![r#"for=198.51.100.3, for="_hidden:_port", for=10.0.0.3"#.into()],
+            x_real_ip: Some("_hidden".into()),
+            ..ForwardingHeaders::default()
+        };
+        let result = analyzer().analyze("10.0.0.1".parse().unwrap(), &obfuscated);
+        assert_eq!(result.client_ip, "10.0.0.3".parse::<IpAddr>().unwrap());
+        assert_eq!(
+            result.indicators,
+            vec![ForwardingIndicator::UpstreamProxyHops(1)]
+        );
+        assert!(!result.is_spoofed());
+    }
+}
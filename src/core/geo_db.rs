@@ -0,0 +1,427 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: geo_db.rs
+    المسار:    src/core/geo_db.rs
+    دور الملف:
+    مدير قواعد بيانات MaxMind القابلة لإعادة التحميل أثناء التشغيل.
+    يراقب مسارات الملفات ويستبدل القارئ بشكل ذري دون إعادة تشغيل الخادم،
+    ويدعم إصدارات City و ASN و Anonymous-IP و ISP لإثراء تحليل الشبكة.
+    المهام الأساسية:
+    1.  تحميل قواعد البيانات والتحقق من نوع الإصدار (database_type).
+    2.  إعادة التحميل عند تغير الملف مع الإبقاء على القارئ السابق عند الفشل.
+    3.  توفير استعلامات ASN والمؤسسة وأعلام الاستضافة/VPN لـ `NetworkAnalyzer`.
+    --------------------------------------------------------------
+    File Name: geo_db.rs
+    Path:     src/core/geo_db.rs
+    File Role:
+    Hot-reloadable MaxMind database manager. It watches database file paths and
+    atomically swaps readers without a server restart, and supports the City,
+    ASN, Anonymous-IP and ISP editions to enrich network analysis.
+    Main Tasks:
+    1.  Load databases and validate their edition (`database_type`).
+    2.  Reload on file change, keeping the previous reader if the new one fails.
+    3.  Provide ASN, organization and hosting/VPN lookups for `NetworkAnalyzer`.
+******************************************************************************************/
+
+use crate::core::geo_resolver::{GeoReaderEnum, MockGeoReader};
+use maxminddb::geoip2::{AnonymousIp, Asn, Isp};
+use maxminddb::Reader;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum GeoDbError {
+    #[error("Failed to read MMDB file '{0}': {1}")]
+    Io(String, String),
+    #[error("Failed to parse MMDB file '{0}': {1}")]
+    Parse(String, String),
+    #[error("MMDB file '{path}' has type '{actual}', expected a {expected:?} edition")]
+    EditionMismatch {
+        path: String,
+        expected: GeoDbEdition,
+        actual: String,
+    },
+    #[error("Could not acquire lock on a resource")]
+    LockFailed,
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// إصدارات قواعد بيانات MaxMind المدعومة.
+/// Supported MaxMind database editions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GeoDbEdition {
+    City,
+    Asn,
+    AnonymousIp,
+    Isp,
+}
+
+impl GeoDbEdition {
+    /// يتحقق من أن `database_type` في البيانات الوصفية يطابق هذا الإصدار.
+    /// Checks that the metadata `database_type` matches this edition.
+    #[must_use]
+    pub fn accepts(self, database_type: &str) -> bool {
+        match self {
+            Self::City => database_type.contains("City") || database_type.contains("Country"),
+            Self::Asn => database_type.contains("ASN"),
+            Self::AnonymousIp => database_type.contains("Anonymous-IP"),
+            Self::Isp => database_type.contains("ISP"),
+        }
+    }
+}
+
+/// مسارات ملفات قواعد البيانات (كلها اختيارية).
+/// Database file paths (all optional).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeoDbConfig {
+    pub city_path: Option<PathBuf>,
+    pub asn_path: Option<PathBuf>,
+    pub anonymous_ip_path: Option<PathBuf>,
+    pub isp_path: Option<PathBuf>,
+}
+
+impl GeoDbConfig {
+    /// يقرأ المسارات من متغيرات البيئة.
+    /// Reads paths from environment variables:
+    /// `GEOIP_DB_PATH`/`MAXMIND_DB_PATH`, `GEOIP_ASN_DB_PATH`,
+    /// `GEOIP_ANONYMOUS_IP_DB_PATH` and `GEOIP_ISP_DB_PATH`.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let path_var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        Self {
+            city_path: path_var("GEOIP_DB_PATH").or_else(|| path_var("MAXMIND_DB_PATH")),
+            asn_path: path_var("GEOIP_ASN_DB_PATH"),
+            anonymous_ip_path: path_var("GEOIP_ANONYMOUS_IP_DB_PATH"),
+            isp_path: path_var("GEOIP_ISP_DB_PATH"),
+        }
+    }
+}
+
+/// معلومات ملكية الشبكة وأعلام الإخفاء لعنوان IP.
+/// Network ownership information and anonymity flags for an IP.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkOwnership {
+    pub asn: Option<u32>,
+    pub organization: Option<String>,
+    pub isp: Option<String>,
+    pub is_hosting_provider: bool,
+    pub is_anonymous_vpn: bool,
+    pub is_public_proxy: bool,
+    pub is_residential_proxy: bool,
+    pub is_tor_exit_node: bool,
+}
+
+impl NetworkOwnership {
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+// ================================================================
+// مدير قواعد البيانات (GeoDbManager)
+// The Database Manager
+// ================================================================
+
+/// حالة ملف مراقب: المسار وآخر بصمة (وقت التعديل والحجم).
+/// Watched file state: path and last seen stamp (mtime and size).
+struct WatchedFile {
+    edition: GeoDbEdition,
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+pub struct GeoDbManager {
+    city: RwLock<Arc<GeoReaderEnum>>,
+    asn: RwLock<Option<Arc<Reader<Vec<u8>>>>>,
+    anonymous_ip: RwLock<Option<Arc<Reader<Vec<u8>>>>>,
+    isp: RwLock<Option<Arc<Reader<Vec<u8>>>>>,
+    watched: Mutex<Vec<WatchedFile>>,
+}
+
+impl GeoDbManager {
+    /// إنشاء مدير من قارئ City ثابت دون مراقبة ملفات.
+    /// Creates a manager from a fixed City reader without watching any file.
+    #[must_use]
+    pub fn from_city_reader(reader: Arc<GeoReaderEnum>) -> Self {
+        Self {
+            city: RwLock::new(reader),
+            asn: RwLock::new(None),
+            anonymous_ip: RwLock::new(None),
+            isp: RwLock::new(None),
+            watched: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// تحميل كل قواعد البيانات المكوّنة. بدون مسار City يُستخدم القارئ الوهمي.
+    /// Loads every configured database. Without a City path the mock reader is used.
+    ///
+    /// # Errors
+    /// Returns `GeoDbError` if a configured file cannot be read, parsed, or has the wrong edition.
+    pub fn load(config: &GeoDbConfig) -> Result<Self, GeoDbError> {
+        let manager = Self::from_city_reader(Arc::new(GeoReaderEnum::Mock(MockGeoReader::new())));
+        let entries = [
+            (GeoDbEdition::City, &config.city_path),
+            (GeoDbEdition::Asn, &config.asn_path),
+            (GeoDbEdition::AnonymousIp, &config.anonymous_ip_path),
+            (GeoDbEdition::Isp, &config.isp_path),
+        ];
+        let mut watched = Vec::new();
+        for (edition, path) in entries {
+            let Some(path) = path else { continue };
+            let reader = Self::open(edition, path)?;
+            manager.install(edition, reader)?;
+            watched.push(WatchedFile {
+                edition,
+                path: path.clone(),
+                stamp: file_stamp(path),
+            });
+        }
+        *manager.watched.lock().map_err(|_| GeoDbError::LockFailed)? = watched;
+        Ok(manager)
+    }
+
+    /// يفتح ملف MMDB ويتحقق من مطابقة الإصدار.
+    /// Opens an MMDB file and validates its edition.
+    ///
+    /// # Errors
+    /// Returns `GeoDbError` on read/parse failure or edition mismatch.
+    pub fn open(edition: GeoDbEdition, path: &Path) -> Result<Reader<Vec<u8>>, GeoDbError> {
+        let display = path.display().to_string();
+        let bytes =
+            std::fs::read(path).map_err(|e| GeoDbError::Io(display.clone(), e.to_string()))?;
+        let reader = Reader::from_source(bytes)
+            .map_err(|e| GeoDbError::Parse(display.clone(), e.to_string()))?;
+        if !edition.accepts(&reader.metadata.database_type) {
+            return Err(GeoDbError::EditionMismatch {
+                path: display,
+                expected: edition,
+                actual: reader.metadata.database_type,
+            });
+        }
+        Ok(reader)
+    }
+
+    /// يستبدل القارئ الحالي للإصدار المحدد بشكل ذري.
+    /// Atomically replaces the current reader for the given edition.
+    ///
+    /// # Errors
+    /// Returns `GeoDbError::LockFailed` if a lock is poisoned.
+    pub fn install(
+        &self,
+        edition: GeoDbEdition,
+        reader: Reader<Vec<u8>>,
+    ) -> Result<(), GeoDbError> {
+        match edition {
+            GeoDbEdition::City => {
+                *self.city.write().map_err(|_| GeoDbError::LockFailed)? =
+                    Arc::new(GeoReaderEnum::Real(reader));
+            }
+            GeoDbEdition::Asn => {
+                *self.asn.write().map_err(|_| GeoDbError::LockFailed)? = Some(Arc::new(reader));
+            }
+            GeoDbEdition::AnonymousIp => {
+                *self
+                    .anonymous_ip
+                    .write()
+                    .map_err(|_| GeoDbError::LockFailed)? = Some(Arc::new(reader));
+            }
+            GeoDbEdition::Isp => {
+                *self.isp.write().map_err(|_| GeoDbError::LockFailed)? = Some(Arc::new(reader));
+            }
+        }
+        Ok(())
+    }
+
+    /// يعيد تحميل الملفات التي تغيرت منذ آخر فحص، ويعيد الإصدارات التي تم تبديلها.
+    /// عند فشل التحميل يبقى القارئ السابق فعالاً ويُسجل الخطأ.
+    /// Reloads files changed since the last check and returns the swapped editions.
+    /// On a failed load the previous reader stays active and the error is logged.
+    pub fn reload_if_changed(&self) -> Vec<GeoDbEdition> {
+        let Ok(mut watched) = self.watched.lock() else {
+            return Vec::new();
+        };
+        let mut swapped = Vec::new();
+        for file in watched.iter_mut() {
+            let stamp = file_stamp(&file.path);
+            if stamp.is_none() || stamp == file.stamp {
+                continue;
+            }
+            match Self::open(file.edition, &file.path).and_then(|r| self.install(file.edition, r)) {
+                Ok(()) => {
+                    file.stamp = stamp;
+                    swapped.push(file.edition);
+                }
+                Err(e) => log::warn!("geo_db_reload_failed edition={:?} error={e}", file.edition),
+            }
+        }
+        swapped
+    }
+
+    /// يطلق مهمة خلفية تفحص الملفات دورياً وتعيد تحميلها عند التغير.
+    /// Spawns a background task that polls the files and reloads them on change.
+    #[must_use]
+    pub fn spawn_watcher(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let manager = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                for edition in manager.reload_if_changed() {
+                    log::info!("GeoIP database reloaded: {edition:?}");
+                }
+            }
+        })
+    }
+
+    /// يعيد القارئ الحالي لقاعدة City.
+    /// Returns the current City reader.
+    #[must_use]
+    pub fn city_reader(&self) -> Arc<GeoReaderEnum> {
+        self.city.read().map_or_else(
+            |poisoned| Arc::clone(poisoned.get_ref()),
+            |guard| Arc::clone(&guard),
+        )
+    }
+
+    /// هل تم تحميل أي من إصدارات ASN أو Anonymous-IP أو ISP؟
+    /// Whether any of the ASN, Anonymous-IP or ISP editions is loaded.
+    #[must_use]
+    pub fn has_network_editions(&self) -> bool {
+        [&self.asn, &self.anonymous_ip, &self.isp]
+            .iter()
+            .any(|slot| slot.read().is_ok_and(|r| r.is_some()))
+    }
+
+    /// يجمع معلومات ASN والمؤسسة وأعلام الإخفاء من الإصدارات المحملة.
+    /// Collects ASN, organization and anonymity flags from the loaded editions.
+    #[must_use]
+    pub fn lookup_network(&self, ip: IpAddr) -> Option<NetworkOwnership> {
+        let mut info = NetworkOwnership::default();
+
+        if let Some(reader) = Self::snapshot(&self.isp) {
+            if let Ok(Some(isp)) = reader.lookup(ip).and_then(|r| r.decode::<Isp>()) {
+                info.asn = isp.autonomous_system_number;
+                info.organization = isp
+                    .organization
+                    .or(isp.autonomous_system_organization)
+                    .map(ToString::to_string);
+                info.isp = isp.isp.map(ToString::to_string);
+            }
+        }
+        if let Some(reader) = Self::snapshot(&self.asn) {
+            if let Ok(Some(asn)) = reader.lookup(ip).and_then(|r| r.decode::<Asn>()) {
+                info.asn = info.asn.or(asn.autonomous_system_number);
+                if info.organization.is_none() {
+                    info.organization = asn.autonomous_system_organization.map(ToString::to_string);
+                }
+            }
+        }
+        if let Some(reader) = Self::snapshot(&self.anonymous_ip) {
+            if let Ok(Some(anon)) = reader.lookup(ip).and_then(|r| r.decode::<AnonymousIp>()) {
+                info.is_hosting_provider = anon.is_hosting_provider.unwrap_or(false);
+                info.is_anonymous_vpn = anon.is_anonymous_vpn.unwrap_or(false);
+                info.is_public_proxy = anon.is_public_proxy.unwrap_or(false);
+                info.is_residential_proxy = anon.is_residential_proxy.unwrap_or(false);
+                info.is_tor_exit_node = anon.is_tor_exit_node.unwrap_or(false);
+            }
+        }
+
+        (!info.is_empty()).then_some(info)
+    }
+
+    fn snapshot(slot: &RwLock<Option<Arc<Reader<Vec<u8>>>>>) -> Option<Arc<Reader<Vec<u8>>>> {
+        slot.read().ok().and_then(|guard| guard.clone())
+    }
+}
+
+/// بصمة الملف (وقت التعديل والحجم) لكشف الاستبدال.
+/// File stamp (mtime and size) used to detect replacement.
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CITY_DB: &str = "GeoLite2-City-Test.mmdb";
+
+    fn temp_copy(name: &str) -> Option<PathBuf> {
+        let bytes = std::fs::read(CITY_DB).ok()?;
+        let path = std::env::temp_dir().join(format!("{name}-{}.mmdb", uuid::Uuid::new_v4()));
+        std::fs::write(&path, bytes).ok()?;
+        Some(path)
+    }
+
+    #[test]
+    fn test_load_city_and_reject_wrong_edition() {
+        let Some(path) = temp_copy("geo-db-edition") else {
+            return;
+        };
+        let config = GeoDbConfig {
+            city_path: Some(path.clone()),
+            ..GeoDbConfig::default()
+        };
+        let manager = GeoDbManager::load(&config).unwrap();
+        assert!(matches!(*manager.city_reader(), GeoReaderEnum::Real(_)));
+        assert!(!manager.has_network_editions());
+
+        let wrong = GeoDbConfig {
+            asn_path: Some(path.clone()),
+            ..GeoDbConfig::default()
+        };
+        assert!(matches!(
+            GeoDbManager::load(&wrong),
+            Err(GeoDbError::EditionMismatch { .. })
+        ));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_reload_keeps_previous_reader_on_corrupt_file() {
+        let Some(path) = temp_copy("geo-db-reload") else {
+            return;
+        };
+        let config = GeoDbConfig {
+            city_path: Some(path.clone()),
+            ..GeoDbConfig::default()
+        };
+        let manager = GeoDbManager::load(&config).unwrap();
+        let before = manager.city_reader();
+
+        std::fs::write(&path, b"not an mmdb file").unwrap();
+        assert!(manager.reload_if_changed().is_empty());
+        assert!(Arc::ptr_eq(&before, &manager.city_reader()));
+
+        std::fs::write(&path, std::fs::read(CITY_DB).unwrap()).unwrap();
+        assert_eq!(manager.reload_if_changed(), vec![GeoDbEdition::City]);
+        assert!(!Arc::ptr_eq(&before, &manager.city_reader()));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,340 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: hosting_ranges.rs
+    المسار:    src/core/hosting_ranges.rs
+    دور الملف:
+    مصنف نطاقات مراكز البيانات ومزودي الاستضافة السحابية.
+    يحمّل قوائم CIDR المنشورة من ملفات محلية (صيغ AWS و GCP و Azure وقوائم CIDR نصية)
+    ويحدد المزود والمنطقة لأي عنوان IP، لاستخدامه في `NetworkAnalyzer`.
+    --------------------------------------------------------------
+    File Name: hosting_ranges.rs
+    Path:     src/core/hosting_ranges.rs
+    File Role:
+    Datacenter and cloud hosting-provider range classifier. It loads published
+    CIDR lists from local files (AWS, GCP, Azure JSON formats and plain CIDR text)
+    and tags the provider and region of any IP for `NetworkAnalyzer`.
+******************************************************************************************/
+
+use crate::utils::ip_prefix::{IpPrefix, PrefixTable};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::path::Path;
+use thiserror::Error;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum HostingRangeError {
+    #[error("Failed to read range file '{0}': {1}")]
+    Io(String, String),
+    #[error("Malformed {0:?} range document: {1}")]
+    Malformed(HostingRangeFormat, String),
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// صيغ ملفات النطاقات المدعومة.
+/// Supported range file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HostingRangeFormat {
+    /// `ip-ranges.json` من AWS.
+    /// AWS `ip-ranges.json`.
+    Aws,
+    /// `cloud.json` من Google Cloud.
+    /// Google Cloud `cloud.json`.
+    Gcp,
+    /// ملف Service Tags من Azure.
+    /// Azure Service Tags file.
+    Azure,
+    /// سطر لكل نطاق: `CIDR [region]`، مع دعم التعليقات بـ `#`.
+    /// One range per line: `CIDR [region]`, with `#` comments.
+    CidrList,
+}
+
+/// نتيجة تصنيف عنوان ضمن نطاق استضافة.
+/// Classification result for an address inside a hosting range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostingMatch {
+    pub provider: String,
+    pub region: Option<String>,
+    pub service: Option<String>,
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone)]
+struct HostingRange {
+    provider: String,
+    region: Option<String>,
+    service: Option<String>,
+}
+
+// ================================================================
+// مصنف نطاقات الاستضافة
+// Hosting Range Classifier
+// ================================================================
+#[derive(Default)]
+pub struct HostingRangeClassifier {
+    ranges: PrefixTable<HostingRange>,
+}
+
+impl HostingRangeClassifier {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// عدد النطاقات المحملة.
+    /// Number of loaded ranges.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// يحمّل ملف نطاقات ويعيد عدد البادئات المضافة.
+    /// Loads a range file and returns the number of prefixes added.
+    ///
+    /// # Errors
+    /// Returns `HostingRangeError` if the file cannot be read or parsed.
+    pub fn load_file(
+        &mut self,
+        format: HostingRangeFormat,
+        provider: &str,
+        path: &Path,
+    ) -> Result<usize, HostingRangeError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| HostingRangeError::Io(path.display().to_string(), e.to_string()))?;
+        self.load_str(format, provider, &content)
+    }
+
+    /// يحمّل محتوى نطاقات من نص ويعيد عدد البادئات المضافة.
+    /// البادئات غير الصالحة داخل مستند صالح يتم تجاهلها.
+    /// Loads range content from text and returns the number of prefixes added.
+    /// Invalid prefixes inside an otherwise valid document are skipped.
+    ///
+    /// # Errors
+    /// Returns `HostingRangeError::Malformed` if the document structure is invalid.
+    pub fn load_str(
+        &mut self,
+        format: HostingRangeFormat,
+        provider: &str,
+        content: &str,
+    ) -> Result<usize, HostingRangeError> {
+        let entries = match format {
+            HostingRangeFormat::Aws => parse_aws(content)?,
+            HostingRangeFormat::Gcp => parse_gcp(content)?,
+            HostingRangeFormat::Azure => parse_azure(content)?,
+            HostingRangeFormat::CidrList => parse_cidr_list(content),
+        };
+        let mut added = 0;
+        for (prefix, region, service) in entries {
+            let Ok(prefix) = prefix.parse::<IpPrefix>() else {
+                continue;
+            };
+            let range = HostingRange {
+                provider: provider.to_string(),
+                region: region.filter(|r| !r.is_empty()),
+                service: service.filter(|s| !s.is_empty()),
+            };
+            if self.ranges.insert(prefix, range).is_none() {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// يصنف عنواناً ويعيد المزود والمنطقة لأطول نطاق مطابق.
+    /// Classifies an address, returning provider and region of the longest matching range.
+    #[must_use]
+    pub fn classify(&self, ip: &IpAddr) -> Option<HostingMatch> {
+        self.ranges
+            .longest_match(ip)
+            .map(|(prefix, range)| HostingMatch {
+                provider: range.provider.clone(),
+                region: range.region.clone(),
+                service: range.service.clone(),
+                prefix: prefix.to_string(),
+            })
+    }
+}
+
+type RangeEntry = (String, Option<String>, Option<String>);
+
+fn parse_json(format: HostingRangeFormat, content: &str) -> Result<Value, HostingRangeError> {
+    serde_json::from_str(content).map_err(|e| HostingRangeError::Malformed(format, e.to_string()))
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(ToString::to_string)
+}
+
+fn parse_aws(content: &str) -> Result<Vec<RangeEntry>, HostingRangeError> {
+    let doc = parse_json(HostingRangeFormat::Aws, content)?;
+    let mut out = Vec::new();
+    for (list, key) in [("prefixes", "ip_prefix"), ("ipv6_prefixes", "ipv6_prefix")] {
+        let Some(items) = doc.get(list).and_then(Value::as_array) else {
+            continue;
+        };
+        for item in items {
+            if let Some(prefix) = str_field(item, key) {
+                out.push((
+                    prefix,
+                    str_field(item, "region"),
+                    str_field(item, "service"),
+                ));
+            }
+        }
+    }
+    if doc.get("prefixes").is_none() && doc.get("ipv6_prefixes").is_none() {
+        return Err(HostingRangeError::Malformed(
+            HostingRangeFormat::Aws,
+            "missing 'prefixes'".to_string(),
+        ));
+    }
+    Ok(out)
+}
+
+fn parse_gcp(content: &str) -> Result<Vec<RangeEntry>, HostingRangeError> {
+    let doc = parse_json(HostingRangeFormat::Gcp, content)?;
+    let items = doc
+        .get("prefixes")
+        .and_then(Value::as_array)
+        .ok_or_else(|| {
+            HostingRangeError::Malformed(HostingRangeFormat::Gcp, "missing 'prefixes'".to_string())
+        })?;
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let prefix = str_field(item, "ipv4Prefix").or_else(|| str_field(item, "ipv6Prefix"))?;
+            Some((prefix, str_field(item, "scope"), str_field(item, "service")))
+        })
+        .collect())
+}
+
+fn parse_azure(content: &str) -> Result<Vec<RangeEntry>, HostingRangeError> {
+    let doc = parse_json(HostingRangeFormat::Azure, content)?;
+    let values = doc.get("values").and_then(Value::as_array).ok_or_else(|| {
+        HostingRangeError::Malformed(HostingRangeFormat::Azure, "missing 'values'".to_string())
+    })?;
+    let mut out = Vec::new();
+    for tag in values {
+        let service = str_field(tag, "name").map(|n| n.split('.').next().unwrap_or("").to_string());
+        let Some(props) = tag.get("properties") else {
+            continue;
+        };
+        let region = str_field(props, "region");
+        let Some(prefixes) = props.get("addressPrefixes").and_then(Value::as_array) else {
+            continue;
+        };
+        for prefix in prefixes.iter().filter_map(Value::as_str) {
+            out.push((prefix.to_string(), region.clone(), service.clone()));
+        }
+    }
+    Ok(out)
+}
+
+fn parse_cidr_list(content: &str) -> Vec<RangeEntry> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let mut parts = line.split(|c: char| c.is_whitespace() || c == ',');
+            let prefix = parts.next()?.to_string();
+            let region = parts.find(|p| !p.is_empty()).map(ToString::to_string);
+            Some((prefix, region, None))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_provider_formats_and_classify() {
+        let mut classifier = HostingRangeClassifier::new();
+        let aws = r#"{"prefixes":[{"ip_prefix":"3.5.140.0/22","region":"ap-northeast-2","service":"EC2"}],
+                      "ipv6_prefixes":[{"ipv6_prefix":"2600:1f14::/35","region":"us-west-2","service":"EC2"}]}"#;
+        let gcp = r#"{"prefixes":[{"ipv4Prefix":"34.1.208.0/20","service":"Google Cloud","scope":"africa-south1"}]}"#;
+        let azure = r#"{"values":[{"name":"AzureCloud.eastus","properties":{"region":"eastus","addressPrefixes":["20.42.0.0/17"]}}]}"#;
+        let plain = "# hetzner\n5.9.0.0/16 fsn1\n\n not-a-cidr\n";
+
+        assert_eq!(
+            classifier
+                .load_str(HostingRangeFormat::Aws, "aws", aws)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            classifier
+                .load_str(HostingRangeFormat::Gcp, "gcp", gcp)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            classifier
+                .load_str(HostingRangeFormat::Azure, "azure", azure)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            classifier
+                .load_str(HostingRangeFormat::CidrList, "hetzner", plain)
+                .unwrap(),
+            1
+        );
+
+        let hit = classifier.classify(&"3.5.141.7".parse().unwrap()).unwrap();
+        assert_eq!(hit.provider, "aws");
+        assert_eq!(hit.region.as_deref(), Some("ap-northeast-2"));
+        let hit = classifier.classify(&"20.42.1.1".parse().unwrap()).unwrap();
+        assert_eq!(hit.service.as_deref(), Some("AzureCloud"));
+        assert_eq!(
+            classifier
+                .classify(&"2600:1f14::10".parse().unwrap())
+                .unwrap()
+                .provider,
+            "aws"
+        );
+        assert_eq!(
+            classifier
+                .classify(&"5.9.1.1".parse().unwrap())
+                .unwrap()
+                .region
+                .as_deref(),
+            Some("fsn1")
+        );
+        assert!(classifier.classify(&"8.8.8.8".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_malformed_document_is_rejected() {
+        let mut classifier = HostingRangeClassifier::new();
+        assert!(classifier
+            .load_str(HostingRangeFormat::Gcp, "gcp", "{\"foo\":1}")
+            .is_err());
+        assert!(classifier
+            .load_str(HostingRangeFormat::Aws, "aws", "not json")
+            .is_err());
+        assert!(classifier.is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: keystroke.rs
+    المسار:    src/core/keystroke.rs
+    دور الملف:
+    ديناميكيات ضغط المفاتيح. تستقبل تسلسلات زمن الضغط (dwell) وزمن الانتقال (flight)
+    لعبارة معروفة أو لنص حر، وتبني قالباً لكل مستخدم (`biometric_template`)،
+    وتقيس العينات الجديدة بمسافة مانهاتن المقاسة. `KeystrokeDynamicsModel` يغلف أي
+    `BehavioralModel` ويضيف خطر الكتابة إلى درجته.
+    --------------------------------------------------------------
+    File Name: keystroke.rs
+    Path:     src/core/keystroke.rs
+    File Role:
+    Keystroke dynamics. Accepts dwell and flight time sequences for a known phrase or for
+    free text, builds a per-user template (`biometric_template`) and scores new samples
+    with the scaled Manhattan distance. `KeystrokeDynamicsModel`
+    wraps any `BehavioralModel` and adds the typing risk to its score.
+******************************************************************************************/
+
+use crate::core::behavior_bio::{BehaviorError, BehaviorInput, BehavioralModel};
+use crate::core::biometric_template::{
+    FeatureTemplate, TemplateBackend, TemplateError, TemplateScore, TemplateSettings, TemplateStore,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// عدد العينات المطلوبة قبل بناء القالب.
+/// Samples required before a template is built.
+pub const MIN_ENROLLMENT_SAMPLES: usize = 5;
+/// أقصى عدد من العينات المحفوظة لكل قالب (الأقدم يُستبدل).
+/// Maximum samples kept per template (the oldest is replaced).
+pub const MAX_TEMPLATE_SAMPLES: usize = 30;
+/// أقل عدد مفاتيح لعينة نص حر.
+/// Minimum keys in a free-text sample.
+pub const MIN_FREE_TEXT_KEYS: usize = 10;
+/// حدود المقارنة: أدنى انحراف 5 مللي ثانية حتى لا يضخم مستخدم ثابت جداً المسافة.
+/// Scoring settings: a 5 ms deviation floor so a very consistent typist does not blow up
+/// the distance.
+const SETTINGS: TemplateSettings = TemplateSettings {
+    min_enrollment: MIN_ENROLLMENT_SAMPLES,
+    max_samples: MAX_TEMPLATE_SAMPLES,
+    absolute_floor: 5.0,
+    relative_floor: 0.0,
+    genuine_distance: 1.5,
+    impostor_distance: 3.5,
+    adapt_below_risk: 0.3,
+    max_profiles: 100_000,
+    idle_days: 180,
+};
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum KeystrokeError {
+    #[error("Keystroke sample has no keys")]
+    Empty,
+
+    #[error("Keystroke timings must be finite and dwell times non-negative")]
+    InvalidTiming,
+
+    #[error("Expected {expected} flight times for {keys} keys, got {actual}")]
+    FlightCount {
+        keys: usize,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("Free-text samples need at least {MIN_FREE_TEXT_KEYS} keys, got {0}")]
+    TooShort(usize),
+
+    #[error("Sample has {actual} features but the phrase template has {expected}")]
+    PhraseMismatch { expected: usize, actual: usize },
+
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// عينة كتابة: زمن ضغط كل مفتاح وزمن الانتقال بين المفاتيح المتتالية.
+/// A typing sample: each key's dwell time and the flight time between consecutive keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeystrokeSample {
+    /// معرف العبارة المعروفة (مثل "login-password")؛ غيابها يعني نصاً حراً.
+    /// Identifier of the known phrase (e.g. "login-password"); absent means free text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phrase: Option<String>,
+    /// زمن الضغط لكل مفتاح (مللي ثانية).
+    /// Dwell time per key (ms).
+    pub dwell_ms: Vec<f64>,
+    /// الزمن بين رفع مفتاح وضغط التالي (مللي ثانية، قد يكون سالباً عند التداخل).
+    /// Time from one key's release to the next key's press (ms, negative on rollover).
+    pub flight_ms: Vec<f64>,
+}
+
+impl KeystrokeSample {
+    /// يتحقق من العينة ويعيد متجه ميزاتها. العبارة المعروفة تُقارن موضعاً بموضع،
+    /// والنص الحر يُلخص بالمتوسط والانحراف والوسيط لكل من الضغط والانتقال.
+    /// Validates the sample and returns its feature vector. A known phrase is compared
+    /// position by position; free text is summarized by mean, deviation and median of
+    /// dwell and flight times.
+    ///
+    /// # Errors
+    /// يعيد `KeystrokeError` إذا كانت التوقيتات فارغة أو غير صالحة أو غير متسقة.
+    /// Returns `KeystrokeError` if the timings are empty, invalid or inconsistent.
+    pub fn features(&self) -> Result<Vec<f64>, KeystrokeError> {
+        let keys = self.dwell_ms.len();
+        if keys == 0 {
+            return Err(KeystrokeError::Empty);
+        }
+        if self.dwell_ms.iter().any(|d| !d.is_finite() || *d < 0.0)
+            || self.flight_ms.iter().any(|f| !f.is_finite())
+        {
+            return Err(KeystrokeError::InvalidTiming);
+        }
+        if self.flight_ms.len() != keys - 1 {
+            return Err(KeystrokeError::FlightCount {
+                keys,
+                expected: keys - 1,
+                actual: self.flight_ms.len(),
+            });
+        }
+        if self.phrase.is_some() {
+            return Ok(self
+                .dwell_ms
+                .iter()
+                .chain(&self.flight_ms)
+                .copied()
+                .collect());
+        }
+        if keys < MIN_FREE_TEXT_KEYS {
+            return Err(KeystrokeError::TooShort(keys));
+        }
+        let mut features = summarize(&self.dwell_ms).to_vec();
+        features.extend(summarize(&self.flight_ms));
+        Ok(features)
+    }
+}
+
+/// المتوسط والانحراف المعياري والوسيط.
+/// Mean, standard deviation and median.
+fn summarize(values: &[f64]) -> [f64; 3] {
+    #[allow(clippy::cast_precision_loss)]
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    };
+    [mean, std, median]
+}
+
+/// قوالب الكتابة لكل مستخدم، مفتاحها (الكيان، العبارة أو النص الحر).
+/// Per-user typing templates, keyed by (entity, phrase or free text).
+pub struct KeystrokeProfiles {
+    store: TemplateStore<(String, Option<String>)>,
+}
+
+impl Default for KeystrokeProfiles {
+    fn default() -> Self {
+        Self {
+            store: TemplateStore::new(SETTINGS),
+        }
+    }
+}
+
+impl KeystrokeProfiles {
+    /// يحفظ القوالب في قاعدة البيانات (انظر `TemplateStore::with_backend`).
+    /// Persists the templates to the database (see `TemplateStore::with_backend`).
+    #[must_use]
+    pub fn with_backend(mut self, backend: Arc<dyn TemplateBackend>) -> Self {
+        self.store = self.store.with_backend("keystroke", backend);
+        self
+    }
+
+    /// يقيّم العينة مقابل قالب الكيان ثم يضيفها إليه أثناء التسجيل أو إذا كانت
+    /// منخفضة الخطر.
+    /// Scores the sample against the entity's template, then folds it in during
+    /// enrollment or when it is low risk.
+    ///
+    /// # Errors
+    /// يعيد `KeystrokeError` إذا كانت العينة غير صالحة أو لا تطابق طول العبارة.
+    /// Returns `KeystrokeError` if the sample is invalid or does not fit the phrase length.
+    pub async fn observe(
+        &self,
+        entity_id: &str,
+        sample: &KeystrokeSample,
+    ) -> Result<TemplateScore, KeystrokeError> {
+        let features = sample.features()?;
+        self.store
+            .observe((entity_id.to_string(), sample.phrase.clone()), features)
+            .await
+            .map_err(|e| match e {
+                TemplateError::DimensionMismatch { expected, actual } => {
+                    KeystrokeError::PhraseMismatch { expected, actual }
+                }
+                TemplateError::Storage(_) => KeystrokeError::Template(e),
+            })
+    }
+
+    /// قالب الكيان الحالي إن وُجد.
+    /// The entity's current template, if any.
+    pub async fn template(&self, entity_id: &str, phrase: Option<&str>) -> Option<FeatureTemplate> {
+        self.store
+            .template(&(entity_id.to_string(), phrase.map(str::to_string)))
+            .await
+    }
+}
+
+// ================================================================
+// النموذج السلوكي
+// Behavioral Model
+// ================================================================
+
+/// يغلف نموذجاً سلوكياً ويضيف خطر الكتابة (بوزن) عند وجود `keystrokes` في المُدخل.
+/// Wraps a behavioral model and adds the weighted typing risk when the input has `keystrokes`.
+pub struct KeystrokeDynamicsModel {
+    inner: Arc<dyn BehavioralModel>,
+    profiles: Arc<KeystrokeProfiles>,
+    weight: f32,
+}
+
+impl KeystrokeDynamicsModel {
+    /// الوزن الافتراضي لخطر الكتابة.
+    /// Default weight of the typing risk.
+    pub const DEFAULT_WEIGHT: f32 = 0.5;
+
+    #[must_use]
+    pub fn new(inner: Arc<dyn BehavioralModel>) -> Self {
+        Self {
+            inner,
+            profiles: Arc::new(KeystrokeProfiles::default()),
+            weight: Self::DEFAULT_WEIGHT,
+        }
+    }
+
+    /// مشاركة القوالب مع مكونات أخرى.
+    /// Share templates with other components.
+    #[must_use]
+    pub fn with_profiles(mut self, profiles: Arc<KeystrokeProfiles>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+#[async_trait]
+impl BehavioralModel for KeystrokeDynamicsModel {
+    async fn analyze(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<f32, BehaviorError> {
+        let score = self.inner.analyze(current, history).await?;
+        let Some(sample) = &current.keystrokes else {
+            return Ok(score);
+        };
+        let typing = self
+            .profiles
+            .observe(&current.entity_id, sample)
+            .await
+            .map_err(|e| BehaviorError::InvalidInput(e.to_string()))?;
+        Ok(self.weight.mul_add(typing.risk, score).min(1.0))
+    }
+
+    fn has_signal(&self, current: &BehaviorInput) -> bool {
+        current.keystrokes.is_some() || self.inner.has_signal(current)
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::behavior_bio::{
+        BehaviorEngine, DefaultAnomalyDetector, DefaultBehavioralModel, NetworkInfo,
+    };
+    use chrono::{TimeZone, Utc};
+
+    /// عينة لعبارة من 8 مفاتيح مع تذبذب حتمي صغير.
+    /// An 8-key phrase sample with a small deterministic jitter.
+    fn phrase_sample(dwell: f64, flight: f64, jitter: u32) -> KeystrokeSample {
+        let wobble = |i: usize| f64::from((jitter * 7 + u32::try_from(i).unwrap() * 3) % 11) - 5.0;
+        KeystrokeSample {
+            phrase: Some("login-password".to_string()),
+            dwell_ms: (0..8).map(|i| dwell + wobble(i)).collect(),
+            flight_ms: (0..7).map(|i| flight + wobble(i + 8)).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_genuine_user_scores_low_and_impostor_high() {
+        let profiles = KeystrokeProfiles::default();
+        for jitter in 0..MIN_ENROLLMENT_SAMPLES as u32 {
+            let score = profiles
+                .observe("u1", &phrase_sample(95.0, 140.0, jitter))
+                .await
+                .unwrap();
+            assert!(!score.enrolled);
+        }
+        assert!(profiles
+            .template("u1", Some("login-password"))
+            .await
+            .is_some());
+
+        let genuine = profiles
+            .observe("u1", &phrase_sample(96.0, 138.0, 11))
+            .await
+            .unwrap();
+        assert!(genuine.enrolled);
+        assert!(genuine.risk < 0.3, "{genuine:?}");
+
+        let impostor = profiles
+            .observe("u1", &phrase_sample(60.0, 230.0, 3))
+            .await
+            .unwrap();
+        assert!((impostor.risk - 1.0).abs() < f32::EPSILON, "{impostor:?}");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_samples_are_rejected() {
+        let profiles = KeystrokeProfiles::default();
+        let mut sample = phrase_sample(95.0, 140.0, 0);
+        sample.flight_ms.pop();
+        assert!(matches!(
+            profiles.observe("u1", &sample).await,
+            Err(KeystrokeError::FlightCount { .. })
+        ));
+
+        profiles
+            .observe("u1", &phrase_sample(95.0, 140.0, 0))
+            .await
+            .unwrap();
+        let mut longer = phrase_sample(95.0, 140.0, 1);
+        longer.dwell_ms.push(90.0);
+        longer.flight_ms.push(120.0);
+        assert!(matches!(
+            profiles.observe("u1", &longer).await,
+            Err(KeystrokeError::PhraseMismatch { .. })
+        ));
+
+        let free_text = KeystrokeSample {
+            phrase: None,
+            dwell_ms: vec![90.0; 4],
+            flight_ms: vec![120.0; 3],
+        };
+        assert!(matches!(
+            free_text.features(),
+            Err(KeystrokeError::TooShort(4))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_model_adds_typing_risk_to_behavior_score() {
+        let engine = BehaviorEngine::new(
+            Arc::new(KeystrokeDynamicsModel::new(Arc::new(
+                DefaultBehavioralModel::default(),
+            ))),
+            Arc::new(DefaultAnomalyDetector {
+                max_speed_kmh: 1200.0,
+            }),
+            50,
+        );
+        let input = |keystrokes: KeystrokeSample| BehaviorInput {
+            entity_id: "u1".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap(),
+            location: (24.7136, 46.6753),
+            network_info: NetworkInfo {
+                ip_address: "10.0.0.1".to_string(),
+                is_vpn: false,
+                connection_type: "WiFi".to_string(),
+            },
+            device_fingerprint: "fp".to_string(),
+            device_similarity: None,
+            keystrokes: Some(keystrokes),
+            pointer_streams: Vec::new(),
+            timezone: None,
+        };
+        for jitter in 0..=MIN_ENROLLMENT_SAMPLES as u32 {
+            engine
+                .process(input(phrase_sample(95.0, 140.0, jitter)))
+                .await
+                .unwrap();
+        }
+        let genuine = engine
+            .process(input(phrase_sample(95.0, 140.0, 9)))
+            .await
+            .unwrap();
+        let impostor = engine
+            .process(input(phrase_sample(60.0, 230.0, 4)))
+            .await
+            .unwrap();
+        assert!(genuine.risk_score < 0.2, "{genuine:?}");
+        assert!(impostor.risk_score >= 0.5, "{impostor:?}");
+    }
+}
@@ -0,0 +1,270 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: location_consistency.rs
+    المسار:    src/core/location_consistency.rs
+    دور الملف:
+    مدقق اتساق الموقع بين عنوان IP والموقع الذي يبلغ عنه الجهاز (GPS).
+    يقارن المسافة بين الموقعين مع نصف قطر الدقة لكل منهما، ويقارن دولة IP بدولة
+    شريحة SIM ومنطقة اللغة (locale)، وينتج درجة عدم تطابق مع الأسباب
+    ليستهلكها `CrossValidationEngine` و `CompositeVerifier`.
+    --------------------------------------------------------------
+    File Name: location_consistency.rs
+    Path:     src/core/location_consistency.rs
+    File Role:
+    IP-vs-device (GPS) location consistency checker. It compares the distance between
+    both fixes against their accuracy radii and the IP country against the SIM country
+    and locale region, producing a mismatch score with reasons consumed by
+    `CrossValidationEngine` and `CompositeVerifier`.
+******************************************************************************************/
+
+use crate::core::network_analyzer::GeoLocation as IpGeoLocation;
+use crate::utils::precision::haversine_km;
+use serde::{Deserialize, Serialize};
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// تلميحات الموقع التي يبلغ عنها الجهاز.
+/// Location hints reported by the device.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceLocationHints<'a> {
+    /// `(lat, lng)` من GPS.
+    /// `(lat, lng)` from GPS.
+    pub gps: Option<(f64, f64)>,
+    /// دقة GPS بالأمتار.
+    /// GPS accuracy in meters.
+    pub gps_accuracy_m: Option<f64>,
+    /// رمز دولة شريحة SIM (ISO 3166-1 alpha-2).
+    /// SIM country code (ISO 3166-1 alpha-2).
+    pub sim_country: Option<&'a str>,
+    /// منطقة اللغة مثل `ar-SA`.
+    /// Locale such as `ar-SA`.
+    pub locale: Option<&'a str>,
+}
+
+/// سبب عدم التطابق.
+/// A mismatch reason.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MismatchReason {
+    /// المسافة بين موقع IP و GPS تتجاوز نصف قطر الدقة المسموح.
+    /// IP-to-GPS distance exceeds the allowed accuracy radius.
+    DistanceBeyondAccuracy { distance_km: f64, allowed_km: f64 },
+    /// دولة IP تختلف عن دولة SIM.
+    /// IP country differs from the SIM country.
+    IpCountryVsSim {
+        ip_country: String,
+        sim_country: String,
+    },
+    /// دولة IP تختلف عن منطقة اللغة.
+    /// IP country differs from the locale region.
+    IpCountryVsLocale {
+        ip_country: String,
+        locale_region: String,
+    },
+}
+
+/// تقرير الاتساق.
+/// Consistency report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationConsistencyReport {
+    /// 0.0 (متسق) إلى 1.0 (متناقض تماماً).
+    /// 0.0 (consistent) to 1.0 (fully contradictory).
+    pub mismatch_score: f32,
+    pub is_mismatch: bool,
+    pub distance_km: Option<f64>,
+    pub reasons: Vec<MismatchReason>,
+}
+
+/// إعدادات المدقق.
+/// Checker configuration.
+#[derive(Debug, Clone)]
+pub struct ConsistencyConfig {
+    /// هامش إضافي فوق نصفي قطر الدقة (كم).
+    /// Extra slack on top of both accuracy radii (km).
+    pub tolerance_km: f64,
+    /// المسافة الزائدة التي تبلغ عندها درجة المسافة حدها الأقصى (كم).
+    /// Excess distance at which the distance component saturates (km).
+    pub saturation_km: f64,
+    pub distance_weight: f32,
+    pub sim_weight: f32,
+    pub locale_weight: f32,
+    /// الدرجة التي يُعتبر عندها الموقع متناقضاً.
+    /// Score at which the location is considered contradictory.
+    pub mismatch_threshold: f32,
+    /// مقدار خصم الثقة لكل وحدة من درجة عدم التطابق.
+    /// Trust deducted per unit of mismatch score.
+    pub trust_penalty_weight: f32,
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            tolerance_km: 50.0,
+            saturation_km: 1_000.0,
+            distance_weight: 0.6,
+            sim_weight: 0.3,
+            locale_weight: 0.1,
+            mismatch_threshold: 0.6,
+            trust_penalty_weight: 0.3,
+        }
+    }
+}
+
+// ================================================================
+// مدقق اتساق الموقع
+// Location Consistency Checker
+// ================================================================
+#[derive(Debug, Clone, Default)]
+pub struct LocationConsistencyChecker {
+    pub config: ConsistencyConfig,
+}
+
+impl LocationConsistencyChecker {
+    #[must_use]
+    pub const fn new(config: ConsistencyConfig) -> Self {
+        Self { config }
+    }
+
+    /// يقارن موقع IP بتلميحات الجهاز.
+    /// Compares the IP location with the device hints.
+    #[must_use]
+    pub fn check(
+        &self,
+        ip_location: &IpGeoLocation,
+        device: &DeviceLocationHints,
+    ) -> LocationConsistencyReport {
+        let cfg = &self.config;
+        let mut score = 0.0_f32;
+        let mut reasons = Vec::new();
+
+        // 1. المسافة مقابل نصف قطر الدقة
+        // 1. Distance versus accuracy radius
+        let distance_km = match (ip_location.latitude, ip_location.longitude, device.gps) {
+            (Some(lat), Some(lng), Some((gps_lat, gps_lng))) => {
+                Some(haversine_km(lat, lng, gps_lat, gps_lng))
+            }
+            _ => None,
+        };
+        if let Some(distance_km) = distance_km {
+            let allowed_km = f64::from(ip_location.accuracy_radius_km)
+                + device.gps_accuracy_m.unwrap_or(0.0).max(0.0) / 1_000.0
+                + cfg.tolerance_km;
+            if distance_km > allowed_km {
+                let excess = ((distance_km - allowed_km) / cfg.saturation_km).min(1.0);
+                #[allow(clippy::cast_possible_truncation)]
+                let component = excess as f32 * cfg.distance_weight;
+                score += component;
+                reasons.push(MismatchReason::DistanceBeyondAccuracy {
+                    distance_km,
+                    allowed_km,
+                });
+            }
+        }
+
+        // 2. الدولة مقابل SIM واللغة
+        // 2. Country versus SIM and locale
+        let ip_country = ip_location.country_iso.to_ascii_uppercase();
+        if !ip_country.is_empty() {
+            if let Some(sim) = device.sim_country.map(|s| s.trim().to_ascii_uppercase()) {
+                if !sim.is_empty() && sim != ip_country {
+                    score += cfg.sim_weight;
+                    reasons.push(MismatchReason::IpCountryVsSim {
+                        ip_country: ip_country.clone(),
+                        sim_country: sim,
+                    });
+                }
+            }
+            if let Some(region) = device.locale.and_then(locale_region) {
+                if region != ip_country {
+                    score += cfg.locale_weight;
+                    reasons.push(MismatchReason::IpCountryVsLocale {
+                        ip_country,
+                        locale_region: region,
+                    });
+                }
+            }
+        }
+
+        let mismatch_score = score.clamp(0.0, 1.0);
+        LocationConsistencyReport {
+            mismatch_score,
+            is_mismatch: mismatch_score >= cfg.mismatch_threshold,
+            distance_km,
+            reasons,
+        }
+    }
+
+    /// يطبق خصم الثقة الناتج عن عدم التطابق على درجة موجودة.
+    /// Applies the mismatch trust penalty to an existing score.
+    #[must_use]
+    pub fn penalize(&self, score: f32, report: &LocationConsistencyReport) -> f32 {
+        (score - report.mismatch_score * self.config.trust_penalty_weight).clamp(0.0, 1.0)
+    }
+}
+
+/// يستخرج رمز المنطقة من `ar-SA` أو `en_US.UTF-8`.
+/// Extracts the region code from `ar-SA` or `en_US.UTF-8`.
+fn locale_region(locale: &str) -> Option<String> {
+    let base = locale.split('.').next().unwrap_or(locale);
+    base.split(['-', '_'])
+        .skip(1)
+        .find(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(str::to_ascii_uppercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn riyadh_ip() -> IpGeoLocation {
+        IpGeoLocation {
+            country_iso: "SA".to_string(),
+            city: "Riyadh".to_string(),
+            accuracy_radius_km: 20,
+            latitude: Some(24.7136),
+            longitude: Some(46.6753),
+        }
+    }
+
+    #[test]
+    fn test_consistent_location_scores_zero() {
+        let checker = LocationConsistencyChecker::default();
+        let report = checker.check(
+            &riyadh_ip(),
+            &DeviceLocationHints {
+                gps: Some((24.80, 46.70)),
+                gps_accuracy_m: Some(15.0),
+                sim_country: Some("sa"),
+                locale: Some("ar-SA"),
+            },
+        );
+        assert!(report.reasons.is_empty());
+        assert!(report.mismatch_score.abs() < f32::EPSILON);
+        assert!(report.distance_km.unwrap() < 20.0);
+    }
+
+    #[test]
+    fn test_distant_gps_and_foreign_sim_is_mismatch() {
+        let checker = LocationConsistencyChecker::default();
+        let report = checker.check(
+            &riyadh_ip(),
+            &DeviceLocationHints {
+                gps: Some((51.5074, -0.1278)),
+                gps_accuracy_m: Some(10.0),
+                sim_country: Some("GB"),
+                locale: Some("en_GB.UTF-8"),
+            },
+        );
+        assert!(report.is_mismatch);
+        assert_eq!(report.reasons.len(), 3);
+        assert!(checker.penalize(1.0, &report) < 0.8);
+    }
+}
@@ -1,11 +1,30 @@
+pub mod account_correlation;
+pub mod behavior_baseline;
 pub mod behavior_bio;
+pub mod behavior_calendar;
+pub mod behavior_feedback;
+pub mod behavior_pipeline;
+pub mod biometric_template;
+pub mod client_fingerprint;
 pub mod composite_verification;
 pub mod cross_location;
+pub mod device_attributes;
 pub mod device_fp;
+pub mod device_integrity;
+pub mod device_match;
+pub mod device_registry;
+pub mod forwarding_chain;
+pub mod geo_db;
 pub mod geo_resolver;
 pub mod history;
+pub mod hosting_ranges;
+pub mod keystroke;
+pub mod location_consistency;
 pub mod network_analyzer;
+pub mod pointer;
+pub mod proxy_db;
 pub mod sensors_analyzer;
+pub mod tor_directory;
 pub mod weather_val;
 
 // #[cfg(target_os = "windows")]
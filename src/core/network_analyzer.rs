@@ -34,6 +34,12 @@
     5.  A fully `async` design for high performance and low resource consumption.
 ******************************************************************************************/
 
+use crate::core::forwarding_chain::{ForwardingAnalysis, ForwardingIndicator};
+use crate::core::geo_db::{GeoDbManager, NetworkOwnership};
+use crate::core::hosting_ranges::{HostingMatch, HostingRangeClassifier};
+pub use crate::core::proxy_db::ProxyDatabase;
+use crate::core::tor_directory::{TorDirectory, TorRelayMatch};
+use crate::security::keyring::IpKeyring;
 use crate::security::secret::SecureBytes;
 use aes_gcm::aead::{Aead, KeyInit};
 use aes_gcm::AeadCore;
@@ -41,7 +47,6 @@ use aes_gcm::{Aes256Gcm, Key};
 use async_trait::async_trait;
 use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 use std::net::IpAddr;
 use std::sync::Arc;
 use thiserror::Error;
@@ -82,6 +87,12 @@ pub struct GeoLocation {
     pub country_iso: String,
     pub city: String,
     pub accuracy_radius_km: u16,
+    /// إحداثيات تقريبية لموقع IP (لمقارنتها بموقع GPS).
+    /// Approximate IP coordinates (for comparison with the GPS fix).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
 }
 
 /// تقرير عن أدوات الإخفاء المستخدمة.
@@ -91,6 +102,24 @@ pub struct ConcealmentReport {
     pub is_vpn: bool,
     pub is_proxy: bool,
     pub is_tor: bool,
+    /// عنوان تابع لمزود استضافة (من قاعدة Anonymous-IP).
+    /// Address belongs to a hosting provider (from the Anonymous-IP database).
+    #[serde(default)]
+    pub is_hosting: bool,
+    /// سلسلة تمرير مزورة (عناصر تالفة أو عميل غير عام).
+    /// Spoofed forwarding chain (malformed hops or a non-public client).
+    #[serde(default)]
+    pub spoofed_forwarding_chain: bool,
+    /// رؤوس تمرير متناقضة فيما بينها.
+    /// Forwarding headers that contradict each other.
+    #[serde(default)]
+    pub inconsistent_forwarding_chain: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forwarding_indicators: Vec<ForwardingIndicator>,
+    /// بصمة واسم مرحل Tor عند وجود العنوان في دليل Tor المحمل.
+    /// Tor relay fingerprint and nickname when the address is in the loaded Tor directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tor_relay: Option<TorRelayMatch>,
 }
 
 /// النتيجة النهائية لتحليل الشبكة.
@@ -101,36 +130,27 @@ pub struct NetworkAnalysisResult {
     pub connection_type: ConnectionType,
     pub geo_location: Option<GeoLocation>,
     pub concealment: ConcealmentReport,
+    /// رقم ASN والمؤسسة عند تحميل قواعد ASN/ISP/Anonymous-IP.
+    /// ASN and organization when the ASN/ISP/Anonymous-IP databases are loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_owner: Option<NetworkOwnership>,
+    /// المزود والمنطقة عند وقوع العنوان ضمن نطاق مركز بيانات منشور.
+    /// Provider and region when the address falls in a published datacenter range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hosting: Option<HostingMatch>,
     pub security_score: f32, // 0.0 (Untrusted) to 1.0 (Fully Trusted)
 }
 
-// ================================================================
-// قاعدة بيانات أدوات التخفي (بروكسي/VPN)
-// Concealment Tools Database (Proxy/VPN)
-// ================================================================
-#[derive(Default)]
-pub struct ProxyDatabase {
-    vpn_ips: HashSet<IpAddr>,
-    proxy_ips: HashSet<IpAddr>,
-    tor_nodes: HashSet<IpAddr>,
-}
-
-impl ProxyDatabase {
-    #[must_use]
-    pub fn is_vpn(&self, ip: &IpAddr) -> bool {
-        self.vpn_ips.contains(ip)
-    }
-    #[must_use]
-    pub fn is_proxy(&self, ip: &IpAddr) -> bool {
-        self.proxy_ips.contains(ip)
-    }
-    #[must_use]
-    pub fn is_tor(&self, ip: &IpAddr) -> bool {
-        self.tor_nodes.contains(ip)
-    }
-
-    // ملاحظة: في تطبيق حقيقي، ستكون هناك دالة لتحديث هذه القوائم من مصدر خارجي.
-    // Note: In a real application, a function would exist to update these lists from an external source.
+/// طريقة حماية عنوان IP في `encrypted_ip`.
+/// How the IP address is protected in `encrypted_ip`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IpProtectionMode {
+    /// تشفير قابل للفك بمفتاح ذي إصدار.
+    /// Reversible encryption under a versioned key.
+    Encrypt,
+    /// اسم مستعار حتمي (HMAC) غير قابل للعكس.
+    /// Deterministic, irreversible keyed pseudonym (HMAC).
+    Pseudonym,
 }
 
 // ================================================================
@@ -144,6 +164,11 @@ impl ProxyDatabase {
 pub trait NetworkInfoProvider: Send + Sync {
     async fn get_connection_type(&self) -> ConnectionType;
     async fn get_public_ip(&self) -> Option<IpAddr>;
+    /// تحليل سلسلة التمرير للطلب الحالي إن توفر.
+    /// Forwarding-chain analysis of the current request, when available.
+    async fn get_forwarding_analysis(&self) -> Option<ForwardingAnalysis> {
+        None
+    }
 }
 
 /// واجهة لمحلل الشبكة بالذكاء الاصطناعي.
@@ -160,7 +185,10 @@ pub trait AiNetworkAnalyzer: Send + Sync {
 pub struct NetworkAnalyzer {
     encryption_key: SecureBytes,
     proxy_db: Arc<RwLock<ProxyDatabase>>,
-    geo_reader: Arc<crate::core::geo_resolver::GeoReaderEnum>,
+    geo_db: Arc<GeoDbManager>,
+    hosting_ranges: Option<Arc<RwLock<HostingRangeClassifier>>>,
+    keyring: Option<(Arc<IpKeyring>, IpProtectionMode)>,
+    tor_directory: Option<(Arc<RwLock<TorDirectory>>, u16)>,
     ai_analyzer: Arc<dyn AiNetworkAnalyzer>,
 }
 
@@ -176,11 +204,57 @@ impl NetworkAnalyzer {
         Self {
             encryption_key,
             proxy_db,
-            geo_reader,
+            geo_db: Arc::new(GeoDbManager::from_city_reader(geo_reader)),
+            hosting_ranges: None,
+            keyring: None,
+            tor_directory: None,
             ai_analyzer,
         }
     }
 
+    /// استبدال قارئ City الثابت بمدير قواعد بيانات قابل لإعادة التحميل.
+    /// Replaces the fixed City reader with a hot-reloadable database manager.
+    #[must_use]
+    pub fn with_geo_databases(mut self, geo_db: Arc<GeoDbManager>) -> Self {
+        self.geo_db = geo_db;
+        self
+    }
+
+    /// تفعيل تصنيف نطاقات مراكز البيانات ومزودي الاستضافة.
+    /// Enables datacenter and hosting-provider range classification.
+    #[must_use]
+    pub fn with_hosting_ranges(mut self, ranges: Arc<RwLock<HostingRangeClassifier>>) -> Self {
+        self.hosting_ranges = Some(ranges);
+        self
+    }
+
+    /// حماية العناوين بحلقة مفاتيح ذات إصدارات بدل المفتاح الثابت للعملية.
+    /// Protects addresses with a versioned keyring instead of the per-process key.
+    #[must_use]
+    pub fn with_keyring(mut self, keyring: Arc<IpKeyring>, mode: IpProtectionMode) -> Self {
+        self.keyring = Some((keyring, mode));
+        self
+    }
+
+    /// تفعيل دليل Tor مع منفذ الخدمة المحمية لتقييم سياسات الخروج.
+    /// Enables the Tor directory, with the protected service port used to evaluate exit policies.
+    #[must_use]
+    pub fn with_tor_directory(
+        mut self,
+        directory: Arc<RwLock<TorDirectory>>,
+        service_port: u16,
+    ) -> Self {
+        self.tor_directory = Some((directory, service_port));
+        self
+    }
+
+    /// حلقة المفاتيح المستخدمة (لواجهة فك التشفير الإدارية).
+    /// The keyring in use (for the admin decryption API).
+    #[must_use]
+    pub fn keyring(&self) -> Option<&Arc<IpKeyring>> {
+        self.keyring.as_ref().map(|(keyring, _)| keyring)
+    }
+
     /// تنفيذ تحليل كامل للشبكة.
     /// Executes a full network analysis.
     ///
@@ -196,13 +270,51 @@ impl NetworkAnalyzer {
 
         // 1. كشف أدوات التخفي
         // 1. Detect concealment tools
-        let db_guard = self.proxy_db.read().await;
-        let concealment = ConcealmentReport {
-            is_vpn: db_guard.is_vpn(&ip),
-            is_proxy: db_guard.is_proxy(&ip),
-            is_tor: db_guard.is_tor(&ip),
+        let listed = self.proxy_db.read().await.classify(&ip);
+        let mut concealment = ConcealmentReport {
+            is_vpn: listed.is_vpn,
+            is_proxy: listed.is_proxy,
+            is_tor: listed.is_tor,
+            ..ConcealmentReport::default()
+        };
+
+        // دمج أعلام قاعدة Anonymous-IP مع قائمة البروكسي المحلية
+        // Merge Anonymous-IP database flags with the local proxy lists
+        let network_owner = self.geo_db.lookup_network(ip);
+        if let Some(owner) = &network_owner {
+            concealment.is_vpn |= owner.is_anonymous_vpn;
+            concealment.is_proxy |= owner.is_public_proxy || owner.is_residential_proxy;
+            concealment.is_tor |= owner.is_tor_exit_node;
+            concealment.is_hosting |= owner.is_hosting_provider;
+        }
+
+        // مطابقة نطاقات مزودي الاستضافة السحابية المنشورة
+        // Match published cloud hosting-provider ranges
+        let hosting = match &self.hosting_ranges {
+            Some(ranges) => ranges.read().await.classify(&ip),
+            None => None,
         };
-        drop(db_guard); // تحرير القفل مبكرًا / Release lock early
+        concealment.is_hosting |= hosting.is_some();
+
+        // مرحلات Tor: فقط المخارج القادرة على الوصول لمنفذ الخدمة تُعد اتصال Tor
+        // Tor relays: only exits that can reach the service port count as Tor traffic
+        if let Some((directory, service_port)) = &self.tor_directory {
+            let relay = directory.read().await.lookup(&ip, *service_port);
+            if let Some(relay) = &relay {
+                concealment.is_tor |= relay.can_reach_service;
+            }
+            concealment.tor_relay = relay;
+        }
+
+        // مؤشرات سلسلة التمرير (تزوير/تناقض/بروكسي مفتوح)
+        // Forwarding-chain indicators (spoofing/inconsistency/open proxy)
+        if let Some(forwarding) = provider.get_forwarding_analysis().await {
+            concealment.is_proxy |= forwarding.has_open_proxy_indicators();
+            concealment.spoofed_forwarding_chain = forwarding.is_spoofed();
+            concealment.inconsistent_forwarding_chain = forwarding.is_inconsistent();
+            concealment.forwarding_indicators = forwarding.indicators;
+        }
+        let connection_type = provider.get_connection_type().await;
 
         // 2. تحديد الموقع الجغرافي
         // 2. Geolocate the IP
@@ -210,7 +322,8 @@ impl NetworkAnalyzer {
 
         // 3. حساب درجة الأمان الأولية (مبدأ المنع)
         // 3. Calculate initial security score (Prevention principle)
-        let security_score = Self::calculate_base_score(&concealment, geo_location.as_ref());
+        let security_score =
+            Self::calculate_base_score(&concealment, geo_location.as_ref(), &connection_type);
 
         // 4. تشفير الـ IP
         // 4. Encrypt the IP
@@ -220,9 +333,11 @@ impl NetworkAnalyzer {
         // 5. Build result and apply AI analysis
         let mut result = NetworkAnalysisResult {
             encrypted_ip,
-            connection_type: provider.get_connection_type().await,
+            connection_type,
             geo_location,
             concealment,
+            network_owner,
+            hosting,
             security_score,
         };
 
@@ -233,19 +348,27 @@ impl NetworkAnalyzer {
 
     /// يحدد الموقع الجغرافي للـ IP باستخدام قاعدة بيانات `MaxMind`.
     /// Geolocates an IP using the `MaxMind` database.
-    fn geolocate_ip(&self, ip: &IpAddr) -> Option<GeoLocation> {
-        let city_opt = self.geo_reader.lookup_city(*ip).ok()?;
+    #[must_use]
+    pub fn geolocate_ip(&self, ip: &IpAddr) -> Option<GeoLocation> {
+        let reader = self.geo_db.city_reader();
+        let city_opt = reader.lookup_city(*ip).ok()?;
         let city_data = city_opt?;
         Some(GeoLocation {
             country_iso: city_data.country.iso_code?.to_string(),
             city: city_data.city.names.english?.to_string(),
             accuracy_radius_km: city_data.location.accuracy_radius?,
+            latitude: city_data.location.latitude,
+            longitude: city_data.location.longitude,
         })
     }
 
     /// يحسب درجة الأمان بناءً على وجود أدوات تخفي.
     /// Calculates the security score based on the presence of concealment tools.
-    fn calculate_base_score(concealment: &ConcealmentReport, geo: Option<&GeoLocation>) -> f32 {
+    fn calculate_base_score(
+        concealment: &ConcealmentReport,
+        geo: Option<&GeoLocation>,
+        connection_type: &ConnectionType,
+    ) -> f32 {
         let mut score: f32 = 1.0;
         if concealment.is_vpn {
             score -= 0.4;
@@ -256,6 +379,20 @@ impl NetworkAnalyzer {
         if concealment.is_tor {
             score -= 0.6;
         }
+        if concealment.spoofed_forwarding_chain || concealment.inconsistent_forwarding_chain {
+            score -= 0.2;
+        }
+        if concealment.is_hosting {
+            score -= 0.2;
+            // عميل يدّعي اتصالاً منزلياً/خلوياً بينما يأتي من مركز بيانات
+            // Client claims a residential/mobile link but comes from a datacenter
+            if matches!(
+                connection_type,
+                ConnectionType::WiFi | ConnectionType::Cellular
+            ) {
+                score -= 0.3;
+            }
+        }
 
         // تعتبر الاتصالات من مواقع غير معروفة أكثر خطورة
         // Connections from unknown locations are considered riskier
@@ -266,9 +403,16 @@ impl NetworkAnalyzer {
         score.max(0.0)
     }
 
-    /// يقوم بتشفير عنوان الـ IP باستخدام AES-256-GCM.
-    /// Encrypts an IP address using AES-256-GCM.
+    /// يحمي عنوان الـ IP بحلقة المفاتيح إن وجدت، وإلا بمفتاح AES-256-GCM الخاص بالعملية.
+    /// Protects an IP with the keyring when set, otherwise with the per-process AES-256-GCM key.
     fn encrypt_ip(&self, ip: &IpAddr) -> Result<String, NetworkError> {
+        if let Some((keyring, mode)) = &self.keyring {
+            let protected = match mode {
+                IpProtectionMode::Encrypt => keyring.encrypt_ip(ip),
+                IpProtectionMode::Pseudonym => keyring.pseudonymize_ip(ip),
+            };
+            return protected.map_err(|e| NetworkError::CryptoError(e.to_string()));
+        }
         let key_slice = self.encryption_key.expose();
         let key = Key::<Aes256Gcm>::from_slice(key_slice);
         let cipher = Aes256Gcm::new(key);
@@ -333,6 +477,8 @@ impl NetworkInfoProvider for MockNetworkProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::proxy_db::ProxyCategory;
+    use crate::utils::ip_prefix::IpPrefix;
     use maxminddb::Reader;
     use std::fs;
     use std::str::FromStr;
@@ -341,8 +487,16 @@ mod tests {
     fn setup_test_engine() -> NetworkAnalyzer {
         // 1. Setup mock proxy database
         let mut db = ProxyDatabase::default();
-        db.vpn_ips.insert(IpAddr::from_str("1.1.1.1").unwrap());
-        db.tor_nodes.insert(IpAddr::from_str("2.2.2.2").unwrap());
+        db.insert(
+            IpPrefix::host(IpAddr::from_str("1.1.1.1").unwrap()),
+            ProxyCategory::Vpn,
+            "test",
+        );
+        db.insert(
+            IpPrefix::host(IpAddr::from_str("2.2.2.2").unwrap()),
+            ProxyCategory::Tor,
+            "test",
+        );
         let proxy_db = Arc::new(RwLock::new(db));
 
         // 2. Load geo database: try file, fallback to hex
@@ -412,6 +566,38 @@ mod tests {
         assert!((result.security_score - 0.4).abs() < 0.15);
     }
 
+    #[tokio::test]
+    async fn test_tor_exit_policy_against_service_port() {
+        use crate::core::tor_directory::TorDocumentFormat;
+        let mut directory = TorDirectory::new();
+        directory
+            .load_str(
+                TorDocumentFormat::Consensus,
+                "r exitOne AAECAwQFBgcICQoLDA0ODxAREhM 2025-01-01 00:00:00 9.9.9.9 9001 0\n\
+                 s Exit Running Valid\n\
+                 p accept 443\n",
+            )
+            .unwrap();
+        let directory = Arc::new(RwLock::new(directory));
+        let provider = MockNetworkProvider {
+            ip: "9.9.9.9".parse().unwrap(),
+            conn_type: ConnectionType::Ethernet,
+        };
+
+        let engine = setup_test_engine().with_tor_directory(Arc::clone(&directory), 443);
+        let result = engine.analyze(&provider).await.unwrap();
+        assert!(result.concealment.is_tor);
+        let relay = result.concealment.tor_relay.unwrap();
+        assert_eq!(relay.nickname.as_deref(), Some("exitOne"));
+
+        // مخرج لا تسمح سياسته بمنفذ الخدمة لا يُعد اتصال Tor
+        // An exit whose policy rejects the service port is not counted as Tor traffic
+        let engine = setup_test_engine().with_tor_directory(directory, 8080);
+        let result = engine.analyze(&provider).await.unwrap();
+        assert!(!result.concealment.is_tor);
+        assert!(!result.concealment.tor_relay.unwrap().can_reach_service);
+    }
+
     #[tokio::test]
     async fn test_ip_encryption_works() {
         let engine = setup_test_engine();
@@ -427,4 +613,39 @@ mod tests {
         // And it should be a valid hex string
         assert!(hex::decode(&result.encrypted_ip).is_ok());
     }
+
+    #[tokio::test]
+    async fn test_datacenter_range_lowers_residential_claim() {
+        let mut ranges = HostingRangeClassifier::new();
+        ranges
+            .load_str(
+                crate::core::hosting_ranges::HostingRangeFormat::CidrList,
+                "aws",
+                "3.5.140.0/22 ap-northeast-2",
+            )
+            .unwrap();
+        let engine = setup_test_engine().with_hosting_ranges(Arc::new(RwLock::new(ranges)));
+
+        let wifi = engine
+            .analyze(&MockNetworkProvider {
+                ip: "3.5.141.7".parse().unwrap(),
+                conn_type: ConnectionType::WiFi,
+            })
+            .await
+            .unwrap();
+        let hosting = wifi.hosting.as_ref().unwrap();
+        assert_eq!(hosting.provider, "aws");
+        assert_eq!(hosting.region.as_deref(), Some("ap-northeast-2"));
+        assert!(wifi.concealment.is_hosting);
+
+        let ethernet = engine
+            .analyze(&MockNetworkProvider {
+                ip: "3.5.141.7".parse().unwrap(),
+                conn_type: ConnectionType::Ethernet,
+            })
+            .await
+            .unwrap();
+        assert!(wifi.security_score < ethernet.security_score);
+        assert!(ethernet.security_score < 1.0);
+    }
 }
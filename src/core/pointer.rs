@@ -0,0 +1,659 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: pointer.rs
+    المسار:    src/core/pointer.rs
+    دور الملف:
+    ديناميكيات المؤشر واللمس. تستخرج من تدفقات أحداث السحب وحركة الفأرة ميزات
+    السرعة والانحناء والضغط وتوزيع التوقفات والاستقامة، وتكشف الحركة المبرمجة
+    (مسار مستقيم تماماً، سرعة ثابتة، ضغط ثابت)، وتحتفظ بقالب لكل مستخدم ونوع جهاز.
+    `PointerDynamicsModel` يضيف الخطر إلى درجة `BehaviorEngine` و
+    `SyntheticPointerDetector` يبلغ عن الحركة المبرمجة كشذوذ.
+    --------------------------------------------------------------
+    File Name: pointer.rs
+    Path:     src/core/pointer.rs
+    File Role:
+    Pointer and touch dynamics. Extracts velocity, curvature, pressure, pause distribution
+    and straightness from swipe and mouse event streams, detects scripted movement
+    (perfectly straight paths, constant velocity, constant pressure) and keeps a template
+    per user and device kind. `PointerDynamicsModel` adds the risk to the `BehaviorEngine`
+    score and `SyntheticPointerDetector` reports scripted movement as an anomaly.
+******************************************************************************************/
+
+use crate::core::behavior_bio::{
+    Anomaly, AnomalyDetector, BehaviorError, BehaviorInput, BehavioralModel, RiskLevel,
+};
+use crate::core::biometric_template::{
+    FeatureTemplate, TemplateBackend, TemplateError, TemplateScore, TemplateSettings, TemplateStore,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// أقل عدد أحداث في التدفق.
+/// Minimum events in a stream.
+pub const MIN_POINTER_EVENTS: usize = 5;
+/// أقل عدد مقاطع حركة قبل الحكم على الحركة بأنها مبرمجة.
+/// Minimum moving segments before movement is judged scripted.
+const MIN_SYNTHETIC_SEGMENTS: usize = 6;
+/// فجوة زمنية (مللي ثانية) تعد توقفاً لا حركة.
+/// Gap (ms) counted as a pause rather than movement.
+const PAUSE_MS: f64 = 100.0;
+const LINEAR_STRAIGHTNESS: f64 = 0.9995;
+const LINEAR_CURVATURE: f64 = 0.001;
+const CONSTANT_VELOCITY_CV: f64 = 0.02;
+const CONSTANT_PRESSURE_STD: f64 = 1e-6;
+/// خطر كل إشارة حركة مبرمجة.
+/// Risk of each scripted-movement signal.
+const SYNTHETIC_SIGNAL_RISK: f32 = 0.5;
+
+/// الميزات بمقاييس مختلفة (بكسل/مللي ثانية، راديان، مللي ثانية)، لذا الحد الأدنى
+/// للانحراف نسبة من المتوسط.
+/// Features have different scales (px/ms, radians, ms), so the deviation floor is a
+/// fraction of the mean.
+const SETTINGS: TemplateSettings = TemplateSettings {
+    min_enrollment: 5,
+    max_samples: 30,
+    absolute_floor: 1e-3,
+    relative_floor: 0.1,
+    genuine_distance: 1.5,
+    impostor_distance: 3.5,
+    adapt_below_risk: 0.3,
+    max_profiles: 100_000,
+    idle_days: 180,
+};
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum PointerError {
+    #[error("Pointer streams need at least {MIN_POINTER_EVENTS} events, got {0}")]
+    TooShort(usize),
+
+    #[error("Pointer events must have finite coordinates and non-decreasing timestamps")]
+    InvalidEvents,
+
+    #[error("Pointer stream has no movement")]
+    NoMovement,
+
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// نوع جهاز الإدخال.
+/// Input device kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointerDevice {
+    Mouse,
+    Touch,
+    Pen,
+}
+
+/// حدث مؤشر واحد: الموضع (بكسل) والزمن (مللي ثانية) والضغط (0..1) إن توفر.
+/// A single pointer event: position (px), time (ms) and pressure (0..1) when available.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PointerEvent {
+    pub x: f64,
+    pub y: f64,
+    pub t_ms: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<f64>,
+}
+
+/// تدفق حركة واحد (سحبة أو مسار فأرة).
+/// A single movement stream (one swipe or mouse trajectory).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointerStream {
+    pub device: PointerDevice,
+    pub events: Vec<PointerEvent>,
+}
+
+/// إشارة حركة مبرمجة.
+/// A scripted-movement signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyntheticSignal {
+    /// مسار مستقيم تماماً بلا أي انحناء.
+    /// A perfectly straight path with no curvature at all.
+    LinearPath,
+    /// سرعة ثابتة دون تسارع أو تباطؤ.
+    /// Constant velocity with no acceleration or deceleration.
+    ConstantVelocity,
+    /// ضغط لمس ثابت تماماً.
+    /// Perfectly constant touch pressure.
+    ConstantPressure,
+}
+
+impl SyntheticSignal {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::LinearPath => "linear_path",
+            Self::ConstantVelocity => "constant_velocity",
+            Self::ConstantPressure => "constant_pressure",
+        }
+    }
+}
+
+/// ميزات تدفق حركة.
+/// Features of a movement stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointerFeatures {
+    /// متوسط السرعة أثناء الحركة (بكسل/مللي ثانية).
+    /// Mean velocity while moving (px/ms).
+    pub mean_velocity: f64,
+    /// معامل تباين السرعة (الانحراف/المتوسط).
+    /// Velocity coefficient of variation (std/mean).
+    pub velocity_cv: f64,
+    /// متوسط تغير الاتجاه بين المقاطع (راديان).
+    /// Mean heading change between segments (radians).
+    pub mean_curvature: f64,
+    /// المسافة المباشرة مقسومة على طول المسار (1.0 = مستقيم).
+    /// Chord length divided by path length (1.0 = straight).
+    pub straightness: f64,
+    /// نسبة زمن التوقف من مدة التدفق.
+    /// Share of the stream duration spent paused.
+    pub pause_ratio: f64,
+    /// متوسط مدة التوقف (مللي ثانية).
+    /// Mean pause length (ms).
+    pub mean_pause_ms: f64,
+    pub mean_pressure: Option<f64>,
+    pub pressure_std: Option<f64>,
+    pub moving_segments: usize,
+}
+
+/// المتوسط والانحراف المعياري.
+/// Mean and standard deviation.
+fn mean_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    (mean, std)
+}
+
+impl PointerStream {
+    /// يستخرج ميزات التدفق.
+    /// Extracts the stream's features.
+    ///
+    /// # Errors
+    /// يعيد `PointerError` إذا كان التدفق قصيراً أو غير صالح أو بلا حركة.
+    /// Returns `PointerError` if the stream is short, invalid or has no movement.
+    pub fn features(&self) -> Result<PointerFeatures, PointerError> {
+        let events = &self.events;
+        if events.len() < MIN_POINTER_EVENTS {
+            return Err(PointerError::TooShort(events.len()));
+        }
+        if events
+            .iter()
+            .any(|e| !(e.x.is_finite() && e.y.is_finite() && e.t_ms.is_finite()))
+            || events.windows(2).any(|w| w[1].t_ms < w[0].t_ms)
+        {
+            return Err(PointerError::InvalidEvents);
+        }
+
+        let mut path_length = 0.0;
+        let mut velocities = Vec::new();
+        let mut headings = Vec::new();
+        let mut pauses = Vec::new();
+        for w in events.windows(2) {
+            let (dx, dy, dt) = (w[1].x - w[0].x, w[1].y - w[0].y, w[1].t_ms - w[0].t_ms);
+            let distance = dx.hypot(dy);
+            path_length += distance;
+            if dt >= PAUSE_MS {
+                pauses.push(dt);
+            } else if dt > 0.0 && distance > 0.0 {
+                velocities.push(distance / dt);
+                headings.push(dy.atan2(dx));
+            }
+        }
+        if path_length <= 0.0 || velocities.is_empty() {
+            return Err(PointerError::NoMovement);
+        }
+
+        let turns: Vec<f64> = headings
+            .windows(2)
+            .map(|h| {
+                let turn = (h[1] - h[0]).rem_euclid(2.0 * PI);
+                turn.min(2.0 * PI - turn)
+            })
+            .collect();
+        let (mean_velocity, velocity_std) = mean_std(&velocities);
+        let (first, last) = (events[0], events[events.len() - 1]);
+        let duration = last.t_ms - first.t_ms;
+        let pressures: Option<Vec<f64>> = events.iter().map(|e| e.pressure).collect();
+        let pressure = pressures.as_deref().map(mean_std);
+
+        Ok(PointerFeatures {
+            mean_velocity,
+            velocity_cv: velocity_std / mean_velocity,
+            mean_curvature: mean_std(&turns).0,
+            straightness: ((last.x - first.x).hypot(last.y - first.y) / path_length).min(1.0),
+            pause_ratio: if duration > 0.0 {
+                pauses.iter().sum::<f64>() / duration
+            } else {
+                0.0
+            },
+            mean_pause_ms: mean_std(&pauses).0,
+            mean_pressure: pressure.map(|(mean, _)| mean),
+            pressure_std: pressure.map(|(_, std)| std),
+            moving_segments: velocities.len(),
+        })
+    }
+}
+
+impl PointerFeatures {
+    /// إشارات الحركة المبرمجة في هذا التدفق.
+    /// Scripted-movement signals in this stream.
+    #[must_use]
+    pub fn synthetic_signals(&self, device: PointerDevice) -> Vec<SyntheticSignal> {
+        let mut signals = Vec::new();
+        if self.moving_segments < MIN_SYNTHETIC_SEGMENTS {
+            return signals;
+        }
+        if self.straightness >= LINEAR_STRAIGHTNESS && self.mean_curvature <= LINEAR_CURVATURE {
+            signals.push(SyntheticSignal::LinearPath);
+        }
+        if self.velocity_cv <= CONSTANT_VELOCITY_CV {
+            signals.push(SyntheticSignal::ConstantVelocity);
+        }
+        if device != PointerDevice::Mouse
+            && self
+                .pressure_std
+                .is_some_and(|std| std <= CONSTANT_PRESSURE_STD)
+        {
+            signals.push(SyntheticSignal::ConstantPressure);
+        }
+        signals
+    }
+
+    /// متجه الميزات للقالب (الضغط 0 إذا لم يتوفر).
+    /// Feature vector for the template (pressure 0 when unavailable).
+    #[must_use]
+    pub fn vector(&self) -> Vec<f64> {
+        vec![
+            self.mean_velocity,
+            self.velocity_cv,
+            self.mean_curvature,
+            self.straightness,
+            self.pause_ratio,
+            self.mean_pause_ms,
+            self.mean_pressure.unwrap_or(0.0),
+        ]
+    }
+}
+
+/// نتيجة تحليل تدفق: مقارنة القالب وإشارات الحركة المبرمجة والخطر المجمع.
+/// Result of analyzing a stream: template comparison, scripted signals and combined risk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointerScore {
+    pub profile: TemplateScore,
+    pub synthetic: Vec<SyntheticSignal>,
+    pub risk: f32,
+}
+
+/// قوالب المؤشر لكل مستخدم، مفتاحها (الكيان، نوع الجهاز).
+/// Per-user pointer templates, keyed by (entity, device kind).
+pub struct PointerProfiles {
+    store: TemplateStore<(String, PointerDevice)>,
+}
+
+impl Default for PointerProfiles {
+    fn default() -> Self {
+        Self {
+            store: TemplateStore::new(SETTINGS),
+        }
+    }
+}
+
+impl PointerProfiles {
+    /// يحفظ القوالب في قاعدة البيانات (انظر `TemplateStore::with_backend`).
+    /// Persists the templates to the database (see `TemplateStore::with_backend`).
+    #[must_use]
+    pub fn with_backend(mut self, backend: Arc<dyn TemplateBackend>) -> Self {
+        self.store = self.store.with_backend("pointer", backend);
+        self
+    }
+
+    /// يحلل التدفق. الحركة المبرمجة لا تُضاف إلى القالب.
+    /// Analyzes the stream. Scripted movement is never folded into the template.
+    ///
+    /// # Errors
+    /// يعيد `PointerError` إذا كان التدفق غير صالح.
+    /// Returns `PointerError` if the stream is invalid.
+    pub async fn observe(
+        &self,
+        entity_id: &str,
+        stream: &PointerStream,
+    ) -> Result<PointerScore, PointerError> {
+        let features = stream.features()?;
+        let synthetic = features.synthetic_signals(stream.device);
+        let key = (entity_id.to_string(), stream.device);
+        let profile = if synthetic.is_empty() {
+            self.store.observe(key, features.vector()).await?
+        } else {
+            let template = self.store.template(&key).await;
+            let distance = template
+                .map(|t| t.distance(&features.vector(), self.store.settings()))
+                .transpose()?;
+            TemplateScore {
+                distance,
+                risk: distance.map_or(0.0, |d| self.store.settings().risk(d)),
+                enrolled: distance.is_some(),
+            }
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let synthetic_risk = (SYNTHETIC_SIGNAL_RISK * synthetic.len() as f32).min(1.0);
+        Ok(PointerScore {
+            risk: profile.risk.max(synthetic_risk),
+            profile,
+            synthetic,
+        })
+    }
+
+    /// قالب الكيان الحالي لنوع الجهاز إن وُجد.
+    /// The entity's current template for the device kind, if any.
+    pub async fn template(
+        &self,
+        entity_id: &str,
+        device: PointerDevice,
+    ) -> Option<FeatureTemplate> {
+        self.store.template(&(entity_id.to_string(), device)).await
+    }
+}
+
+// ================================================================
+// النموذج السلوكي وكاشف الشذوذ
+// Behavioral Model and Anomaly Detector
+// ================================================================
+
+/// يغلف نموذجاً سلوكياً ويضيف أعلى خطر مؤشر (بوزن) عند وجود `pointer_streams`.
+/// Wraps a behavioral model and adds the highest weighted pointer risk when the input
+/// has `pointer_streams`.
+pub struct PointerDynamicsModel {
+    inner: Arc<dyn BehavioralModel>,
+    profiles: Arc<PointerProfiles>,
+    weight: f32,
+}
+
+impl PointerDynamicsModel {
+    /// الوزن الافتراضي لخطر المؤشر.
+    /// Default weight of the pointer risk.
+    pub const DEFAULT_WEIGHT: f32 = 0.5;
+
+    #[must_use]
+    pub fn new(inner: Arc<dyn BehavioralModel>) -> Self {
+        Self {
+            inner,
+            profiles: Arc::new(PointerProfiles::default()),
+            weight: Self::DEFAULT_WEIGHT,
+        }
+    }
+
+    /// مشاركة القوالب مع مكونات أخرى.
+    /// Share templates with other components.
+    #[must_use]
+    pub fn with_profiles(mut self, profiles: Arc<PointerProfiles>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+#[async_trait]
+impl BehavioralModel for PointerDynamicsModel {
+    async fn analyze(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<f32, BehaviorError> {
+        let score = self.inner.analyze(current, history).await?;
+        let mut pointer_risk: f32 = 0.0;
+        for stream in &current.pointer_streams {
+            let result = self
+                .profiles
+                .observe(&current.entity_id, stream)
+                .await
+                .map_err(|e| BehaviorError::InvalidInput(e.to_string()))?;
+            pointer_risk = pointer_risk.max(result.risk);
+        }
+        Ok(self.weight.mul_add(pointer_risk, score).min(1.0))
+    }
+
+    fn has_signal(&self, current: &BehaviorInput) -> bool {
+        !current.pointer_streams.is_empty() || self.inner.has_signal(current)
+    }
+}
+
+/// يغلف كاشف شذوذ ويبلغ عن الحركة المبرمجة إذا لم يجد الكاشف الداخلي شيئاً.
+/// Wraps an anomaly detector and reports scripted movement when the inner detector
+/// finds nothing.
+pub struct SyntheticPointerDetector {
+    inner: Arc<dyn AnomalyDetector>,
+}
+
+impl SyntheticPointerDetector {
+    #[must_use]
+    pub fn new(inner: Arc<dyn AnomalyDetector>) -> Self {
+        Self { inner }
+    }
+
+    fn scripted_movement(current: &BehaviorInput) -> Option<String> {
+        let mut signals: Vec<&str> = Vec::new();
+        for stream in &current.pointer_streams {
+            // التدفقات غير الصالحة يرفضها النموذج برسالة أوضح
+            // Invalid streams are rejected by the model with a clearer message
+            if let Ok(features) = stream.features() {
+                for signal in features.synthetic_signals(stream.device) {
+                    if !signals.contains(&signal.as_str()) {
+                        signals.push(signal.as_str());
+                    }
+                }
+            }
+        }
+        (!signals.is_empty()).then(|| {
+            format!(
+                "Anomaly detected: Scripted pointer movement ({}).",
+                signals.join(", ")
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl AnomalyDetector for SyntheticPointerDetector {
+    async fn detect(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Option<String>, BehaviorError> {
+        if let Some(anomaly) = self.inner.detect(current, history).await? {
+            return Ok(Some(anomaly));
+        }
+        Ok(Self::scripted_movement(current))
+    }
+
+    /// خطر الحركة المبرمجة يضيفه `PointerDynamicsModel`، لذا الوزن هنا صفري.
+    /// Scripted-movement risk is added by `PointerDynamicsModel`, so the weight here is zero.
+    async fn detect_all(
+        &self,
+        current: &BehaviorInput,
+        history: &VecDeque<BehaviorInput>,
+    ) -> Result<Vec<Anomaly>, BehaviorError> {
+        let mut anomalies = self.inner.detect_all(current, history).await?;
+        if let Some(message) = Self::scripted_movement(current) {
+            anomalies.push(Anomaly {
+                code: "scripted_pointer".to_string(),
+                severity: RiskLevel::High,
+                message,
+                weight: 0.0,
+            });
+        }
+        Ok(anomalies)
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::behavior_bio::{
+        BehaviorEngine, DefaultAnomalyDetector, DefaultBehavioralModel, NetworkInfo,
+    };
+    use chrono::{TimeZone, Utc};
+
+    /// سحبة بشرية: قوس مع تسارع ثم تباطؤ وضغط متغير وتوقف قصير.
+    /// A human swipe: an arc that accelerates then decelerates, varying pressure and a
+    /// short pause.
+    fn human_swipe(scale: f64, bend: f64) -> PointerStream {
+        let mut t = 0.0;
+        let events = (0..20)
+            .map(|i| {
+                let s = f64::from(i) / 19.0;
+                t += if i == 10 {
+                    160.0
+                } else {
+                    8.0 + 6.0 * (s - 0.5).abs()
+                };
+                PointerEvent {
+                    x: scale * 300.0 * (s * s * (3.0 - 2.0 * s)),
+                    y: bend * (s * PI).sin() * 40.0,
+                    t_ms: t,
+                    pressure: Some(0.4 + 0.2 * (s * 5.0).sin().abs()),
+                }
+            })
+            .collect();
+        PointerStream {
+            device: PointerDevice::Touch,
+            events,
+        }
+    }
+
+    fn scripted_swipe() -> PointerStream {
+        PointerStream {
+            device: PointerDevice::Touch,
+            events: (0..20)
+                .map(|i| PointerEvent {
+                    x: f64::from(i) * 15.0,
+                    y: 100.0,
+                    t_ms: f64::from(i) * 10.0,
+                    pressure: Some(0.5),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_scripted_movement_is_detected() {
+        let human = human_swipe(1.0, 1.0).features().unwrap();
+        assert!(
+            human.synthetic_signals(PointerDevice::Touch).is_empty(),
+            "{human:?}"
+        );
+        assert!(human.straightness < 1.0 && human.pause_ratio > 0.0);
+
+        let scripted = scripted_swipe().features().unwrap();
+        assert_eq!(
+            scripted.synthetic_signals(PointerDevice::Touch),
+            vec![
+                SyntheticSignal::LinearPath,
+                SyntheticSignal::ConstantVelocity,
+                SyntheticSignal::ConstantPressure,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_profile_separates_owner_from_other_user() {
+        let profiles = PointerProfiles::default();
+        for i in 0..SETTINGS.min_enrollment {
+            let jitter = f64::from(u32::try_from(i).unwrap()) * 0.02;
+            profiles
+                .observe("u1", &human_swipe(1.0 + jitter, 1.0 - jitter))
+                .await
+                .unwrap();
+        }
+        assert!(profiles
+            .template("u1", PointerDevice::Touch)
+            .await
+            .is_some());
+        assert!(profiles
+            .template("u1", PointerDevice::Mouse)
+            .await
+            .is_none());
+
+        let owner = profiles
+            .observe("u1", &human_swipe(1.03, 0.97))
+            .await
+            .unwrap();
+        assert!(owner.profile.enrolled && owner.risk < 0.3, "{owner:?}");
+        let other = profiles
+            .observe("u1", &human_swipe(3.0, -2.5))
+            .await
+            .unwrap();
+        assert!(other.risk > 0.5 && other.synthetic.is_empty(), "{other:?}");
+    }
+
+    #[tokio::test]
+    async fn test_engine_reports_scripted_pointer_anomaly() {
+        let engine = BehaviorEngine::new(
+            Arc::new(PointerDynamicsModel::new(Arc::new(
+                DefaultBehavioralModel::default(),
+            ))),
+            Arc::new(SyntheticPointerDetector::new(Arc::new(
+                DefaultAnomalyDetector {
+                    max_speed_kmh: 1200.0,
+                },
+            ))),
+            10,
+        );
+        let input = BehaviorInput {
+            entity_id: "u1".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap(),
+            location: (24.7136, 46.6753),
+            network_info: NetworkInfo {
+                ip_address: "10.0.0.1".to_string(),
+                is_vpn: false,
+                connection_type: "WiFi".to_string(),
+            },
+            device_fingerprint: "fp".to_string(),
+            device_similarity: None,
+            keystrokes: None,
+            pointer_streams: vec![human_swipe(1.0, 1.0), scripted_swipe()],
+            timezone: None,
+        };
+        let result = engine.process(input).await.unwrap();
+        assert!(result.anomaly_detected);
+        assert!(
+            result.reasoning.contains("linear_path"),
+            "{}",
+            result.reasoning
+        );
+        assert!(result.risk_score >= 0.5, "{result:?}");
+    }
+}
@@ -0,0 +1,640 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: proxy_db.rs
+    المسار:    src/core/proxy_db.rs
+    دور الملف:
+    قاعدة بيانات أدوات التخفي (VPN/Proxy/Tor) المبنية على نطاقات CIDR.
+    تدعم تحميل قوائم خروج Tor وملفات FireHOL netset وقوائم CIDR النصية وملفات CSV
+    مع فئة لكل سطر، مع حفظ المصدر وتاريخ الانتهاء لكل مدخل،
+    وإعادة تحميل ذرية لا تحجب قراءات `NetworkAnalyzer::analyze`.
+    --------------------------------------------------------------
+    File Name: proxy_db.rs
+    Path:     src/core/proxy_db.rs
+    File Role:
+    CIDR-aware concealment-tool database (VPN/Proxy/Tor).
+    Loads Tor exit lists, FireHOL netsets, plain CIDR text and CSV files with a
+    per-line category, keeps the source and expiry of every entry, and swaps in
+    reloaded data atomically without blocking `NetworkAnalyzer::analyze` readers.
+******************************************************************************************/
+
+use crate::utils::ip_prefix::{IpPrefix, PrefixTable};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum ProxyDbError {
+    #[error("Failed to read proxy list '{0}': {1}")]
+    Io(String, String),
+    #[error("Unknown proxy list format or category: {0}")]
+    InvalidSpec(String),
+    #[error("Could not acquire lock on the proxy database")]
+    LockFailed,
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// فئة أداة التخفي.
+/// Concealment tool category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProxyCategory {
+    Vpn,
+    Proxy,
+    Tor,
+}
+
+impl FromStr for ProxyCategory {
+    type Err = ProxyDbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "vpn" => Ok(Self::Vpn),
+            "proxy" | "open_proxy" | "public_proxy" => Ok(Self::Proxy),
+            "tor" | "tor_exit" => Ok(Self::Tor),
+            other => Err(ProxyDbError::InvalidSpec(other.to_string())),
+        }
+    }
+}
+
+/// صيغ ملفات القوائم المدعومة.
+/// Supported list file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxyListFormat {
+    /// قائمة خروج Tor: إما `ExitAddress <ip> ...` (exit-addresses) أو عنوان لكل سطر.
+    /// Tor exit list: either `ExitAddress <ip> ...` (exit-addresses) or one IP per line.
+    TorExitList,
+    /// ملف FireHOL/ipset netset: نطاق لكل سطر مع تعليقات `#`.
+    /// FireHOL/ipset netset: one range per line with `#` comments.
+    Netset,
+    /// قائمة CIDR نصية (يسمح بتعليق في نهاية السطر).
+    /// Plain CIDR text (trailing comments allowed).
+    CidrList,
+    /// CSV: `cidr,category[,expires_at]` مع صف عناوين اختياري.
+    /// CSV: `cidr,category[,expires_at]` with an optional header row.
+    Csv,
+}
+
+impl FromStr for ProxyListFormat {
+    type Err = ProxyDbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tor" | "tor_exit" | "tor_exit_list" => Ok(Self::TorExitList),
+            "netset" | "firehol" | "ipset" => Ok(Self::Netset),
+            "cidr" | "cidr_list" => Ok(Self::CidrList),
+            "csv" => Ok(Self::Csv),
+            other => Err(ProxyDbError::InvalidSpec(other.to_string())),
+        }
+    }
+}
+
+/// مصدر قائمة قابل لإعادة التحميل.
+/// A reloadable list source.
+#[derive(Debug, Clone)]
+pub struct ProxyListSource {
+    pub name: String,
+    pub format: ProxyListFormat,
+    pub path: PathBuf,
+    /// الفئة الافتراضية (تتجاهلها ملفات CSV التي تحدد الفئة لكل سطر).
+    /// Default category (CSV files carrying a per-line category override it).
+    pub category: ProxyCategory,
+    /// مدة صلاحية المدخلات منذ التحميل (None = بلا انتهاء).
+    /// Entry lifetime from load time (None = never expires).
+    pub ttl: Option<Duration>,
+}
+
+impl ProxyListSource {
+    /// يحلل مواصفة بصيغة `format:category:path`.
+    /// Parses a spec in the form `format:category:path`.
+    ///
+    /// # Errors
+    /// Returns `ProxyDbError::InvalidSpec` if the spec is malformed.
+    pub fn parse_spec(spec: &str) -> Result<Self, ProxyDbError> {
+        let mut parts = spec.trim().splitn(3, ':');
+        let (Some(format), Some(category), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ProxyDbError::InvalidSpec(spec.to_string()));
+        };
+        let path = PathBuf::from(path.trim());
+        Ok(Self {
+            name: path
+                .file_name()
+                .map_or_else(|| spec.to_string(), |n| n.to_string_lossy().into_owned()),
+            format: format.parse()?,
+            category: category.parse()?,
+            path,
+            ttl: None,
+        })
+    }
+
+    /// مدة صلاحية مدخلات هذا المصدر منذ تحميلها.
+    /// Lifetime of this source's entries from load time.
+    #[must_use]
+    pub const fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+/// مدخل واحد في قاعدة البيانات.
+/// A single database entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyEntry {
+    pub category: ProxyCategory,
+    pub source: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ProxyEntry {
+    fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|exp| exp > now)
+    }
+}
+
+/// نتيجة مطابقة عنوان مع نطاق في القائمة.
+/// A match of an address against a listed range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyMatch {
+    pub prefix: String,
+    pub category: ProxyCategory,
+    pub source: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// فئات القائمة التي يقع فيها عنوان، من بحث واحد.
+/// The list categories an address falls in, from a single lookup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyClassification {
+    pub is_vpn: bool,
+    pub is_proxy: bool,
+    pub is_tor: bool,
+}
+
+/// ملخص عملية تحميل.
+/// Summary of a load operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyLoadReport {
+    pub loaded: usize,
+    pub skipped_lines: usize,
+}
+
+/// الجداول الثابتة التي تتم قراءتها؛ تُستبدل كاملة عند إعادة التحميل.
+/// Immutable tables that readers see; replaced wholesale on reload.
+#[derive(Clone, Default)]
+pub struct ProxyTables {
+    entries: PrefixTable<Vec<ProxyEntry>>,
+}
+
+impl ProxyTables {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// عدد النطاقات المسجلة.
+    /// Number of registered ranges.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// يضيف مدخلاً لنطاق (النطاق الواحد قد يحمل عدة فئات ومصادر).
+    /// Adds an entry for a range (one range may carry several categories and sources).
+    pub fn insert(&mut self, prefix: IpPrefix, entry: ProxyEntry) {
+        let mut existing = self.entries.insert(prefix, Vec::new()).unwrap_or_default();
+        existing.retain(|e| !(e.category == entry.category && e.source == entry.source));
+        existing.push(entry);
+        self.entries.insert(prefix, existing);
+    }
+
+    /// يحمّل محتوى قائمة بحسب الصيغة.
+    /// Loads list content according to its format.
+    pub fn load_str(
+        &mut self,
+        format: ProxyListFormat,
+        category: ProxyCategory,
+        source: &str,
+        expires_at: Option<DateTime<Utc>>,
+        content: &str,
+    ) -> ProxyLoadReport {
+        let mut report = ProxyLoadReport::default();
+        let mut first_line = true;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let is_first = std::mem::replace(&mut first_line, false);
+            let parsed = match format {
+                ProxyListFormat::TorExitList => parse_tor_line(line).map(|p| (p, category, None)),
+                ProxyListFormat::Netset | ProxyListFormat::CidrList => line
+                    .split_whitespace()
+                    .next()
+                    .and_then(|t| t.parse::<IpPrefix>().ok())
+                    .map(|p| (p, category, None)),
+                ProxyListFormat::Csv => parse_csv_line(line),
+            };
+            match parsed {
+                Some((prefix, category, line_expiry)) => {
+                    self.insert(
+                        prefix,
+                        ProxyEntry {
+                            category,
+                            source: source.to_string(),
+                            expires_at: line_expiry.or(expires_at),
+                        },
+                    );
+                    report.loaded += 1;
+                }
+                // صف عناوين CSV أو قيود Tor الوصفية لا تُعد أخطاء
+                // CSV header rows and Tor descriptor keywords are not errors
+                None if is_metadata_line(format, line, is_first) => {}
+                None => report.skipped_lines += 1,
+            }
+        }
+        report
+    }
+
+    /// كل المطابقات السارية لعنوان.
+    /// All live matches for an address.
+    #[must_use]
+    pub fn lookup_at(&self, ip: &IpAddr, now: DateTime<Utc>) -> Vec<ProxyMatch> {
+        self.entries
+            .all_matches(ip)
+            .into_iter()
+            .flat_map(|(prefix, entries)| {
+                entries
+                    .iter()
+                    .filter(move |e| e.is_live(now))
+                    .map(move |e| ProxyMatch {
+                        prefix: prefix.to_string(),
+                        category: e.category,
+                        source: e.source.clone(),
+                        expires_at: e.expires_at,
+                    })
+            })
+            .collect()
+    }
+
+    /// يحذف المدخلات المنتهية ويعيد عدد النطاقات المحذوفة.
+    /// Drops expired entries and returns how many ranges were removed.
+    pub fn purge_expired(&mut self, now: DateTime<Utc>) -> usize {
+        let before = self.entries.len();
+        let mut kept = PrefixTable::new();
+        for (prefix, entries) in self.entries.iter() {
+            let live: Vec<ProxyEntry> =
+                entries.iter().filter(|e| e.is_live(now)).cloned().collect();
+            if !live.is_empty() {
+                kept.insert(*prefix, live);
+            }
+        }
+        self.entries = kept;
+        before - self.entries.len()
+    }
+}
+
+fn parse_tor_line(line: &str) -> Option<IpPrefix> {
+    let token = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["ExitAddress", addr, ..] => *addr,
+        [addr] => *addr,
+        _ => return None,
+    };
+    token.parse::<IpAddr>().ok().map(IpPrefix::host)
+}
+
+fn parse_csv_line(line: &str) -> Option<(IpPrefix, ProxyCategory, Option<DateTime<Utc>>)> {
+    let mut cols = line.split(',').map(|c| c.trim().trim_matches('"'));
+    let prefix = cols.next()?.parse::<IpPrefix>().ok()?;
+    let category = cols.next()?.parse::<ProxyCategory>().ok()?;
+    let expires_at = match cols.next().filter(|c| !c.is_empty()) {
+        Some(raw) => Some(DateTime::parse_from_rfc3339(raw).ok()?.with_timezone(&Utc)),
+        None => None,
+    };
+    Some((prefix, category, expires_at))
+}
+
+fn is_metadata_line(format: ProxyListFormat, line: &str, is_first: bool) -> bool {
+    match format {
+        ProxyListFormat::TorExitList => {
+            let keyword = line.split_whitespace().next().unwrap_or("");
+            matches!(keyword, "ExitNode" | "Published" | "LastStatus")
+        }
+        ProxyListFormat::Csv => is_first,
+        ProxyListFormat::Netset | ProxyListFormat::CidrList => false,
+    }
+}
+
+// ================================================================
+// قاعدة بيانات أدوات التخفي (بروكسي/VPN)
+// Concealment Tools Database (Proxy/VPN)
+// ================================================================
+
+/// يحتفظ بلقطة من الجداول خلف `Arc`؛ القراء يأخذون نسخة من المؤشر
+/// وإعادة التحميل تبني جداول جديدة كاملة ثم تستبدلها بخطوة واحدة.
+/// Holds a snapshot of the tables behind an `Arc`; readers clone the pointer
+/// and a reload builds complete new tables before swapping them in one step.
+#[derive(Default)]
+pub struct ProxyDatabase {
+    tables: RwLock<Arc<ProxyTables>>,
+    sources: Vec<ProxyListSource>,
+}
+
+impl ProxyDatabase {
+    /// قاعدة بيانات مرتبطة بمصادر ملفات لإعادة التحميل.
+    /// A database bound to file sources for reloading.
+    #[must_use]
+    pub fn with_sources(sources: Vec<ProxyListSource>) -> Self {
+        Self {
+            tables: RwLock::default(),
+            sources,
+        }
+    }
+
+    #[must_use]
+    pub fn sources(&self) -> &[ProxyListSource] {
+        &self.sources
+    }
+
+    /// لقطة الجداول الحالية.
+    /// The current tables snapshot.
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<ProxyTables> {
+        self.tables.read().map_or_else(
+            |poisoned| Arc::clone(&poisoned.into_inner()),
+            |g| Arc::clone(&g),
+        )
+    }
+
+    /// يستبدل الجداول ذرياً.
+    /// Atomically replaces the tables.
+    ///
+    /// # Errors
+    /// Returns `ProxyDbError::LockFailed` if the internal lock is poisoned.
+    pub fn replace(&self, tables: ProxyTables) -> Result<(), ProxyDbError> {
+        let mut guard = self.tables.write().map_err(|_| ProxyDbError::LockFailed)?;
+        *guard = Arc::new(tables);
+        Ok(())
+    }
+
+    /// يحذف المدخلات المنتهية من الجداول الحالية ويعيد عدد النطاقات المحذوفة، حتى لا
+    /// تبقى في الذاكرة عندما تفشل إعادة التحميل.
+    /// Drops expired entries from the current tables and returns how many ranges were
+    /// removed, so they do not linger in memory while reloads keep failing.
+    ///
+    /// # Errors
+    /// Returns `ProxyDbError::LockFailed` if the internal lock is poisoned.
+    pub fn purge_expired(&self) -> Result<usize, ProxyDbError> {
+        let mut tables = (*self.snapshot()).clone();
+        let removed = tables.purge_expired(Utc::now());
+        if removed > 0 {
+            self.replace(tables)?;
+        }
+        Ok(removed)
+    }
+
+    /// يضيف مدخلاً مباشرة (للتهيئة البرمجية والاختبارات).
+    /// Adds an entry directly (for programmatic seeding and tests).
+    pub fn insert(&mut self, prefix: IpPrefix, category: ProxyCategory, source: &str) {
+        let tables = self
+            .tables
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        Arc::make_mut(tables).insert(
+            prefix,
+            ProxyEntry {
+                category,
+                source: source.to_string(),
+                expires_at: None,
+            },
+        );
+    }
+
+    /// يعيد تحميل كل المصادر من الملفات ويستبدل الجداول عند النجاح فقط.
+    /// Reloads every source from disk and swaps the tables only on success.
+    ///
+    /// # Errors
+    /// Returns `ProxyDbError` if a source cannot be read; the old tables stay active.
+    pub fn reload(&self) -> Result<ProxyLoadReport, ProxyDbError> {
+        let (tables, report) = Self::load_sources(&self.sources)?;
+        self.replace(tables)?;
+        Ok(report)
+    }
+
+    /// يقرأ المصادر من الملفات إلى جداول جديدة دون لمس الجداول الحالية (إدخال/إخراج
+    /// حاجب، يُستدعى من `spawn_blocking` في السياق غير المتزامن).
+    /// Reads the sources from disk into new tables without touching the current ones
+    /// (blocking I/O, call it from `spawn_blocking` in async contexts).
+    ///
+    /// # Errors
+    /// Returns `ProxyDbError::Io` if a source cannot be read.
+    pub fn load_sources(
+        sources: &[ProxyListSource],
+    ) -> Result<(ProxyTables, ProxyLoadReport), ProxyDbError> {
+        let now = Utc::now();
+        let mut tables = ProxyTables::new();
+        let mut report = ProxyLoadReport::default();
+        for source in sources {
+            let content = std::fs::read_to_string(&source.path)
+                .map_err(|e| ProxyDbError::Io(source.path.display().to_string(), e.to_string()))?;
+            let part = tables.load_str(
+                source.format,
+                source.category,
+                &source.name,
+                source.ttl.map(|ttl| now + ttl),
+                &content,
+            );
+            report.loaded += part.loaded;
+            report.skipped_lines += part.skipped_lines;
+        }
+        Ok((tables, report))
+    }
+
+    /// كل المطابقات السارية لعنوان مع المصدر وتاريخ الانتهاء.
+    /// All live matches for an address with their source and expiry.
+    #[must_use]
+    pub fn lookup(&self, ip: &IpAddr) -> Vec<ProxyMatch> {
+        self.snapshot().lookup_at(ip, Utc::now())
+    }
+
+    /// يصنف العنوان في كل الفئات ببحث واحد.
+    /// Classifies the address in every category with one lookup.
+    #[must_use]
+    pub fn classify(&self, ip: &IpAddr) -> ProxyClassification {
+        self.lookup(ip)
+            .iter()
+            .fold(ProxyClassification::default(), |mut found, m| {
+                match m.category {
+                    ProxyCategory::Vpn => found.is_vpn = true,
+                    ProxyCategory::Proxy => found.is_proxy = true,
+                    ProxyCategory::Tor => found.is_tor = true,
+                }
+                found
+            })
+    }
+
+    #[must_use]
+    pub fn is_vpn(&self, ip: &IpAddr) -> bool {
+        self.classify(ip).is_vpn
+    }
+    #[must_use]
+    pub fn is_proxy(&self, ip: &IpAddr) -> bool {
+        self.classify(ip).is_proxy
+    }
+    #[must_use]
+    pub fn is_tor(&self, ip: &IpAddr) -> bool {
+        self.classify(ip).is_tor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loaders_and_cidr_lookup() {
+        let mut tables = ProxyTables::new();
+        let tor = "ExitNode 0011BD2485AD45D984EC4159C88FC066E5E3300E\n\
+                   Published 2024-01-01 00:00:00\n\
+                   ExitAddress 185.220.101.1 2024-01-01 00:10:00\n\
+                   LastStatus 2024-01-01 01:00:00\n";
+        let netset = "# FireHOL level1\n203.0.113.0/24\n2001:db8:bad::/48\n";
+        let csv = "cidr,category,expires_at\n198.51.100.0/24,vpn,\n192.0.2.0/24,bogus\n";
+
+        let r = tables.load_str(
+            ProxyListFormat::TorExitList,
+            ProxyCategory::Tor,
+            "tor",
+            None,
+            tor,
+        );
+        assert_eq!(
+            r,
+            ProxyLoadReport {
+                loaded: 1,
+                skipped_lines: 0
+            }
+        );
+        let r = tables.load_str(
+            ProxyListFormat::Netset,
+            ProxyCategory::Proxy,
+            "firehol",
+            None,
+            netset,
+        );
+        assert_eq!(r.loaded, 2);
+        let r = tables.load_str(
+            ProxyListFormat::Csv,
+            ProxyCategory::Proxy,
+            "vendor",
+            None,
+            csv,
+        );
+        assert_eq!(
+            r,
+            ProxyLoadReport {
+                loaded: 1,
+                skipped_lines: 1
+            }
+        );
+
+        let db = ProxyDatabase::default();
+        db.replace(tables).unwrap();
+        assert!(db.is_tor(&"185.220.101.1".parse().unwrap()));
+        assert!(db.is_proxy(&"203.0.113.77".parse().unwrap()));
+        assert!(db.is_proxy(&"2001:db8:bad::1".parse().unwrap()));
+        assert!(db.is_vpn(&"198.51.100.9".parse().unwrap()));
+        assert!(!db.is_vpn(&"203.0.113.77".parse().unwrap()));
+        assert_eq!(
+            db.classify(&"185.220.101.1".parse().unwrap()),
+            ProxyClassification {
+                is_tor: true,
+                ..ProxyClassification::default()
+            }
+        );
+        let hit = db.lookup(&"203.0.113.77".parse().unwrap());
+        assert_eq!(hit[0].source, "firehol");
+        assert_eq!(hit[0].prefix, "203.0.113.0/24");
+    }
+
+    #[test]
+    fn test_expiry_and_atomic_reload() {
+        let now = Utc::now();
+        let mut tables = ProxyTables::new();
+        tables.load_str(
+            ProxyListFormat::CidrList,
+            ProxyCategory::Vpn,
+            "old",
+            Some(now - Duration::minutes(1)),
+            "10.0.0.0/8",
+        );
+        assert!(tables
+            .lookup_at(&"10.1.1.1".parse().unwrap(), now)
+            .is_empty());
+        assert_eq!(tables.purge_expired(now), 1);
+
+        let dir = std::env::temp_dir().join(format!("proxy_db_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vpn.txt");
+        std::fs::write(&path, "10.0.0.0/8 # corp vpn\n").unwrap();
+        let spec = format!("cidr:vpn:{}", path.display());
+        let db = ProxyDatabase::with_sources(vec![ProxyListSource::parse_spec(&spec).unwrap()]);
+        assert_eq!(db.reload().unwrap().loaded, 1);
+        let before = db.snapshot();
+        assert!(db.is_vpn(&"10.2.3.4".parse().unwrap()));
+
+        // مصدر بمدة صلاحية تنتهي مدخلاته بعدها
+        // A source with a lifetime has its entries expire after it
+        let source = ProxyListSource::parse_spec(&spec)
+            .unwrap()
+            .with_ttl(Duration::hours(1));
+        let (tables, report) = ProxyDatabase::load_sources(&[source]).unwrap();
+        assert_eq!(report.loaded, 1);
+        let ip = "10.2.3.4".parse().unwrap();
+        assert_eq!(tables.lookup_at(&ip, Utc::now()).len(), 1);
+        assert!(tables
+            .lookup_at(&ip, Utc::now() + Duration::hours(2))
+            .is_empty());
+
+        // المدخلات المنتهية تُحذف من الجداول الحية أيضاً
+        // Expired entries are dropped from the live tables too
+        let live = ProxyDatabase::default();
+        let mut expired = ProxyTables::new();
+        expired.load_str(
+            ProxyListFormat::CidrList,
+            ProxyCategory::Vpn,
+            "stale",
+            Some(now - Duration::minutes(1)),
+            "10.0.0.0/8",
+        );
+        live.replace(expired).unwrap();
+        assert_eq!(live.purge_expired().unwrap(), 1);
+        assert!(live.snapshot().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(db.reload().is_err());
+        assert!(db.is_vpn(&"10.2.3.4".parse().unwrap()));
+        assert_eq!(before.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,682 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: tor_directory.rs
+    المسار:    src/core/tor_directory.rs
+    دور الملف:
+    محلل مستندات دليل شبكة Tor المقروءة من القرص: وثيقة الإجماع (network-status
+    consensus)، وقائمة عناوين الخروج (exit-addresses)، وواصفات المرحلات (server
+    descriptors). يستخرج مرحلات الخروج مع بصمتها واسمها وسياسة الخروج، ليتمكن
+    `NetworkAnalyzer` من التمييز بين مخارج Tor القادرة فعلاً على الوصول إلى منفذ
+    خدمتنا وبقية المرحلات.
+    --------------------------------------------------------------
+    File Name: tor_directory.rs
+    Path:     src/core/tor_directory.rs
+    File Role:
+    Parser for Tor directory documents read from disk: the network-status consensus,
+    the exit-addresses list and relay server descriptors. It extracts exit relays with
+    their fingerprint, nickname and exit policy so `NetworkAnalyzer` can tell Tor exits
+    that can actually reach our service port apart from other relays.
+******************************************************************************************/
+
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+// ================================================================
+// الأخطاء المخصصة للوحدة
+// Custom Module Errors
+// ================================================================
+#[derive(Debug, Error)]
+pub enum TorDirectoryError {
+    #[error("Failed to read Tor document '{0}': {1}")]
+    Io(String, String),
+    #[error("Unknown Tor document format: {0}")]
+    UnknownFormat(String),
+    #[error("Malformed Tor document at line {0}: {1}")]
+    Malformed(usize, String),
+}
+
+// ================================================================
+// نماذج البيانات الأساسية
+// Core Data Models
+// ================================================================
+
+/// أنواع مستندات دليل Tor المدعومة.
+/// Supported Tor directory document types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TorDocumentFormat {
+    /// وثيقة الإجماع (الكاملة أو microdesc) مع أسطر `r`/`a`/`s`/`p`.
+    /// Network-status consensus (full or microdesc) with `r`/`a`/`s`/`p` lines.
+    Consensus,
+    /// قائمة `exit-addresses` من TorDNSEL (`ExitNode`/`ExitAddress`).
+    /// TorDNSEL `exit-addresses` list (`ExitNode`/`ExitAddress`).
+    ExitList,
+    /// واصفات المرحلات (`router`/`fingerprint`/`accept`/`reject`).
+    /// Relay server descriptors (`router`/`fingerprint`/`accept`/`reject`).
+    ServerDescriptors,
+}
+
+impl FromStr for TorDocumentFormat {
+    type Err = TorDirectoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "consensus" | "microdesc_consensus" => Ok(Self::Consensus),
+            "exit_list" | "exit_addresses" | "exitlist" => Ok(Self::ExitList),
+            "descriptors" | "server_descriptors" => Ok(Self::ServerDescriptors),
+            other => Err(TorDirectoryError::UnknownFormat(other.to_string())),
+        }
+    }
+}
+
+/// نطاق منافذ مغلق.
+/// An inclusive port range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl PortRange {
+    const ALL: Self = Self { min: 1, max: 65535 };
+
+    #[must_use]
+    pub const fn contains(&self, port: u16) -> bool {
+        self.min <= port && port <= self.max
+    }
+}
+
+/// نمط العنوان في قاعدة سياسة الخروج.
+/// Address pattern of an exit-policy rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PolicyAddress {
+    /// `*` أو `*4` أو `*6`: كل العناوين.
+    /// `*`, `*4` or `*6`: every address.
+    Any,
+    /// `private`: الشبكات الخاصة وعنوان المرحل نفسه.
+    /// `private`: private networks and the relay's own address.
+    Private,
+    /// عنوان أو شبكة محددة.
+    /// A specific address or network.
+    Network(String),
+}
+
+/// قاعدة واحدة من سياسة خروج كاملة.
+/// A single rule of a full exit policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitPolicyRule {
+    pub accept: bool,
+    pub address: PolicyAddress,
+    pub ports: PortRange,
+}
+
+/// سياسة الخروج لمرحل.
+/// A relay's exit policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitPolicy {
+    /// ملخص المنافذ من الإجماع (`p accept 80,443`).
+    /// Port summary from the consensus (`p accept 80,443`).
+    Summary { accept: bool, ports: Vec<PortRange> },
+    /// القواعد الكاملة من الواصف، تُطبق بالترتيب وأول تطابق يحسم.
+    /// Full rules from the descriptor, applied in order with first match winning.
+    Rules(Vec<ExitPolicyRule>),
+}
+
+impl ExitPolicy {
+    /// هل تسمح السياسة بالخروج إلى عنوان عام على هذا المنفذ؟
+    /// القواعد الخاصة بعناوين محددة أو `private` لا تنطبق على "أي عنوان عام" فتُتجاوز،
+    /// وعند عدم تطابق أي قاعدة يُقبل الاتصال كما تنص مواصفة الدليل.
+    /// Does the policy allow exiting to a public address on this port?
+    /// Rules for specific addresses or `private` do not cover "any public address" and
+    /// are skipped; when no rule matches the stream is accepted, as dir-spec states.
+    #[must_use]
+    pub fn allows_port(&self, port: u16) -> bool {
+        match self {
+            Self::Summary { accept, ports } => {
+                ports.iter().any(|range| range.contains(port)) == *accept
+            }
+            Self::Rules(rules) => rules
+                .iter()
+                .find(|rule| rule.address == PolicyAddress::Any && rule.ports.contains(port))
+                .is_none_or(|rule| rule.accept),
+        }
+    }
+
+    /// هل ترفض السياسة كل المنافذ؟ (مرحل ليس مخرجاً)
+    /// Does the policy reject every port? (relay is not an exit)
+    #[must_use]
+    pub fn rejects_all(&self) -> bool {
+        match self {
+            Self::Summary { accept, ports } => {
+                (*accept && ports.is_empty()) || (!*accept && ports.contains(&PortRange::ALL))
+            }
+            Self::Rules(rules) => rules
+                .iter()
+                .find(|rule| rule.address == PolicyAddress::Any)
+                .is_some_and(|rule| !rule.accept && rule.ports == PortRange::ALL),
+        }
+    }
+}
+
+/// مرحل Tor كما يظهر في المستندات المحملة.
+/// A Tor relay as seen in the loaded documents.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TorRelay {
+    /// البصمة (SHA-1 بصيغة hex كبيرة).
+    /// Fingerprint (upper-case hex SHA-1).
+    pub fingerprint: String,
+    pub nickname: Option<String>,
+    /// عناوين OR المعلنة.
+    /// Advertised OR addresses.
+    pub or_addresses: Vec<IpAddr>,
+    /// عناوين الخروج المرصودة (قد تختلف عن عناوين OR).
+    /// Observed exit addresses (may differ from the OR addresses).
+    pub exit_addresses: Vec<IpAddr>,
+    pub flags: Vec<String>,
+    pub policy: Option<ExitPolicy>,
+    pub ipv6_policy: Option<ExitPolicy>,
+}
+
+impl TorRelay {
+    /// يعتبر المرحل مخرجاً إن رُصد خروجه، أو حمل علم `Exit`، أو سمحت سياسته بمنفذ ما.
+    /// A relay is an exit if it was observed exiting, carries the `Exit` flag,
+    /// or its policy allows some port.
+    #[must_use]
+    pub fn is_exit(&self) -> bool {
+        !self.exit_addresses.is_empty()
+            || self.flags.iter().any(|f| f == "Exit")
+            || self.policy.as_ref().is_some_and(|p| !p.rejects_all())
+    }
+
+    /// هل يستطيع المرحل الخروج إلى منفذ الخدمة من هذا العنوان؟
+    /// بدون سياسة معروفة يُفترض أنه يستطيع (الحالة الأسوأ).
+    /// Can the relay exit to the service port from this address?
+    /// Without a known policy it is assumed it can (worst case).
+    #[must_use]
+    pub fn can_exit_to(&self, ip: &IpAddr, port: u16) -> bool {
+        let policy = match ip {
+            IpAddr::V4(_) => self.policy.as_ref(),
+            IpAddr::V6(_) => self.ipv6_policy.as_ref().or(self.policy.as_ref()),
+        };
+        match policy {
+            Some(policy) => policy.allows_port(port),
+            None => self.is_exit(),
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        if other.nickname.is_some() {
+            self.nickname = other.nickname;
+        }
+        for ip in other.or_addresses {
+            if !self.or_addresses.contains(&ip) {
+                self.or_addresses.push(ip);
+            }
+        }
+        for ip in other.exit_addresses {
+            if !self.exit_addresses.contains(&ip) {
+                self.exit_addresses.push(ip);
+            }
+        }
+        if !other.flags.is_empty() {
+            self.flags = other.flags;
+        }
+        // السياسة الكاملة أدق من الملخص
+        // A full policy is more precise than a summary
+        match (&self.policy, other.policy) {
+            (Some(ExitPolicy::Rules(_)), Some(ExitPolicy::Summary { .. })) | (_, None) => {}
+            (_, Some(policy)) => self.policy = Some(policy),
+        }
+        if other.ipv6_policy.is_some() {
+            self.ipv6_policy = other.ipv6_policy;
+        }
+    }
+}
+
+/// نتيجة مطابقة عنوان مع مرحل Tor.
+/// Result of matching an address against a Tor relay.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TorRelayMatch {
+    pub fingerprint: String,
+    pub nickname: Option<String>,
+    pub is_exit: bool,
+    /// هل يستطيع المرحل الخروج إلى منفذ خدمتنا.
+    /// Whether the relay can exit to our service port.
+    pub can_reach_service: bool,
+}
+
+// ================================================================
+// دليل Tor
+// Tor Directory
+// ================================================================
+#[derive(Debug, Clone, Default)]
+pub struct TorDirectory {
+    relays: HashMap<String, TorRelay>,
+    by_ip: HashMap<IpAddr, Vec<String>>,
+}
+
+impl TorDirectory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// عدد المرحلات المعروفة.
+    /// Number of known relays.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.relays.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.relays.is_empty()
+    }
+
+    #[must_use]
+    pub fn relay(&self, fingerprint: &str) -> Option<&TorRelay> {
+        self.relays.get(&fingerprint.to_ascii_uppercase())
+    }
+
+    /// يحمّل مستنداً من القرص ويعيد عدد المرحلات المقروءة منه.
+    /// Loads a document from disk and returns the number of relays read from it.
+    ///
+    /// # Errors
+    /// Returns `TorDirectoryError` if the file cannot be read or parsed.
+    pub fn load_file(
+        &mut self,
+        format: TorDocumentFormat,
+        path: &Path,
+    ) -> Result<usize, TorDirectoryError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| TorDirectoryError::Io(path.display().to_string(), e.to_string()))?;
+        self.load_str(format, &content)
+    }
+
+    /// يحمّل مستنداً من نص ويدمج مرحلاته حسب البصمة.
+    /// Loads a document from text and merges its relays by fingerprint.
+    ///
+    /// # Errors
+    /// Returns `TorDirectoryError::Malformed` if a relay entry cannot be parsed.
+    pub fn load_str(
+        &mut self,
+        format: TorDocumentFormat,
+        content: &str,
+    ) -> Result<usize, TorDirectoryError> {
+        let relays = match format {
+            TorDocumentFormat::Consensus => parse_consensus(content)?,
+            TorDocumentFormat::ExitList => parse_exit_list(content)?,
+            TorDocumentFormat::ServerDescriptors => parse_descriptors(content)?,
+        };
+        let count = relays.len();
+        for relay in relays {
+            let fingerprint = relay.fingerprint.clone();
+            let entry = self.relays.entry(fingerprint.clone()).or_default();
+            entry.fingerprint = fingerprint;
+            entry.merge(relay);
+        }
+        self.rebuild_index();
+        Ok(count)
+    }
+
+    fn rebuild_index(&mut self) {
+        self.by_ip.clear();
+        for relay in self.relays.values() {
+            for ip in relay.or_addresses.iter().chain(&relay.exit_addresses) {
+                let fingerprints = self.by_ip.entry(*ip).or_default();
+                if !fingerprints.contains(&relay.fingerprint) {
+                    fingerprints.push(relay.fingerprint.clone());
+                }
+            }
+        }
+    }
+
+    /// يبحث عن مرحل بهذا العنوان، مفضلاً المخارج القادرة على الوصول إلى المنفذ.
+    /// Looks up a relay at this address, preferring exits that can reach the port.
+    #[must_use]
+    pub fn lookup(&self, ip: &IpAddr, service_port: u16) -> Option<TorRelayMatch> {
+        self.by_ip
+            .get(ip)?
+            .iter()
+            .filter_map(|fingerprint| self.relays.get(fingerprint))
+            .map(|relay| TorRelayMatch {
+                fingerprint: relay.fingerprint.clone(),
+                nickname: relay.nickname.clone(),
+                is_exit: relay.is_exit(),
+                can_reach_service: relay.can_exit_to(ip, service_port),
+            })
+            .max_by_key(|m| (m.can_reach_service, m.is_exit))
+    }
+}
+
+// ================================================================
+// المحللات
+// Parsers
+// ================================================================
+
+fn malformed(line_no: usize, message: impl Into<String>) -> TorDirectoryError {
+    TorDirectoryError::Malformed(line_no, message.into())
+}
+
+fn normalize_fingerprint(raw: &str) -> Option<String> {
+    let hex: String = raw
+        .trim_start_matches('$')
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    (hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| hex.to_ascii_uppercase())
+}
+
+/// هوية الإجماع مرمزة base64 بدون حشو.
+/// Consensus identities are unpadded base64.
+fn identity_to_fingerprint(identity: &str) -> Option<String> {
+    let bytes = STANDARD_NO_PAD
+        .decode(identity.trim_end_matches('='))
+        .ok()?;
+    (bytes.len() == 20).then(|| hex::encode_upper(bytes))
+}
+
+/// يقرأ `1.2.3.4` أو `[2001:db8::1]` أو `[2001:db8::1]:9001`.
+/// Reads `1.2.3.4`, `[2001:db8::1]` or `[2001:db8::1]:9001`.
+fn parse_or_address(raw: &str) -> Option<IpAddr> {
+    if let Some(rest) = raw.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    raw.parse()
+        .ok()
+        .or_else(|| raw.rsplit_once(':')?.0.parse().ok())
+}
+
+fn parse_port_range(raw: &str) -> Option<PortRange> {
+    if raw == "*" {
+        return Some(PortRange::ALL);
+    }
+    let (min, max) = match raw.split_once('-') {
+        Some((min, max)) => (min.parse().ok()?, max.parse().ok()?),
+        None => {
+            let port = raw.parse().ok()?;
+            (port, port)
+        }
+    };
+    (min <= max).then_some(PortRange { min, max })
+}
+
+/// `accept 80,443,1000-2000` أو `reject 1-65535`.
+fn parse_summary(line_no: usize, rest: &str) -> Result<ExitPolicy, TorDirectoryError> {
+    let mut parts = rest.split_whitespace();
+    let accept = match parts.next() {
+        Some("accept") => true,
+        Some("reject") => false,
+        _ => {
+            return Err(malformed(
+                line_no,
+                "policy summary must start with accept/reject",
+            ))
+        }
+    };
+    let ports = parts
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|p| !p.is_empty())
+        .map(|p| parse_port_range(p).ok_or_else(|| malformed(line_no, format!("bad port '{p}'"))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ExitPolicy::Summary { accept, ports })
+}
+
+/// `accept *:80` أو `reject 10.0.0.0/8:*` أو `reject6 [2001:db8::]/32:*`.
+fn parse_policy_rule(
+    line_no: usize,
+    accept: bool,
+    pattern: &str,
+) -> Result<ExitPolicyRule, TorDirectoryError> {
+    let (address, ports) = pattern
+        .rsplit_once(':')
+        .ok_or_else(|| malformed(line_no, format!("bad exit pattern '{pattern}'")))?;
+    let ports = parse_port_range(ports)
+        .ok_or_else(|| malformed(line_no, format!("bad port range in '{pattern}'")))?;
+    let address = match address {
+        "*" | "*4" | "*6" => PolicyAddress::Any,
+        "private" => PolicyAddress::Private,
+        other => PolicyAddress::Network(other.to_string()),
+    };
+    Ok(ExitPolicyRule {
+        accept,
+        address,
+        ports,
+    })
+}
+
+fn parse_consensus(content: &str) -> Result<Vec<TorRelay>, TorDirectoryError> {
+    let mut relays = Vec::new();
+    let mut current: Option<TorRelay> = None;
+    for (idx, line) in content.lines().enumerate() {
+        let line_no = idx + 1;
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            // r <nickname> <identity> [<digest>] <date> <time> <ip> <orport> <dirport>
+            "r" => {
+                relays.extend(current.take());
+                let fields: Vec<&str> = rest.split_whitespace().collect();
+                if fields.len() < 7 {
+                    return Err(malformed(line_no, "router status line is too short"));
+                }
+                let fingerprint = identity_to_fingerprint(fields[1])
+                    .ok_or_else(|| malformed(line_no, "bad relay identity"))?;
+                let ip = fields[fields.len() - 3]
+                    .parse()
+                    .map_err(|_| malformed(line_no, "bad relay address"))?;
+                current = Some(TorRelay {
+                    fingerprint,
+                    nickname: Some(fields[0].to_string()),
+                    or_addresses: vec![ip],
+                    ..TorRelay::default()
+                });
+            }
+            "a" => {
+                if let (Some(relay), Some(ip)) = (current.as_mut(), parse_or_address(rest.trim())) {
+                    relay.or_addresses.push(ip);
+                }
+            }
+            "s" => {
+                if let Some(relay) = current.as_mut() {
+                    relay.flags = rest.split_whitespace().map(ToString::to_string).collect();
+                }
+            }
+            "p" => {
+                if let Some(relay) = current.as_mut() {
+                    relay.policy = Some(parse_summary(line_no, rest)?);
+                }
+            }
+            // نهاية قائمة المرحلات
+            // End of the relay list
+            "directory-footer" => break,
+            _ => {}
+        }
+    }
+    relays.extend(current);
+    Ok(relays)
+}
+
+fn parse_exit_list(content: &str) -> Result<Vec<TorRelay>, TorDirectoryError> {
+    let mut relays = Vec::new();
+    let mut current: Option<TorRelay> = None;
+    for (idx, line) in content.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("ExitNode") => {
+                relays.extend(current.take());
+                let fingerprint = parts
+                    .next()
+                    .and_then(normalize_fingerprint)
+                    .ok_or_else(|| malformed(line_no, "bad ExitNode fingerprint"))?;
+                current = Some(TorRelay {
+                    fingerprint,
+                    ..TorRelay::default()
+                });
+            }
+            Some("ExitAddress") => {
+                let relay = current
+                    .as_mut()
+                    .ok_or_else(|| malformed(line_no, "ExitAddress before ExitNode"))?;
+                let ip = parts
+                    .next()
+                    .and_then(|ip| ip.parse().ok())
+                    .ok_or_else(|| malformed(line_no, "bad ExitAddress"))?;
+                relay.exit_addresses.push(ip);
+            }
+            _ => {}
+        }
+    }
+    relays.extend(current);
+    Ok(relays)
+}
+
+fn parse_descriptors(content: &str) -> Result<Vec<TorRelay>, TorDirectoryError> {
+    let mut relays = Vec::new();
+    let mut current: Option<TorRelay> = None;
+    let mut rules = Vec::new();
+    let mut finish = |relay: Option<TorRelay>, rules: &mut Vec<ExitPolicyRule>| {
+        if let Some(mut relay) = relay {
+            if !rules.is_empty() {
+                relay.policy = Some(ExitPolicy::Rules(std::mem::take(rules)));
+            }
+            // الواصف بلا بصمة لا يمكن ربطه بمرحل
+            // A descriptor without a fingerprint cannot be tied to a relay
+            if !relay.fingerprint.is_empty() {
+                relays.push(relay);
+            }
+        }
+        rules.clear();
+    };
+    for (idx, line) in content.lines().enumerate() {
+        let line_no = idx + 1;
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            // router <nickname> <address> <orport> <socksport> <dirport>
+            "router" => {
+                finish(current.take(), &mut rules);
+                let mut fields = rest.split_whitespace();
+                let nickname = fields.next().map(ToString::to_string);
+                let ip = fields
+                    .next()
+                    .and_then(|ip| ip.parse().ok())
+                    .ok_or_else(|| malformed(line_no, "bad router address"))?;
+                current = Some(TorRelay {
+                    nickname,
+                    or_addresses: vec![ip],
+                    ..TorRelay::default()
+                });
+            }
+            "fingerprint" => {
+                if let Some(relay) = current.as_mut() {
+                    relay.fingerprint = normalize_fingerprint(rest)
+                        .ok_or_else(|| malformed(line_no, "bad fingerprint"))?;
+                }
+            }
+            "or-address" => {
+                if let (Some(relay), Some(ip)) = (current.as_mut(), parse_or_address(rest.trim())) {
+                    relay.or_addresses.push(ip);
+                }
+            }
+            "accept" | "reject" if current.is_some() => {
+                rules.push(parse_policy_rule(
+                    line_no,
+                    keyword == "accept",
+                    rest.trim(),
+                )?);
+            }
+            "ipv6-policy" => {
+                if let Some(relay) = current.as_mut() {
+                    relay.ipv6_policy = Some(parse_summary(line_no, rest)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    finish(current, &mut rules);
+    Ok(relays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONSENSUS: &str = "network-status-version 3\n\
+        vote-status consensus\n\
+        r exitOne AAECAwQFBgcICQoLDA0ODxAREhM ZGlnZXN0ZGlnZXN0ZGlnZXN0ZGk 2025-01-01 00:00:00 185.220.101.1 9001 0\n\
+        a [2001:db8::1]:9001\n\
+        s Exit Fast Running Stable Valid\n\
+        p accept 80,443\n\
+        r middleRelay FBQTEhEQDw4NDAsKCQgHBgUEAwI ZGlnZXN0ZGlnZXN0ZGlnZXN0ZGk 2025-01-01 00:00:00 198.51.100.7 9001 9030\n\
+        s Fast Guard Running Stable Valid\n\
+        p reject 1-65535\n\
+        directory-footer\n";
+
+    #[test]
+    fn test_consensus_summary_policies() {
+        let mut dir = TorDirectory::new();
+        assert_eq!(
+            dir.load_str(TorDocumentFormat::Consensus, CONSENSUS)
+                .unwrap(),
+            2
+        );
+
+        let exit = dir.lookup(&"185.220.101.1".parse().unwrap(), 443).unwrap();
+        assert_eq!(exit.fingerprint, "000102030405060708090A0B0C0D0E0F10111213");
+        assert_eq!(exit.nickname.as_deref(), Some("exitOne"));
+        assert!(exit.is_exit && exit.can_reach_service);
+        assert!(
+            !dir.lookup(&"185.220.101.1".parse().unwrap(), 8080)
+                .unwrap()
+                .can_reach_service
+        );
+        assert!(dir.lookup(&"2001:db8::1".parse().unwrap(), 80).is_some());
+
+        let middle = dir.lookup(&"198.51.100.7".parse().unwrap(), 443).unwrap();
+        assert!(!middle.is_exit && !middle.can_reach_service);
+    }
+
+    #[test]
+    fn test_exit_list_and_descriptor_merge() {
+        let mut dir = TorDirectory::new();
+        dir.load_str(TorDocumentFormat::Consensus, CONSENSUS)
+            .unwrap();
+        let exit_list = "ExitNode 000102030405060708090A0B0C0D0E0F10111213\n\
+            Published 2025-01-01 00:00:00\n\
+            ExitAddress 203.0.113.9 2025-01-01 01:00:00\n";
+        dir.load_str(TorDocumentFormat::ExitList, exit_list)
+            .unwrap();
+        let descriptor = "router exitOne 185.220.101.1 9001 0 0\n\
+            fingerprint 0001 0203 0405 0607 0809 0A0B 0C0D 0E0F 1011 1213\n\
+            reject private:*\n\
+            accept *:8000-8100\n\
+            reject *:*\n";
+        dir.load_str(TorDocumentFormat::ServerDescriptors, descriptor)
+            .unwrap();
+
+        // عنوان الخروج المرصود يقود إلى المرحل نفسه بسياسته الكاملة
+        // The observed exit address leads to the same relay with its full policy
+        let via_exit = dir.lookup(&"203.0.113.9".parse().unwrap(), 8080).unwrap();
+        assert_eq!(via_exit.nickname.as_deref(), Some("exitOne"));
+        assert!(via_exit.can_reach_service);
+        assert!(
+            !dir.lookup(&"203.0.113.9".parse().unwrap(), 443)
+                .unwrap()
+                .can_reach_service
+        );
+        assert_eq!(dir.len(), 2);
+    }
+}
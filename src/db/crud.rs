@@ -1,4 +1,6 @@
-use crate::db::models::{SecurityAlert, User};
+use crate::db::models::{
+    AnalysisVerdict, Device, DeviceTrustState, RiskAnalysis, SecurityAlert, User, Verdict,
+};
 use chrono::{NaiveDateTime, Utc};
 use rusqlite::params;
 use tokio_rusqlite::Connection;
@@ -8,6 +10,56 @@ fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
     NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok()
 }
 
+const DEVICE_COLUMNS: &str = "id, user_id, device_fingerprint, friendly_name, metadata, created_at, last_seen_at, trust_state";
+
+fn device_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Device> {
+    let id_str: String = row.get(0)?;
+    let user_id_str: String = row.get(1)?;
+    let metadata_str: String = row.get(4)?;
+    let created_at_str: String = row.get(5)?;
+    let last_seen_at_str: Option<String> = row.get(6)?;
+    let trust_state_str: String = row.get(7)?;
+    let created_at = parse_datetime(&created_at_str).unwrap_or_else(|| Utc::now().naive_utc());
+
+    Ok(Device {
+        id: Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::nil()),
+        user_id: Uuid::parse_str(&user_id_str).unwrap_or_else(|_| Uuid::nil()),
+        device_fingerprint: row.get(2)?,
+        friendly_name: row.get(3)?,
+        metadata: serde_json::from_str(&metadata_str).unwrap_or(serde_json::Value::Null),
+        created_at,
+        last_seen_at: last_seen_at_str
+            .and_then(|s| parse_datetime(&s))
+            .unwrap_or(created_at),
+        // حالة غير معروفة تُعامل كمشبوهة
+        // An unknown state is treated as suspicious
+        trust_state: trust_state_str
+            .parse()
+            .unwrap_or(DeviceTrustState::Suspicious),
+    })
+}
+
+const ANALYSIS_COLUMNS: &str =
+    "a.id, a.requested_by, a.entity_id, a.source, a.risk_score, a.rules, a.input, a.created_at";
+
+fn analysis_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RiskAnalysis> {
+    let id_str: String = row.get(0)?;
+    let requested_by_str: String = row.get(1)?;
+    let rules_str: String = row.get(5)?;
+    let input_str: String = row.get(6)?;
+    let created_at_str: String = row.get(7)?;
+    Ok(RiskAnalysis {
+        id: Uuid::parse_str(&id_str).unwrap_or_else(|_| Uuid::nil()),
+        requested_by: Uuid::parse_str(&requested_by_str).unwrap_or_else(|_| Uuid::nil()),
+        entity_id: row.get(2)?,
+        source: row.get(3)?,
+        risk_score: row.get(4)?,
+        rules: serde_json::from_str(&rules_str).unwrap_or_default(),
+        input: serde_json::from_str(&input_str).unwrap_or(serde_json::Value::Null),
+        created_at: parse_datetime(&created_at_str).unwrap_or_else(|| Utc::now().naive_utc()),
+    })
+}
+
 pub async fn init_schema(pool: &Connection) -> Result<(), tokio_rusqlite::Error> {
     crate::db::migrations::run_migrations(pool).await
 }
@@ -100,3 +152,336 @@ pub async fn create_security_alert(
     })
     .await
 }
+
+/// يدرج جهازاً جديداً؛ إن كانت البصمة مسجلة للمستخدم يُحدَّث وقت آخر ظهور فقط.
+/// Inserts a new device; if the fingerprint is already bound to the user only the last-seen time is updated.
+pub async fn insert_device(
+    pool: &Connection,
+    device: &Device,
+) -> Result<(), tokio_rusqlite::Error> {
+    let device = device.clone();
+    pool.call(move |conn| {
+        conn.execute(
+            r#"
+            INSERT INTO devices (id, user_id, device_fingerprint, friendly_name, metadata, created_at, last_seen_at, trust_state)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(user_id, device_fingerprint) DO UPDATE SET
+                last_seen_at = excluded.last_seen_at
+            "#,
+            params![
+                device.id.to_string(),
+                device.user_id.to_string(),
+                device.device_fingerprint,
+                device.friendly_name,
+                device.metadata.to_string(),
+                device.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                device.last_seen_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                device.trust_state.as_str(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+pub async fn get_device_by_id(
+    pool: &Connection,
+    device_id: &Uuid,
+) -> Result<Option<Device>, tokio_rusqlite::Error> {
+    let device_id = device_id.to_string();
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {DEVICE_COLUMNS} FROM devices WHERE id = ?1"
+        ))?;
+        let mut rows = stmt.query(params![device_id])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        Ok(Some(device_from_row(row)?))
+    })
+    .await
+}
+
+pub async fn get_device_by_fingerprint(
+    pool: &Connection,
+    user_id: &Uuid,
+    fingerprint: &str,
+) -> Result<Option<Device>, tokio_rusqlite::Error> {
+    let user_id = user_id.to_string();
+    let fingerprint = fingerprint.to_string();
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {DEVICE_COLUMNS} FROM devices WHERE user_id = ?1 AND device_fingerprint = ?2"
+        ))?;
+        let mut rows = stmt.query(params![user_id, fingerprint])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        Ok(Some(device_from_row(row)?))
+    })
+    .await
+}
+
+/// أجهزة المستخدم من الأحدث ظهوراً إلى الأقدم.
+/// The user's devices, most recently seen first.
+pub async fn list_devices_for_user(
+    pool: &Connection,
+    user_id: &Uuid,
+) -> Result<Vec<Device>, tokio_rusqlite::Error> {
+    let user_id = user_id.to_string();
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {DEVICE_COLUMNS} FROM devices WHERE user_id = ?1 ORDER BY last_seen_at DESC, created_at DESC"
+        ))?;
+        let devices = stmt
+            .query_map(params![user_id], device_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(devices)
+    })
+    .await
+}
+
+/// يحدّث بصمة الجهاز وبياناته ووقت آخر ظهور (عند رؤيته أو انحراف بصمته).
+/// Updates a device's fingerprint, metadata and last-seen time (on sight or fingerprint drift).
+pub async fn touch_device(
+    pool: &Connection,
+    device_id: &Uuid,
+    fingerprint: &str,
+    metadata: &serde_json::Value,
+    last_seen_at: NaiveDateTime,
+) -> Result<bool, tokio_rusqlite::Error> {
+    let device_id = device_id.to_string();
+    let fingerprint = fingerprint.to_string();
+    let metadata = metadata.to_string();
+    let last_seen_at = last_seen_at.format("%Y-%m-%d %H:%M:%S").to_string();
+    pool.call(move |conn| {
+        let updated = conn.execute(
+            "UPDATE devices SET device_fingerprint = ?2, metadata = ?3, last_seen_at = ?4 WHERE id = ?1",
+            params![device_id, fingerprint, metadata, last_seen_at],
+        )?;
+        Ok(updated > 0)
+    })
+    .await
+}
+
+pub async fn update_device_trust_state(
+    pool: &Connection,
+    device_id: &Uuid,
+    trust_state: DeviceTrustState,
+) -> Result<bool, tokio_rusqlite::Error> {
+    let device_id = device_id.to_string();
+    pool.call(move |conn| {
+        let updated = conn.execute(
+            "UPDATE devices SET trust_state = ?2 WHERE id = ?1",
+            params![device_id, trust_state.as_str()],
+        )?;
+        Ok(updated > 0)
+    })
+    .await
+}
+
+pub async fn rename_device(
+    pool: &Connection,
+    device_id: &Uuid,
+    friendly_name: &str,
+) -> Result<bool, tokio_rusqlite::Error> {
+    let device_id = device_id.to_string();
+    let friendly_name = friendly_name.to_string();
+    pool.call(move |conn| {
+        let updated = conn.execute(
+            "UPDATE devices SET friendly_name = ?2 WHERE id = ?1",
+            params![device_id, friendly_name],
+        )?;
+        Ok(updated > 0)
+    })
+    .await
+}
+
+pub async fn insert_risk_analysis(
+    pool: &Connection,
+    analysis: &RiskAnalysis,
+) -> Result<(), tokio_rusqlite::Error> {
+    let analysis = analysis.clone();
+    pool.call(move |conn| {
+        conn.execute(
+            r#"
+            INSERT INTO risk_analyses (id, requested_by, entity_id, source, risk_score, rules, input, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                analysis.id.to_string(),
+                analysis.requested_by.to_string(),
+                analysis.entity_id,
+                analysis.source,
+                analysis.risk_score,
+                serde_json::Value::from(analysis.rules).to_string(),
+                analysis.input.to_string(),
+                analysis.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+pub async fn get_risk_analysis(
+    pool: &Connection,
+    analysis_id: &Uuid,
+) -> Result<Option<RiskAnalysis>, tokio_rusqlite::Error> {
+    let analysis_id = analysis_id.to_string();
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ANALYSIS_COLUMNS} FROM risk_analyses a WHERE a.id = ?1"
+        ))?;
+        let mut rows = stmt.query(params![analysis_id])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        Ok(Some(analysis_from_row(row)?))
+    })
+    .await
+}
+
+/// يسجل حكم المحلل؛ إعادة التصنيف تستبدل الحكم السابق.
+/// Records the analyst verdict; relabeling replaces the previous verdict.
+pub async fn upsert_analysis_verdict(
+    pool: &Connection,
+    verdict: &AnalysisVerdict,
+) -> Result<(), tokio_rusqlite::Error> {
+    let verdict = verdict.clone();
+    pool.call(move |conn| {
+        conn.execute(
+            r#"
+            INSERT INTO analysis_verdicts (analysis_id, verdict, analyst_id, notes, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(analysis_id) DO UPDATE SET
+                verdict = excluded.verdict,
+                analyst_id = excluded.analyst_id,
+                notes = excluded.notes,
+                created_at = excluded.created_at
+            "#,
+            params![
+                verdict.analysis_id.to_string(),
+                verdict.verdict.as_str(),
+                verdict.analyst_id.to_string(),
+                verdict.notes,
+                verdict.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// التحليلات المصنفة مع أحكامها بترتيب وقت الحكم، مع تصفية اختيارية بالمصدر.
+/// Labeled analyses with their verdicts in verdict order, optionally filtered by source.
+pub async fn list_labeled_analyses(
+    pool: &Connection,
+    source: Option<&str>,
+) -> Result<Vec<(RiskAnalysis, Verdict)>, tokio_rusqlite::Error> {
+    let source = source.map(ToString::to_string);
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ANALYSIS_COLUMNS}, v.verdict FROM risk_analyses a \
+             JOIN analysis_verdicts v ON v.analysis_id = a.id \
+             WHERE ?1 IS NULL OR a.source = ?1 ORDER BY v.created_at ASC, a.created_at ASC"
+        ))?;
+        let labeled = stmt
+            .query_map(params![source], |row| {
+                let verdict: String = row.get(8)?;
+                Ok((analysis_from_row(row)?, verdict.parse::<Verdict>().ok()))
+            })?
+            .filter_map(|row| match row {
+                Ok((analysis, Some(verdict))) => Some(Ok((analysis, verdict))),
+                Ok((_, None)) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(labeled)
+    })
+    .await
+}
+
+/// أحدث تحليلات الكيان، من الأحدث إلى الأقدم.
+/// The entity's most recent analyses, newest first.
+pub async fn list_recent_analyses_for_entity(
+    pool: &Connection,
+    entity_id: &str,
+    limit: usize,
+) -> Result<Vec<RiskAnalysis>, tokio_rusqlite::Error> {
+    let entity_id = entity_id.to_string();
+    let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ANALYSIS_COLUMNS} FROM risk_analyses a WHERE a.entity_id = ?1 \
+             ORDER BY a.created_at DESC LIMIT ?2"
+        ))?;
+        let analyses = stmt
+            .query_map(params![entity_id, limit], analysis_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(analyses)
+    })
+    .await
+}
+
+/// عينات القالب البيومتري المخزنة، ما لم تكن أقدم من `not_before`.
+/// The stored biometric template samples, unless older than `not_before`.
+pub async fn get_biometric_samples(
+    pool: &Connection,
+    kind: &str,
+    template_key: &str,
+    not_before: NaiveDateTime,
+) -> Result<Option<Vec<Vec<f64>>>, tokio_rusqlite::Error> {
+    let kind = kind.to_string();
+    let template_key = template_key.to_string();
+    pool.call(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT samples FROM biometric_templates \
+             WHERE kind = ?1 AND template_key = ?2 AND updated_at >= ?3",
+        )?;
+        let mut rows = stmt.query(params![
+            kind,
+            template_key,
+            not_before.format("%Y-%m-%d %H:%M:%S").to_string(),
+        ])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        let samples: String = row.get(0)?;
+        Ok(serde_json::from_str(&samples).ok())
+    })
+    .await
+}
+
+/// يحفظ عينات القالب البيومتري، مستبدلاً النسخة السابقة.
+/// Stores the biometric template samples, replacing the previous copy.
+pub async fn upsert_biometric_samples(
+    pool: &Connection,
+    kind: &str,
+    template_key: &str,
+    samples: &[Vec<f64>],
+    updated_at: NaiveDateTime,
+) -> Result<(), tokio_rusqlite::Error> {
+    let kind = kind.to_string();
+    let template_key = template_key.to_string();
+    let samples = serde_json::to_string(samples).unwrap_or_else(|_| "[]".to_string());
+    pool.call(move |conn| {
+        conn.execute(
+            r#"
+            INSERT INTO biometric_templates (kind, template_key, samples, updated_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(kind, template_key) DO UPDATE SET
+                samples = excluded.samples,
+                updated_at = excluded.updated_at
+            "#,
+            params![
+                kind,
+                template_key,
+                samples,
+                updated_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
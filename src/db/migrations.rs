@@ -3,6 +3,9 @@ use tokio_rusqlite::Connection;
 const MIGRATIONS: &[(i64, &str)] = &[
     (1, include_str!("migrations/0001_initial.sql")),
     (2, include_str!("migrations/0002_indexes.sql")),
+    (3, include_str!("migrations/0003_device_registry.sql")),
+    (4, include_str!("migrations/0004_analysis_feedback.sql")),
+    (5, include_str!("migrations/0005_biometric_templates.sql")),
 ];
 
 pub async fn run_migrations(pool: &Connection) -> Result<(), tokio_rusqlite::Error> {
@@ -50,3 +53,46 @@ pub async fn run_migrations(pool: &Connection) -> Result<(), tokio_rusqlite::Err
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_device_registry_migration_keeps_latest_duplicate() {
+        let pool = Connection::open_in_memory().await.unwrap();
+        pool.call(|conn| {
+            conn.execute_batch(MIGRATIONS[0].1)?;
+            conn.execute_batch(MIGRATIONS[1].1)?;
+            conn.execute_batch(
+                r"
+                CREATE TABLE schema_migrations (
+                    version INTEGER PRIMARY KEY NOT NULL,
+                    applied_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now'))
+                );
+                INSERT INTO schema_migrations (version) VALUES (1), (2);
+                INSERT INTO devices VALUES
+                    ('old', 'u1', 'fp', 'phone', '{}', '2025-01-01 00:00:00'),
+                    ('new', 'u1', 'fp', 'phone', '{}', '2025-02-01 00:00:00'),
+                    ('other', 'u2', 'fp', 'laptop', '{}', '2025-01-01 00:00:00');
+                ",
+            )?;
+            Ok::<_, tokio_rusqlite::Error>(())
+        })
+        .await
+        .unwrap();
+
+        run_migrations(&pool).await.unwrap();
+        let ids: Vec<String> = pool
+            .call(|conn| {
+                let mut stmt = conn.prepare("SELECT id FROM devices ORDER BY id")?;
+                let ids = stmt
+                    .query_map([], |row| row.get(0))?
+                    .collect::<Result<_, _>>()?;
+                Ok::<_, tokio_rusqlite::Error>(ids)
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids, ["new", "other"]);
+    }
+}
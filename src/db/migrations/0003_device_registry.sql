@@ -0,0 +1,17 @@
+ALTER TABLE devices ADD COLUMN trust_state TEXT NOT NULL DEFAULT 'new';
+ALTER TABLE devices ADD COLUMN last_seen_at TEXT;
+UPDATE devices SET last_seen_at = created_at WHERE last_seen_at IS NULL;
+-- Keep only the most recent row per (user_id, device_fingerprint) so the unique index can be built.
+DELETE FROM devices
+WHERE rowid NOT IN (
+    SELECT rowid FROM (
+        SELECT rowid,
+               ROW_NUMBER() OVER (
+                   PARTITION BY user_id, device_fingerprint
+                   ORDER BY last_seen_at DESC, created_at DESC, rowid DESC
+               ) AS position
+        FROM devices
+    )
+    WHERE position = 1
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_devices_user_fingerprint ON devices(user_id, device_fingerprint);
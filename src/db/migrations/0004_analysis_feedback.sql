@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS risk_analyses (
+    id TEXT PRIMARY KEY NOT NULL,
+    requested_by TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    source TEXT NOT NULL,
+    risk_score REAL NOT NULL,
+    rules TEXT NOT NULL,
+    input TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS analysis_verdicts (
+    analysis_id TEXT PRIMARY KEY NOT NULL REFERENCES risk_analyses(id),
+    verdict TEXT NOT NULL,
+    analyst_id TEXT NOT NULL,
+    notes TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_risk_analyses_entity ON risk_analyses(entity_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_risk_analyses_source ON risk_analyses(source);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS biometric_templates (
+    kind TEXT NOT NULL,
+    template_key TEXT NOT NULL,
+    samples TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (kind, template_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_biometric_templates_updated ON biometric_templates(updated_at);
@@ -38,6 +38,7 @@
 ******************************************************************************************/
 
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 // ===================== نماذج البيانات الأساسية =====================
 // ===================== Core Data Models =====================
@@ -59,8 +60,54 @@ pub struct User {
     pub last_login_at: Option<chrono::NaiveDateTime>,
 }
 
+/// Arabic: حالة الثقة في جهاز مسجل. الإلغاء نهائي: الجهاز الملغى لا يعود موثوقًا.
+/// English: Trust state of a registered device. Revocation is final: a revoked device never becomes trusted again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceTrustState {
+    New,
+    Trusted,
+    Suspicious,
+    Revoked,
+}
+
+impl DeviceTrustState {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Trusted => "trusted",
+            Self::Suspicious => "suspicious",
+            Self::Revoked => "revoked",
+        }
+    }
+
+    /// Arabic: هل الانتقال إلى الحالة المطلوبة مسموح؟ لا عودة إلى "جديد" ولا خروج من "ملغى".
+    /// English: Is the transition allowed? Nothing returns to `New` and nothing leaves `Revoked`.
+    #[must_use]
+    pub fn can_transition_to(self, next: Self) -> bool {
+        self == next || (self != Self::Revoked && next != Self::New)
+    }
+}
+
+impl FromStr for DeviceTrustState {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "new" => Ok(Self::New),
+            "trusted" => Ok(Self::Trusted),
+            "suspicious" => Ok(Self::Suspicious),
+            "revoked" => Ok(Self::Revoked),
+            _ => Err(()),
+        }
+    }
+}
+
 /// Arabic: يمثل جهازًا مسجلاً في النظام. كل جهاز له هوية فريدة خاصة به.
+/// `created_at` هو وقت أول ظهور للجهاز.
 /// English: Represents a registered device in the system. Each device has its own unique identity.
+/// `created_at` is when the device was first seen.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Device {
     pub id: uuid::Uuid,
@@ -69,6 +116,66 @@ pub struct Device {
     pub friendly_name: String,
     pub metadata: serde_json::Value,
     pub created_at: chrono::NaiveDateTime,
+    pub last_seen_at: chrono::NaiveDateTime,
+    pub trust_state: DeviceTrustState,
+}
+
+/// Arabic: حكم المحلل على قرار مخاطر مخزن.
+/// English: An analyst's verdict on a stored risk decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Fraud,
+    Legit,
+}
+
+impl Verdict {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Fraud => "fraud",
+            Self::Legit => "legit",
+        }
+    }
+}
+
+impl FromStr for Verdict {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fraud" => Ok(Self::Fraud),
+            "legit" => Ok(Self::Legit),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Arabic: قرار مخاطر مخزن من `BehaviorEngine` أو `CrossValidationEngine` ليُصنَّف لاحقاً.
+/// `rules` هي رموز حالات الشذوذ التي أطلقها القرار، و`input` هو مدخل السلوك كـ JSON.
+/// English: A stored risk decision from `BehaviorEngine` or `CrossValidationEngine`, to be labeled later.
+/// `rules` are the anomaly codes the decision fired and `input` is the behavior input as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskAnalysis {
+    pub id: uuid::Uuid,
+    pub requested_by: uuid::Uuid,
+    pub entity_id: String,
+    pub source: String,
+    pub risk_score: f32,
+    pub rules: Vec<String>,
+    pub input: serde_json::Value,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Arabic: حكم محلل على تحليل؛ حكم واحد لكل تحليل والأحدث يستبدل السابق.
+/// English: An analyst verdict on an analysis; one per analysis, the latest replaces the previous.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisVerdict {
+    pub analysis_id: uuid::Uuid,
+    pub verdict: Verdict,
+    pub analyst_id: uuid::Uuid,
+    pub notes: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
 }
 
 /// Arabic: يمثل سجلاً لموقع جغرافي تم التحقق منه وتوقيعه.
@@ -0,0 +1,887 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: ffi.rs
+    المسار:    src/ffi.rs
+    دور الملف:
+    واجهة C بمقابض معتمة طويلة العمر: يُنشأ المحرك ويُهيأ مرة واحدة عبر
+    `mkt_engine_create`، ثم تُستدعى محركات الموقع والبصمة والسلوك والشبكة والحساسات
+    والتحقق المتقاطع بمدخلات ومخرجات JSON (نفس أجسام طلبات HTTP)، وتعيد كل دالة
+    رمز حالة رقمياً مع رسالة آخر خطأ لكل خيط. يحمل كل مقبض مفاتيحه وبيئة تشغيل
+    tokio خاصة به فتبقى النتائج ثابتة بين الاستدعاءات.
+    --------------------------------------------------------------
+    File Name: ffi.rs
+    Path:     src/ffi.rs
+    File Role:
+    Long-lived opaque-handle C API: an engine is created and configured once through
+    `mkt_engine_create`, then the geo, fingerprint, behavior, network, sensors and
+    cross-validation engines are called with JSON in and out (the same bodies as the
+    HTTP requests). Every function returns a numeric status code, with a per-thread
+    last-error message. Each handle owns its keys and its own tokio runtime, so results
+    stay stable between calls.
+******************************************************************************************/
+
+use crate::api::behavior::BehaviorAnalyzeRequest;
+use crate::api::device::DeviceResolveRequest;
+use crate::api::geo::GeoResolveRequest;
+use crate::api::network::NetworkAnalyzeRequest;
+use crate::api::sensors::SensorsAnalyzeRequest;
+use crate::core::behavior_baseline::{BaselineBehavioralModel, BaselineConfig};
+use crate::core::behavior_bio::{BehaviorEngine, DefaultAnomalyDetector, DefaultBehavioralModel};
+use crate::core::behavior_pipeline::{BehaviorComponents, IpAttribution, PipelineConfig};
+use crate::core::client_fingerprint::{KnownClientDb, TransportFingerprintInput};
+use crate::core::cross_location::{
+    CrossValidationEngine, CrossValidationInput, DefaultScoringStrategy, ValidationResult,
+};
+use crate::core::device_fp::{
+    AdaptiveFingerprint, AdaptiveFingerprintEngine, DefaultAiProcessor, DefaultQuantumEngine,
+    DefaultSecurityMonitor, EnvironmentProfile,
+};
+use crate::core::geo_db::{GeoDbConfig, GeoDbManager};
+use crate::core::geo_resolver::{DefaultAiModel, DefaultBlockchain, GeoResolver, ResolveParams};
+use crate::core::keystroke::KeystrokeDynamicsModel;
+use crate::core::network_analyzer::{
+    ConnectionType, DefaultAiNetworkAnalyzer, NetworkAnalyzer, NetworkInfoProvider,
+};
+use crate::core::pointer::{PointerDynamicsModel, SyntheticPointerDetector};
+use crate::core::proxy_db::{ProxyDatabase, ProxyListSource};
+use crate::core::sensors_analyzer::{DefaultSensorAnomalyDetector, SensorsAnalyzerEngine};
+use crate::security::fingerprint_keys::FingerprintKeyring;
+use crate::security::secret::SecureBytes;
+use rand_core::{OsRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::net::IpAddr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use zeroize::Zeroizing;
+
+// ================================================================
+// رموز الحالة والأخطاء
+// Status codes and errors
+// ================================================================
+
+/// رمز الحالة الذي تعيده كل دوال `mkt_*`.
+/// Status code returned by every `mkt_*` function.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MktStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    InvalidJson = 3,
+    InvalidConfig = 4,
+    EngineError = 5,
+    Panic = 6,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum FfiError {
+    #[error("Null pointer passed for '{0}'")]
+    NullPointer(&'static str),
+    #[error("Argument is not valid UTF-8: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("Invalid JSON request: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Invalid engine configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Engine error: {0}")]
+    Engine(String),
+}
+
+impl FfiError {
+    pub(crate) fn engine(e: impl std::fmt::Display) -> Self {
+        Self::Engine(e.to_string())
+    }
+
+    pub(crate) fn config(e: impl std::fmt::Display) -> Self {
+        Self::InvalidConfig(e.to_string())
+    }
+
+    pub(crate) const fn status(&self) -> MktStatus {
+        match self {
+            Self::NullPointer(_) => MktStatus::NullPointer,
+            Self::InvalidUtf8(_) => MktStatus::InvalidUtf8,
+            Self::InvalidJson(_) => MktStatus::InvalidJson,
+            Self::InvalidConfig(_) => MktStatus::InvalidConfig,
+            Self::Engine(_) => MktStatus::EngineError,
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: Option<String>) {
+    let message = message.map(|m| {
+        CString::new(m.replace('\0', " ")).unwrap_or_else(|_| CString::from(c"unknown error"))
+    });
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = message);
+}
+
+// ================================================================
+// إعدادات المحرك
+// Engine configuration
+// ================================================================
+
+/// إعدادات `mkt_engine_create` بصيغة JSON؛ كل الحقول اختيارية.
+/// `mkt_engine_create` configuration as JSON; every field is optional.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    /// سر رئيسي hex (32 بايت على الأقل) تُشتق منه مفاتيح توقيع المحركات.
+    /// بدونه تُولد مفاتيح عشوائية لعمر المقبض.
+    /// Hex master secret (at least 32 bytes) the engines' signing keys are derived from.
+    /// Without it random keys are generated for the lifetime of the handle.
+    pub secret_key: Option<String>,
+    /// مفاتيح البصمة بصيغة `id:hex,id:hex` لبصمات ثابتة عبر العمليات.
+    /// Fingerprint keys as `id:hex,id:hex` for fingerprints that are stable across processes.
+    pub fingerprint_keys: Option<String>,
+    pub active_fingerprint_key_id: Option<u32>,
+    pub geo_db: GeoDbConfig,
+    /// قوائم البروكسي بصيغة `format:category:path`.
+    /// Proxy lists as `format:category:path`.
+    pub proxy_list_sources: Vec<String>,
+    pub known_clients_path: Option<PathBuf>,
+    pub behavior_history_limit: usize,
+    pub max_speed_kmh: f64,
+    pub behavior_baseline: BaselineConfig,
+    /// ملف إعدادات خط الكواشف والنماذج؛ يستبدل `max_speed_kmh` و`behavior_baseline`.
+    /// Detector/model pipeline config file; replaces `max_speed_kmh` and `behavior_baseline`.
+    pub behavior_pipeline_path: Option<PathBuf>,
+    pub location_weight: f32,
+    pub fingerprint_weight: f32,
+    pub behavior_weight: f32,
+    pub enforce_processing_budget: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            secret_key: None,
+            fingerprint_keys: None,
+            active_fingerprint_key_id: None,
+            geo_db: GeoDbConfig::default(),
+            proxy_list_sources: Vec::new(),
+            known_clients_path: None,
+            behavior_history_limit: 10,
+            max_speed_kmh: 1200.0,
+            behavior_baseline: BaselineConfig::default(),
+            behavior_pipeline_path: None,
+            location_weight: 0.4,
+            fingerprint_weight: 0.3,
+            behavior_weight: 0.3,
+            enforce_processing_budget: false,
+        }
+    }
+}
+
+/// مصدر مفاتيح التوقيع: سر رئيسي مشتق منه، أو عشوائي.
+/// Signing key source: derived from a master secret, or random.
+struct KeySource(Option<Zeroizing<Vec<u8>>>);
+
+impl KeySource {
+    fn key(&self, purpose: &str, len: usize) -> SecureBytes {
+        let mut bytes = vec![0_u8; len];
+        match &self.0 {
+            Some(master) => {
+                blake3::Hasher::new_derive_key(&format!("mkt_ksa_geo_sec ffi {purpose}"))
+                    .update(master)
+                    .finalize_xof()
+                    .fill(&mut bytes)
+            }
+            None => OsRng.fill_bytes(&mut bytes),
+        }
+        SecureBytes::new(bytes)
+    }
+}
+
+impl EngineConfig {
+    /// يبني كل المحركات من الإعدادات (يستخدمه مقبض C وربط Python).
+    /// Builds every engine from the configuration (used by the C handle and the Python binding).
+    pub(crate) fn build(&self) -> Result<CrossValidationEngine, FfiError> {
+        let master = self
+            .secret_key
+            .as_deref()
+            .map(|hex_key| {
+                hex::decode(hex_key.trim())
+                    .map(Zeroizing::new)
+                    .map_err(FfiError::config)
+            })
+            .transpose()?;
+        if master.as_ref().is_some_and(|m| m.len() < 32) {
+            return Err(FfiError::config("secret_key must be at least 32 bytes"));
+        }
+        let keys = KeySource(master);
+        let geo_db = Arc::new(GeoDbManager::load(&self.geo_db).map_err(FfiError::config)?);
+        let geo_reader = geo_db.city_reader();
+        let geo_resolver = Arc::new(GeoResolver::new(
+            keys.key("geo", 32),
+            Arc::new(DefaultAiModel),
+            Arc::new(DefaultBlockchain),
+            true,
+            false,
+            Arc::clone(&geo_reader),
+        ));
+
+        let fp_engine = AdaptiveFingerprintEngine::new(
+            Arc::new(DefaultSecurityMonitor::new()),
+            Arc::new(DefaultQuantumEngine::new().map_err(FfiError::engine)?),
+            Arc::new(DefaultAiProcessor),
+            Arc::new(RwLock::new(EnvironmentProfile::defaults())),
+        )
+        .with_processing_budget_enforcement(self.enforce_processing_budget);
+        let fp_engine = match &self.fingerprint_keys {
+            Some(spec) => fp_engine.with_fingerprint_keys(Arc::new(
+                FingerprintKeyring::from_spec(spec, self.active_fingerprint_key_id)
+                    .map_err(FfiError::config)?,
+            )),
+            None => fp_engine,
+        };
+        let fp_engine = match &self.known_clients_path {
+            Some(path) => fp_engine.with_known_clients(Arc::new(
+                KnownClientDb::load_file(path).map_err(FfiError::config)?,
+            )),
+            None => fp_engine,
+        };
+
+        let (behavior_model, behavior_detector): BehaviorComponents =
+            match &self.behavior_pipeline_path {
+                Some(path) => {
+                    let attribution: Arc<dyn IpAttribution> = Arc::clone(&geo_db) as _;
+                    PipelineConfig::load_file(path)
+                        .and_then(|config| config.build(Some(attribution)))
+                        .map_err(FfiError::config)?
+                }
+                None => {
+                    let baseline =
+                        BaselineBehavioralModel::new(Arc::new(DefaultBehavioralModel::default()))
+                            .with_config(self.behavior_baseline);
+                    (
+                        Arc::new(PointerDynamicsModel::new(Arc::new(
+                            KeystrokeDynamicsModel::new(Arc::new(baseline)),
+                        ))),
+                        Arc::new(SyntheticPointerDetector::new(Arc::new(
+                            DefaultAnomalyDetector {
+                                max_speed_kmh: self.max_speed_kmh,
+                            },
+                        ))),
+                    )
+                }
+            };
+        let behavior_engine = Arc::new(BehaviorEngine::new(
+            behavior_model,
+            behavior_detector,
+            self.behavior_history_limit,
+        ));
+        let sensors_engine = Arc::new(SensorsAnalyzerEngine::new(
+            keys.key("sensors", 48),
+            Arc::new(DefaultSensorAnomalyDetector::default()),
+        ));
+
+        let proxy_db = ProxyDatabase::with_sources(
+            self.proxy_list_sources
+                .iter()
+                .map(|spec| ProxyListSource::parse_spec(spec))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(FfiError::config)?,
+        );
+        if !proxy_db.sources().is_empty() {
+            proxy_db.reload().map_err(FfiError::config)?;
+        }
+        let network_engine = Arc::new(
+            NetworkAnalyzer::new(
+                keys.key("network", 32),
+                Arc::new(RwLock::new(proxy_db)),
+                geo_reader,
+                Arc::new(DefaultAiNetworkAnalyzer),
+            )
+            .with_geo_databases(geo_db),
+        );
+
+        Ok(CrossValidationEngine::new(
+            geo_resolver,
+            Arc::new(fp_engine),
+            behavior_engine,
+            sensors_engine,
+            network_engine,
+            Arc::new(DefaultScoringStrategy {
+                location_weight: self.location_weight,
+                fingerprint_weight: self.fingerprint_weight,
+                behavior_weight: self.behavior_weight,
+            }),
+            keys.key("verdict", 32),
+        ))
+    }
+}
+
+// ================================================================
+// المقبض المعتم
+// The opaque handle
+// ================================================================
+
+/// مقبض معتم يملك كل المحركات وبيئة تشغيل tokio.
+/// Opaque handle owning every engine and a tokio runtime.
+pub struct MktEngine {
+    runtime: tokio::runtime::Runtime,
+    engines: CrossValidationEngine,
+}
+
+impl MktEngine {
+    fn new(config: &EngineConfig) -> Result<Self, FfiError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(FfiError::engine)?;
+        Ok(Self {
+            runtime,
+            engines: config.build()?,
+        })
+    }
+
+    fn fingerprint(&self, request: FingerprintRequest) -> Result<impl Serialize, FfiError> {
+        self.runtime
+            .block_on(generate_fingerprint(&self.engines.fp_engine, &request))
+    }
+
+    fn cross_validate(&self, request: GeoResolveRequest) -> Result<impl Serialize, FfiError> {
+        self.runtime
+            .block_on(cross_validate(&self.engines, &request))
+    }
+}
+
+/// جسم `POST /api/device/resolve` مع بصمة النقل. المضيف المضمّن هو من ينهي TLS،
+/// لذا يُقبل منه `transport` مباشرة بخلاف عملاء HTTP.
+/// A `POST /api/device/resolve` body plus the transport fingerprint. The embedding host
+/// terminates TLS itself, so unlike HTTP clients it may pass `transport` directly.
+#[derive(Deserialize)]
+pub struct FingerprintRequest {
+    #[serde(flatten)]
+    pub device: DeviceResolveRequest,
+    #[serde(default)]
+    pub transport: Option<TransportFingerprintInput>,
+}
+
+/// يولد البصمة من جسم `FingerprintRequest` ويقيّم بصمة النقل إن وُجدت.
+/// Generates the fingerprint from a `FingerprintRequest` body and assesses the transport
+/// fingerprint when present.
+pub(crate) async fn generate_fingerprint(
+    engine: &AdaptiveFingerprintEngine,
+    request: &FingerprintRequest,
+) -> Result<AdaptiveFingerprint, FfiError> {
+    let transport = &request.transport;
+    let request = &request.device;
+    let mut fingerprint = match &request.attributes {
+        Some(attributes) => {
+            engine
+                .generate_fingerprint_from_attributes(attributes)
+                .await
+        }
+        None => {
+            engine
+                .generate_fingerprint(&request.os, &request.device_info, &request.environment_data)
+                .await
+        }
+    }
+    .map_err(FfiError::engine)?;
+    if let Some(transport) = transport {
+        let claimed = request
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.browser.as_ref())
+            .map(|browser| browser.name.as_str());
+        engine
+            .assess_transport(&mut fingerprint, claimed, transport)
+            .map_err(FfiError::engine)?;
+    }
+    Ok(fingerprint)
+}
+
+/// التحقق المتقاطع من جسم `POST /api/geo/resolve`.
+/// Cross-validation from a `POST /api/geo/resolve` body.
+pub(crate) async fn cross_validate(
+    engines: &CrossValidationEngine,
+    request: &GeoResolveRequest,
+) -> Result<ValidationResult, FfiError> {
+    let input = CrossValidationInput {
+        ip_address: request.ip_address,
+        gps_data: request.gps_data,
+        sim_country: request.sim_country.as_deref(),
+        locale: request.locale.as_deref(),
+        os_info: &request.os_info,
+        device_details: &request.device_details,
+        environment_context: &request.environment_context,
+        behavior_input: request.behavior_input.clone(),
+    };
+    engines.validate(input).await.map_err(FfiError::engine)
+}
+
+/// طلب حل الموقع وحده (بدون التحقق المتقاطع).
+/// Request to resolve the location alone (without cross-validation).
+#[derive(Debug, Deserialize)]
+pub struct GeoLookupRequest {
+    #[serde(default)]
+    pub ip_address: Option<IpAddr>,
+    #[serde(default)]
+    pub gps_data: Option<(f64, f64, u8, f64)>,
+}
+
+struct FfiNetworkProvider {
+    ip: IpAddr,
+    conn_type: ConnectionType,
+}
+
+#[async_trait::async_trait]
+impl NetworkInfoProvider for FfiNetworkProvider {
+    async fn get_connection_type(&self) -> ConnectionType {
+        self.conn_type.clone()
+    }
+    async fn get_public_ip(&self) -> Option<IpAddr> {
+        Some(self.ip)
+    }
+}
+
+// ================================================================
+// أدوات التمرير عبر الحدود
+// Boundary helpers
+// ================================================================
+
+/// # Safety
+/// `ptr` فارغ أو سلسلة C صالحة.
+/// `ptr` is null or a valid C string.
+unsafe fn read_str<'a>(ptr: *const c_char, name: &'static str) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::NullPointer(name));
+    }
+    Ok(unsafe { CStr::from_ptr(ptr) }.to_str()?)
+}
+
+/// # Safety
+/// `ptr` فارغ أو سلسلة C صالحة.
+/// `ptr` is null or a valid C string.
+unsafe fn read_json<T: DeserializeOwned>(ptr: *const c_char) -> Result<T, FfiError> {
+    Ok(serde_json::from_str(unsafe {
+        read_str(ptr, "request_json")
+    }?)?)
+}
+
+/// # Safety
+/// `engine` فارغ أو مقبض من `mkt_engine_create` لم يُحرر.
+/// `engine` is null or a live handle from `mkt_engine_create`.
+unsafe fn engine_ref<'a>(engine: *const MktEngine) -> Result<&'a MktEngine, FfiError> {
+    unsafe { engine.as_ref() }.ok_or(FfiError::NullPointer("engine"))
+}
+
+/// ينفذ الاستدعاء ويلتقط الذعر ويكتب JSON النتيجة في `out_json` ويحدّث آخر خطأ.
+/// Runs the call, catches panics, writes the result JSON to `out_json` and updates the last error.
+///
+/// # Safety
+/// `out_json` فارغ أو مؤشر صالح للكتابة.
+/// `out_json` is null or valid for writes.
+unsafe fn json_call<T: Serialize>(
+    out_json: *mut *mut c_char,
+    call: impl FnOnce() -> Result<T, FfiError>,
+) -> MktStatus {
+    if out_json.is_null() {
+        set_last_error(Some(FfiError::NullPointer("out_json").to_string()));
+        return MktStatus::NullPointer;
+    }
+    unsafe { *out_json = std::ptr::null_mut() };
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let value = call()?;
+        let json = serde_json::to_string(&value).map_err(FfiError::engine)?;
+        CString::new(json).map_err(FfiError::engine)
+    }));
+    match result {
+        Ok(Ok(json)) => {
+            set_last_error(None);
+            unsafe { *out_json = json.into_raw() };
+            MktStatus::Ok
+        }
+        Ok(Err(e)) => {
+            set_last_error(Some(e.to_string()));
+            e.status()
+        }
+        Err(_) => {
+            set_last_error(Some("Panic inside the engine".to_string()));
+            MktStatus::Panic
+        }
+    }
+}
+
+// ================================================================
+// الدوال المصدرة
+// Exported functions
+// ================================================================
+
+/// ينشئ مقبض محرك من إعدادات JSON (`NULL` للإعدادات الافتراضية).
+/// Creates an engine handle from a JSON configuration (`NULL` for the defaults).
+///
+/// # Safety
+/// `config_json` فارغ أو سلسلة C صالحة، و `out_engine` مؤشر صالح للكتابة.
+/// المقبض يُحرر مرة واحدة فقط عبر `mkt_engine_destroy`.
+/// `config_json` is null or a valid C string and `out_engine` is valid for writes.
+/// The handle must be freed exactly once with `mkt_engine_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_engine_create(
+    config_json: *const c_char,
+    out_engine: *mut *mut MktEngine,
+) -> MktStatus {
+    if out_engine.is_null() {
+        set_last_error(Some(FfiError::NullPointer("out_engine").to_string()));
+        return MktStatus::NullPointer;
+    }
+    unsafe { *out_engine = std::ptr::null_mut() };
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let config = if config_json.is_null() {
+            EngineConfig::default()
+        } else {
+            unsafe { read_json(config_json) }?
+        };
+        MktEngine::new(&config)
+    }));
+    match result {
+        Ok(Ok(engine)) => {
+            set_last_error(None);
+            unsafe { *out_engine = Box::into_raw(Box::new(engine)) };
+            MktStatus::Ok
+        }
+        Ok(Err(e)) => {
+            set_last_error(Some(e.to_string()));
+            e.status()
+        }
+        Err(_) => {
+            set_last_error(Some("Panic while creating the engine".to_string()));
+            MktStatus::Panic
+        }
+    }
+}
+
+/// يعيد تهيئة مقبض قائم بإعدادات جديدة؛ عند الفشل يبقى المقبض بإعداداته السابقة.
+/// يُصفّر تاريخ السلوك، ولا يجوز أن يجري أي استدعاء آخر على المقبض نفسه أثناءه.
+/// Reconfigures a live handle; on failure the handle keeps its previous configuration.
+/// Behavior history is reset, and no other call may run on the same handle meanwhile.
+///
+/// # Safety
+/// `engine` مقبض حي من `mkt_engine_create` و `config_json` فارغ أو سلسلة C صالحة.
+/// `engine` is a live handle from `mkt_engine_create` and `config_json` is null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_engine_configure(
+    engine: *mut MktEngine,
+    config_json: *const c_char,
+) -> MktStatus {
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        set_last_error(Some(FfiError::NullPointer("engine").to_string()));
+        return MktStatus::NullPointer;
+    };
+    let mut fresh: *mut MktEngine = std::ptr::null_mut();
+    let status = unsafe { mkt_engine_create(config_json, &mut fresh) };
+    if status == MktStatus::Ok {
+        *engine = *unsafe { Box::from_raw(fresh) };
+    }
+    status
+}
+
+/// يحرر مقبض المحرك (تجاهل `NULL`).
+/// Frees an engine handle (`NULL` is ignored).
+///
+/// # Safety
+/// `engine` فارغ أو مقبض من `mkt_engine_create` لم يُحرر بعد.
+/// `engine` is null or a handle from `mkt_engine_create` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_engine_destroy(engine: *mut MktEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// يولد بصمة الجهاز؛ الطلب بصيغة جسم `POST /api/device/resolve`.
+/// Generates a device fingerprint; the request has the `POST /api/device/resolve` body.
+///
+/// # Safety
+/// `engine` مقبض حي، `request_json` سلسلة C صالحة، و `out_json` صالح للكتابة؛
+/// النتيجة تُحرر عبر `mkt_string_free`.
+/// `engine` is a live handle, `request_json` a valid C string and `out_json` valid for
+/// writes; the result is freed with `mkt_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_fingerprint_generate(
+    engine: *const MktEngine,
+    request_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> MktStatus {
+    unsafe {
+        json_call(out_json, || {
+            engine_ref(engine)?.fingerprint(read_json(request_json)?)
+        })
+    }
+}
+
+/// يحل الموقع من `{ip_address, gps_data}`.
+/// Resolves a location from `{ip_address, gps_data}`.
+///
+/// # Safety
+/// مثل `mkt_fingerprint_generate`.
+/// Same as `mkt_fingerprint_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_geo_resolve(
+    engine: *const MktEngine,
+    request_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> MktStatus {
+    unsafe {
+        json_call(out_json, || {
+            let engine = engine_ref(engine)?;
+            let request: GeoLookupRequest = read_json(request_json)?;
+            engine
+                .runtime
+                .block_on(engine.engines.geo_resolver.resolve(ResolveParams {
+                    ip: request.ip_address,
+                    gps: request.gps_data,
+                    sim_location: None,
+                    satellite_location: None,
+                    indoor_data: None,
+                    ar_data: None,
+                    mfa_token: None,
+                }))
+                .map_err(FfiError::engine)
+        })
+    }
+}
+
+/// يحلل السلوك؛ الطلب بصيغة جسم `POST /api/behavior/analyze`.
+/// Analyzes behavior; the request has the `POST /api/behavior/analyze` body.
+///
+/// # Safety
+/// مثل `mkt_fingerprint_generate`.
+/// Same as `mkt_fingerprint_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_behavior_analyze(
+    engine: *const MktEngine,
+    request_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> MktStatus {
+    unsafe {
+        json_call(out_json, || {
+            let engine = engine_ref(engine)?;
+            let request: BehaviorAnalyzeRequest = read_json(request_json)?;
+            engine
+                .runtime
+                .block_on(engine.engines.behavior_engine.process(request.input))
+                .map_err(FfiError::engine)
+        })
+    }
+}
+
+/// يحلل الشبكة؛ الطلب بصيغة جسم `POST /api/network/analyze`.
+/// Analyzes the network; the request has the `POST /api/network/analyze` body.
+///
+/// # Safety
+/// مثل `mkt_fingerprint_generate`.
+/// Same as `mkt_fingerprint_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_network_analyze(
+    engine: *const MktEngine,
+    request_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> MktStatus {
+    unsafe {
+        json_call(out_json, || {
+            let engine = engine_ref(engine)?;
+            let request: NetworkAnalyzeRequest = read_json(request_json)?;
+            let provider = FfiNetworkProvider {
+                ip: request.ip,
+                conn_type: request.conn_type,
+            };
+            engine
+                .runtime
+                .block_on(engine.engines.network_engine.analyze(&provider))
+                .map_err(FfiError::engine)
+        })
+    }
+}
+
+/// يحلل قراءة حساس؛ الطلب بصيغة جسم `POST /api/sensors/analyze`.
+/// Analyzes a sensor reading; the request has the `POST /api/sensors/analyze` body.
+///
+/// # Safety
+/// مثل `mkt_fingerprint_generate`.
+/// Same as `mkt_fingerprint_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_sensors_analyze(
+    engine: *const MktEngine,
+    request_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> MktStatus {
+    unsafe {
+        json_call(out_json, || {
+            let engine = engine_ref(engine)?;
+            let request: SensorsAnalyzeRequest = read_json(request_json)?;
+            engine
+                .runtime
+                .block_on(
+                    engine
+                        .engines
+                        .sensors_engine
+                        .analyze(request.reading, &request.history),
+                )
+                .map_err(FfiError::engine)
+        })
+    }
+}
+
+/// التحقق المتقاطع الكامل؛ الطلب بصيغة جسم `POST /api/geo/resolve`.
+/// Full cross-validation; the request has the `POST /api/geo/resolve` body.
+///
+/// # Safety
+/// مثل `mkt_fingerprint_generate`.
+/// Same as `mkt_fingerprint_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_cross_validate(
+    engine: *const MktEngine,
+    request_json: *const c_char,
+    out_json: *mut *mut c_char,
+) -> MktStatus {
+    unsafe {
+        json_call(out_json, || {
+            engine_ref(engine)?.cross_validate(read_json(request_json)?)
+        })
+    }
+}
+
+/// رسالة آخر خطأ على هذا الخيط، أو `NULL` إذا نجح آخر استدعاء.
+/// المؤشر يبقى صالحاً حتى الاستدعاء التالي لأي دالة `mkt_*` على الخيط نفسه ولا يُحرر.
+/// Message of the last error on this thread, or `NULL` if the last call succeeded.
+/// The pointer stays valid until the next `mkt_*` call on the same thread and must not be freed.
+#[no_mangle]
+pub extern "C" fn mkt_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|slot| {
+        slot.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |m| m.as_ptr())
+    })
+}
+
+/// يحرر سلسلة JSON أعادتها دالة `mkt_*`.
+/// Frees a JSON string returned by a `mkt_*` function.
+///
+/// # Safety
+/// `ptr` فارغ أو سلسلة من `out_json` لم تُحرر بعد.
+/// `ptr` is null or a string from `out_json` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mkt_string_free(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        drop(unsafe { CString::from_raw(ptr) });
+    }
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(
+        f: unsafe extern "C" fn(*const MktEngine, *const c_char, *mut *mut c_char) -> MktStatus,
+        engine: *const MktEngine,
+        request: &str,
+    ) -> (MktStatus, Option<serde_json::Value>) {
+        let request = CString::new(request).unwrap();
+        let mut out = std::ptr::null_mut();
+        let status = unsafe { f(engine, request.as_ptr(), &mut out) };
+        let value = (!out.is_null()).then(|| {
+            let json = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+            unsafe { mkt_string_free(out) };
+            serde_json::from_str(&json).unwrap()
+        });
+        (status, value)
+    }
+
+    fn create(config: &str) -> *mut MktEngine {
+        let config = CString::new(config).unwrap();
+        let mut engine = std::ptr::null_mut();
+        assert_eq!(
+            unsafe { mkt_engine_create(config.as_ptr(), &mut engine) },
+            MktStatus::Ok
+        );
+        engine
+    }
+
+    const DEVICE: &str = r#"{"os":"Android","device_info":"Pixel-8","environment_data":"wifi"}"#;
+
+    #[test]
+    fn test_handle_gives_stable_results_across_calls_and_handles() {
+        let config = r#"{"secret_key":"0101010101010101010101010101010101010101010101010101010101010101",
+            "fingerprint_keys":"1:0202020202020202020202020202020202020202020202020202020202020202",
+            "enforce_processing_budget":false}"#;
+        let first = create(config);
+        let second = create(config);
+
+        let (status, a) = call(mkt_fingerprint_generate, first, DEVICE);
+        assert_eq!(status, MktStatus::Ok);
+        let (_, b) = call(mkt_fingerprint_generate, first, DEVICE);
+        let (_, c) = call(mkt_fingerprint_generate, second, DEVICE);
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        assert_eq!(a["base_fp"], b["base_fp"]);
+        assert_eq!(a["base_fp"], c["base_fp"]);
+        assert_eq!(a["key_id"], 1);
+        assert!(mkt_last_error_message().is_null());
+
+        let behavior = r#"{"input":{"entity_id":"u1","timestamp":"2026-01-01T00:00:00Z",
+            "location":[24.7,46.7],"network_info":{"ip_address":"10.0.0.1","is_vpn":false,
+            "connection_type":"WiFi"},"device_fingerprint":"fp"}}"#;
+        let (status, result) = call(mkt_behavior_analyze, first, behavior);
+        assert_eq!(status, MktStatus::Ok);
+        assert!(result.unwrap().is_object());
+
+        unsafe {
+            mkt_engine_destroy(first);
+            mkt_engine_destroy(second);
+        }
+    }
+
+    #[test]
+    fn test_errors_return_codes_and_last_error_message() {
+        let engine = create("{}");
+
+        let (status, out) = call(mkt_fingerprint_generate, engine, "{not json");
+        assert_eq!(status, MktStatus::InvalidJson);
+        assert!(out.is_none());
+        let message = unsafe { CStr::from_ptr(mkt_last_error_message()) };
+        assert!(message
+            .to_str()
+            .unwrap()
+            .starts_with("Invalid JSON request"));
+
+        let (status, _) = call(mkt_geo_resolve, std::ptr::null(), "{}");
+        assert_eq!(status, MktStatus::NullPointer);
+
+        let bad_config = CString::new(r#"{"secret_key":"00ff"}"#).unwrap();
+        assert_eq!(
+            unsafe { mkt_engine_configure(engine, bad_config.as_ptr()) },
+            MktStatus::InvalidConfig
+        );
+        let unknown_field = CString::new(r#"{"secret":"x"}"#).unwrap();
+        assert_eq!(
+            unsafe { mkt_engine_configure(engine, unknown_field.as_ptr()) },
+            MktStatus::InvalidJson
+        );
+        // المقبض يبقى صالحاً بعد فشل إعادة التهيئة
+        // The handle stays usable after a failed reconfiguration
+        let (status, _) = call(mkt_fingerprint_generate, engine, DEVICE);
+        assert_eq!(status, MktStatus::Ok);
+
+        unsafe { mkt_engine_destroy(engine) };
+    }
+}
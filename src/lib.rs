@@ -23,6 +23,9 @@ Supported crate types:
 pub mod api;
 pub mod core;
 pub mod db;
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod security;
 pub mod utils;
 
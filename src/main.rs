@@ -48,87 +48,21 @@ use actix_web::{
 };
 use config::Config;
 use config::Environment;
-use maxminddb::Reader;
 use mkt_ksa_geo_sec::core::weather_val::{OpenMeteoProvider, WeatherEngine, WeatherProvider};
 use mkt_ksa_geo_sec::db::crud;
 use mkt_ksa_geo_sec::db::models::User;
 use mkt_ksa_geo_sec::security::ai_guard::AiGuardConfig;
 use mkt_ksa_geo_sec::security::ai_guard::RequestAiGuard;
 use mkt_ksa_geo_sec::security::jwt::JwtManager;
+use mkt_ksa_geo_sec::security::keyring::IpKeyring;
 use mkt_ksa_geo_sec::security::ratelimit::RateLimitConfig;
 use mkt_ksa_geo_sec::security::ratelimit::RateLimiter;
 
-/// Build the default fingerprint environment profiles map.
-///
-/// This centralizes the default configuration for the different device
-/// categories (mobile, desktop, IoT, server) so that it can be reused
-/// consistently from a single place.
-fn build_default_fp_env_profiles(
-) -> std::collections::HashMap<String, mkt_ksa_geo_sec::core::device_fp::EnvironmentProfile> {
-    let mut fp_env_profiles = std::collections::HashMap::<
-        String,
-        mkt_ksa_geo_sec::core::device_fp::EnvironmentProfile,
-    >::new();
-
-    fp_env_profiles.insert(
-        "mobile".to_string(),
-        mkt_ksa_geo_sec::core::device_fp::EnvironmentProfile {
-            os_type: "Mobile".to_string(),
-            device_category: "Phone/Tablet".to_string(),
-            threat_level: 6,
-            resource_constraints: mkt_ksa_geo_sec::core::device_fp::ResourceConstraints {
-                max_memory_kb: 512,
-                max_processing_us: 5_000,
-            },
-        },
-    );
-
-    fp_env_profiles.insert(
-        "desktop".to_string(),
-        mkt_ksa_geo_sec::core::device_fp::EnvironmentProfile {
-            os_type: "Desktop".to_string(),
-            device_category: "PC/Workstation".to_string(),
-            threat_level: 4,
-            resource_constraints: mkt_ksa_geo_sec::core::device_fp::ResourceConstraints {
-                max_memory_kb: 2_048,
-                max_processing_us: 10_000,
-            },
-        },
-    );
-
-    fp_env_profiles.insert(
-        "iot".to_string(),
-        mkt_ksa_geo_sec::core::device_fp::EnvironmentProfile {
-            os_type: "IoT".to_string(),
-            device_category: "Embedded".to_string(),
-            threat_level: 7,
-            resource_constraints: mkt_ksa_geo_sec::core::device_fp::ResourceConstraints {
-                max_memory_kb: 256,
-                max_processing_us: 4_000,
-            },
-        },
-    );
-
-    fp_env_profiles.insert(
-        "server".to_string(),
-        mkt_ksa_geo_sec::core::device_fp::EnvironmentProfile {
-            os_type: "Server".to_string(),
-            device_category: "Datacenter Node".to_string(),
-            threat_level: 8,
-            resource_constraints: mkt_ksa_geo_sec::core::device_fp::ResourceConstraints {
-                max_memory_kb: 8_192,
-                max_processing_us: 15_000,
-            },
-        },
-    );
-
-    fp_env_profiles
-}
+use mkt_ksa_geo_sec::security::fingerprint_keys::FingerprintKeyring;
 use mkt_ksa_geo_sec::security::secret::SecureBytes;
 use mkt_ksa_geo_sec::security::secret::SecureString;
 use rand_core::OsRng;
 use rand_core::RngCore;
-use std::collections::HashMap;
 use std::collections::HashSet;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
@@ -138,20 +72,38 @@ use tokio::sync::RwLock;
 
 // --- استيراد شامل لجميع المحركات وتبعياتها ---
 // --- Comprehensive import of all engines and their dependencies ---
+use mkt_ksa_geo_sec::core::account_correlation::AccountCorrelator;
+use mkt_ksa_geo_sec::core::behavior_baseline::BaselineBehavioralModel;
 use mkt_ksa_geo_sec::core::behavior_bio::{
-    BehaviorEngine, DefaultAnomalyDetector, DefaultBehavioralModel,
+    BehaviorEngine, BehaviorInput, BehavioralModel, DefaultAnomalyDetector, DefaultBehavioralModel,
 };
+use mkt_ksa_geo_sec::core::behavior_calendar::ActivityCalendar;
+use mkt_ksa_geo_sec::core::behavior_feedback::{FeedbackAwareModel, LabeledHistory};
+use mkt_ksa_geo_sec::core::behavior_pipeline::{BehaviorComponents, IpAttribution, PipelineConfig};
+use mkt_ksa_geo_sec::core::biometric_template::{SqliteTemplateBackend, TemplateBackend};
+use mkt_ksa_geo_sec::core::client_fingerprint::KnownClientDb;
 use mkt_ksa_geo_sec::core::composite_verification::CompositeVerifier;
 use mkt_ksa_geo_sec::core::cross_location::{CrossValidationEngine, DefaultScoringStrategy};
 use mkt_ksa_geo_sec::core::device_fp::{
     AdaptiveFingerprintEngine, DefaultAiProcessor as FpAiProcessor, DefaultQuantumEngine,
-    DefaultSecurityMonitor,
+    DefaultSecurityMonitor, EnvironmentProfile, FingerprintError, SecurityMonitor,
 };
+use mkt_ksa_geo_sec::core::forwarding_chain::ForwardingChainAnalyzer;
+use mkt_ksa_geo_sec::core::geo_db::{GeoDbConfig, GeoDbManager};
 use mkt_ksa_geo_sec::core::geo_resolver::{
     DefaultAiModel as GeoAiModel, DefaultBlockchain, GeoResolver,
 };
-use mkt_ksa_geo_sec::core::network_analyzer::NetworkAnalyzer;
+use mkt_ksa_geo_sec::core::hosting_ranges::{HostingRangeClassifier, HostingRangeFormat};
+use mkt_ksa_geo_sec::core::keystroke::{KeystrokeDynamicsModel, KeystrokeProfiles};
+use mkt_ksa_geo_sec::core::location_consistency::LocationConsistencyChecker;
+use mkt_ksa_geo_sec::core::network_analyzer::{IpProtectionMode, NetworkAnalyzer};
+use mkt_ksa_geo_sec::core::pointer::{
+    PointerDynamicsModel, PointerProfiles, SyntheticPointerDetector,
+};
+use mkt_ksa_geo_sec::core::proxy_db::{ProxyDatabase, ProxyListSource};
 use mkt_ksa_geo_sec::core::sensors_analyzer::SensorsAnalyzerEngine;
+use mkt_ksa_geo_sec::core::tor_directory::{TorDirectory, TorDocumentFormat};
+use mkt_ksa_geo_sec::security::threat_signatures::ThreatSignatureError;
 // إذا فعّلت النسخة من GitHub استخدم:
 // use crate::security::ratelimit::rate_limiter_dynamic;
 
@@ -196,6 +148,99 @@ fn env_usize_or_default(name: &str, default: usize) -> usize {
         .unwrap_or(default)
 }
 
+fn env_bool_or_default(name: &str, default: bool) -> bool {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse::<bool>().ok())
+        .unwrap_or(default)
+}
+
+// Arabic: تحميل نطاقات مزودي الاستضافة من الملفات المحددة في متغيرات البيئة
+// English: Load hosting-provider ranges from the files named in environment variables
+fn load_hosting_ranges() -> std::io::Result<HostingRangeClassifier> {
+    let mut classifier = HostingRangeClassifier::new();
+    let mut sources: Vec<(HostingRangeFormat, String, String)> = [
+        ("HOSTING_AWS_RANGES_PATH", HostingRangeFormat::Aws, "aws"),
+        ("HOSTING_GCP_RANGES_PATH", HostingRangeFormat::Gcp, "gcp"),
+        (
+            "HOSTING_AZURE_RANGES_PATH",
+            HostingRangeFormat::Azure,
+            "azure",
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(var, format, provider)| {
+        std::env::var(var)
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+            .map(|path| (format, provider.to_string(), path))
+    })
+    .collect();
+    // صيغة: "provider=path,provider=path"
+    // Format: "provider=path,provider=path"
+    if let Ok(lists) = std::env::var("HOSTING_CIDR_LIST_PATHS") {
+        for entry in lists.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (provider, path) = entry.split_once('=').ok_or_else(|| {
+                io_invalid_input(format!(
+                    "HOSTING_CIDR_LIST_PATHS entry '{entry}' must be provider=path"
+                ))
+            })?;
+            sources.push((
+                HostingRangeFormat::CidrList,
+                provider.trim().to_string(),
+                path.trim().to_string(),
+            ));
+        }
+    }
+    for (format, provider, path) in sources {
+        let added = classifier
+            .load_file(format, &provider, std::path::Path::new(&path))
+            .map_err(|e| io_invalid_data(e.to_string()))?;
+        println!("Loaded {added} hosting ranges for '{provider}' from {path}");
+    }
+    Ok(classifier)
+}
+
+// Arabic: تحميل مستندات دليل Tor (الإجماع/قائمة الخروج/الواصفات) من المسارات المحددة
+// English: Load Tor directory documents (consensus/exit list/descriptors) from the configured paths
+fn load_tor_directory() -> std::io::Result<TorDirectory> {
+    let mut directory = TorDirectory::new();
+    for (var, format) in [
+        ("TOR_CONSENSUS_PATH", TorDocumentFormat::Consensus),
+        ("TOR_EXIT_LIST_PATH", TorDocumentFormat::ExitList),
+        ("TOR_DESCRIPTORS_PATH", TorDocumentFormat::ServerDescriptors),
+    ] {
+        let Some(path) = std::env::var(var).ok().filter(|p| !p.trim().is_empty()) else {
+            continue;
+        };
+        let relays = directory
+            .load_file(format, std::path::Path::new(&path))
+            .map_err(|e| io_invalid_data(format!("{var}: {e}")))?;
+        println!("Loaded {relays} Tor relays from {path}");
+    }
+    Ok(directory)
+}
+
+/// Arabic: يبني مراقب الأمان مع مفتاح ملفات التوقيعات وحد خطورة الرفض.
+/// English: Builds the security monitor with the signature-file key and block severity.
+fn build_security_monitor() -> std::io::Result<DefaultSecurityMonitor> {
+    let monitor = DefaultSecurityMonitor::new()
+        .with_block_severity(env_u8_or_default("THREAT_BLOCK_SEVERITY", 7));
+    let Some(key) = std::env::var("THREAT_SIGNATURES_KEY")
+        .ok()
+        .filter(|k| !k.trim().is_empty())
+    else {
+        return Ok(monitor);
+    };
+    let key = hex::decode(key.trim())
+        .ok()
+        .filter(|k| k.len() >= 32)
+        .ok_or_else(|| {
+            io_invalid_input("THREAT_SIGNATURES_KEY must be at least 32 hex-encoded bytes")
+        })?;
+    Ok(monitor.with_signature_key(SecureBytes::new(key)))
+}
+
 fn io_invalid_input(message: impl Into<String>) -> IoError {
     IoError::new(ErrorKind::InvalidInput, message.into())
 }
@@ -316,36 +361,27 @@ async fn main() -> std::io::Result<()> {
 
     // Arabic: في وضع قاعدة البيانات نُحمّل قاعدة GeoIP فعلية من ملف MMDB بشكل صارم
     // English: In DB mode, strictly load a real GeoIP MMDB file
-    let geo_reader: Arc<mkt_ksa_geo_sec::core::geo_resolver::GeoReaderEnum> = if db_pool.is_some() {
-        let geo_db_path = std::env::var("GEOIP_DB_PATH")
-            .or_else(|_| std::env::var("MAXMIND_DB_PATH"))
-            .unwrap_or_else(|_| "GeoLite2-City-Test.mmdb".to_string());
-
-        let geo_db_bytes = std::fs::read(&geo_db_path).map_err(|e| {
-            io_invalid_data(format!(
-                "Failed to read GeoIP MMDB file at '{}': {}. Set GEOIP_DB_PATH or MAXMIND_DB_PATH to a valid MaxMind DB.",
-                geo_db_path, e
-            ))
-        })?;
-
-        let reader = Reader::from_source(geo_db_bytes).map_err(|e| {
-            io_invalid_data(format!(
-                "Failed to parse GeoIP MMDB file at '{}': {}. The file is not a valid MaxMind DB.",
-                geo_db_path, e
-            ))
-        })?;
-
-        Arc::new(mkt_ksa_geo_sec::core::geo_resolver::GeoReaderEnum::Real(
-            reader,
-        ))
-    } else {
+    let mut geo_db_config = GeoDbConfig::from_env();
+    if db_pool.is_some() && geo_db_config.city_path.is_none() {
+        geo_db_config.city_path = Some("GeoLite2-City-Test.mmdb".into());
+    }
+    if db_pool.is_none() && geo_db_config.city_path.is_none() {
         println!(
             "[DEV MODE] لن يتم تحميل قاعدة بيانات MaxMind geo DB. سيتم استخدام كائن وهمي عبر Enum."
         );
-        Arc::new(mkt_ksa_geo_sec::core::geo_resolver::GeoReaderEnum::Mock(
-            mkt_ksa_geo_sec::core::geo_resolver::MockGeoReader::new(),
+    }
+    let geo_db = Arc::new(GeoDbManager::load(&geo_db_config).map_err(|e| {
+        io_invalid_data(format!(
+            "{e}. Set GEOIP_DB_PATH or MAXMIND_DB_PATH to a valid MaxMind DB."
         ))
-    };
+    })?);
+    // Arabic: مراقبة ملفات MMDB وإعادة تحميلها دون إعادة تشغيل (0 لتعطيل المراقبة)
+    // English: Watch MMDB files and hot-reload them without restart (0 disables watching)
+    let geo_db_reload_seconds = env_u64_or_default("GEOIP_RELOAD_INTERVAL_SECONDS", 300);
+    if geo_db_reload_seconds > 0 {
+        let _geo_db_watcher = geo_db.spawn_watcher(Duration::from_secs(geo_db_reload_seconds));
+    }
+    let geo_reader = geo_db.city_reader();
 
     let geo_resolver = Arc::new(GeoResolver::new(
         random_secret_bytes(32),
@@ -356,29 +392,197 @@ async fn main() -> std::io::Result<()> {
         geo_reader.clone(),
     ));
 
-    let mut fp_env_profiles = HashMap::new();
     // Populate the fingerprint environment profiles from the centralized defaults.
-    fp_env_profiles.extend(build_default_fp_env_profiles());
+    let fp_env_profiles = EnvironmentProfile::defaults();
+
+    // Arabic: توقيعات التهديد الموقّعة تُحمّل عند الإقلاع ثم يُعاد فحص الملف دورياً
+    // English: Signed threat signatures are loaded at startup and the file is re-checked periodically
+    let security_monitor = Arc::new(build_security_monitor()?);
+    if let Some(path) = std::env::var("THREAT_SIGNATURES_PATH")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+    {
+        let raw = std::fs::read_to_string(path.trim())
+            .map_err(|e| io_invalid_data(format!("THREAT_SIGNATURES_PATH: {e}")))?;
+        security_monitor
+            .update_threat_database(&raw)
+            .await
+            .map_err(|e| io_invalid_data(format!("THREAT_SIGNATURES_PATH: {e}")))?;
+        println!(
+            "Loaded threat signatures version {}",
+            security_monitor
+                .threat_signatures_version()
+                .await
+                .unwrap_or(0)
+        );
+        let reload_seconds = env_u64_or_default("THREAT_SIGNATURES_RELOAD_SECONDS", 300);
+        if reload_seconds > 0 {
+            let security_monitor = Arc::clone(&security_monitor);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(reload_seconds));
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let result = match tokio::fs::read_to_string(path.trim()).await {
+                        Ok(raw) => security_monitor.update_threat_database(&raw).await,
+                        Err(e) => Err(ThreatSignatureError::Io(e).into()),
+                    };
+                    match result {
+                        Ok(()) => println!(
+                            "Threat signatures updated to version {}",
+                            security_monitor
+                                .threat_signatures_version()
+                                .await
+                                .unwrap_or(0)
+                        ),
+                        // الملف لم يتغير
+                        // The file has not changed
+                        Err(FingerprintError::ThreatSignatures(
+                            ThreatSignatureError::StaleVersion { .. },
+                        )) => {}
+                        Err(e) => eprintln!(
+                            "Threat signature reload failed, keeping previous signatures: {e}"
+                        ),
+                    }
+                }
+            });
+        }
+    }
 
     // 2. إنشاء محرك DeviceFPEngine
-    let fp_engine = Arc::new(AdaptiveFingerprintEngine::new(
-        Arc::new(DefaultSecurityMonitor::new()),
+    let fp_engine = AdaptiveFingerprintEngine::new(
+        security_monitor,
         Arc::new(
             DefaultQuantumEngine::new()
                 .map_err(|e| io_invalid_data(format!("Failed to create quantum engine: {e}")))?,
         ),
         Arc::new(FpAiProcessor),
         Arc::new(RwLock::new(fp_env_profiles)),
+    )
+    // Arabic: رفض البصمات التي تتجاوز ميزانية المعالجة (الافتراضي: تسجيل تحذير فقط)
+    // English: Reject fingerprints over the processing budget (default: log a warning only)
+    .with_processing_budget_enforcement(env_bool_or_default(
+        "FINGERPRINT_ENFORCE_PROCESSING_BUDGET",
+        false,
     ));
+    // Arabic: مفاتيح بصمة مشتركة تجعل البصمة ثابتة عبر إعادة التشغيل والنسخ المتعددة
+    // English: Shared fingerprint keys keep fingerprints stable across restarts and replicas
+    let fp_engine = match FingerprintKeyring::from_env()
+        .map_err(|e| io_invalid_data(format!("Fingerprint keys: {e}")))?
+    {
+        Some(keys) => {
+            println!("Using fingerprint key id {}", keys.active_key_id());
+            fp_engine.with_fingerprint_keys(Arc::new(keys))
+        }
+        None => {
+            eprintln!(
+                "FINGERPRINT_KEYRING_PATH/FINGERPRINT_KEYS not set; device fingerprints change on restart"
+            );
+            fp_engine
+        }
+    };
+    // Arabic: قاعدة بصمات TLS/HTTP2 للعملاء المعروفين لكشف وكلاء المستخدم المزيفين
+    // English: Known-client TLS/HTTP2 fingerprints to flag forged user agents
+    let fp_engine = match std::env::var("KNOWN_CLIENT_FINGERPRINTS_PATH") {
+        Ok(path) if !path.trim().is_empty() => {
+            let known = KnownClientDb::load_file(path.trim())
+                .map_err(|e| io_invalid_data(format!("Known client fingerprints: {e}")))?;
+            println!("Loaded {} known client fingerprints", known.len());
+            fp_engine.with_known_clients(Arc::new(known))
+        }
+        _ => fp_engine,
+    };
+    let fp_engine = Arc::new(fp_engine);
 
     // 3. إنشاء محرك BehaviorEngine
-    let behavior_engine = Arc::new(BehaviorEngine::new(
-        Arc::new(DefaultBehavioralModel),
-        Arc::new(DefaultAnomalyDetector {
-            max_speed_kmh: 1200.0,
-        }),
-        10,
+    // Arabic: قوالب الكتابة والمؤشر تُحفظ في قاعدة البيانات إن وُجدت
+    // English: Keystroke and pointer templates are persisted to the database when there is one
+    let template_backend: Option<Arc<dyn TemplateBackend>> = db_pool
+        .clone()
+        .map(|pool| Arc::new(SqliteTemplateBackend::new(pool)) as Arc<dyn TemplateBackend>);
+    // Arabic: خط كواشف ونماذج من ملف إعدادات إن وُجد، وإلا التركيب الافتراضي
+    // English: Detector/model pipeline from a config file if given, else the default composition
+    // Arabic: أيام نهاية الأسبوع المحلية، مثلاً "sat,sun" للإمارات (الافتراضي الجمعة والسبت)؛
+    // تُطبق على ملف الخط أيضاً
+    // English: Local weekend days, e.g. "sat,sun" for the UAE (defaults to Friday and Saturday);
+    // they override the pipeline file's calendar too
+    let weekend_days: Option<Vec<chrono::Weekday>> = std::env::var("BEHAVIOR_WEEKEND_DAYS")
+        .ok()
+        .filter(|days| !days.trim().is_empty())
+        .map(|days| {
+            days.split(',')
+                .map(|day| day.trim().parse::<chrono::Weekday>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| io_invalid_data(format!("BEHAVIOR_WEEKEND_DAYS: {e}")))
+        })
+        .transpose()?;
+    let (behavior_model, behavior_detector): BehaviorComponents =
+        match std::env::var("BEHAVIOR_PIPELINE_PATH") {
+            Ok(path) if !path.trim().is_empty() => {
+                let attribution: Arc<dyn IpAttribution> = geo_db.clone();
+                let pipeline = PipelineConfig::load_file(std::path::Path::new(path.trim()))
+                    .and_then(|mut config| {
+                        if let Some(days) = &weekend_days {
+                            config.calendar.weekend.clone_from(days);
+                        }
+                        config.build_with_templates(Some(attribution), template_backend.clone())
+                    })
+                    .map_err(|e| io_invalid_data(format!("Behavior pipeline: {e}")))?;
+                println!("Loaded behavior pipeline from {}", path.trim());
+                pipeline
+            }
+            _ => {
+                let mut calendar = ActivityCalendar::default();
+                if let Some(days) = weekend_days {
+                    calendar.weekend = days;
+                }
+                let mut keystroke_profiles = KeystrokeProfiles::default();
+                let mut pointer_profiles = PointerProfiles::default();
+                if let Some(backend) = &template_backend {
+                    keystroke_profiles = keystroke_profiles.with_backend(Arc::clone(backend));
+                    pointer_profiles = pointer_profiles.with_backend(Arc::clone(backend));
+                }
+                (
+                    Arc::new(
+                        PointerDynamicsModel::new(Arc::new(
+                            KeystrokeDynamicsModel::new(Arc::new(BaselineBehavioralModel::new(
+                                Arc::new(DefaultBehavioralModel::default().with_calendar(calendar)),
+                            )))
+                            .with_profiles(Arc::new(keystroke_profiles)),
+                        ))
+                        .with_profiles(Arc::new(pointer_profiles)),
+                    ),
+                    Arc::new(SyntheticPointerDetector::new(Arc::new(
+                        DefaultAnomalyDetector {
+                            max_speed_kmh: 1200.0,
+                        },
+                    ))),
+                )
+            }
+        };
+    // Arabic: أحكام المحللين المخزنة تُحمّل ليستهلكها النموذج (أجهزة ومواقع مؤكدة)
+    // English: Stored analyst verdicts are loaded for the model to consume (confirmed devices and locations)
+    let labeled_history = Arc::new(LabeledHistory::default());
+    if let Some(pool) = &db_pool {
+        let labeled = crud::list_labeled_analyses(pool, None)
+            .await
+            .map_err(|e| io_invalid_data(format!("Analyst verdicts: {e}")))?;
+        for (analysis, verdict) in &labeled {
+            if let Ok(input) = serde_json::from_value::<BehaviorInput>(analysis.input.clone()) {
+                labeled_history.record(&input, *verdict).await;
+            }
+        }
+        println!("Loaded {} analyst verdicts", labeled.len());
+    }
+    let behavior_model: Arc<dyn BehavioralModel> = Arc::new(FeedbackAwareModel::new(
+        behavior_model,
+        Arc::clone(&labeled_history),
     ));
+    let timezone_source: Arc<dyn IpAttribution> = geo_db.clone();
+    let behavior_engine = Arc::new(
+        BehaviorEngine::new(behavior_model, behavior_detector, 10)
+            .with_attribution(timezone_source),
+    );
 
     // 4. إنشاء استراتيجية حساب النقاط
     let scoring_strategy = Arc::new(DefaultScoringStrategy {
@@ -392,15 +596,144 @@ async fn main() -> std::io::Result<()> {
         Arc::new(mkt_ksa_geo_sec::core::sensors_analyzer::DefaultSensorAnomalyDetector::default()),
     ));
 
-    let proxy_db = Arc::new(RwLock::new(
-        mkt_ksa_geo_sec::core::network_analyzer::ProxyDatabase::default(),
-    ));
-    let network_engine = Arc::new(NetworkAnalyzer::new(
-        random_secret_bytes(32),
-        proxy_db,
-        geo_reader.clone(),
-        Arc::new(mkt_ksa_geo_sec::core::network_analyzer::DefaultAiNetworkAnalyzer),
-    ));
+    // Arabic: قوائم البروكسي/VPN/Tor بصيغة "format:category:path" مفصولة بفواصل، مع مدة
+    // صلاحية اختيارية للمدخلات منذ التحميل (0 = بلا انتهاء)
+    // English: Proxy/VPN/Tor lists as comma-separated "format:category:path" specs, with an
+    // optional entry lifetime from load time (0 = never expires)
+    let proxy_ttl_seconds = env_u64_or_default("PROXY_LIST_TTL_SECONDS", 0);
+    let proxy_sources = std::env::var("PROXY_LIST_SOURCES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .map(|spec| {
+            ProxyListSource::parse_spec(spec).map(|source| match proxy_ttl_seconds {
+                0 => source,
+                seconds => source.with_ttl(chrono::Duration::seconds(
+                    i64::try_from(seconds).unwrap_or(i64::MAX),
+                )),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| io_invalid_input(format!("PROXY_LIST_SOURCES: {e}")))?;
+    let proxy_db = ProxyDatabase::with_sources(proxy_sources);
+    if !proxy_db.sources().is_empty() {
+        let report = proxy_db
+            .reload()
+            .map_err(|e| io_invalid_data(format!("PROXY_LIST_SOURCES: {e}")))?;
+        println!(
+            "Loaded {} proxy list entries ({} lines skipped)",
+            report.loaded, report.skipped_lines
+        );
+    }
+    let proxy_db = Arc::new(RwLock::new(proxy_db));
+    // Arabic: إعادة تحميل دورية ذرية؛ الملفات تُقرأ خارج الأقفال في spawn_blocking ثم تُستبدل
+    // الجداول بقفل كتابة قصير فلا تُحجب عمليات التحليل
+    // English: Periodic atomic reload; files are read outside any lock in spawn_blocking, then
+    // the tables are swapped under a short write lock so analysis is never blocked
+    let proxy_reload_seconds = env_u64_or_default("PROXY_LIST_RELOAD_SECONDS", 3600);
+    let proxy_reload_sources = proxy_db.read().await.sources().to_vec();
+    if proxy_reload_seconds > 0 && !proxy_reload_sources.is_empty() {
+        let proxy_db = Arc::clone(&proxy_db);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(proxy_reload_seconds));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let sources = proxy_reload_sources.clone();
+                let loaded =
+                    tokio::task::spawn_blocking(move || ProxyDatabase::load_sources(&sources))
+                        .await;
+                match loaded {
+                    Ok(Ok((tables, _))) => {
+                        if let Err(e) = proxy_db.read().await.replace(tables) {
+                            eprintln!("Proxy list swap failed, keeping previous data: {e}");
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        eprintln!("Proxy list reload failed, keeping previous data: {e}");
+                    }
+                    Err(e) => eprintln!("Proxy list reload task failed: {e}"),
+                }
+                // Arabic: البيانات السابقة المحتفظ بها تفقد مدخلاتها المنتهية
+                // English: Kept previous data still loses its expired entries
+                match proxy_db.read().await.purge_expired() {
+                    Ok(0) => {}
+                    Ok(removed) => println!("Dropped {removed} expired proxy list ranges"),
+                    Err(e) => eprintln!("Proxy list purge failed: {e}"),
+                }
+            }
+        });
+    }
+    // Arabic: حلقة مفاتيح حماية عناوين IP؛ بدون IP_KEYRING_PATH تكون مؤقتة وتضيع عند إعادة التشغيل
+    // English: IP protection keyring; without IP_KEYRING_PATH it is ephemeral and lost on restart
+    let ip_keyring = Arc::new(match std::env::var("IP_KEYRING_PATH") {
+        Ok(path) if !path.trim().is_empty() => IpKeyring::open_or_create(
+            std::path::Path::new(path.trim()),
+            env_usize_or_default("IP_KEYRING_MAX_KEYS", 12),
+        )
+        .map_err(|e| io_invalid_data(format!("IP_KEYRING_PATH: {e}")))?,
+        _ => {
+            println!(
+                "[DEV MODE] IP_KEYRING_PATH not set; encrypted IPs will not survive a restart."
+            );
+            IpKeyring::ephemeral()
+        }
+    });
+    let ip_key_rotation_hours = env_u64_or_default("IP_KEY_ROTATION_HOURS", 720);
+    if ip_key_rotation_hours > 0 {
+        let max_age =
+            chrono::Duration::hours(i64::try_from(ip_key_rotation_hours).unwrap_or(i64::MAX));
+        let _ip_key_rotation = ip_keyring.spawn_rotation(max_age, Duration::from_secs(3600));
+    }
+    let ip_protection_mode = match std::env::var("IP_PROTECTION_MODE")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "pseudonym" | "hmac" => IpProtectionMode::Pseudonym,
+        _ => IpProtectionMode::Encrypt,
+    };
+
+    // Arabic: دليل Tor يعاد تحميله دورياً؛ منفذ الخدمة يحدد المخارج القادرة على الوصول إلينا
+    // English: Periodically reloaded Tor directory; the service port decides which exits can reach us
+    let tor_directory = Arc::new(RwLock::new(load_tor_directory()?));
+    let tor_service_port = u16::try_from(env_u64_or_default("TOR_SERVICE_PORT", 443))
+        .map_err(|_| io_invalid_input("TOR_SERVICE_PORT must be a valid port"))?;
+    let tor_reload_seconds = env_u64_or_default("TOR_DIRECTORY_RELOAD_SECONDS", 3600);
+    // Arabic: الملفات تُقرأ في spawn_blocking؛ دليل فارغ عند الإقلاع لا يوقف إعادة التحميل
+    // English: Files are read in spawn_blocking; an empty directory at startup does not stop reloads
+    if tor_reload_seconds > 0 {
+        let tor_directory = Arc::clone(&tor_directory);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(tor_reload_seconds));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match tokio::task::spawn_blocking(load_tor_directory).await {
+                    Ok(Ok(fresh)) => *tor_directory.write().await = fresh,
+                    Ok(Err(e)) => {
+                        eprintln!("Tor directory reload failed, keeping previous data: {e}");
+                    }
+                    Err(e) => eprintln!("Tor directory reload task failed: {e}"),
+                }
+            }
+        });
+    }
+
+    let network_engine = Arc::new(
+        NetworkAnalyzer::new(
+            random_secret_bytes(32),
+            proxy_db,
+            geo_reader.clone(),
+            Arc::new(mkt_ksa_geo_sec::core::network_analyzer::DefaultAiNetworkAnalyzer),
+        )
+        .with_geo_databases(Arc::clone(&geo_db))
+        .with_hosting_ranges(Arc::new(RwLock::new(load_hosting_ranges()?)))
+        .with_keyring(ip_keyring, ip_protection_mode)
+        .with_tor_directory(tor_directory, tor_service_port),
+    );
 
     let weather_providers: Vec<Arc<dyn WeatherProvider>> = vec![Arc::new(OpenMeteoProvider::new())];
     let weather_engine = Arc::new(WeatherEngine::new(weather_providers));
@@ -438,6 +771,7 @@ async fn main() -> std::io::Result<()> {
         behavior: behavior_engine,
         device_fp: fp_engine,
         network: network_engine,
+        location_consistency: LocationConsistencyChecker::default(),
     });
 
     // 7. تجميع كل الخدمات في الحالة المشتركة
@@ -475,7 +809,15 @@ async fn main() -> std::io::Result<()> {
             ) as u16,
         })),
         api_key: Some(SecureString::new(api_key)),
+        forwarding: Arc::new(ForwardingChainAnalyzer::from_env()),
+        transport_proxy_secret: std::env::var("TRANSPORT_PROXY_SECRET")
+            .ok()
+            .map(|secret| secret.trim().to_string())
+            .filter(|secret| !secret.is_empty())
+            .map(SecureString::new),
         alert_memory: Arc::new(mkt_ksa_geo_sec::app_state::AlertMemoryStore::new(256)),
+        access_correlator: Arc::new(AccountCorrelator::default().with_attribution(geo_db.clone())),
+        labeled_history,
         db_pool,
     });
 
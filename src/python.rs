@@ -0,0 +1,528 @@
+/******************************************************************************************
+     📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+* 📄 رخصة Apache 2.0 – يسمح بالاستخدام والتعديل بشرط النسبة وعدم تقديم ضمانات.
+* MKT KSA Geolocation Security – Developed by Mansour Bin Khalid (KSA 🇸🇦)
+* Licensed under Apache 2.0 – https://www.apache.org/licenses/LICENSE-2.0
+* © 2025 All rights reserved.
+
+    اسم الملف: python.rs
+    المسار:    src/python.rs
+    دور الملف:
+    ربط Python اختياري (الميزة `python`، يُبنى كحزمة wheel عبر maturin) يعرض
+    `GeoResolver` و `BehaviorEngine` و `AdaptiveFingerprintEngine` و
+    `SensorsAnalyzerEngine` و `CrossValidationEngine` بأنواع Python الأصلية
+    (dict/list/str/int/float) مع نسخ `*_async` قابلة للانتظار، لتعمل التحليلات
+    خارج الخط بنفس منطق Rust في الإنتاج. الإعدادات هي نفسها إعدادات مقبض C.
+    --------------------------------------------------------------
+    File Name: python.rs
+    Path:     src/python.rs
+    File Role:
+    Optional Python binding (the `python` feature, built as a wheel with maturin)
+    exposing `GeoResolver`, `BehaviorEngine`, `AdaptiveFingerprintEngine`,
+    `SensorsAnalyzerEngine` and `CrossValidationEngine` with native Python types
+    (dict/list/str/int/float) and awaitable `*_async` variants, so offline analyses run
+    the same Rust logic as production. The configuration is the C handle's configuration.
+******************************************************************************************/
+
+use crate::api::behavior::BehaviorAnalyzeRequest;
+use crate::api::geo::GeoResolveRequest;
+use crate::api::sensors::SensorsAnalyzeRequest;
+use crate::core::behavior_bio::BehaviorEngine;
+use crate::core::cross_location::CrossValidationEngine;
+use crate::core::device_fp::AdaptiveFingerprintEngine;
+use crate::core::geo_resolver::{GeoResolver, ResolveParams};
+use crate::core::sensors_analyzer::SensorsAnalyzerEngine;
+use crate::ffi::{
+    cross_validate, generate_fingerprint, EngineConfig, FfiError, FingerprintRequest,
+    GeoLookupRequest,
+};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use std::future::Future;
+use std::sync::Arc;
+
+// ================================================================
+// تحويل الأنواع والأخطاء
+// Type and error conversion
+// ================================================================
+
+impl From<FfiError> for PyErr {
+    fn from(e: FfiError) -> Self {
+        match e {
+            FfiError::Engine(_) => PyRuntimeError::new_err(e.to_string()),
+            _ => PyValueError::new_err(e.to_string()),
+        }
+    }
+}
+
+/// يحول قيمة JSON إلى كائن Python أصلي.
+/// Converts a JSON value into a native Python object.
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any().unbind(),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any().unbind(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any().unbind(),
+            _ => PyFloat::new(py, n.as_f64().unwrap_or(f64::NAN))
+                .into_any()
+                .unbind(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any().unbind(),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(to_py(py, item)?)?;
+            }
+            list.into_any().unbind()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, to_py(py, item)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+/// يحول كائن Python إلى JSON؛ كائنات `datetime` تُرسل بصيغة ISO 8601.
+/// Converts a Python object into JSON; `datetime` objects are sent as ISO 8601.
+fn from_py(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        Ok(Value::Null)
+    } else if let Ok(b) = obj.downcast::<PyBool>() {
+        Ok(Value::Bool(b.is_true()))
+    } else if obj.is_instance_of::<PyInt>() {
+        match obj.extract::<i64>() {
+            Ok(i) => Ok(Value::from(i)),
+            Err(_) => Ok(Value::from(obj.extract::<u64>()?)),
+        }
+    } else if let Ok(f) = obj.downcast::<PyFloat>() {
+        Number::from_f64(f.value())
+            .map(Value::Number)
+            .ok_or_else(|| PyValueError::new_err("NaN and infinity are not valid JSON numbers"))
+    } else if let Ok(s) = obj.downcast::<PyString>() {
+        Ok(Value::String(s.to_str()?.to_string()))
+    } else if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut map = Map::with_capacity(dict.len());
+        for (key, item) in dict.iter() {
+            let key = key
+                .downcast::<PyString>()
+                .map_err(|_| PyTypeError::new_err("dict keys must be str"))?;
+            map.insert(key.to_str()?.to_string(), from_py(&item)?);
+        }
+        Ok(Value::Object(map))
+    } else if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        obj.try_iter()?
+            .map(|item| from_py(&item?))
+            .collect::<PyResult<Vec<_>>>()
+            .map(Value::Array)
+    } else if obj.hasattr("isoformat")? {
+        Ok(Value::String(obj.call_method0("isoformat")?.extract()?))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "unsupported type '{}'",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+fn extract<T: DeserializeOwned>(obj: &Bound<'_, PyAny>) -> PyResult<T> {
+    serde_json::from_value(from_py(obj)?).map_err(|e| FfiError::InvalidJson(e).into())
+}
+
+fn into_py<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    to_py(py, &serde_json::to_value(value).map_err(FfiError::engine)?)
+}
+
+/// ينفذ المستقبل على بيئة tokio المشتركة مع تحرير GIL.
+/// Runs the future on the shared tokio runtime with the GIL released.
+fn run_blocking<T: Serialize + Send>(
+    py: Python<'_>,
+    future: impl Future<Output = Result<T, FfiError>> + Send,
+) -> PyResult<PyObject> {
+    let value = py.allow_threads(|| pyo3_async_runtimes::tokio::get_runtime().block_on(future))?;
+    into_py(py, &value)
+}
+
+/// يعيد كائناً قابلاً للانتظار من `asyncio`.
+/// Returns an `asyncio` awaitable.
+fn run_async<'py, T: Serialize + Send + 'static>(
+    py: Python<'py>,
+    future: impl Future<Output = Result<T, FfiError>> + Send + 'static,
+) -> PyResult<Bound<'py, PyAny>> {
+    pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        let value = future.await?;
+        Python::with_gil(|py| into_py(py, &value))
+    })
+}
+
+fn build_engines(config: Option<&Bound<'_, PyAny>>) -> PyResult<CrossValidationEngine> {
+    let config: EngineConfig = match config {
+        Some(config) => extract(config)?,
+        None => EngineConfig::default(),
+    };
+    Ok(config.build()?)
+}
+
+// ================================================================
+// الأصناف المعروضة
+// Exposed classes
+// ================================================================
+
+/// محلل الموقع: `resolve(ip_address=None, gps_data=None)`.
+/// Location resolver: `resolve(ip_address=None, gps_data=None)`.
+#[pyclass(name = "GeoResolver", module = "mkt_ksa_geo_sec", frozen)]
+pub struct PyGeoResolver {
+    inner: Arc<GeoResolver>,
+}
+
+impl PyGeoResolver {
+    fn lookup(
+        inner: Arc<GeoResolver>,
+        ip_address: Option<&str>,
+        gps_data: Option<(f64, f64, u8, f64)>,
+    ) -> PyResult<impl Future<Output = Result<crate::GeoLocation, FfiError>> + Send + 'static> {
+        let request = GeoLookupRequest {
+            ip_address: ip_address
+                .map(str::parse)
+                .transpose()
+                .map_err(|e| PyValueError::new_err(format!("invalid ip_address: {e}")))?,
+            gps_data,
+        };
+        Ok(async move {
+            inner
+                .resolve(ResolveParams {
+                    ip: request.ip_address,
+                    gps: request.gps_data,
+                    sim_location: None,
+                    satellite_location: None,
+                    indoor_data: None,
+                    ar_data: None,
+                    mfa_token: None,
+                })
+                .await
+                .map_err(FfiError::engine)
+        })
+    }
+}
+
+#[pymethods]
+impl PyGeoResolver {
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        Ok(Self {
+            inner: build_engines(config)?.geo_resolver,
+        })
+    }
+
+    #[pyo3(signature = (ip_address=None, gps_data=None))]
+    fn resolve(
+        &self,
+        py: Python<'_>,
+        ip_address: Option<&str>,
+        gps_data: Option<(f64, f64, u8, f64)>,
+    ) -> PyResult<PyObject> {
+        run_blocking(
+            py,
+            Self::lookup(Arc::clone(&self.inner), ip_address, gps_data)?,
+        )
+    }
+
+    #[pyo3(signature = (ip_address=None, gps_data=None))]
+    fn resolve_async<'py>(
+        &self,
+        py: Python<'py>,
+        ip_address: Option<&str>,
+        gps_data: Option<(f64, f64, u8, f64)>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        run_async(
+            py,
+            Self::lookup(Arc::clone(&self.inner), ip_address, gps_data)?,
+        )
+    }
+}
+
+/// محرك السلوك: `process(input)` حيث `input` بصيغة `BehaviorInput`.
+/// Behavior engine: `process(input)` where `input` has the `BehaviorInput` shape.
+#[pyclass(name = "BehaviorEngine", module = "mkt_ksa_geo_sec", frozen)]
+pub struct PyBehaviorEngine {
+    inner: Arc<BehaviorEngine>,
+}
+
+#[pymethods]
+impl PyBehaviorEngine {
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        Ok(Self {
+            inner: build_engines(config)?.behavior_engine,
+        })
+    }
+
+    fn process(&self, py: Python<'_>, input: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let request = BehaviorAnalyzeRequest {
+            input: extract(input)?,
+        };
+        let inner = &self.inner;
+        run_blocking(py, async move {
+            inner.process(request.input).await.map_err(FfiError::engine)
+        })
+    }
+
+    fn process_async<'py>(
+        &self,
+        py: Python<'py>,
+        input: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let request = BehaviorAnalyzeRequest {
+            input: extract(input)?,
+        };
+        let inner = Arc::clone(&self.inner);
+        run_async(py, async move {
+            inner.process(request.input).await.map_err(FfiError::engine)
+        })
+    }
+}
+
+/// محرك البصمة: `generate(request)` بجسم `POST /api/device/resolve`.
+/// Fingerprint engine: `generate(request)` with a `POST /api/device/resolve` body.
+#[pyclass(name = "AdaptiveFingerprintEngine", module = "mkt_ksa_geo_sec", frozen)]
+pub struct PyAdaptiveFingerprintEngine {
+    inner: Arc<AdaptiveFingerprintEngine>,
+}
+
+#[pymethods]
+impl PyAdaptiveFingerprintEngine {
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        Ok(Self {
+            inner: build_engines(config)?.fp_engine,
+        })
+    }
+
+    fn generate(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let request: FingerprintRequest = extract(request)?;
+        let inner = &self.inner;
+        run_blocking(
+            py,
+            async move { generate_fingerprint(inner, &request).await },
+        )
+    }
+
+    fn generate_async<'py>(
+        &self,
+        py: Python<'py>,
+        request: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let request: FingerprintRequest = extract(request)?;
+        let inner = Arc::clone(&self.inner);
+        run_async(
+            py,
+            async move { generate_fingerprint(&inner, &request).await },
+        )
+    }
+}
+
+/// محرك الحساسات: `analyze(reading, history=None)`.
+/// Sensors engine: `analyze(reading, history=None)`.
+#[pyclass(name = "SensorsAnalyzerEngine", module = "mkt_ksa_geo_sec", frozen)]
+pub struct PySensorsAnalyzerEngine {
+    inner: Arc<SensorsAnalyzerEngine>,
+}
+
+fn sensors_request(
+    reading: &Bound<'_, PyAny>,
+    history: Option<&Bound<'_, PyAny>>,
+) -> PyResult<SensorsAnalyzeRequest> {
+    Ok(SensorsAnalyzeRequest {
+        reading: extract(reading)?,
+        history: history.map(extract).transpose()?.unwrap_or_default(),
+    })
+}
+
+#[pymethods]
+impl PySensorsAnalyzerEngine {
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        Ok(Self {
+            inner: build_engines(config)?.sensors_engine,
+        })
+    }
+
+    #[pyo3(signature = (reading, history=None))]
+    fn analyze(
+        &self,
+        py: Python<'_>,
+        reading: &Bound<'_, PyAny>,
+        history: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let request = sensors_request(reading, history)?;
+        let inner = &self.inner;
+        run_blocking(py, async move {
+            inner
+                .analyze(request.reading, &request.history)
+                .await
+                .map_err(FfiError::engine)
+        })
+    }
+
+    #[pyo3(signature = (reading, history=None))]
+    fn analyze_async<'py>(
+        &self,
+        py: Python<'py>,
+        reading: &Bound<'py, PyAny>,
+        history: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let request = sensors_request(reading, history)?;
+        let inner = Arc::clone(&self.inner);
+        run_async(py, async move {
+            inner
+                .analyze(request.reading, &request.history)
+                .await
+                .map_err(FfiError::engine)
+        })
+    }
+}
+
+/// محرك التحقق المتقاطع: `validate(request)` بجسم `POST /api/geo/resolve`.
+/// خصائصه تعيد المحركات الفرعية التي يستخدمها (تشارك المفاتيح وتاريخ السلوك).
+/// Cross-validation engine: `validate(request)` with a `POST /api/geo/resolve` body.
+/// Its properties return the sub-engines it uses (sharing keys and behavior history).
+#[pyclass(name = "CrossValidationEngine", module = "mkt_ksa_geo_sec", frozen)]
+pub struct PyCrossValidationEngine {
+    inner: Arc<CrossValidationEngine>,
+}
+
+#[pymethods]
+impl PyCrossValidationEngine {
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        Ok(Self {
+            inner: Arc::new(build_engines(config)?),
+        })
+    }
+
+    fn validate(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let request: GeoResolveRequest = extract(request)?;
+        let inner = &self.inner;
+        run_blocking(py, async move { cross_validate(inner, &request).await })
+    }
+
+    fn validate_async<'py>(
+        &self,
+        py: Python<'py>,
+        request: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let request: GeoResolveRequest = extract(request)?;
+        let inner = Arc::clone(&self.inner);
+        run_async(py, async move { cross_validate(&inner, &request).await })
+    }
+
+    #[getter]
+    fn geo_resolver(&self) -> PyGeoResolver {
+        PyGeoResolver {
+            inner: Arc::clone(&self.inner.geo_resolver),
+        }
+    }
+
+    #[getter]
+    fn behavior_engine(&self) -> PyBehaviorEngine {
+        PyBehaviorEngine {
+            inner: Arc::clone(&self.inner.behavior_engine),
+        }
+    }
+
+    #[getter]
+    fn fingerprint_engine(&self) -> PyAdaptiveFingerprintEngine {
+        PyAdaptiveFingerprintEngine {
+            inner: Arc::clone(&self.inner.fp_engine),
+        }
+    }
+
+    #[getter]
+    fn sensors_engine(&self) -> PySensorsAnalyzerEngine {
+        PySensorsAnalyzerEngine {
+            inner: Arc::clone(&self.inner.sensors_engine),
+        }
+    }
+}
+
+/// وحدة Python `mkt_ksa_geo_sec`.
+/// The `mkt_ksa_geo_sec` Python module.
+#[pymodule]
+fn mkt_ksa_geo_sec(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGeoResolver>()?;
+    m.add_class::<PyBehaviorEngine>()?;
+    m.add_class::<PyAdaptiveFingerprintEngine>()?;
+    m.add_class::<PySensorsAnalyzerEngine>()?;
+    m.add_class::<PyCrossValidationEngine>()?;
+    Ok(())
+}
+
+// ================================================================
+// اختبارات
+// Tests
+// ================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_python_engines_share_rust_logic() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "mkt_ksa_geo_sec").unwrap();
+            mkt_ksa_geo_sec(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("mkt", module).unwrap();
+            let script = CString::new(
+                r#"
+import asyncio, datetime
+config = {
+    "secret_key": "01" * 32,
+    "fingerprint_keys": "1:" + "02" * 32,
+    "enforce_processing_budget": False,
+}
+engines = mkt.CrossValidationEngine(config)
+device = {"os": "Android", "device_info": "Pixel-8", "environment_data": "wifi"}
+first = engines.fingerprint_engine.generate(device)
+second = mkt.AdaptiveFingerprintEngine(config).generate(device)
+assert first["base_fp"] == second["base_fp"], (first, second)
+assert first["key_id"] == 1
+
+behavior = engines.behavior_engine.process({
+    "entity_id": "u1",
+    "timestamp": datetime.datetime(2026, 1, 1, tzinfo=datetime.timezone.utc),
+    "location": (24.7, 46.7),
+    "network_info": {"ip_address": "10.0.0.1", "is_vpn": False, "connection_type": "WiFi"},
+    "device_fingerprint": first["base_fp"],
+})
+assert isinstance(behavior, dict)
+
+async def main():
+    return await engines.fingerprint_engine.generate_async(device)
+assert asyncio.run(main())["base_fp"] == first["base_fp"]
+
+try:
+    engines.fingerprint_engine.generate({"attributes": 5})
+    raise AssertionError("expected ValueError")
+except ValueError:
+    pass
+"#,
+            )
+            .unwrap();
+            py.run(&script, Some(&globals), None).unwrap();
+        });
+    }
+}
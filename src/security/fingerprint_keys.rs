@@ -0,0 +1,262 @@
+/******************************************************************************************
+*  📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+*  ملف: src/security/fingerprint_keys.rs
+*
+*  الهدف: مفاتيح بصمة الأجهزة الثابتة عبر إعادة التشغيل والنسخ المتعددة. المفاتيح
+*  تُقرأ من ملف مشترك أو من متغيرات البيئة ولكل منها معرف، والبصمة تحمل معرف المفتاح
+*  الذي حُسبت به، فتبقى المفاتيح القديمة متاحة لمطابقة البصمات المخزنة أثناء التدوير.
+*
+*  Purpose: Device-fingerprint keys that stay stable across restarts and replicas. Keys
+*  come from a shared file or environment variables and each has an id; fingerprints
+*  carry the id of the key they were computed with, so retired keys remain available to
+*  match stored fingerprints during rotation.
+******************************************************************************************/
+
+use crate::security::key_file::StoredKeyring;
+use crate::security::secret::SecureBytes;
+use chrono::{DateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const KEY_LEN: usize = 64;
+const MIN_KEY_LEN: usize = 32;
+
+/// أخطاء مفاتيح البصمة
+/// Fingerprint key errors
+#[derive(Debug, thiserror::Error)]
+pub enum FingerprintKeyError {
+    #[error("fingerprint key storage error: {0}")]
+    Storage(String),
+    #[error("unknown fingerprint key id {0}")]
+    UnknownKeyId(u32),
+    #[error("invalid fingerprint key spec: {0}")]
+    InvalidSpec(String),
+}
+
+/// مفاتيح البصمة: مفتاح نشط للبصمات الجديدة ومفاتيح متقاعدة للمطابقة فقط.
+/// Fingerprint keys: an active key for new fingerprints and retired keys for matching only.
+pub struct FingerprintKeyring {
+    active: u32,
+    keys: BTreeMap<u32, (SecureBytes, DateTime<Utc>)>,
+    path: Option<PathBuf>,
+}
+
+impl FingerprintKeyring {
+    /// يقرأ المفاتيح من `FINGERPRINT_KEYRING_PATH` (ملف يُنشأ إن لم يوجد) أو من
+    /// `FINGERPRINT_KEYS` بصيغة `id:hex,id:hex` مع `FINGERPRINT_ACTIVE_KEY_ID` الاختياري.
+    /// Reads keys from `FINGERPRINT_KEYRING_PATH` (a file created when missing) or from
+    /// `FINGERPRINT_KEYS` as `id:hex,id:hex` with an optional `FINGERPRINT_ACTIVE_KEY_ID`.
+    ///
+    /// # Errors
+    /// Returns `FingerprintKeyError` if the configured keys cannot be loaded.
+    pub fn from_env() -> Result<Option<Self>, FingerprintKeyError> {
+        if let Some(path) = std::env::var("FINGERPRINT_KEYRING_PATH")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+        {
+            return Self::open_or_create(Path::new(path.trim())).map(Some);
+        }
+        let Some(spec) = std::env::var("FINGERPRINT_KEYS")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let active = std::env::var("FINGERPRINT_ACTIVE_KEY_ID")
+            .ok()
+            .map(|id| {
+                id.trim()
+                    .parse()
+                    .map_err(|_| FingerprintKeyError::InvalidSpec(format!("active key id '{id}'")))
+            })
+            .transpose()?;
+        Self::from_spec(&spec, active).map(Some)
+    }
+
+    /// يبني الحلقة من `id:hex,id:hex`؛ المفتاح النشط افتراضياً هو صاحب أكبر معرف.
+    /// Builds the keyring from `id:hex,id:hex`; the active key defaults to the highest id.
+    ///
+    /// # Errors
+    /// Returns `FingerprintKeyError` if an entry is malformed or the active id is unknown.
+    pub fn from_spec(spec: &str, active: Option<u32>) -> Result<Self, FingerprintKeyError> {
+        let mut keys = BTreeMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| FingerprintKeyError::InvalidSpec("expected id:hex".to_string()))?;
+            let id: u32 = id
+                .trim()
+                .parse()
+                .map_err(|_| FingerprintKeyError::InvalidSpec(format!("key id '{id}'")))?;
+            keys.insert(id, (decode_key(id, key.trim())?, Utc::now()));
+        }
+        let active = active
+            .or_else(|| keys.keys().next_back().copied())
+            .ok_or_else(|| FingerprintKeyError::InvalidSpec("no keys".to_string()))?;
+        if !keys.contains_key(&active) {
+            return Err(FingerprintKeyError::UnknownKeyId(active));
+        }
+        Ok(Self {
+            active,
+            keys,
+            path: None,
+        })
+    }
+
+    /// يفتح ملف المفاتيح المشترك أو ينشئه بمفتاح أول.
+    /// Opens the shared key file or creates it with a first key.
+    ///
+    /// # Errors
+    /// Returns `FingerprintKeyError` if the file cannot be read, parsed or written.
+    pub fn open_or_create(path: &Path) -> Result<Self, FingerprintKeyError> {
+        if !path.exists() {
+            let mut keyring = Self {
+                active: 0,
+                keys: BTreeMap::new(),
+                path: Some(path.to_path_buf()),
+            };
+            keyring.rotate()?;
+            return Ok(keyring);
+        }
+        let stored = StoredKeyring::read(path)
+            .map_err(|e| FingerprintKeyError::Storage(format!("{}: {e}", path.display())))?;
+        let mut keys = BTreeMap::new();
+        for entry in stored.keys {
+            keys.insert(
+                entry.id,
+                (decode_key(entry.id, &entry.key)?, entry.created_at),
+            );
+        }
+        if !keys.contains_key(&stored.active_key_id) {
+            return Err(FingerprintKeyError::UnknownKeyId(stored.active_key_id));
+        }
+        Ok(Self {
+            active: stored.active_key_id,
+            keys,
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    /// يولد مفتاحاً نشطاً جديداً مع الإبقاء على القديمة للمطابقة، ويحفظ الملف إن وجد.
+    /// Generates a new active key while keeping older ones for matching, persisting the file if any.
+    ///
+    /// # Errors
+    /// Returns `FingerprintKeyError::Storage` if persisting fails.
+    pub fn rotate(&mut self) -> Result<u32, FingerprintKeyError> {
+        let mut bytes = vec![0_u8; KEY_LEN];
+        OsRng.fill_bytes(&mut bytes);
+        let next = self.keys.keys().next_back().map_or(1, |id| id + 1);
+        self.keys
+            .insert(next, (SecureBytes::new(bytes), Utc::now()));
+        self.active = next;
+        self.persist()?;
+        Ok(next)
+    }
+
+    /// يحذف مفتاحاً متقاعداً بعد انتهاء الترحيل.
+    /// Drops a retired key once migration is complete.
+    ///
+    /// # Errors
+    /// Returns `FingerprintKeyError` if the id is the active key or persisting fails.
+    pub fn retire(&mut self, key_id: u32) -> Result<(), FingerprintKeyError> {
+        if key_id == self.active {
+            return Err(FingerprintKeyError::InvalidSpec(
+                "cannot retire the active key".to_string(),
+            ));
+        }
+        self.keys
+            .remove(&key_id)
+            .ok_or(FingerprintKeyError::UnknownKeyId(key_id))?;
+        self.persist()
+    }
+
+    #[must_use]
+    pub const fn active_key_id(&self) -> u32 {
+        self.active
+    }
+
+    #[must_use]
+    pub fn active_key(&self) -> &SecureBytes {
+        // المفتاح النشط موجود دائماً بحكم البناء
+        // The active key always exists by construction
+        &self.keys[&self.active].0
+    }
+
+    #[must_use]
+    pub fn key(&self, key_id: u32) -> Option<&SecureBytes> {
+        self.keys.get(&key_id).map(|(key, _)| key)
+    }
+
+    /// كل المفاتيح من الأحدث إلى الأقدم.
+    /// Every key, newest first.
+    pub fn keys(&self) -> impl Iterator<Item = (u32, &SecureBytes)> {
+        self.keys.iter().rev().map(|(id, (key, _))| (*id, key))
+    }
+
+    fn persist(&self) -> Result<(), FingerprintKeyError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        StoredKeyring::new(
+            self.active,
+            self.keys
+                .iter()
+                .map(|(id, (key, created_at))| (*id, key.expose(), *created_at)),
+        )
+        .write(path)
+        .map_err(|e| FingerprintKeyError::Storage(format!("{}: {e}", path.display())))
+    }
+}
+
+fn decode_key(id: u32, key: &str) -> Result<SecureBytes, FingerprintKeyError> {
+    hex::decode(key)
+        .ok()
+        .filter(|b| b.len() >= MIN_KEY_LEN)
+        .map(SecureBytes::new)
+        .ok_or_else(|| {
+            FingerprintKeyError::InvalidSpec(format!(
+                "key {id} must be at least {MIN_KEY_LEN} hex-encoded bytes"
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_keyring_survives_reopen_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("fp-keys-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("fingerprint_keys.json");
+
+        let mut keyring = FingerprintKeyring::open_or_create(&path).unwrap();
+        let first = keyring.active_key().expose().to_vec();
+        assert_eq!(keyring.rotate().unwrap(), 2);
+
+        let reopened = FingerprintKeyring::open_or_create(&path).unwrap();
+        assert_eq!(reopened.active_key_id(), 2);
+        assert_eq!(reopened.key(1).unwrap().expose(), first.as_slice());
+        assert_eq!(
+            reopened.keys().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_spec_parsing() {
+        let spec = format!("1:{},2:{}", "11".repeat(32), "22".repeat(32));
+        let keyring = FingerprintKeyring::from_spec(&spec, Some(1)).unwrap();
+        assert_eq!(keyring.active_key_id(), 1);
+        assert!(FingerprintKeyring::from_spec(&spec, Some(3)).is_err());
+        assert!(FingerprintKeyring::from_spec("1:abcd", None).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+/******************************************************************************************
+*  📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+*  ملف: src/security/key_file.rs
+*
+*  الهدف: صيغة ملف حلقة المفاتيح المشتركة بين حلقة عناوين IP ومفاتيح البصمة، مع قراءته
+*  وكتابته ذرياً بصلاحيات المالك فقط.
+*
+*  Purpose: The keyring file format shared by the IP keyring and the fingerprint keys,
+*  with reading it and writing it atomically with owner-only permissions.
+******************************************************************************************/
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// مفتاح مخزن بترميز hex.
+/// A stored key, hex encoded.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredKey {
+    pub id: u32,
+    pub key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// محتوى ملف حلقة المفاتيح.
+/// Contents of a keyring file.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredKeyring {
+    pub active_key_id: u32,
+    pub keys: Vec<StoredKey>,
+}
+
+impl StoredKeyring {
+    pub fn new<'a>(
+        active_key_id: u32,
+        keys: impl IntoIterator<Item = (u32, &'a [u8], DateTime<Utc>)>,
+    ) -> Self {
+        Self {
+            active_key_id,
+            keys: keys
+                .into_iter()
+                .map(|(id, key, created_at)| StoredKey {
+                    id,
+                    key: hex::encode(key),
+                    created_at,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let raw = std::fs::read_to_string(path)?;
+        serde_json::from_str(&raw).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// كتابة ذرية عبر ملف مؤقت يُنشأ بصلاحية 0600 ثم إعادة تسمية.
+    /// Atomic write through a temporary file created with mode 0600, then renamed.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp = path.with_extension("tmp");
+        // ملف مؤقت متبقٍ قد يحمل صلاحيات أوسع، فيُحذف ليُنشأ من جديد
+        // A leftover temporary file may carry wider permissions, so it is recreated
+        match std::fs::remove_file(&tmp) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    }
+}
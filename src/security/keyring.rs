@@ -0,0 +1,462 @@
+/******************************************************************************************
+*  📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+*  ملف: src/security/keyring.rs
+*
+*  الهدف: حلقة مفاتيح ذات إصدارات لحماية عناوين IP. كل نص مشفر يحمل معرف المفتاح،
+*  ويمكن تدوير المفاتيح دورياً مع الإبقاء على القديمة لفك التشفير. فك التشفير مقصور
+*  على دور المدير عبر `PolicyEngine`، ويتوفر وضع اسم مستعار حتمي (HMAC) للربط بين
+*  الأحداث دون كشف العنوان.
+*
+*  Purpose: Versioned keyring protecting IP addresses. Every ciphertext embeds its key
+*  id, keys can be rotated on a schedule while old ones remain for decryption, decryption
+*  is restricted to the admin role through `PolicyEngine`, and a deterministic keyed
+*  pseudonym (HMAC) mode correlates events without exposing the address.
+******************************************************************************************/
+
+use crate::security::key_file::StoredKeyring;
+use crate::security::policy::{Action, PolicyContext, PolicyEngine, PolicyError};
+use crate::security::secret::SecureBytes;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, Nonce};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const KEY_LEN: usize = 64;
+const NONCE_LEN: usize = 12;
+
+/// أخطاء حلقة المفاتيح
+/// Keyring errors
+#[derive(Debug, thiserror::Error)]
+pub enum KeyringError {
+    #[error("keyring storage error: {0}")]
+    Storage(String),
+    #[error("unknown key id {0}")]
+    UnknownKeyId(u32),
+    #[error("malformed protected IP token")]
+    InvalidToken,
+    #[error("crypto failure: {0}")]
+    Crypto(String),
+    #[error("not authorized to decrypt: {0}")]
+    Unauthorized(PolicyError),
+    #[error("could not acquire keyring lock")]
+    LockFailed,
+}
+
+struct KeyMaterial {
+    key: SecureBytes,
+    created_at: DateTime<Utc>,
+}
+
+impl KeyMaterial {
+    fn generate() -> Self {
+        let mut bytes = vec![0_u8; KEY_LEN];
+        OsRng.fill_bytes(&mut bytes);
+        Self {
+            key: SecureBytes::new(bytes),
+            created_at: Utc::now(),
+        }
+    }
+
+    // أول 32 بايت لـ AES-256-GCM والباقي لـ HMAC حتى لا يُستخدم المفتاح نفسه لغرضين
+    // First 32 bytes for AES-256-GCM, the rest for HMAC, so no key serves two purposes
+    fn cipher_key(&self) -> &[u8] {
+        &self.key.expose()[..32]
+    }
+
+    fn mac_key(&self) -> &[u8] {
+        &self.key.expose()[32..]
+    }
+}
+
+struct KeyringState {
+    active: u32,
+    keys: BTreeMap<u32, KeyMaterial>,
+}
+
+/// حلقة مفاتيح حماية عناوين IP.
+/// IP protection keyring.
+pub struct IpKeyring {
+    state: RwLock<KeyringState>,
+    path: Option<PathBuf>,
+    max_keys: usize,
+}
+
+impl IpKeyring {
+    /// حلقة مؤقتة في الذاكرة بمفتاح عشوائي واحد (تضيع عند إعادة التشغيل).
+    /// Ephemeral in-memory keyring with one random key (lost on restart).
+    #[must_use]
+    pub fn ephemeral() -> Self {
+        let mut keys = BTreeMap::new();
+        keys.insert(1, KeyMaterial::generate());
+        Self {
+            state: RwLock::new(KeyringState { active: 1, keys }),
+            path: None,
+            max_keys: usize::MAX,
+        }
+    }
+
+    /// يفتح ملف الحلقة أو ينشئه بمفتاح أول؛ يُحتفظ بآخر `max_keys` مفاتيح فقط.
+    /// Opens the keyring file or creates it with a first key; only the newest `max_keys` are kept.
+    ///
+    /// # Errors
+    /// Returns `KeyringError::Storage` if the file cannot be read, parsed or written.
+    pub fn open_or_create(path: &Path, max_keys: usize) -> Result<Self, KeyringError> {
+        let keyring = if path.exists() {
+            let stored = StoredKeyring::read(path)
+                .map_err(|e| KeyringError::Storage(format!("{}: {e}", path.display())))?;
+            let mut keys = BTreeMap::new();
+            for entry in stored.keys {
+                let bytes = hex::decode(&entry.key)
+                    .ok()
+                    .filter(|b| b.len() == KEY_LEN)
+                    .ok_or_else(|| KeyringError::Storage(format!("bad key {}", entry.id)))?;
+                keys.insert(
+                    entry.id,
+                    KeyMaterial {
+                        key: SecureBytes::new(bytes),
+                        created_at: entry.created_at,
+                    },
+                );
+            }
+            if !keys.contains_key(&stored.active_key_id) {
+                return Err(KeyringError::UnknownKeyId(stored.active_key_id));
+            }
+            Self {
+                state: RwLock::new(KeyringState {
+                    active: stored.active_key_id,
+                    keys,
+                }),
+                path: Some(path.to_path_buf()),
+                max_keys: max_keys.max(1),
+            }
+        } else {
+            let keyring = Self {
+                path: Some(path.to_path_buf()),
+                max_keys: max_keys.max(1),
+                ..Self::ephemeral()
+            };
+            {
+                let state = keyring.state.read().map_err(|_| KeyringError::LockFailed)?;
+                keyring.persist(state.active, state.keys.iter().map(|(id, m)| (*id, m)))?;
+            }
+            keyring
+        };
+        Ok(keyring)
+    }
+
+    /// معرف المفتاح النشط.
+    /// Active key id.
+    ///
+    /// # Errors
+    /// Returns `KeyringError::LockFailed` if the lock is poisoned.
+    pub fn active_key_id(&self) -> Result<u32, KeyringError> {
+        Ok(self
+            .state
+            .read()
+            .map_err(|_| KeyringError::LockFailed)?
+            .active)
+    }
+
+    /// كل معرفات المفاتيح المتاحة لفك التشفير.
+    /// Every key id still available for decryption.
+    ///
+    /// # Errors
+    /// Returns `KeyringError::LockFailed` if the lock is poisoned.
+    pub fn key_ids(&self) -> Result<Vec<u32>, KeyringError> {
+        Ok(self
+            .state
+            .read()
+            .map_err(|_| KeyringError::LockFailed)?
+            .keys
+            .keys()
+            .copied()
+            .collect())
+    }
+
+    /// يولد مفتاحاً جديداً ويجعله نشطاً، ويحذف الأقدم بعد تجاوز الحد.
+    /// Generates a new active key and drops the oldest beyond the retention limit.
+    /// The in-memory keyring only changes once the new set has been persisted.
+    ///
+    /// # Errors
+    /// Returns `KeyringError` if the lock is poisoned or persisting fails.
+    pub fn rotate(&self) -> Result<u32, KeyringError> {
+        let mut state = self.state.write().map_err(|_| KeyringError::LockFailed)?;
+        let next = state.keys.keys().next_back().map_or(1, |id| id + 1);
+        let material = KeyMaterial::generate();
+        let evicted = (state.keys.len() + 1).saturating_sub(self.max_keys);
+        self.persist(
+            next,
+            state
+                .keys
+                .iter()
+                .skip(evicted)
+                .map(|(id, material)| (*id, material))
+                .chain(std::iter::once((next, &material))),
+        )?;
+        state.keys.insert(next, material);
+        state.active = next;
+        while state.keys.len() > self.max_keys {
+            state.keys.pop_first();
+        }
+        Ok(next)
+    }
+
+    /// يدوّر المفتاح إذا تجاوز عمر المفتاح النشط `max_age`.
+    /// Rotates when the active key is older than `max_age`.
+    ///
+    /// # Errors
+    /// Returns `KeyringError` if rotation fails.
+    pub fn rotate_if_older_than(&self, max_age: Duration) -> Result<Option<u32>, KeyringError> {
+        let created_at = {
+            let state = self.state.read().map_err(|_| KeyringError::LockFailed)?;
+            state
+                .keys
+                .get(&state.active)
+                .map(|k| k.created_at)
+                .ok_or(KeyringError::UnknownKeyId(state.active))?
+        };
+        if Utc::now() - created_at < max_age {
+            return Ok(None);
+        }
+        self.rotate().map(Some)
+    }
+
+    /// يشغل مهمة خلفية تفحص عمر المفتاح النشط دورياً وتدوّره عند الحاجة.
+    /// Spawns a background task that periodically rotates the active key when it ages out.
+    pub fn spawn_rotation(
+        self: &Arc<Self>,
+        max_age: Duration,
+        check_every: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let keyring = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check_every);
+            loop {
+                interval.tick().await;
+                match keyring.rotate_if_older_than(max_age) {
+                    Ok(Some(id)) => log::info!("IP keyring rotated to key id {id}"),
+                    Ok(None) => {}
+                    Err(e) => log::warn!("IP keyring rotation failed: {e}"),
+                }
+            }
+        })
+    }
+
+    /// يشفر عنواناً بالمفتاح النشط: `k<id>.<hex(nonce||ciphertext)>`.
+    /// Encrypts an address with the active key: `k<id>.<hex(nonce||ciphertext)>`.
+    ///
+    /// # Errors
+    /// Returns `KeyringError` if the lock is poisoned or encryption fails.
+    pub fn encrypt_ip(&self, ip: &IpAddr) -> Result<String, KeyringError> {
+        let state = self.state.read().map_err(|_| KeyringError::LockFailed)?;
+        let material = state
+            .keys
+            .get(&state.active)
+            .ok_or(KeyringError::UnknownKeyId(state.active))?;
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(material.cipher_key()));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = format!("k{}", state.active);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: ip.to_string().as_bytes(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|e| KeyringError::Crypto(e.to_string()))?;
+        let mut combined = nonce.to_vec();
+        combined.extend_from_slice(&ciphertext);
+        Ok(format!("{aad}.{}", hex::encode(combined)))
+    }
+
+    /// اسم مستعار حتمي بالمفتاح النشط: `p<id>.<hex(hmac)>`.
+    /// Deterministic pseudonym with the active key: `p<id>.<hex(hmac)>`.
+    ///
+    /// # Errors
+    /// Returns `KeyringError` if the lock is poisoned.
+    pub fn pseudonymize_ip(&self, ip: &IpAddr) -> Result<String, KeyringError> {
+        let active = self.active_key_id()?;
+        self.pseudonymize_ip_with(ip, active)
+    }
+
+    /// اسم مستعار بمفتاح محدد، لمطابقة أحداث قديمة بعد التدوير.
+    /// Pseudonym under a specific key, to match older events after rotation.
+    ///
+    /// # Errors
+    /// Returns `KeyringError::UnknownKeyId` if the key was pruned.
+    pub fn pseudonymize_ip_with(&self, ip: &IpAddr, key_id: u32) -> Result<String, KeyringError> {
+        let state = self.state.read().map_err(|_| KeyringError::LockFailed)?;
+        let material = state
+            .keys
+            .get(&key_id)
+            .ok_or(KeyringError::UnknownKeyId(key_id))?;
+        let mut mac = <HmacSha256 as hmac::KeyInit>::new_from_slice(material.mac_key())
+            .map_err(|e| KeyringError::Crypto(e.to_string()))?;
+        mac.update(ip.to_string().as_bytes());
+        Ok(format!(
+            "p{key_id}.{}",
+            hex::encode(mac.finalize().into_bytes())
+        ))
+    }
+
+    /// يفك تشفير عنوان بعد التحقق من أن السياق يملك دور المدير.
+    /// Decrypts an address after checking the context holds the admin role.
+    ///
+    /// # Errors
+    /// Returns `KeyringError::Unauthorized` for non-admin contexts, or a token/crypto error.
+    pub fn decrypt_ip(&self, token: &str, context: &PolicyContext) -> Result<IpAddr, KeyringError> {
+        PolicyEngine::can_execute(context, &Action::DecryptIpAddress)
+            .map_err(KeyringError::Unauthorized)?;
+
+        let (prefix, payload) = token.split_once('.').ok_or(KeyringError::InvalidToken)?;
+        let key_id = prefix
+            .strip_prefix('k')
+            .and_then(|id| id.parse::<u32>().ok())
+            .ok_or(KeyringError::InvalidToken)?;
+        let combined = hex::decode(payload).map_err(|_| KeyringError::InvalidToken)?;
+        if combined.len() <= NONCE_LEN {
+            return Err(KeyringError::InvalidToken);
+        }
+        let (nonce, ciphertext) = combined.split_at(NONCE_LEN);
+
+        let state = self.state.read().map_err(|_| KeyringError::LockFailed)?;
+        let material = state
+            .keys
+            .get(&key_id)
+            .ok_or(KeyringError::UnknownKeyId(key_id))?;
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(material.cipher_key()));
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: prefix.as_bytes(),
+                },
+            )
+            .map_err(|e| KeyringError::Crypto(e.to_string()))?;
+        String::from_utf8(plaintext)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(KeyringError::InvalidToken)
+    }
+
+    fn persist<'a>(
+        &self,
+        active: u32,
+        keys: impl Iterator<Item = (u32, &'a KeyMaterial)>,
+    ) -> Result<(), KeyringError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        StoredKeyring::new(
+            active,
+            keys.map(|(id, material)| (id, material.key.expose(), material.created_at)),
+        )
+        .write(path)
+        .map_err(|e| KeyringError::Storage(format!("{}: {e}", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::policy::{Role, UserStatus};
+    use uuid::Uuid;
+
+    fn context(roles: &[Role]) -> PolicyContext<'_> {
+        PolicyContext {
+            user_id: Uuid::new_v4(),
+            roles,
+            status: &UserStatus::Active,
+            trust_score: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_encrypt_rotate_and_admin_only_decrypt() {
+        let keyring = IpKeyring::ephemeral();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let old_token = keyring.encrypt_ip(&ip).unwrap();
+        assert!(old_token.starts_with("k1."));
+
+        assert_eq!(keyring.rotate().unwrap(), 2);
+        let new_token = keyring.encrypt_ip(&ip).unwrap();
+        assert!(new_token.starts_with("k2."));
+
+        let admin = context(&[Role::Admin]);
+        assert_eq!(keyring.decrypt_ip(&old_token, &admin).unwrap(), ip);
+        assert_eq!(keyring.decrypt_ip(&new_token, &admin).unwrap(), ip);
+        assert!(matches!(
+            keyring.decrypt_ip(&new_token, &context(&[Role::Moderator])),
+            Err(KeyringError::Unauthorized(_))
+        ));
+        let tampered = new_token.replacen("k2.", "k1.", 1);
+        assert!(keyring.decrypt_ip(&tampered, &admin).is_err());
+    }
+
+    #[test]
+    fn test_pseudonym_and_persistence() {
+        let dir = std::env::temp_dir().join(format!("ip_keyring_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keyring.json");
+        let ip: IpAddr = "198.51.100.4".parse().unwrap();
+
+        let keyring = IpKeyring::open_or_create(&path, 2).unwrap();
+        let pseudonym = keyring.pseudonymize_ip(&ip).unwrap();
+        assert_eq!(pseudonym, keyring.pseudonymize_ip(&ip).unwrap());
+        let token = keyring.encrypt_ip(&ip).unwrap();
+        keyring.rotate().unwrap();
+        keyring.rotate().unwrap();
+        assert_eq!(keyring.key_ids().unwrap(), vec![2, 3]);
+        assert!(keyring
+            .rotate_if_older_than(Duration::days(1))
+            .unwrap()
+            .is_none());
+
+        let reopened = IpKeyring::open_or_create(&path, 2).unwrap();
+        assert_eq!(reopened.active_key_id().unwrap(), 3);
+        assert!(matches!(
+            reopened.decrypt_ip(&token, &context(&[Role::Admin])),
+            Err(KeyringError::UnknownKeyId(1))
+        ));
+        assert_eq!(
+            reopened.pseudonymize_ip_with(&ip, 2).unwrap(),
+            keyring.pseudonymize_ip_with(&ip, 2).unwrap()
+        );
+        assert_ne!(reopened.pseudonymize_ip(&ip).unwrap(), pseudonym);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failed_persist_leaves_keyring_unchanged() {
+        let dir = std::env::temp_dir().join(format!("ip_keyring_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keyring.json");
+        let ip: IpAddr = "192.0.2.9".parse().unwrap();
+
+        let keyring = IpKeyring::open_or_create(&path, 2).unwrap();
+        let token = keyring.encrypt_ip(&ip).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // تعذر الحفظ لا يبدّل المفتاح النشط ولا يحذف مفاتيح قديمة
+        // A failed save neither switches the active key nor evicts old keys
+        assert!(matches!(keyring.rotate(), Err(KeyringError::Storage(_))));
+        assert_eq!(keyring.active_key_id().unwrap(), 1);
+        assert_eq!(keyring.key_ids().unwrap(), vec![1]);
+        assert_eq!(
+            keyring
+                .decrypt_ip(&token, &context(&[Role::Admin]))
+                .unwrap(),
+            ip
+        );
+    }
+}
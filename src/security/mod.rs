@@ -22,6 +22,10 @@
     allowing other parts of the project to use them easily.
 ******************************************************************************************/
 
+// Arabic: مفاتيح بصمة الأجهزة الثابتة عبر إعادة التشغيل مع التدوير
+// English: Device-fingerprint keys stable across restarts, with rotation
+pub mod fingerprint_keys;
+
 // Arabic: وحدة التحقق من المدخلات (Input Validator)
 // English: Input Validator module
 pub mod input_validator;
@@ -30,6 +34,14 @@ pub mod input_validator;
 // English: JWT module
 pub mod jwt;
 
+// Arabic: صيغة ملف حلقة المفاتيح وكتابته الذرية بصلاحيات المالك فقط
+// English: Keyring file format and its atomic, owner-only write
+mod key_file;
+
+// Arabic: حلقة مفاتيح حماية عناوين IP مع التدوير والأسماء المستعارة
+// English: IP protection keyring with rotation and pseudonyms
+pub mod keyring;
+
 // Arabic: وحدة السياسات الأمنية
 // English: Security Policy module
 pub mod policy;
@@ -42,6 +54,10 @@ pub mod ratelimit;
 // English: High-security signing utilities (no OpenSSL)
 pub mod signing;
 
+// Arabic: قاعدة توقيعات التهديد الموقّعة والقابلة للتحديث
+// English: Signed, updatable threat signature database
+pub mod threat_signatures;
+
 // Arabic: طبقة تغليف لوحدة الأسرار لتوحيد الاستدعاءات وعزل تغييرات الإصدارات
 // English: Secret wrapper layer to unify calls and isolate version changes
 pub mod secret;
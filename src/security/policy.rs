@@ -67,10 +67,28 @@ pub enum UserStatus {
     Banned,
 }
 
+impl UserStatus {
+    /// Arabic: يحول قيمة عمود `status` في جدول المستخدمين؛ القيم غير المعروفة تُعامل كإيقاف.
+    /// English: Converts the users table `status` column; unknown values are treated as suspended.
+    #[must_use]
+    pub fn from_record(status: &str) -> Self {
+        match status.to_lowercase().as_str() {
+            "active" => Self::Active,
+            "banned" => Self::Banned,
+            _ => Self::Suspended,
+        }
+    }
+}
+
 /// Arabic: تعريف الأدوار المختلفة في النظام.
 /// English: Defines the different roles within the system.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Role {
+    /// Arabic: حساب خدمة خلفية (مثل بوابة الدخول) يبلّغ عن أحداث لحسابات أخرى؛ ليس ضمن
+    /// تسلسل صلاحيات المستخدمين.
+    /// English: A backend service account (such as the login gateway) that reports events
+    /// for other accounts; not part of the user permission hierarchy.
+    Service,
     /// Arabic: المستخدم العادي، يمتلك الصلاحيات الأساسية على بياناته فقط.
     /// English: A standard user, has basic permissions on their own data.
     User,
@@ -92,6 +110,7 @@ impl FromStr for Role {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
+            "service" => Ok(Self::Service),
             "user" => Ok(Self::User),
             "trusted_user" => Ok(Self::TrustedUser),
             "moderator" => Ok(Self::Moderator),
@@ -141,12 +160,30 @@ pub enum Action<'a> {
     /// Arabic: الوصول إلى بيانات مستخدم آخر (للمشرفين).
     /// English: Accessing another user's data (for admins).
     ReadUserData { target_user_id: &'a Uuid },
+    /// Arabic: قراءة الملف الشخصي الكامل مع الملخص السلوكي (لصاحبه أو للمدير فقط).
+    /// English: Reading the full profile with its behavioral summary (owner or admin only).
+    ReadUserProfile { target_user_id: &'a Uuid },
     /// Arabic: إنشاء تقرير أمني.
     /// English: Generating a security report.
     GenerateSecurityReport,
     /// Arabic: إجراء حساس يتطلب درجة ثقة عالية.
     /// English: A sensitive action that requires a high trust score.
     PerformSensitiveTransaction,
+    /// Arabic: فك تشفير عنوان IP محمي (للمدير فقط).
+    /// English: Decrypting a protected IP address (admin only).
+    DecryptIpAddress,
+    /// Arabic: إدارة جهاز (إعادة تسمية أو إلغاء) - لمالكه أو للمدير.
+    /// English: Managing a device (rename or revoke) - for its owner or an admin.
+    ManageDevice { owner_id: &'a Uuid },
+    /// Arabic: تغيير حالة الثقة في جهاز (للمدير فقط).
+    /// English: Changing a device's trust state (admin only).
+    SetDeviceTrust,
+    /// Arabic: تصنيف قرار مخاطر كاحتيال أو شرعي (للمشرفين).
+    /// English: Labeling a risk decision as fraud or legit (for moderators).
+    LabelRiskDecision,
+    /// Arabic: إرسال أحداث دخول أو بيانات سلوك باسم حسابات أخرى (لحسابات الخدمة).
+    /// English: Submitting access events or behavior data on behalf of other accounts (for service accounts).
+    SubmitOnBehalf,
 }
 
 /// Arabic: محرك السياسات الذكي.
@@ -194,13 +231,17 @@ impl PolicyEngine {
         // --- Stage 4: Role-Based Checks (RBAC) ---
         let has_permission = context.roles.iter().any(|role| match action {
             Action::ReadOwnData | Action::UpdateOwnProfile => true,
-            Action::ReadDeviceData { .. } | Action::GenerateSecurityReport => {
-                *role >= Role::Moderator
-            }
+            Action::SubmitOnBehalf => *role == Role::Service,
+            Action::ReadDeviceData { .. }
+            | Action::GenerateSecurityReport
+            | Action::LabelRiskDecision => *role >= Role::Moderator,
             Action::ReadUserData { target_user_id } => {
                 &context.user_id == *target_user_id || *role >= Role::Moderator
             }
+            Action::ReadUserProfile { target_user_id } => &context.user_id == *target_user_id,
             Action::PerformSensitiveTransaction => *role >= Role::TrustedUser,
+            Action::ManageDevice { owner_id } => &context.user_id == *owner_id,
+            Action::DecryptIpAddress | Action::SetDeviceTrust => false,
         });
 
         if has_permission {
@@ -250,6 +291,10 @@ mod tests {
 
         // --- صلاحيات المستخدم العادي ---
         // --- Basic user permissions ---
+        assert_eq!(
+            PolicyEngine::can_execute(&user_context, &Action::SubmitOnBehalf),
+            Err(PolicyError::InsufficientPermissions)
+        );
         assert_eq!(
             PolicyEngine::can_execute(&user_context, &Action::ReadOwnData),
             Ok(())
@@ -273,6 +318,42 @@ mod tests {
             Err(PolicyError::InsufficientPermissions)
         );
 
+        assert_eq!(
+            PolicyEngine::can_execute(
+                &user_context,
+                &Action::ManageDevice {
+                    owner_id: &other_user_id
+                }
+            ),
+            Err(PolicyError::InsufficientPermissions)
+        );
+        assert_eq!(
+            PolicyEngine::can_execute(&moderator_context, &Action::SetDeviceTrust),
+            Err(PolicyError::InsufficientPermissions)
+        );
+
+        // --- حساب الخدمة يبلّغ باسم الآخرين ولا يقرأ بياناتهم ---
+        // --- A service account reports for others but cannot read their data ---
+        let service_context = PolicyContext {
+            user_id,
+            roles: &[Role::Service],
+            status: &active_status,
+            trust_score: 0.0,
+        };
+        assert_eq!(
+            PolicyEngine::can_execute(&service_context, &Action::SubmitOnBehalf),
+            Ok(())
+        );
+        assert_eq!(
+            PolicyEngine::can_execute(
+                &service_context,
+                &Action::ReadUserData {
+                    target_user_id: &other_user_id
+                }
+            ),
+            Err(PolicyError::InsufficientPermissions)
+        );
+
         // --- صلاحيات المشرف ---
         // --- Moderator permissions ---
         assert_eq!(
@@ -284,6 +365,15 @@ mod tests {
             ),
             Ok(())
         );
+        assert_eq!(
+            PolicyEngine::can_execute(
+                &moderator_context,
+                &Action::ReadUserProfile {
+                    target_user_id: &other_user_id
+                }
+            ),
+            Err(PolicyError::InsufficientPermissions)
+        );
         assert_eq!(
             PolicyEngine::can_execute(&moderator_context, &Action::GenerateSecurityReport),
             Ok(())
@@ -292,6 +382,14 @@ mod tests {
             PolicyEngine::can_execute(&user_context, &Action::GenerateSecurityReport),
             Err(PolicyError::InsufficientPermissions)
         );
+        assert_eq!(
+            PolicyEngine::can_execute(&moderator_context, &Action::LabelRiskDecision),
+            Ok(())
+        );
+        assert_eq!(
+            PolicyEngine::can_execute(&user_context, &Action::LabelRiskDecision),
+            Err(PolicyError::InsufficientPermissions)
+        );
 
         // --- صلاحيات درجة الثقة ---
         // --- Trust score permissions ---
@@ -319,6 +417,15 @@ mod tests {
             ),
             Ok(())
         );
+        assert_eq!(
+            PolicyEngine::can_execute(
+                &admin_context,
+                &Action::ReadUserProfile {
+                    target_user_id: &other_user_id
+                }
+            ),
+            Ok(())
+        );
     }
 
     /// Arabic: اختبار رفض الإجراءات بناءً على حالة الحساب (موقوف/محظور).
@@ -359,6 +466,7 @@ mod tests {
     fn test_role_from_str() {
         assert_eq!(Role::from_str("user").unwrap(), Role::User);
         assert_eq!(Role::from_str("ADMIN").unwrap(), Role::Admin); // Case-insensitive
+        assert_eq!(Role::from_str("service").unwrap(), Role::Service);
         assert!(Role::from_str("guest").is_err());
     }
 }
@@ -0,0 +1,432 @@
+/******************************************************************************************
+*  📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+*  ملف: src/security/threat_signatures.rs
+*
+*  الهدف: قاعدة توقيعات التهديد القابلة للتحميل لـ `SecurityMonitor`. ملف التوقيعات
+*  JSON مُرقّم الإصدار وموقّع بـ HMAC-SHA512؛ كل توقيع له نمط وخطورة وحقل هدف ونوع
+*  مطابقة (تام، جزئي، تعبير نمطي). الملفات الأحدث تُدمج (إضافة/استبدال/حذف بالمعرف)
+*  أو تستبدل المجموعة كاملة، والإصدارات القديمة تُرفض لمنع إعادة التشغيل.
+*
+*  Purpose: Loadable threat signature database for `SecurityMonitor`. The signature file
+*  is versioned JSON signed with HMAC-SHA512; each signature has a pattern, severity,
+*  target field and match kind (exact, substring, regex). Newer files are merged
+*  (add/replace/remove by id) or replace the whole set, and older versions are rejected
+*  to prevent replay.
+******************************************************************************************/
+
+use crate::security::secret::SecureBytes;
+use crate::security::signing::{sign_struct_excluding_field, verify_struct_excluding_field};
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// صيغة ملف التوقيعات المدعومة.
+/// Supported signature file format.
+pub const SIGNATURE_FILE_FORMAT: u32 = 1;
+
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+/// أخطاء قاعدة التوقيعات
+/// Signature database errors
+#[derive(Debug, thiserror::Error)]
+pub enum ThreatSignatureError {
+    #[error("threat signature file error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("threat signature file is malformed: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("unsupported threat signature format {0}")]
+    UnsupportedFormat(u32),
+    #[error("threat signature file signature is invalid")]
+    InvalidSignature,
+    #[error("threat signature version {offered} is not newer than {current}")]
+    StaleVersion { current: u64, offered: u64 },
+    #[error("invalid pattern in signature '{id}': {message}")]
+    InvalidPattern { id: String, message: String },
+}
+
+/// الحقل الذي يُطبق عليه التوقيع.
+/// Field the signature applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureTarget {
+    Os,
+    DeviceInfo,
+    Any,
+}
+
+/// نوع المطابقة؛ التام والجزئي لا يميزان حالة الأحرف.
+/// Match kind; exact and substring are case-insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Exact,
+    Substring,
+    Regex,
+}
+
+/// توقيع تهديد واحد.
+/// A single threat signature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreatSignature {
+    pub id: String,
+    pub pattern: String,
+    #[serde(rename = "match")]
+    pub match_kind: MatchKind,
+    pub target: SignatureTarget,
+    /// 1 (منخفضة) إلى 10 (حرجة).
+    /// 1 (low) to 10 (critical).
+    pub severity: u8,
+}
+
+/// ملف التوقيعات الموقّع.
+/// Signed signature file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignatureFile {
+    pub format: u32,
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_at: Option<DateTime<Utc>>,
+    /// `true`: لقطة كاملة تستبدل المجموعة؛ `false`: تحديث يُدمج فوقها.
+    /// `true`: a full snapshot replacing the set; `false`: an update merged on top.
+    #[serde(default)]
+    pub full: bool,
+    pub signatures: Vec<ThreatSignature>,
+    /// معرفات توقيعات تُحذف عند الدمج.
+    /// Signature ids removed when merging.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    /// HMAC-SHA512 بصيغة hex فوق الملف دون هذا الحقل.
+    /// Hex HMAC-SHA512 over the file without this field.
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl SignatureFile {
+    /// يوقّع الملف بالمفتاح المعطى (لأدوات النشر والاختبارات).
+    /// Signs the file with the given key (for publishing tools and tests).
+    ///
+    /// # Errors
+    /// Returns `ThreatSignatureError::InvalidSignature` if signing fails.
+    pub fn sign(&mut self, key: &SecureBytes) -> Result<(), ThreatSignatureError> {
+        self.signature.clear();
+        let signature = sign_struct_excluding_field(self, "signature", key)
+            .map_err(|_| ThreatSignatureError::InvalidSignature)?;
+        self.signature = hex::encode(signature);
+        Ok(())
+    }
+
+    /// يحلل ملفاً ويتحقق من صيغته وتوقيعه.
+    /// Parses a file and checks its format and signature.
+    ///
+    /// # Errors
+    /// Returns `ThreatSignatureError` if the file is malformed, of an unknown format or
+    /// its signature does not verify.
+    pub fn parse_signed(raw: &str, key: &SecureBytes) -> Result<Self, ThreatSignatureError> {
+        let file: Self = serde_json::from_str(raw)?;
+        if file.format != SIGNATURE_FILE_FORMAT {
+            return Err(ThreatSignatureError::UnsupportedFormat(file.format));
+        }
+        let signature =
+            hex::decode(&file.signature).map_err(|_| ThreatSignatureError::InvalidSignature)?;
+        if !verify_struct_excluding_field(&file, "signature", &signature, key) {
+            return Err(ThreatSignatureError::InvalidSignature);
+        }
+        Ok(file)
+    }
+}
+
+/// توقيع مطابق مع خطورته.
+/// A matched signature with its severity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreatHit {
+    pub id: String,
+    pub severity: u8,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Exact(String),
+    Substring(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+struct CompiledSignature {
+    target: SignatureTarget,
+    severity: u8,
+    matcher: Matcher,
+}
+
+impl CompiledSignature {
+    fn compile(signature: &ThreatSignature) -> Result<Self, ThreatSignatureError> {
+        let invalid = |message: String| ThreatSignatureError::InvalidPattern {
+            id: signature.id.clone(),
+            message,
+        };
+        if signature.pattern.is_empty() {
+            return Err(invalid("empty pattern".to_string()));
+        }
+        if !(1..=10).contains(&signature.severity) {
+            return Err(invalid(format!(
+                "severity {} out of 1-10",
+                signature.severity
+            )));
+        }
+        let matcher = match signature.match_kind {
+            MatchKind::Exact => Matcher::Exact(signature.pattern.to_lowercase()),
+            MatchKind::Substring => Matcher::Substring(signature.pattern.to_lowercase()),
+            MatchKind::Regex => Matcher::Regex(
+                RegexBuilder::new(&signature.pattern)
+                    .size_limit(MAX_REGEX_SIZE)
+                    .build()
+                    .map_err(|e| invalid(e.to_string()))?,
+            ),
+        };
+        Ok(Self {
+            target: signature.target,
+            severity: signature.severity,
+            matcher,
+        })
+    }
+
+    fn matches(&self, os: &str, device_info: &str) -> bool {
+        let fields: &[&str] = match self.target {
+            SignatureTarget::Os => &[os],
+            SignatureTarget::DeviceInfo => &[device_info],
+            SignatureTarget::Any => &[os, device_info],
+        };
+        fields.iter().any(|field| match &self.matcher {
+            Matcher::Exact(pattern) => field.trim().to_lowercase() == *pattern,
+            Matcher::Substring(pattern) => field.to_lowercase().contains(pattern.as_str()),
+            Matcher::Regex(regex) => regex.is_match(field),
+        })
+    }
+}
+
+/// قاعدة التوقيعات المحمّلة.
+/// The loaded signature database.
+#[derive(Debug, Clone)]
+pub struct ThreatSignatureDb {
+    version: u64,
+    signatures: BTreeMap<String, CompiledSignature>,
+}
+
+impl Default for ThreatSignatureDb {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ThreatSignatureDb {
+    /// التوقيعات المضمنة (الإصدار 0) إلى أن يُحمّل ملف موقّع.
+    /// Built-in signatures (version 0) until a signed file is loaded.
+    #[must_use]
+    pub fn builtin() -> Self {
+        let mut signatures = BTreeMap::new();
+        for (id, severity) in [("rootkit", 9), ("memory_scrape", 7)] {
+            signatures.insert(
+                id.to_string(),
+                CompiledSignature {
+                    target: SignatureTarget::Any,
+                    severity,
+                    matcher: Matcher::Substring(id.to_string()),
+                },
+            );
+        }
+        Self {
+            version: 0,
+            signatures,
+        }
+    }
+
+    #[must_use]
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// يطبق ملفاً موقّعاً: يُرفض كله إن كان أي نمط غير صالح أو الإصدار غير أحدث.
+    /// Applies a signed file: it is rejected whole if any pattern is invalid or the
+    /// version is not newer.
+    ///
+    /// # Errors
+    /// Returns `ThreatSignatureError` if verification, the version check or compilation fails.
+    pub fn apply_signed(
+        &mut self,
+        raw: &str,
+        key: &SecureBytes,
+    ) -> Result<u64, ThreatSignatureError> {
+        let file = SignatureFile::parse_signed(raw, key)?;
+        self.apply(&file)
+    }
+
+    /// يحمّل ملفاً موقّعاً من القرص ويطبقه.
+    /// Loads a signed file from disk and applies it.
+    ///
+    /// # Errors
+    /// Returns `ThreatSignatureError` if the file cannot be read or applied.
+    pub fn load_file(
+        &mut self,
+        path: &Path,
+        key: &SecureBytes,
+    ) -> Result<u64, ThreatSignatureError> {
+        let raw = std::fs::read_to_string(path)?;
+        self.apply_signed(&raw, key)
+    }
+
+    fn apply(&mut self, file: &SignatureFile) -> Result<u64, ThreatSignatureError> {
+        if file.version <= self.version {
+            return Err(ThreatSignatureError::StaleVersion {
+                current: self.version,
+                offered: file.version,
+            });
+        }
+        let compiled = file
+            .signatures
+            .iter()
+            .map(|s| CompiledSignature::compile(s).map(|c| (s.id.clone(), c)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if file.full {
+            self.signatures.clear();
+        }
+        for id in &file.removed {
+            self.signatures.remove(id);
+        }
+        self.signatures.extend(compiled);
+        self.version = file.version;
+        Ok(self.version)
+    }
+
+    /// أخطر توقيع مطابق، إن وجد.
+    /// The most severe matching signature, if any.
+    #[must_use]
+    pub fn scan(&self, os: &str, device_info: &str) -> Option<ThreatHit> {
+        self.signatures
+            .iter()
+            .filter(|(_, s)| s.matches(os, device_info))
+            .max_by_key(|(_, s)| s.severity)
+            .map(|(id, s)| ThreatHit {
+                id: id.clone(),
+                severity: s.severity,
+            })
+    }
+
+    /// كل التوقيعات المطابقة.
+    /// Every matching signature.
+    #[must_use]
+    pub fn scan_all(&self, os: &str, device_info: &str) -> Vec<ThreatHit> {
+        self.signatures
+            .iter()
+            .filter(|(_, s)| s.matches(os, device_info))
+            .map(|(id, s)| ThreatHit {
+                id: id.clone(),
+                severity: s.severity,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SecureBytes {
+        SecureBytes::new(vec![7; 32])
+    }
+
+    fn signature(id: &str, pattern: &str, kind: MatchKind, severity: u8) -> ThreatSignature {
+        ThreatSignature {
+            id: id.to_string(),
+            pattern: pattern.to_string(),
+            match_kind: kind,
+            target: SignatureTarget::Any,
+            severity,
+        }
+    }
+
+    fn signed(version: u64, full: bool, signatures: Vec<ThreatSignature>) -> String {
+        let mut file = SignatureFile {
+            format: SIGNATURE_FILE_FORMAT,
+            version,
+            issued_at: None,
+            full,
+            signatures,
+            removed: vec!["memory_scrape".to_string()],
+            signature: String::new(),
+        };
+        file.sign(&key()).unwrap();
+        serde_json::to_string(&file).unwrap()
+    }
+
+    #[test]
+    fn test_signed_update_merges_and_rejects_replay() {
+        let mut db = ThreatSignatureDb::builtin();
+        let raw = signed(
+            3,
+            false,
+            vec![signature(
+                "frida",
+                r"(?i)frida-(server|gadget)",
+                MatchKind::Regex,
+                8,
+            )],
+        );
+        assert_eq!(db.apply_signed(&raw, &key()).unwrap(), 3);
+        assert_eq!(db.version(), 3);
+        assert_eq!(db.scan("Android", "FRIDA-server 16").unwrap().id, "frida");
+        assert!(db.scan("memory_scrape", "").is_none());
+        assert_eq!(db.scan("rootkit", "").unwrap().severity, 9);
+        assert!(matches!(
+            db.apply_signed(&raw, &key()),
+            Err(ThreatSignatureError::StaleVersion {
+                current: 3,
+                offered: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_tampered_or_invalid_files_are_rejected_whole() {
+        let mut db = ThreatSignatureDb::builtin();
+        let raw = signed(2, true, vec![signature("x", "x", MatchKind::Exact, 5)]);
+        let tampered = raw.replace("\"severity\":5", "\"severity\":1");
+        assert!(matches!(
+            db.apply_signed(&tampered, &key()),
+            Err(ThreatSignatureError::InvalidSignature)
+        ));
+        assert!(matches!(
+            db.apply_signed(&raw, &SecureBytes::new(vec![8; 32])),
+            Err(ThreatSignatureError::InvalidSignature)
+        ));
+
+        let bad = signed(
+            2,
+            true,
+            vec![
+                signature("ok", "ok", MatchKind::Exact, 5),
+                signature("bad", "(", MatchKind::Regex, 5),
+            ],
+        );
+        assert!(matches!(
+            db.apply_signed(&bad, &key()),
+            Err(ThreatSignatureError::InvalidPattern { .. })
+        ));
+        assert_eq!(db.version(), 0);
+        assert_eq!(db.len(), 2);
+
+        db.apply_signed(&raw, &key()).unwrap();
+        assert_eq!(db.len(), 1);
+        assert!(db.scan("X", "").is_some());
+    }
+}
@@ -0,0 +1,348 @@
+/******************************************************************************************
+*  📍 منصة تحليل الأمان الجغرافي MKT KSA – تطوير منصور بن خالد
+*  ملف: src/utils/ip_prefix.rs
+*
+*  الهدف: تمثيل نطاقات CIDR لعناوين IPv4/IPv6 وجدول مطابقة أطول بادئة (Longest-Prefix-Match)
+*  مشترك بين قوائم البروكسي ونطاقات مزودي الاستضافة.
+*
+*  Purpose: IPv4/IPv6 CIDR prefixes and a longest-prefix-match table shared by the
+*  proxy lists and the hosting-provider ranges.
+******************************************************************************************/
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid IP prefix: {0}")]
+pub struct IpPrefixError(pub String);
+
+/// نطاق CIDR مُطبّع (تُصفّر البتات خارج طول البادئة).
+/// A normalized CIDR prefix (bits beyond the prefix length are zeroed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IpPrefix {
+    addr: IpAddr,
+    len: u8,
+}
+
+impl IpPrefix {
+    /// # Errors
+    /// Returns `IpPrefixError` if `len` exceeds the address width.
+    pub fn new(addr: IpAddr, len: u8) -> Result<Self, IpPrefixError> {
+        let max = max_len(&addr);
+        if len > max {
+            return Err(IpPrefixError(format!("{addr}/{len}")));
+        }
+        let addr = match addr {
+            IpAddr::V4(v4) => IpAddr::V4(Ipv4Addr::from(mask_u32(u32::from(v4), len))),
+            IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(mask_u128(u128::from(v6), len))),
+        };
+        Ok(Self { addr, len })
+    }
+
+    /// نطاق يغطي عنواناً واحداً فقط.
+    /// A prefix covering a single host.
+    #[must_use]
+    pub fn host(addr: IpAddr) -> Self {
+        let addr = canonical_ip(addr);
+        Self {
+            addr,
+            len: max_len(&addr),
+        }
+    }
+
+    #[must_use]
+    pub const fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    #[must_use]
+    pub const fn prefix_len(&self) -> u8 {
+        self.len
+    }
+
+    #[must_use]
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, canonical_ip(*ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                mask_u32(u32::from(ip), self.len) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                mask_u128(u128::from(ip), self.len) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpPrefix {
+    type Err = IpPrefixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr_part, len_part) = s.split_once('/').map_or((s, None), |(a, l)| (a, Some(l)));
+        let addr = addr_part
+            .parse::<IpAddr>()
+            .map_err(|_| IpPrefixError(s.to_string()))?;
+        let addr = canonical_ip(addr);
+        let len = match len_part {
+            Some(l) => l.parse::<u8>().map_err(|_| IpPrefixError(s.to_string()))?,
+            None => max_len(&addr),
+        };
+        Self::new(addr, len)
+    }
+}
+
+impl fmt::Display for IpPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+/// يحوّل عناوين IPv4-mapped IPv6 (`::ffff:a.b.c.d`) إلى IPv4.
+/// Converts IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) to IPv4.
+#[must_use]
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+const fn max_len(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+const fn mask_u32(value: u32, len: u8) -> u32 {
+    if len == 0 {
+        0
+    } else {
+        value & (u32::MAX << (32 - len))
+    }
+}
+
+const fn mask_u128(value: u128, len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        value & (u128::MAX << (128 - len))
+    }
+}
+
+// ================================================================
+// جدول مطابقة أطول بادئة (شجرة ثنائية)
+// Longest-prefix-match table (binary trie)
+// ================================================================
+
+#[derive(Clone)]
+struct TrieNode<V> {
+    children: [Option<usize>; 2],
+    entry: Option<(IpPrefix, V)>,
+}
+
+impl<V> TrieNode<V> {
+    const fn empty() -> Self {
+        Self {
+            children: [None, None],
+            entry: None,
+        }
+    }
+}
+
+/// جدول بادئات IPv4/IPv6 يعيد أطول بادئة مطابقة لعنوان.
+/// IPv4/IPv6 prefix table returning the longest matching prefix for an address.
+#[derive(Clone)]
+pub struct PrefixTable<V> {
+    v4: Vec<TrieNode<V>>,
+    v6: Vec<TrieNode<V>>,
+    len: usize,
+}
+
+impl<V> Default for PrefixTable<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> PrefixTable<V> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            v4: vec![TrieNode::empty()],
+            v6: vec![TrieNode::empty()],
+            len: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// يدرج أو يستبدل القيمة المرتبطة ببادئة، ويعيد القيمة السابقة إن وجدت.
+    /// Inserts or replaces the value for a prefix, returning the previous value.
+    pub fn insert(&mut self, prefix: IpPrefix, value: V) -> Option<V> {
+        let (nodes, bits) = self.tree_mut(prefix.addr);
+        let mut idx = 0;
+        for depth in 0..prefix.len {
+            let bit = bit_at(bits, depth, prefix.addr);
+            idx = if let Some(next) = nodes[idx].children[bit] {
+                next
+            } else {
+                nodes.push(TrieNode::empty());
+                let next = nodes.len() - 1;
+                nodes[idx].children[bit] = Some(next);
+                next
+            };
+        }
+        let previous = nodes[idx].entry.replace((prefix, value)).map(|(_, v)| v);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// أطول بادئة تحتوي العنوان مع قيمتها.
+    /// The longest prefix containing the address, with its value.
+    #[must_use]
+    pub fn longest_match(&self, ip: &IpAddr) -> Option<(&IpPrefix, &V)> {
+        let ip = canonical_ip(*ip);
+        let (nodes, bits) = match ip {
+            IpAddr::V4(v4) => (&self.v4, u128::from(u32::from(v4))),
+            IpAddr::V6(v6) => (&self.v6, u128::from(v6)),
+        };
+        let mut best = nodes[0].entry.as_ref();
+        let mut idx = 0;
+        for depth in 0..max_len(&ip) {
+            let Some(next) = nodes[idx].children[bit_at(bits, depth, ip)] else {
+                break;
+            };
+            idx = next;
+            if nodes[idx].entry.is_some() {
+                best = nodes[idx].entry.as_ref();
+            }
+        }
+        best.map(|(prefix, value)| (prefix, value))
+    }
+
+    /// كل البادئات المطابقة للعنوان من الأقصر إلى الأطول.
+    /// Every prefix matching the address, from shortest to longest.
+    #[must_use]
+    pub fn all_matches(&self, ip: &IpAddr) -> Vec<(&IpPrefix, &V)> {
+        let ip = canonical_ip(*ip);
+        let (nodes, bits) = match ip {
+            IpAddr::V4(v4) => (&self.v4, u128::from(u32::from(v4))),
+            IpAddr::V6(v6) => (&self.v6, u128::from(v6)),
+        };
+        let mut out = Vec::new();
+        let mut idx = 0;
+        let mut depth = 0;
+        loop {
+            if let Some((prefix, value)) = nodes[idx].entry.as_ref() {
+                out.push((prefix, value));
+            }
+            if depth == max_len(&ip) {
+                break;
+            }
+            let Some(next) = nodes[idx].children[bit_at(bits, depth, ip)] else {
+                break;
+            };
+            idx = next;
+            depth += 1;
+        }
+        out
+    }
+
+    /// يمر على كل المدخلات.
+    /// Iterates over every entry.
+    pub fn iter(&self) -> impl Iterator<Item = (&IpPrefix, &V)> {
+        self.v4
+            .iter()
+            .chain(self.v6.iter())
+            .filter_map(|node| node.entry.as_ref().map(|(p, v)| (p, v)))
+    }
+
+    /// يحتفظ فقط بالمدخلات التي تحقق الشرط.
+    /// Keeps only the entries matching the predicate.
+    pub fn retain(&mut self, mut keep: impl FnMut(&IpPrefix, &V) -> bool) {
+        let mut removed = 0;
+        for node in self.v4.iter_mut().chain(self.v6.iter_mut()) {
+            if let Some((prefix, value)) = node.entry.as_ref() {
+                if !keep(prefix, value) {
+                    node.entry = None;
+                    removed += 1;
+                }
+            }
+        }
+        self.len -= removed;
+    }
+
+    fn tree_mut(&mut self, addr: IpAddr) -> (&mut Vec<TrieNode<V>>, u128) {
+        match addr {
+            IpAddr::V4(v4) => (&mut self.v4, u128::from(u32::from(v4))),
+            IpAddr::V6(v6) => (&mut self.v6, u128::from(v6)),
+        }
+    }
+}
+
+fn bit_at(bits: u128, depth: u8, addr: IpAddr) -> usize {
+    let width = u32::from(max_len(&addr));
+    usize::from((bits >> (width - 1 - u32::from(depth))) & 1 == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_parse_and_contains() {
+        let p: IpPrefix = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(p.to_string(), "10.0.0.0/8");
+        assert!(p.contains(&"10.200.0.1".parse().unwrap()));
+        assert!(!p.contains(&"11.0.0.1".parse().unwrap()));
+        assert!(p.contains(&"::ffff:10.0.0.5".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpPrefix>().is_err());
+        let v6: IpPrefix = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(&"2001:db8:1::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_longest_prefix_match() {
+        let mut table = PrefixTable::new();
+        table.insert("10.0.0.0/8".parse().unwrap(), "wide");
+        table.insert("10.1.0.0/16".parse().unwrap(), "narrow");
+        table.insert("2001:db8::/32".parse().unwrap(), "v6");
+        assert_eq!(table.len(), 3);
+
+        let hit = table.longest_match(&"10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!(*hit.1, "narrow");
+        let hit = table.longest_match(&"10.2.2.3".parse().unwrap()).unwrap();
+        assert_eq!(*hit.1, "wide");
+        assert_eq!(table.all_matches(&"10.1.2.3".parse().unwrap()).len(), 2);
+        assert!(table
+            .longest_match(&"192.168.0.1".parse().unwrap())
+            .is_none());
+        assert_eq!(
+            *table
+                .longest_match(&"2001:db8::1".parse().unwrap())
+                .unwrap()
+                .1,
+            "v6"
+        );
+
+        table.retain(|_, v| *v != "narrow");
+        assert_eq!(table.len(), 2);
+        let hit = table.longest_match(&"10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!(*hit.1, "wide");
+    }
+}
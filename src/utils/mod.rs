@@ -38,3 +38,7 @@ pub mod logger;
 // Arabic: وحدة الدقة والحسابات الرقمية والجغرافية
 // English: Precision and numeric/geo utilities module
 pub mod precision;
+
+// Arabic: نطاقات CIDR وجدول مطابقة أطول بادئة
+// English: CIDR prefixes and longest-prefix-match table
+pub mod ip_prefix;
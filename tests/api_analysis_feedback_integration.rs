@@ -0,0 +1,88 @@
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use mkt_ksa_geo_sec::api;
+use serde_json::json;
+use uuid::Uuid;
+
+mod support;
+use support::{build_state_with_db, issue_token, seed_user};
+
+#[actix_web::test]
+async fn analyst_verdicts_are_stored_reported_and_consumed() {
+    let (state, _user_id, token, _) = build_state_with_db(100).await;
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::config)).await;
+    let analyst_token = issue_token(
+        seed_user(state.db_pool.as_ref().unwrap(), "analyst").await,
+        "moderator",
+    );
+
+    let analyze = || {
+        test::TestRequest::post()
+            .uri("/api/behavior/analyze")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+            .set_json(json!({ "input": {
+                "entity_id": "feedback-entity",
+                "timestamp": "2025-01-15T12:00:00Z",
+                "location": [24.7136, 46.6753],
+                "network_info": {
+                    "ip_address": "8.8.8.8",
+                    "is_vpn": true,
+                    "connection_type": "WiFi"
+                },
+                "device_fingerprint": "new-laptop"
+            }}))
+            .to_request()
+    };
+    let first: serde_json::Value = test::call_and_read_body_json(&app, analyze()).await;
+    let analysis_id = first["data"]["analysis_id"].as_str().unwrap().to_string();
+    let first_score = first["data"]["risk_score"].as_f64().unwrap();
+    assert!(first_score > 0.0);
+
+    let verdict = |bearer: &str, id: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/analyses/{id}/verdict"))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {bearer}")))
+            .set_json(json!({ "verdict": "legit", "notes": "user confirmed new laptop" }))
+            .to_request()
+    };
+    assert_eq!(
+        test::call_service(&app, verdict(&token, &analysis_id))
+            .await
+            .status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        test::call_service(&app, verdict(&analyst_token, &Uuid::new_v4().to_string()))
+            .await
+            .status(),
+        StatusCode::NOT_FOUND
+    );
+    let labeled: serde_json::Value =
+        test::call_and_read_body_json(&app, verdict(&analyst_token, &analysis_id)).await;
+    assert_eq!(labeled["data"]["verdict"], "legit");
+
+    // الجهاز والموقع المؤكدان يخفضان الدرجة في التحليل التالي
+    // The confirmed device and location lower the score of the next analysis
+    let second: serde_json::Value = test::call_and_read_body_json(&app, analyze()).await;
+    assert!(second["data"]["risk_score"].as_f64().unwrap() < first_score);
+
+    let report = test::TestRequest::get()
+        .uri("/api/analyses/report?source=behavior&thresholds=0.1,0.5")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {analyst_token}")))
+        .to_request();
+    let report: serde_json::Value = test::call_and_read_body_json(&app, report).await;
+    assert_eq!(report["data"]["labeled"], 1);
+    assert_eq!(report["data"]["legit"], 1);
+    assert_eq!(report["data"]["thresholds"][0]["false_positives"], 1);
+    assert!(report["data"]["thresholds"][0]["recall"].is_null());
+
+    let forbidden_report = test::TestRequest::get()
+        .uri("/api/analyses/report")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, forbidden_report).await.status(),
+        StatusCode::FORBIDDEN
+    );
+}
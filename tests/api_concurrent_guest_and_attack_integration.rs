@@ -58,7 +58,9 @@ async fn concurrent_guest_users_cover_core_routes() {
                 .insert_header(auth.clone())
                 .to_request();
             let resp_user = test::call_service(&app, req_user).await;
-            assert_eq!(resp_user.status(), StatusCode::NOT_FOUND);
+            // ضيف بلا سجل محلي لا يُسمح له بقراءة ملف المستخدم
+            // A guest without a local record may not read user profiles
+            assert_eq!(resp_user.status(), StatusCode::FORBIDDEN);
 
             let req_behavior = test::TestRequest::post()
                 .uri("/api/behavior/analyze")
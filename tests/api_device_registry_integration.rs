@@ -0,0 +1,196 @@
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use mkt_ksa_geo_sec::api;
+use serde_json::json;
+
+mod support;
+use support::{build_state_with_db, issue_token, seed_user, TEST_PROXY_SECRET};
+
+#[actix_web::test]
+async fn device_registry_binds_lists_and_revokes() {
+    let (state, user_id, token, other_user_id) = build_state_with_db(100).await;
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::config)).await;
+
+    let resolve = || {
+        test::TestRequest::post()
+            .uri("/api/device/resolve")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+            .set_json(json!({
+                "os": "Android",
+                "device_info": "Pixel-8",
+                "environment_data": "mobile-wifi"
+            }))
+            .to_request()
+    };
+    let first: serde_json::Value = test::call_and_read_body_json(&app, resolve()).await;
+    let registration = &first["data"]["registration"];
+    assert_eq!(registration["is_new"], true);
+    assert_eq!(registration["device"]["trust_state"], "new");
+    assert!(first["data"]["base_fp"].is_string());
+    let device_id = registration["device"]["id"].as_str().unwrap().to_string();
+
+    let second: serde_json::Value = test::call_and_read_body_json(&app, resolve()).await;
+    assert_eq!(second["data"]["registration"]["is_new"], false);
+    assert_eq!(second["data"]["registration"]["device"]["id"], device_id);
+
+    let list = test::TestRequest::get()
+        .uri("/api/devices")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .to_request();
+    let devices: serde_json::Value = test::call_and_read_body_json(&app, list).await;
+    assert_eq!(devices["data"].as_array().unwrap().len(), 1);
+
+    let other_token = issue_token(other_user_id, "user");
+    let foreign_list = test::TestRequest::get()
+        .uri(&format!("/api/users/{user_id}/devices"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {other_token}")))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, foreign_list).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    let foreign_revoke = test::TestRequest::post()
+        .uri(&format!("/api/devices/{device_id}/revoke"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {other_token}")))
+        .to_request();
+    // جهاز مستخدم آخر يُرد عليه كجهاز غير موجود
+    // Another user's device is answered as missing
+    assert_eq!(
+        test::call_service(&app, foreign_revoke).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let missing_revoke = test::TestRequest::post()
+        .uri(&format!("/api/devices/{}/revoke", uuid::Uuid::new_v4()))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {other_token}")))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, missing_revoke).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let self_trust = test::TestRequest::post()
+        .uri(&format!("/api/devices/{device_id}/trust"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .set_json(json!({ "trust_state": "trusted" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, self_trust).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let rename = test::TestRequest::post()
+        .uri(&format!("/api/devices/{device_id}/rename"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .set_json(json!({ "friendly_name": "My phone" }))
+        .to_request();
+    let renamed: serde_json::Value = test::call_and_read_body_json(&app, rename).await;
+    assert_eq!(renamed["data"]["friendly_name"], "My phone");
+
+    let revoke = test::TestRequest::post()
+        .uri(&format!("/api/devices/{device_id}/revoke"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .to_request();
+    let revoked: serde_json::Value = test::call_and_read_body_json(&app, revoke).await;
+    assert_eq!(revoked["data"]["trust_state"], "revoked");
+
+    let admin_token = issue_token(
+        seed_user(state.db_pool.as_ref().unwrap(), "admin").await,
+        "admin",
+    );
+    let admin_list = test::TestRequest::get()
+        .uri(&format!("/api/users/{user_id}/devices"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {admin_token}")))
+        .to_request();
+    let admin_devices: serde_json::Value = test::call_and_read_body_json(&app, admin_list).await;
+    assert_eq!(admin_devices["data"][0]["trust_state"], "revoked");
+
+    let restore = test::TestRequest::post()
+        .uri(&format!("/api/devices/{device_id}/trust"))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {admin_token}")))
+        .set_json(json!({ "trust_state": "trusted" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, restore).await.status(),
+        StatusCode::CONFLICT
+    );
+}
+
+#[actix_web::test]
+async fn chrome_user_agent_on_bare_tls_stack_starts_suspicious() {
+    let (state, _user_id, token, _other_user_id) = build_state_with_db(100).await;
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::config)).await;
+
+    // ClientHello بـ TLS 1.2 وشيفرة واحدة بلا ALPN ولا GREASE، كما ترسله مكتبات HTTP البسيطة
+    // A TLS 1.2 ClientHello with one cipher, no ALPN and no GREASE, as plain HTTP libraries send
+    let bare_hello = "AQAAKQMDAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAALALwEA";
+    let resolve = |peer: &str, secret: &str, client_hello: Option<&str>| {
+        let mut request = test::TestRequest::post()
+            .uri("/api/device/resolve")
+            .peer_addr(peer.parse().unwrap())
+            .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+            .insert_header((
+                header::USER_AGENT,
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+            ))
+            .insert_header(("X-Transport-Proxy-Secret", secret.to_string()));
+        if let Some(client_hello) = client_hello {
+            request = request.insert_header(("X-TLS-ClientHello", client_hello.to_string()));
+        }
+        // بصمة النقل في الجسم يرسلها العميل نفسه فتُتجاهل دائماً
+        // A transport fingerprint in the body comes from the client itself and is always ignored
+        request
+            .set_json(json!({ "transport": { "client_hello": bare_hello } }))
+            .to_request()
+    };
+    let proxy = "10.0.0.5:443";
+
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, resolve(proxy, TEST_PROXY_SECRET, Some(bare_hello)))
+            .await;
+    let transport = &body["data"]["transport"];
+    assert_eq!(transport["claimed_family"], "chrome");
+    assert_eq!(transport["ja3"].as_str().unwrap().len(), 32);
+    assert!(transport["ja4"]
+        .as_str()
+        .unwrap()
+        .starts_with("t12i010000_"));
+    assert_eq!(
+        transport["mismatches"],
+        json!([
+            "browser_without_h2",
+            "browser_without_tls13",
+            "missing_grease"
+        ])
+    );
+    assert_eq!(
+        body["data"]["registration"]["device"]["trust_state"],
+        "suspicious"
+    );
+
+    let malformed = resolve(proxy, TEST_PROXY_SECRET, Some("AQAAKQMD"));
+    assert_eq!(
+        test::call_service(&app, malformed).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // خارج الوكيل الموثوق أو بسر خاطئ لا تُقرأ بصمة النقل
+    // Outside the trusted proxy, or with a wrong secret, no transport fingerprint is read
+    for (peer, secret) in [
+        ("203.0.113.7:443", TEST_PROXY_SECRET),
+        (proxy, "wrong-secret"),
+    ] {
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, resolve(peer, secret, Some(bare_hello))).await;
+        assert!(body["data"]["transport"].is_null());
+    }
+
+    // الوكيل الموثوق الذي لا يمرر البصمة يُعاقب
+    // A trusted proxy that forwards no fingerprint is penalized
+    let missing: serde_json::Value =
+        test::call_and_read_body_json(&app, resolve(proxy, TEST_PROXY_SECRET, None)).await;
+    assert_eq!(
+        missing["data"]["transport"]["mismatches"],
+        json!(["missing_transport_fingerprint"])
+    );
+}
@@ -6,7 +6,7 @@ use mkt_ksa_geo_sec::db::migrations;
 use serde_json::json;
 
 mod support;
-use support::build_state_with_db;
+use support::{build_state_with_db, seed_user};
 
 fn sample_behavior_input() -> serde_json::Value {
     json!({
@@ -166,7 +166,7 @@ async fn migrations_are_idempotent_and_versioned() {
         })
         .await
         .expect("count migration versions");
-    assert_eq!(versions_count, 2);
+    assert_eq!(versions_count, 5);
 
     let users_table_exists: i64 = db
         .call(|conn| {
@@ -256,3 +256,51 @@ async fn spoofed_x_forwarded_for_does_not_bypass_rate_limit_by_default() {
     let third_resp = test::call_service(&app, third).await;
     assert_eq!(third_resp.status(), StatusCode::TOO_MANY_REQUESTS);
 }
+
+#[actix_web::test]
+async fn decrypt_ip_is_restricted_to_admin_role() {
+    let (state, _user_id, token, _) = build_state_with_db(100).await;
+
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::config)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/network/decrypt_ip")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .set_json(json!({ "encrypted_ip": "k1.00" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn access_events_require_a_service_account() {
+    let (state, _user_id, token, other_user_id) = build_state_with_db(100).await;
+    let service_id = seed_user(state.db_pool.as_ref().unwrap(), "login-gateway").await;
+    let service_token = state
+        .jwt_manager
+        .generate_token(service_id, vec!["service".to_string()])
+        .expect("generate token");
+
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::config)).await;
+
+    let access = |bearer: &str| {
+        test::TestRequest::post()
+            .uri("/api/behavior/access")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {bearer}")))
+            .set_json(json!({
+                "account_id": other_user_id,
+                "ip": "203.0.113.7",
+                "outcome": "failure"
+            }))
+            .to_request()
+    };
+    // مستخدم عادي لا يستطيع اختلاق أحداث لحسابات أخرى
+    // A regular user cannot forge events for other accounts
+    assert_eq!(
+        test::call_service(&app, access(&token)).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    let recorded: serde_json::Value =
+        test::call_and_read_body_json(&app, access(&service_token)).await;
+    assert!(recorded["data"]["alerts"].as_array().unwrap().is_empty());
+}
@@ -1,18 +1,22 @@
 use actix_web::web;
 use mkt_ksa_geo_sec::app_state::AlertMemoryStore;
 use mkt_ksa_geo_sec::app_state::AppState;
+use mkt_ksa_geo_sec::core::account_correlation::AccountCorrelator;
 use mkt_ksa_geo_sec::core::behavior_bio::{
     BehaviorEngine, DefaultAnomalyDetector, DefaultBehavioralModel,
 };
+use mkt_ksa_geo_sec::core::behavior_feedback::{FeedbackAwareModel, LabeledHistory};
 use mkt_ksa_geo_sec::core::composite_verification::CompositeVerifier;
 use mkt_ksa_geo_sec::core::cross_location::{CrossValidationEngine, DefaultScoringStrategy};
 use mkt_ksa_geo_sec::core::device_fp::{
     AdaptiveFingerprintEngine, DefaultAiProcessor as FpAiProcessor, DefaultQuantumEngine,
     DefaultSecurityMonitor,
 };
+use mkt_ksa_geo_sec::core::forwarding_chain::ForwardingChainAnalyzer;
 use mkt_ksa_geo_sec::core::geo_resolver::{
     DefaultAiModel as GeoAiModel, DefaultBlockchain, GeoReaderEnum, GeoResolver, MockGeoReader,
 };
+use mkt_ksa_geo_sec::core::location_consistency::LocationConsistencyChecker;
 use mkt_ksa_geo_sec::core::network_analyzer::{
     DefaultAiNetworkAnalyzer, NetworkAnalyzer, ProxyDatabase,
 };
@@ -32,6 +36,44 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+/// نطاق وكيل إنهاء TLS وسره المشترك في الاختبارات.
+/// The TLS-terminating proxy range and shared secret used in tests.
+#[allow(dead_code)]
+pub const TEST_PROXY_CIDR: &str = "10.0.0.0/8";
+#[allow(dead_code)]
+pub const TEST_PROXY_SECRET: &str = "integration-proxy-secret";
+
+/// يضيف مستخدماً نشطاً؛ فحوص السياسة تقرأ حالة المستدعي من جدول المستخدمين.
+/// Seeds an active user; policy checks read the caller's status from the users table.
+#[allow(dead_code)]
+pub async fn seed_user(db: &tokio_rusqlite::Connection, username: &str) -> Uuid {
+    let user = User {
+        id: Uuid::new_v4(),
+        username: username.to_string(),
+        email: format!("{username}@example.local"),
+        password_hash: "hash".to_string(),
+        status: "active".to_string(),
+        created_at: chrono::Utc::now().naive_utc(),
+        last_login_at: Some(chrono::Utc::now().naive_utc()),
+    };
+    crud::upsert_user(db, &user).await.expect("seed user");
+    user.id
+}
+
+/// يصدر توكناً بدور واحد بسر الاختبارات.
+/// Issues a single-role token signed with the test secret.
+#[allow(dead_code)]
+pub fn issue_token(user_id: Uuid, role: &str) -> String {
+    let jwt = JwtManager::new(
+        &SecureString::new("integration_test_jwt_secret_key_more_than_32".to_string()),
+        3600,
+        "mkt_ksa_geo_sec".to_string(),
+        "api_clients".to_string(),
+    );
+    jwt.generate_token(user_id, vec![role.to_string()])
+        .expect("token generation")
+}
+
 pub async fn build_state_with_db(max_requests: u32) -> (web::Data<AppState>, Uuid, String, Uuid) {
     let geo_reader = Arc::new(GeoReaderEnum::Mock(MockGeoReader::new()));
 
@@ -101,8 +143,12 @@ pub async fn build_state_with_db(max_requests: u32) -> (web::Data<AppState>, Uui
         Arc::new(RwLock::new(fp_env_profiles)),
     ));
 
+    let labeled_history = Arc::new(LabeledHistory::default());
     let behavior_engine = Arc::new(BehaviorEngine::new(
-        Arc::new(DefaultBehavioralModel),
+        Arc::new(FeedbackAwareModel::new(
+            Arc::new(DefaultBehavioralModel::default()),
+            Arc::clone(&labeled_history),
+        )),
         Arc::new(DefaultAnomalyDetector {
             max_speed_kmh: 1200.0,
         }),
@@ -140,6 +186,7 @@ pub async fn build_state_with_db(max_requests: u32) -> (web::Data<AppState>, Uui
         behavior: behavior_engine,
         device_fp: fp_engine,
         network: network_engine,
+        location_consistency: LocationConsistencyChecker::default(),
     });
 
     let weather_providers: Vec<Arc<dyn WeatherProvider>> = vec![Arc::new(OpenMeteoProvider::new())];
@@ -164,23 +211,13 @@ pub async fn build_state_with_db(max_requests: u32) -> (web::Data<AppState>, Uui
         .expect("open sqlite memory db");
     crud::init_schema(&db).await.expect("init schema");
 
-    let user_id = Uuid::new_v4();
-    let user = User {
-        id: user_id,
-        username: "integration-user".to_string(),
-        email: "integration@example.local".to_string(),
-        password_hash: "hash".to_string(),
-        status: "active".to_string(),
-        created_at: chrono::Utc::now().naive_utc(),
-        last_login_at: Some(chrono::Utc::now().naive_utc()),
-    };
-    crud::upsert_user(&db, &user).await.expect("seed user");
+    let user_id = seed_user(&db, "integration-user").await;
 
     let token = jwt_manager
         .generate_token(user_id, vec!["user".to_string()])
         .expect("generate token");
 
-    let other_user_id = Uuid::new_v4();
+    let other_user_id = seed_user(&db, "other-user").await;
 
     let state = web::Data::new(AppState {
         x_engine,
@@ -190,7 +227,13 @@ pub async fn build_state_with_db(max_requests: u32) -> (web::Data<AppState>, Uui
         rate_limiter,
         ai_guard: Arc::new(RequestAiGuard::default()),
         api_key: None,
+        forwarding: Arc::new(ForwardingChainAnalyzer::new([TEST_PROXY_CIDR
+            .parse()
+            .expect("test proxy range")])),
+        transport_proxy_secret: Some(SecureString::new(TEST_PROXY_SECRET.to_string())),
         alert_memory: Arc::new(AlertMemoryStore::new(64)),
+        access_correlator: Arc::new(AccountCorrelator::default()),
+        labeled_history,
         db_pool: Some(db),
     });
 